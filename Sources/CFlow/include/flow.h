@@ -20,6 +20,12 @@ extern "C" {
 /// Opaque handle to the Flow engine
 typedef struct FlowHandle FlowHandle;
 
+/// Callback for results delivered while an operation is in progress
+/// @param success Whether this is a result (true) or an error message (false)
+/// @param result Result text, only valid for the duration of the call
+/// @param context Caller-provided context pointer
+typedef void (*ResultCallback)(bool success, const char* result, void* context);
+
 // ============ Lifecycle ============
 
 /// Initialize the Flow engine
@@ -38,9 +44,13 @@ void flow_destroy(FlowHandle* handle);
 /// @return true on success
 bool flow_start_recording(FlowHandle* handle);
 
+/// Returned by flow_stop_recording when the recording holds no speech
+#define FLOW_NO_SPEECH UINT64_MAX
+
 /// Stop audio recording and get the duration
+/// Recordings without speech are discarded when voice activity detection is on
 /// @param handle Engine handle
-/// @return Duration in milliseconds, or 0 on failure
+/// @return Duration in milliseconds, 0 on failure, or FLOW_NO_SPEECH if no speech was detected
 uint64_t flow_stop_recording(FlowHandle* handle);
 
 /// Check if currently recording
@@ -49,24 +59,267 @@ uint64_t flow_stop_recording(FlowHandle* handle);
 bool flow_is_recording(FlowHandle* handle);
 
 /// Get current audio level (RMS amplitude) from the recording
+/// Also fails over to the default input device when the one in use was disconnected;
+/// see flow_take_audio_device_events_json
 /// @param handle Engine handle
 /// @return Value between 0.0 and 1.0, or 0.0 if not recording
 float flow_get_audio_level(FlowHandle* handle);
 
+/// Record from an audio file (WAV or FLAC) instead of the microphone
+/// Lets the recording pipeline run on machines without an input device
+/// @param handle Engine handle
+/// @param path File to play back, or NULL to use the microphone again
+/// @return true on success
+bool flow_set_input_file(FlowHandle* handle, const char* path);
+
+// ============ Input Devices ============
+
+/// Get the available input devices as JSON
+/// [{"id", "name", "is_default", "configs": [{"channels", "min_sample_rate", "max_sample_rate", "sample_format"}]}]
+/// @param handle Engine handle
+/// @return JSON string (caller must free with flow_free_string), or NULL on error
+char* flow_get_input_devices_json(FlowHandle* handle);
+
+/// Record from an input device, given its id or name (persisted)
+/// The device must be connected; it is saved by id when the host provides one
+/// @param handle Engine handle
+/// @param device Device id or name, or NULL/empty to follow the system default input
+/// @return true on success
+bool flow_set_input_device(FlowHandle* handle, const char* device);
+
+/// Get the selected input device id or name
+/// @param handle Engine handle
+/// @return Device (caller must free with flow_free_string), or NULL when following the system default
+char* flow_get_input_device(FlowHandle* handle);
+
+/// Take input device changes since the last call as JSON
+/// A missing selected device, or one disconnected mid-recording, fails over to the default input
+/// [{"type": "failed_over", "from", "to", "reason"} | {"type": "capture_failed", "device", "reason"}]
+/// @param handle Engine handle
+/// @return JSON string (caller must free with flow_free_string)
+char* flow_take_audio_device_events_json(FlowHandle* handle);
+
+// ============ Pre-roll ============
+
+/// Keep the microphone open between recordings so each one starts with the audio
+/// heard just before flow_start_recording (persisted)
+/// @param handle Engine handle
+/// @param pre_roll_ms Audio to prepend, capped at 2000; 0 turns pre-roll off and closes the device
+/// @param idle_timeout_secs Close the device after this long without a recording (0 for the default, 30)
+/// @return true on success
+bool flow_set_pre_roll(FlowHandle* handle, uint32_t pre_roll_ms, uint32_t idle_timeout_secs);
+
+/// Get the pre-roll length
+/// @param handle Engine handle
+/// @return Milliseconds of audio prepended to recordings, 0 when off
+uint32_t flow_get_pre_roll_ms(FlowHandle* handle);
+
+/// Open the microphone ahead of a recording so pre-roll audio is available
+/// Calling again while warm restarts the idle timeout
+/// @param handle Engine handle
+/// @return true on success; false when pre-roll is off, audio comes from a file, or the device fails
+bool flow_warm_microphone(FlowHandle* handle);
+
+/// Check whether the microphone is being kept open for pre-roll
+/// @param handle Engine handle
+/// @return true if the device is open between recordings
+bool flow_is_microphone_warm(FlowHandle* handle);
+
+// ============ Hands-free ============
+
+/// Let recordings end by themselves once the speaker stops (persisted, off by default)
+/// Poll flow_get_auto_stop_json while recording to learn when one has ended
+/// @param handle Engine handle
+/// @param enabled Whether recordings stop themselves
+/// @param trailing_silence_ms Silence after speech that ends a recording (0 for the default, 1500)
+/// @param min_duration_ms Silence never ends a shorter recording (0 for the default, 1000)
+/// @param max_duration_ms Recordings end at this length regardless (0 for the default, 120000)
+/// @return true on success; false when the minimum exceeds the maximum
+bool flow_set_hands_free(FlowHandle* handle, bool enabled, uint32_t trailing_silence_ms, uint32_t min_duration_ms, uint32_t max_duration_ms);
+
+/// Get the hands-free settings as JSON
+/// {"enabled", "trailing_silence_ms", "min_duration_ms", "max_duration_ms"}
+/// @param handle Engine handle
+/// @return JSON string (caller must free with flow_free_string)
+char* flow_get_hands_free_json(FlowHandle* handle);
+
+/// Check whether the current hands-free recording has ended by itself
+/// Capture is paused from then on; call flow_stop_recording to collect the recording
+/// @param handle Engine handle
+/// @return JSON {"reason": "silence" | "max_duration", "duration_ms"} (caller must free with flow_free_string), or NULL while it hasn't
+char* flow_get_auto_stop_json(FlowHandle* handle);
+
+// ============ Live Transcription ============
+
+/// Transcribe recordings while they are captured (persisted, off by default)
+/// Each stretch of speech is transcribed once a pause ends it, so flow_transcribe only has the tail left
+/// Recordings for a provider that formats text itself (the worker) are still sent whole
+/// @param handle Engine handle
+/// @param enabled Whether recordings are transcribed as they are captured
+/// @return true on success
+bool flow_set_live_transcription(FlowHandle* handle, bool enabled);
+
+/// Check whether recordings are transcribed while they are captured
+/// @param handle Engine handle
+/// @return true if live transcription is on
+bool flow_is_live_transcription_enabled(FlowHandle* handle);
+
+/// Get what has been transcribed of the current recording so far
+/// "stable_text" is the start of "text" that won't change; "revision" increases whenever "text" does
+/// Available until flow_transcribe
+/// @param handle Engine handle
+/// @return JSON {"text", "stable_text", "audio_ms", "revision"} (caller must free with flow_free_string), or NULL when the recording is not transcribed live
+char* flow_get_partial_transcription_json(FlowHandle* handle);
+
+// ============ Audio Processing ============
+
+/// Choose the clean-up applied to recordings before transcription (persisted)
+/// @param handle Engine handle
+/// @param high_pass Remove rumble and hum below 100Hz (on by default)
+/// @param noise_suppression Suppress steady background noise (off by default)
+/// @param agc Level speech and limit peaks (on by default)
+/// @return true on success
+bool flow_set_audio_processing(FlowHandle* handle, bool high_pass, bool noise_suppression, bool agc);
+
+/// Get the audio processing stages as JSON: {"high_pass", "noise_suppression", "agc"}
+/// @param handle Engine handle
+/// @return JSON string (caller must free with flow_free_string)
+char* flow_get_audio_processing_json(FlowHandle* handle);
+
+// ============ Voice Activity Detection ============
+
+/// Turn voice activity detection on or off (persisted, on by default)
+/// While on, silence is trimmed before transcription and recordings without speech are skipped
+/// @param handle Engine handle
+/// @param enabled Whether to detect speech
+/// @return true on success
+bool flow_set_vad_enabled(FlowHandle* handle, bool enabled);
+
+/// Check whether voice activity detection is on
+/// @param handle Engine handle
+/// @return true if enabled
+bool flow_is_vad_enabled(FlowHandle* handle);
+
+/// Set the neural model that confirms speech (persisted)
+/// @param handle Engine handle
+/// @param path Safetensors model file, or NULL/empty to use energy detection only
+/// @return true on success; on failure the previous model is kept
+bool flow_set_vad_model(FlowHandle* handle, const char* path);
+
+/// Check whether a neural VAD model is loaded
+/// @param handle Engine handle
+/// @return true if loaded
+bool flow_has_vad_model(FlowHandle* handle);
+
 // ============ Transcription ============
 
 /// Transcribe the recorded audio and process it
 /// @param handle Engine handle
 /// @param app_name Name of the current app (for mode selection), or NULL
-/// @return Processed text (caller must free with flow_free_string), or NULL on failure
+/// @return Processed text (caller must free with flow_free_string), an empty string if the
+///         recording holds no speech (no provider is called), or NULL on failure
 char* flow_transcribe(FlowHandle* handle, const char* app_name);
 
 /// Retry the last transcription using cached audio
 /// @param handle Engine handle
 /// @param app_name Name of the current app (for mode selection), or NULL
-/// @return Processed text (caller must free with flow_free_string), or NULL on failure
+/// @return Processed text (caller must free with flow_free_string), an empty string if the
+///         audio holds no speech, or NULL on failure
 char* flow_retry_last_transcription(FlowHandle* handle, const char* app_name);
 
+/// Transcribe an audio file (WAV or FLAC) and process it like a recording
+/// @param handle Engine handle
+/// @param path Path to the audio file
+/// @param app_name Name of the current app (for mode selection), or NULL
+/// @return Processed text (caller must free with flow_free_string), an empty string if the
+///         file holds no speech, or NULL on failure
+char* flow_transcribe_file(FlowHandle* handle, const char* path, const char* app_name);
+
+/// Format text with the active completion provider, streaming output as it is generated
+/// Providers without streaming support deliver the whole result as a single chunk
+/// @param handle Engine handle
+/// @param text Text to format
+/// @param app_name Name of the current app (for mode selection), or NULL
+/// @param callback Called with (true, chunk) for each chunk, or (false, error) on failure
+/// @param context Passed through to callback
+/// @return Full formatted text (caller must free with flow_free_string), or NULL on failure
+char* flow_complete_streaming(
+    FlowHandle* handle,
+    const char* text,
+    const char* app_name,
+    ResultCallback callback,
+    void* context
+);
+
+// ============ Audio Archive ============
+
+/// Keep recordings on disk with their history entries, for replay and re-transcription
+/// (persisted, off by default). Recordings outside the new limits are deleted right away,
+/// even when turning the archive off
+/// @param handle Engine handle
+/// @param enabled Whether new recordings are archived
+/// @param max_mb Archive size before the oldest recordings are deleted (0 for no limit)
+/// @param max_age_days Days recordings are kept (0 for no limit)
+/// @return true on success
+bool flow_set_audio_archive(FlowHandle* handle, bool enabled, uint32_t max_mb, uint32_t max_age_days);
+
+/// Get the audio archive settings and usage as JSON
+/// {"enabled", "max_mb", "max_age_days", "recordings", "total_bytes"} (0 limits mean none)
+/// @param handle Engine handle
+/// @return JSON string (caller must free with flow_free_string)
+char* flow_get_audio_archive_json(FlowHandle* handle);
+
+/// Get the archived recording of a history entry for playback
+/// @param handle Engine handle
+/// @param id History entry id
+/// @return Path to a FLAC file (caller must free with flow_free_string), or NULL if there is none
+char* flow_get_archived_audio_path(FlowHandle* handle, const char* id);
+
+/// Export the archived recording of a history entry as a 16-bit mono WAV file
+/// @param handle Engine handle
+/// @param id History entry id
+/// @param path Destination file
+/// @return true on success
+bool flow_export_archived_audio(FlowHandle* handle, const char* id, const char* path);
+
+/// Delete the archived recording of a history entry (the entry itself is kept)
+/// @param handle Engine handle
+/// @param id History entry id
+/// @return true if a recording was deleted
+bool flow_delete_archived_audio(FlowHandle* handle, const char* id);
+
+/// Transcribe the archived recording of a history entry again, updating the entry
+/// @param handle Engine handle
+/// @param id History entry id
+/// @param provider "auto", "openai" or "local", or NULL for the active provider
+/// @param model OpenAI model name, or a built-in or installed Whisper model id with "local"
+///              (NULL for the provider's configured model)
+/// @return Processed text (caller must free with flow_free_string), an empty string if the
+///         recording holds no speech, or NULL on failure (the entry is left unchanged)
+char* flow_retranscribe(FlowHandle* handle, const char* id, const char* provider, const char* model);
+
+// ============ Transcription Queue ============
+
+/// Be told when a queued transcription is resolved. A transcription is queued when the
+/// provider can't be reached; it is retried in the background with backoff until it goes
+/// through (updating its history entry) or fails for good
+/// @param handle Engine handle
+/// @param callback Called from a background thread with (true, {"id", "status", "text"}) or
+///                 (false, {"id", "status", "error"}); NULL to stop notifications
+/// @param context Passed through to callback
+void flow_set_transcription_queue_callback(FlowHandle* handle, ResultCallback callback, void* context);
+
+/// Get the number of transcriptions waiting to be retried
+/// @param handle Engine handle
+/// @return Number of queued transcriptions
+size_t flow_queued_transcription_count(FlowHandle* handle);
+
+/// Retry every queued transcription now instead of waiting out the backoff,
+/// e.g. when the network comes back
+/// @param handle Engine handle
+/// @return Number of queued transcriptions
+size_t flow_process_transcription_queue(FlowHandle* handle);
+
 // ============ Shortcuts ============
 
 /// Add a voice shortcut
@@ -208,7 +461,7 @@ char* flow_get_stats_json(FlowHandle* handle);
 /// Get recent transcriptions as JSON
 /// @param handle Engine handle
 /// @param limit Maximum number of transcriptions to return
-/// @return JSON string (caller must free with flow_free_string)
+/// @return JSON string (caller must free with flow_free_string); entries include "confidence", timed "segments" and "low_confidence_spans" (offsets into raw_text) when available
 char* flow_get_recent_transcriptions_json(FlowHandle* handle, size_t limit);
 
 /// Get all shortcuts as JSON
@@ -220,29 +473,30 @@ char* flow_get_shortcuts_json(FlowHandle* handle);
 
 /// Switch completion provider (loads API key from database)
 /// @param handle Engine handle
-/// @param provider 0 = OpenAI, 1 = Gemini, 2 = OpenRouter
+/// @param provider 0 = OpenAI, 1 = Gemini, 2 = OpenRouter, 3 = Anthropic
 /// @return true on success
 bool flow_switch_completion_provider(FlowHandle* handle, uint8_t provider);
 
 /// Set completion provider with API key (saves both)
 /// @param handle Engine handle
-/// @param provider 0 = OpenAI, 1 = Gemini, 2 = OpenRouter
+/// @param provider 0 = OpenAI, 1 = Gemini, 2 = OpenRouter, 3 = Anthropic
 /// @param api_key API key for the provider
 /// @return true on success
 bool flow_set_completion_provider(FlowHandle* handle, uint8_t provider, const char* api_key);
 
 /// Get current completion provider
 /// @param handle Engine handle
-/// @return 0 = OpenAI, 1 = Gemini, 2 = OpenRouter, 255 = Unknown
+/// @return 0 = OpenAI, 1 = Gemini, 2 = OpenRouter, 3 = Anthropic, 255 = Unknown
 uint8_t flow_get_completion_provider(FlowHandle* handle);
 
 /// Get API key for a specific provider in masked form (e.g., "sk-••••••••")
 /// @param handle Engine handle
-/// @param provider 0 = OpenAI, 1 = Gemini, 2 = OpenRouter
+/// @param provider 0 = OpenAI, 1 = Gemini, 2 = OpenRouter, 3 = Anthropic
 /// @return Masked API key string (caller must free with flow_free_string) or NULL if not set
 char* flow_get_api_key(FlowHandle* handle, uint8_t provider);
 
 /// Set transcription mode (local or remote)
+/// Local transcriptions are formatted with the completion provider, when one is configured
 /// @param handle Engine handle
 /// @param use_local true for local Whisper, false for cloud provider
 /// @param whisper_model Whisper model: 0 = Tiny (39MB), 1 = Base (142MB), 2 = Small (466MB)
@@ -252,7 +506,7 @@ bool flow_set_transcription_mode(FlowHandle* handle, bool use_local, uint8_t whi
 /// Get current transcription mode settings
 /// @param handle Engine handle
 /// @param out_use_local Output parameter for use_local flag
-/// @param out_whisper_model Output parameter for whisper_model (0-4)
+/// @param out_whisper_model Output parameter for whisper_model (0-4, or 255 for an installed custom model)
 /// @return true on success, false on database error
 bool flow_get_transcription_mode(FlowHandle* handle, bool* out_use_local, uint8_t* out_whisper_model);
 
@@ -280,6 +534,149 @@ bool flow_set_cloud_transcription_provider(FlowHandle* handle, uint8_t provider)
 /// @return 0 = OpenAI, 1 = Base10, 255 = Unknown
 uint8_t flow_get_cloud_transcription_provider(FlowHandle* handle);
 
+// ============ OpenAI-Compatible Endpoint ============
+
+/// Set the base URL for the OpenAI providers (self-hosted OpenAI-compatible servers)
+/// @param handle Engine handle
+/// @param base_url e.g. "http://localhost:11434/v1", or NULL/empty for api.openai.com
+/// @return true on success
+bool flow_set_openai_base_url(FlowHandle* handle, const char* base_url);
+
+/// Get the custom base URL for the OpenAI providers
+/// @param handle Engine handle
+/// @return Base URL (caller must free with flow_free_string), or NULL when using api.openai.com
+char* flow_get_openai_base_url(FlowHandle* handle);
+
+/// List the models served by the configured endpoint's /models route
+/// @param handle Engine handle
+/// @return JSON array of model ids (caller must free with flow_free_string), or NULL on failure
+char* flow_list_openai_models(FlowHandle* handle);
+
+/// Set the models used by the OpenAI providers (validated against the endpoint's model list)
+/// @param handle Engine handle
+/// @param transcription_model Model for transcription, NULL to leave unchanged, empty for default
+/// @param completion_model Model for completion, NULL to leave unchanged, empty for default
+/// @return true on success
+bool flow_set_openai_models(
+    FlowHandle* handle,
+    const char* transcription_model,
+    const char* completion_model
+);
+
+// ============ Language ============
+
+/// Set the spoken language hint for transcription
+/// @param handle Engine handle
+/// @param language ISO 639-1 code or English name (e.g. "de", "german"), or NULL/"auto" to auto-detect
+/// @return true on success, false for unsupported languages
+bool flow_set_transcription_language(FlowHandle* handle, const char* language);
+
+/// Get the spoken language hint for transcription
+/// @param handle Engine handle
+/// @return ISO 639-1 code (caller must free with flow_free_string), or NULL when auto-detecting
+char* flow_get_transcription_language(FlowHandle* handle);
+
+/// Translate speech to English instead of transcribing it in the spoken language
+/// @param handle Engine handle
+/// @param enabled Whether to translate
+/// @return true on success
+bool flow_set_translate_to_english(FlowHandle* handle, bool enabled);
+
+/// Check whether speech is translated to English
+/// @param handle Engine handle
+/// @return true if translation is enabled
+bool flow_is_translate_to_english(FlowHandle* handle);
+
+// ============ Local Whisper Decoding ============
+
+/// Set the beam size for local Whisper decoding
+/// Takes effect the next time local transcription is enabled
+/// @param handle Engine handle
+/// @param beam_size 1 (greedy, default) to 8
+/// @return true on success
+bool flow_set_whisper_beam_size(FlowHandle* handle, uint8_t beam_size);
+
+/// Get the beam size for local Whisper decoding
+/// @param handle Engine handle
+/// @return Beam size (1 = greedy)
+uint8_t flow_get_whisper_beam_size(FlowHandle* handle);
+
+// ============ Custom Vocabulary ============
+
+/// Add a term (name, product, jargon) to the custom vocabulary
+/// Vocabulary, shortcut triggers, learned corrections and frequent contacts prompt transcription
+/// @param handle Engine handle
+/// @param term Term to add (duplicates are ignored, case-insensitive)
+/// @return true on success
+bool flow_add_vocabulary_term(FlowHandle* handle, const char* term);
+
+/// Remove a term from the custom vocabulary
+/// @param handle Engine handle
+/// @param term Term to remove (case-insensitive)
+/// @return true if the term was removed
+bool flow_remove_vocabulary_term(FlowHandle* handle, const char* term);
+
+/// Get the custom vocabulary
+/// @param handle Engine handle
+/// @return JSON array of strings (caller must free with flow_free_string), or NULL on error
+char* flow_get_vocabulary_terms(FlowHandle* handle);
+
+// ============ Whisper Model Registry ============
+
+/// Install a Whisper checkpoint (safetensors or GGUF) in the background
+/// Downloads resume after interruption and are verified against the hub's SHA-256
+/// @param handle Engine handle
+/// @param source "owner/name" or "owner/name@revision" on Hugging Face, or an absolute path to a
+///               directory with config.json, tokenizer.json and one weights file
+/// @return Model id (caller must free with flow_free_string), or NULL if the source is invalid
+///         or another install is running
+char* flow_install_whisper_model(FlowHandle* handle, const char* source);
+
+/// Get progress of the current or last model install
+/// @param handle Engine handle
+/// @return JSON object with model_id, state (idle/downloading/verifying/installed/failed),
+///         downloaded_bytes, total_bytes and error (caller must free with flow_free_string)
+char* flow_get_model_install_progress_json(FlowHandle* handle);
+
+/// Get installed Whisper models
+/// @param handle Engine handle
+/// @return JSON array with id, source, quantized, files (name, size, sha256), installed_at and
+///         size_bytes on disk (caller must free with flow_free_string), or NULL on error
+char* flow_get_installed_whisper_models_json(FlowHandle* handle);
+
+/// Delete an installed Whisper model and any partial download of it
+/// @param handle Engine handle
+/// @param model_id Model id
+/// @return true if the model was removed
+bool flow_delete_whisper_model(FlowHandle* handle, const char* model_id);
+
+/// Re-hash an installed model's files against the SHA-256 recorded at install
+/// @param handle Engine handle
+/// @param model_id Model id
+/// @return true if every file matches
+bool flow_verify_whisper_model(FlowHandle* handle, const char* model_id);
+
+/// Switch to local transcription with an installed model
+/// @param handle Engine handle
+/// @param model_id Model id
+/// @return true on success
+bool flow_use_whisper_model(FlowHandle* handle, const char* model_id);
+
+// ============ Offline Mode ============
+
+/// Turn offline mode on or off (persisted)
+/// While on, no network request is made: transcription uses local Whisper with the selected
+/// model (which must already be installed) and text is formatted on-device
+/// @param handle Engine handle
+/// @param enabled Whether to stay offline
+/// @return true on success
+bool flow_set_offline_mode(FlowHandle* handle, bool enabled);
+
+/// Check whether offline mode is on
+/// @param handle Engine handle
+/// @return true if offline
+bool flow_is_offline_mode(FlowHandle* handle);
+
 // ============ Error Handling ============
 
 /// Get the last error message
@@ -0,0 +1,45 @@
+//
+// AudioArchive.swift
+// Flow
+//
+// Recordings kept on disk with their history entries, for replay and re-transcription.
+//
+
+import Foundation
+
+/// Audio archive settings and usage
+public struct AudioArchiveSettings: Codable, Equatable {
+    public var enabled: Bool
+    /// Archive size before the oldest recordings are deleted (0 for no limit)
+    public var maxMb: UInt32
+    /// Days recordings are kept (0 for no limit)
+    public var maxAgeDays: UInt32
+    /// Recordings currently archived
+    public private(set) var recordings: Int
+    public private(set) var totalBytes: UInt64
+
+    public init(enabled: Bool = false, maxMb: UInt32 = 500, maxAgeDays: UInt32 = 30) {
+        self.enabled = enabled
+        self.maxMb = maxMb
+        self.maxAgeDays = maxAgeDays
+        self.recordings = 0
+        self.totalBytes = 0
+    }
+
+    enum CodingKeys: String, CodingKey {
+        case enabled
+        case maxMb = "max_mb"
+        case maxAgeDays = "max_age_days"
+        case recordings
+        case totalBytes = "total_bytes"
+    }
+}
+
+/// Provider used to transcribe an archived recording again
+public enum RetranscriptionProvider: String {
+    /// The worker, with OpenAI as its fallback
+    case auto
+    case openai
+    /// Local Whisper
+    case local
+}
@@ -0,0 +1,30 @@
+//
+// AudioProcessing.swift
+// Flow
+//
+// Clean-up stages applied to recordings before transcription.
+//
+
+import Foundation
+
+/// Which audio processing stages run on recordings
+public struct AudioProcessing: Codable, Equatable {
+    /// Remove rumble and hum below 100Hz
+    public var highPass: Bool
+    /// Suppress steady background noise such as fans and air conditioning
+    public var noiseSuppression: Bool
+    /// Bring speech to a consistent level and limit peaks
+    public var agc: Bool
+
+    public init(highPass: Bool = true, noiseSuppression: Bool = false, agc: Bool = true) {
+        self.highPass = highPass
+        self.noiseSuppression = noiseSuppression
+        self.agc = agc
+    }
+
+    enum CodingKeys: String, CodingKey {
+        case highPass = "high_pass"
+        case noiseSuppression = "noise_suppression"
+        case agc
+    }
+}
@@ -64,12 +64,14 @@ public enum CompletionProvider: UInt8, Sendable {
     case openAI = 0
     case gemini = 1
     case openRouter = 2
+    case anthropic = 3
 
     public var displayName: String {
         switch self {
         case .openAI: return "OpenAI"
         case .gemini: return "Gemini"
         case .openRouter: return "OpenRouter"
+        case .anthropic: return "Anthropic"
         }
     }
 }
@@ -160,6 +162,7 @@ public struct CorrectionValidation: Sendable {
 /// Main interface to the Flow engine
 public final class Flow: @unchecked Sendable {
     private let handle: OpaquePointer?
+    private var queueHandler: Unmanaged<QueueHandlerBox>?
 
     /// Initialize the Flow engine
     /// - Parameter dbPath: Optional path to the SQLite database. If nil, uses default location.
@@ -184,6 +187,7 @@ public final class Flow: @unchecked Sendable {
         if let handle = handle {
             flow_destroy(handle)
         }
+        queueHandler?.release()
     }
 
     /// Check if the engine is properly initialized
@@ -208,8 +212,12 @@ public final class Flow: @unchecked Sendable {
         return flow_start_recording(handle)
     }
 
+    /// Returned by `stopRecording()` when the recording holds no speech
+    public static let noSpeech = UInt64.max
+
     /// Stop audio recording
-    /// - Returns: Duration of the recording in milliseconds, or 0 on failure
+    /// - Returns: Duration of the recording in milliseconds, 0 on failure,
+    ///   or `Flow.noSpeech` when no speech was detected (nothing to transcribe)
     public func stopRecording() -> UInt64 {
         guard let handle = handle else { return 0 }
         return flow_stop_recording(handle)
@@ -222,17 +230,206 @@ public final class Flow: @unchecked Sendable {
     }
 
     /// Get current audio level (RMS amplitude) from the recording
+    /// Also fails over to the default input when the device in use was disconnected (see `takeAudioDeviceEvents`)
     /// - Returns: A value between 0.0 and 1.0, or 0.0 if not recording
     public var audioLevel: Float {
         guard let handle = handle else { return 0.0 }
         return flow_get_audio_level(handle)
     }
 
+    /// Record from an audio file (WAV or FLAC) instead of the microphone
+    /// - Parameter url: File to play back, or nil to use the microphone again
+    /// - Returns: true on success
+    public func setInputFile(_ url: URL?) -> Bool {
+        guard let handle = handle else { return false }
+        guard let url else { return flow_set_input_file(handle, nil) }
+        return url.path.withCString { flow_set_input_file(handle, $0) }
+    }
+
+    // MARK: - Input Devices
+
+    /// Input devices currently connected
+    public var inputDevices: [InputDevice] {
+        guard let handle = handle else { return [] }
+        guard let cString = flow_get_input_devices_json(handle) else { return [] }
+        let json = String(cString: cString)
+        flow_free_string(cString)
+        guard let data = json.data(using: .utf8) else { return [] }
+        return (try? JSONDecoder().decode([InputDevice].self, from: data)) ?? []
+    }
+
+    /// Selected input device id or name, or nil when following the system default
+    public var selectedInputDevice: String? {
+        guard let handle = handle else { return nil }
+        guard let cString = flow_get_input_device(handle) else { return nil }
+        let device = String(cString: cString)
+        flow_free_string(cString)
+        return device
+    }
+
+    /// Record from a connected input device (persisted)
+    /// - Parameter device: Device id or name, or nil to follow the system default
+    /// - Returns: true on success
+    public func setInputDevice(_ device: String?) -> Bool {
+        guard let handle = handle else { return false }
+        guard let device else { return flow_set_input_device(handle, nil) }
+        return device.withCString { flow_set_input_device(handle, $0) }
+    }
+
+    /// Input device changes since the last call, such as failing over after a disconnect
+    public func takeAudioDeviceEvents() -> [AudioDeviceEvent] {
+        guard let handle = handle else { return [] }
+        guard let cString = flow_take_audio_device_events_json(handle) else { return [] }
+        let json = String(cString: cString)
+        flow_free_string(cString)
+        guard let data = json.data(using: .utf8) else { return [] }
+        return (try? JSONDecoder().decode([AudioDeviceEvent].self, from: data)) ?? []
+    }
+
+    // MARK: - Pre-roll
+
+    /// Milliseconds of audio from before each recording starts that are kept (0 when off)
+    public var preRollMs: UInt32 {
+        guard let handle = handle else { return 0 }
+        return flow_get_pre_roll_ms(handle)
+    }
+
+    /// Keep the microphone open between recordings so the first word isn't clipped
+    /// - Parameters:
+    ///   - milliseconds: Audio to prepend, capped at 2000; 0 turns pre-roll off
+    ///   - idleTimeoutSeconds: Close the microphone after this long without a recording (0 for 30)
+    /// - Returns: true on success
+    public func setPreRoll(milliseconds: UInt32, idleTimeoutSeconds: UInt32 = 0) -> Bool {
+        guard let handle = handle else { return false }
+        return flow_set_pre_roll(handle, milliseconds, idleTimeoutSeconds)
+    }
+
+    /// Open the microphone ahead of a recording so pre-roll audio is available
+    /// - Returns: true on success
+    @discardableResult
+    public func warmMicrophone() -> Bool {
+        guard let handle = handle else { return false }
+        return flow_warm_microphone(handle)
+    }
+
+    /// Whether the microphone is being kept open for pre-roll
+    public var isMicrophoneWarm: Bool {
+        guard let handle = handle else { return false }
+        return flow_is_microphone_warm(handle)
+    }
+
+    // MARK: - Hands-free
+
+    /// Hands-free recording settings
+    public var handsFree: HandsFreeSettings {
+        guard let handle = handle else { return HandsFreeSettings() }
+        guard let cString = flow_get_hands_free_json(handle) else { return HandsFreeSettings() }
+        let json = String(cString: cString)
+        flow_free_string(cString)
+        guard let data = json.data(using: .utf8) else { return HandsFreeSettings() }
+        return (try? JSONDecoder().decode(HandsFreeSettings.self, from: data)) ?? HandsFreeSettings()
+    }
+
+    /// Let recordings end by themselves once the speaker stops (persisted)
+    /// - Returns: true on success; false when the minimum exceeds the maximum
+    public func setHandsFree(_ settings: HandsFreeSettings) -> Bool {
+        guard let handle = handle else { return false }
+        return flow_set_hands_free(
+            handle, settings.enabled, settings.trailingSilenceMs,
+            settings.minDurationMs, settings.maxDurationMs)
+    }
+
+    /// Why the current hands-free recording ended by itself, once it has
+    /// Call `stopRecording()` to collect it
+    public var autoStop: AutoStop? {
+        guard let handle = handle else { return nil }
+        guard let cString = flow_get_auto_stop_json(handle) else { return nil }
+        let json = String(cString: cString)
+        flow_free_string(cString)
+        guard let data = json.data(using: .utf8) else { return nil }
+        return try? JSONDecoder().decode(AutoStop.self, from: data)
+    }
+
+    // MARK: - Live Transcription
+
+    /// Whether recordings are transcribed while they are captured
+    public var liveTranscriptionEnabled: Bool {
+        guard let handle = handle else { return false }
+        return flow_is_live_transcription_enabled(handle)
+    }
+
+    /// Transcribe recordings while they are captured, so only the tail is left at the end (persisted)
+    /// - Returns: true on success
+    public func setLiveTranscription(_ enabled: Bool) -> Bool {
+        guard let handle = handle else { return false }
+        return flow_set_live_transcription(handle, enabled)
+    }
+
+    /// What has been transcribed of the current recording so far
+    /// nil when the recording is not transcribed live
+    public var partialTranscription: PartialTranscription? {
+        guard let handle = handle else { return nil }
+        guard let cString = flow_get_partial_transcription_json(handle) else { return nil }
+        let json = String(cString: cString)
+        flow_free_string(cString)
+        guard let data = json.data(using: .utf8) else { return nil }
+        return try? JSONDecoder().decode(PartialTranscription.self, from: data)
+    }
+
+    // MARK: - Audio Processing
+
+    /// Clean-up stages applied to recordings before transcription
+    public var audioProcessing: AudioProcessing {
+        guard let handle = handle else { return AudioProcessing() }
+        guard let cString = flow_get_audio_processing_json(handle) else { return AudioProcessing() }
+        let json = String(cString: cString)
+        flow_free_string(cString)
+        guard let data = json.data(using: .utf8) else { return AudioProcessing() }
+        return (try? JSONDecoder().decode(AudioProcessing.self, from: data)) ?? AudioProcessing()
+    }
+
+    /// Choose the clean-up applied to recordings (persisted)
+    /// - Returns: true on success
+    public func setAudioProcessing(_ processing: AudioProcessing) -> Bool {
+        guard let handle = handle else { return false }
+        return flow_set_audio_processing(
+            handle, processing.highPass, processing.noiseSuppression, processing.agc)
+    }
+
+    // MARK: - Voice Activity Detection
+
+    /// Whether silence is trimmed and recordings without speech are skipped (on by default)
+    public var isVADEnabled: Bool {
+        guard let handle = handle else { return false }
+        return flow_is_vad_enabled(handle)
+    }
+
+    /// Turn voice activity detection on or off
+    /// - Returns: true on success
+    public func setVADEnabled(_ enabled: Bool) -> Bool {
+        guard let handle = handle else { return false }
+        return flow_set_vad_enabled(handle, enabled)
+    }
+
+    /// Whether a neural VAD model is loaded
+    public var hasVADModel: Bool {
+        guard let handle = handle else { return false }
+        return flow_has_vad_model(handle)
+    }
+
+    /// Set the safetensors model that confirms speech, or nil to use energy detection only
+    /// - Returns: true on success
+    public func setVADModel(path: String?) -> Bool {
+        guard let handle = handle else { return false }
+        guard let path else { return flow_set_vad_model(handle, nil) }
+        return path.withCString { flow_set_vad_model(handle, $0) }
+    }
+
     // MARK: - Transcription
 
     /// Transcribe the recorded audio and process it
     /// - Parameter appName: Optional name of the current app for mode selection
-    /// - Returns: Processed text, or nil on failure
+    /// - Returns: Processed text, an empty string if no speech was detected, or nil on failure
     public func transcribe(appName: String? = nil) -> String? {
         guard let handle = handle else { return nil }
 
@@ -260,7 +457,7 @@ public final class Flow: @unchecked Sendable {
 
     /// Retry the last transcription using cached audio
     /// - Parameter appName: Optional name of the current app for mode selection
-    /// - Returns: Processed text, or nil on failure
+    /// - Returns: Processed text, an empty string if no speech was detected, or nil on failure
     public func retryLastTranscription(appName: String? = nil) -> String? {
         guard let handle = handle else { return nil }
 
@@ -279,6 +476,193 @@ public final class Flow: @unchecked Sendable {
         return string
     }
 
+    /// Transcribe an audio file (WAV or FLAC) and process it like a recording
+    /// - Parameters:
+    ///   - url: Location of the audio file
+    ///   - appName: Optional name of the current app for mode selection
+    /// - Returns: Processed text, an empty string if no speech was detected, or nil on failure
+    public func transcribeFile(at url: URL, appName: String? = nil) -> String? {
+        guard let handle = handle else { return nil }
+
+        let result: UnsafeMutablePointer<CChar>? = url.path.withCString { cPath in
+            if let app = appName {
+                return app.withCString { cApp in
+                    flow_transcribe_file(handle, cPath, cApp)
+                }
+            }
+            return flow_transcribe_file(handle, cPath, nil)
+        }
+
+        guard let cString = result else { return nil }
+        let string = String(cString: cString)
+        flow_free_string(cString)
+        return string
+    }
+
+    // MARK: - Audio Archive
+
+    /// Audio archive settings and usage
+    public var audioArchive: AudioArchiveSettings {
+        guard let handle = handle else { return AudioArchiveSettings() }
+        guard let cString = flow_get_audio_archive_json(handle) else { return AudioArchiveSettings() }
+        let json = String(cString: cString)
+        flow_free_string(cString)
+        guard let data = json.data(using: .utf8) else { return AudioArchiveSettings() }
+        return (try? JSONDecoder().decode(AudioArchiveSettings.self, from: data)) ?? AudioArchiveSettings()
+    }
+
+    /// Keep recordings with their history entries (persisted)
+    /// Recordings outside the new limits are deleted right away
+    /// - Returns: true on success
+    public func setAudioArchive(_ settings: AudioArchiveSettings) -> Bool {
+        guard let handle = handle else { return false }
+        return flow_set_audio_archive(handle, settings.enabled, settings.maxMb, settings.maxAgeDays)
+    }
+
+    /// The archived recording of a history entry, for playback (a FLAC file)
+    public func archivedAudioURL(for transcriptionId: String) -> URL? {
+        guard let handle = handle else { return nil }
+        guard let cString = transcriptionId.withCString({ flow_get_archived_audio_path(handle, $0) }) else {
+            return nil
+        }
+        let path = String(cString: cString)
+        flow_free_string(cString)
+        return URL(fileURLWithPath: path)
+    }
+
+    /// Export the archived recording of a history entry as a WAV file
+    /// - Returns: true on success
+    public func exportArchivedAudio(for transcriptionId: String, to url: URL) -> Bool {
+        guard let handle = handle else { return false }
+        return transcriptionId.withCString { cId in
+            url.path.withCString { cPath in
+                flow_export_archived_audio(handle, cId, cPath)
+            }
+        }
+    }
+
+    /// Delete the archived recording of a history entry (the entry is kept)
+    /// - Returns: true if a recording was deleted
+    public func deleteArchivedAudio(for transcriptionId: String) -> Bool {
+        guard let handle = handle else { return false }
+        return transcriptionId.withCString { flow_delete_archived_audio(handle, $0) }
+    }
+
+    /// Transcribe the archived recording of a history entry again, updating the entry
+    /// - Parameters:
+    ///   - transcriptionId: History entry id
+    ///   - provider: Provider to use, or nil for the active one
+    ///   - model: OpenAI model name, or a Whisper model id with `.local` (nil for the configured model)
+    /// - Returns: Processed text, an empty string if no speech was detected, or nil on failure
+    public func retranscribe(
+        _ transcriptionId: String,
+        provider: RetranscriptionProvider? = nil,
+        model: String? = nil
+    ) -> String? {
+        guard let handle = handle else { return nil }
+
+        func withOptionalCString<T>(_ value: String?, _ body: (UnsafePointer<CChar>?) -> T) -> T {
+            guard let value else { return body(nil) }
+            return value.withCString { body($0) }
+        }
+
+        let result: UnsafeMutablePointer<CChar>? = transcriptionId.withCString { cId in
+            withOptionalCString(provider?.rawValue) { cProvider in
+                withOptionalCString(model) { cModel in
+                    flow_retranscribe(handle, cId, cProvider, cModel)
+                }
+            }
+        }
+
+        guard let cString = result else { return nil }
+        let string = String(cString: cString)
+        flow_free_string(cString)
+        return string
+    }
+
+    /// Format text with the active completion provider, streaming output as it is generated
+    /// - Parameters:
+    ///   - text: The text to format
+    ///   - appName: Optional name of the current app for mode selection
+    ///   - onChunk: Called synchronously for each chunk of text as it arrives
+    /// - Returns: The full formatted text, or nil on failure
+    public func completeStreaming(
+        _ text: String,
+        appName: String? = nil,
+        onChunk: @escaping (String) -> Void
+    ) -> String? {
+        guard let handle = handle else { return nil }
+
+        final class ChunkBox {
+            let onChunk: (String) -> Void
+            init(_ onChunk: @escaping (String) -> Void) { self.onChunk = onChunk }
+        }
+
+        let box = Unmanaged.passRetained(ChunkBox(onChunk))
+        defer { box.release() }
+
+        let callback: ResultCallback = { success, result, context in
+            guard success, let result = result, let context = context else { return }
+            let box = Unmanaged<ChunkBox>.fromOpaque(context).takeUnretainedValue()
+            box.onChunk(String(cString: result))
+        }
+
+        let result: UnsafeMutablePointer<CChar>? = text.withCString { cText in
+            if let app = appName {
+                return app.withCString { cApp in
+                    flow_complete_streaming(handle, cText, cApp, callback, box.toOpaque())
+                }
+            }
+            return flow_complete_streaming(handle, cText, nil, callback, box.toOpaque())
+        }
+
+        guard let cString = result else { return nil }
+        let string = String(cString: cString)
+        flow_free_string(cString)
+        return string
+    }
+
+    // MARK: - Transcription Queue
+
+    private final class QueueHandlerBox {
+        let handler: (QueuedTranscriptionResult) -> Void
+        init(_ handler: @escaping (QueuedTranscriptionResult) -> Void) { self.handler = handler }
+    }
+
+    /// Number of transcriptions waiting to be retried after a network failure
+    public var queuedTranscriptionCount: Int {
+        guard let handle = handle else { return 0 }
+        return flow_queued_transcription_count(handle)
+    }
+
+    /// Retry every queued transcription now instead of waiting out the backoff
+    /// - Returns: The number of queued transcriptions
+    @discardableResult
+    public func processTranscriptionQueue() -> Int {
+        guard let handle = handle else { return 0 }
+        return flow_process_transcription_queue(handle)
+    }
+
+    /// Be told when a queued transcription goes through or fails for good
+    /// - Parameter handler: Called on a background thread, or nil to stop notifications
+    public func setTranscriptionQueueHandler(_ handler: ((QueuedTranscriptionResult) -> Void)?) {
+        guard let handle = handle else { return }
+
+        let callback: ResultCallback = { _, result, context in
+            guard let result = result, let context = context else { return }
+            let box = Unmanaged<QueueHandlerBox>.fromOpaque(context).takeUnretainedValue()
+            guard let data = String(cString: result).data(using: .utf8),
+                  let queued = try? JSONDecoder().decode(QueuedTranscriptionResult.self, from: data)
+            else { return }
+            box.handler(queued)
+        }
+
+        let box = handler.map { Unmanaged.passRetained(QueueHandlerBox($0)) }
+        flow_set_transcription_queue_callback(handle, box == nil ? nil : callback, box?.toOpaque())
+        queueHandler?.release()
+        queueHandler = box
+    }
+
     // MARK: - Shortcuts
 
     /// Add a voice shortcut
@@ -589,27 +973,7 @@ public final class Flow: @unchecked Sendable {
 
         guard let data = jsonString.data(using: .utf8) else { return [] }
         let decoder = JSONDecoder()
-        decoder.dateDecodingStrategy = .custom { decoder in
-            let container = try decoder.singleValueContainer()
-            let dateString = try container.decode(String.self)
-
-            let fractionalFormatter = ISO8601DateFormatter()
-            fractionalFormatter.formatOptions = [.withInternetDateTime, .withFractionalSeconds]
-            if let date = fractionalFormatter.date(from: dateString) {
-                return date
-            }
-
-            let standardFormatter = ISO8601DateFormatter()
-            standardFormatter.formatOptions = [.withInternetDateTime]
-            if let date = standardFormatter.date(from: dateString) {
-                return date
-            }
-
-            throw DecodingError.dataCorruptedError(
-                in: container,
-                debugDescription: "Invalid date: \(dateString)"
-            )
-        }
+        decoder.dateDecodingStrategy = .flowISO8601
         return (try? decoder.decode([TranscriptionSummary].self, from: data)) ?? []
     }
 
@@ -697,6 +1061,12 @@ public final class Flow: @unchecked Sendable {
         getMaskedApiKey(for: .openRouter)
     }
 
+    /// Get the Anthropic API key in masked form (e.g., "sk-••••••••")
+    /// - Returns: Masked API key or nil if not set
+    public var maskedAnthropicKey: String? {
+        getMaskedApiKey(for: .anthropic)
+    }
+
     /// Set transcription mode (local or remote)
     /// - Parameter mode: The transcription mode to use
     /// - Returns: true on success
@@ -763,5 +1133,208 @@ public final class Flow: @unchecked Sendable {
         return CloudTranscriptionProvider(rawValue: rawValue)
     }
 
+    // MARK: - OpenAI-Compatible Endpoint
+
+    /// Base URL for the OpenAI providers, or nil when using api.openai.com
+    public var openAIBaseURL: String? {
+        guard let handle = handle else { return nil }
+        guard let cString = flow_get_openai_base_url(handle) else { return nil }
+        let string = String(cString: cString)
+        flow_free_string(cString)
+        return string
+    }
+
+    /// Point the OpenAI providers at a self-hosted OpenAI-compatible server
+    /// - Parameter baseURL: e.g. "http://localhost:11434/v1", or nil to restore api.openai.com
+    /// - Returns: true on success
+    public func setOpenAIBaseURL(_ baseURL: String?) -> Bool {
+        guard let handle = handle else { return false }
+        guard let baseURL = baseURL else { return flow_set_openai_base_url(handle, nil) }
+        return baseURL.withCString { cURL in
+            flow_set_openai_base_url(handle, cURL)
+        }
+    }
+
+    /// List the models served by the configured endpoint
+    /// - Returns: Model ids, or nil on failure
+    public func listOpenAIModels() -> [String]? {
+        guard let handle = handle else { return nil }
+        guard let cString = flow_list_openai_models(handle) else { return nil }
+        let json = String(cString: cString)
+        flow_free_string(cString)
+        guard let data = json.data(using: .utf8) else { return nil }
+        return try? JSONDecoder().decode([String].self, from: data)
+    }
+
+    /// Set the models used by the OpenAI providers (validated against the endpoint)
+    /// - Parameters:
+    ///   - transcriptionModel: Model for transcription, nil to leave unchanged, "" for default
+    ///   - completionModel: Model for completion, nil to leave unchanged, "" for default
+    /// - Returns: true on success
+    public func setOpenAIModels(transcriptionModel: String?, completionModel: String?) -> Bool {
+        guard let handle = handle else { return false }
+        func withOptionalCString<T>(_ string: String?, _ body: (UnsafePointer<CChar>?) -> T) -> T {
+            guard let string = string else { return body(nil) }
+            return string.withCString { body($0) }
+        }
+        return withOptionalCString(transcriptionModel) { cTranscription in
+            withOptionalCString(completionModel) { cCompletion in
+                flow_set_openai_models(handle, cTranscription, cCompletion)
+            }
+        }
+    }
+
+    // MARK: - Language
+
+    /// Spoken language hint (ISO 639-1 code), or nil when auto-detecting
+    public var transcriptionLanguage: String? {
+        guard let handle = handle else { return nil }
+        guard let cString = flow_get_transcription_language(handle) else { return nil }
+        let string = String(cString: cString)
+        flow_free_string(cString)
+        return string
+    }
+
+    /// Set the spoken language hint for transcription
+    /// - Parameter language: ISO 639-1 code or English name (e.g. "de", "german"), or nil to auto-detect
+    /// - Returns: true on success, false for unsupported languages
+    public func setTranscriptionLanguage(_ language: String?) -> Bool {
+        guard let handle = handle else { return false }
+        guard let language = language else { return flow_set_transcription_language(handle, nil) }
+        return language.withCString { cLanguage in
+            flow_set_transcription_language(handle, cLanguage)
+        }
+    }
+
+    /// Whether speech is translated to English instead of transcribed in the spoken language
+    public var translateToEnglish: Bool {
+        guard let handle = handle else { return false }
+        return flow_is_translate_to_english(handle)
+    }
+
+    /// Enable or disable translating speech to English
+    /// - Returns: true on success
+    public func setTranslateToEnglish(_ enabled: Bool) -> Bool {
+        guard let handle = handle else { return false }
+        return flow_set_translate_to_english(handle, enabled)
+    }
+
+    /// Beam size for local Whisper decoding (1 = greedy)
+    public var whisperBeamSize: Int {
+        guard let handle = handle else { return 1 }
+        return Int(flow_get_whisper_beam_size(handle))
+    }
+
+    /// Set the beam size for local Whisper decoding (takes effect when local transcription is next enabled)
+    /// - Parameter beamSize: 1 (greedy) to 8
+    /// - Returns: true on success
+    public func setWhisperBeamSize(_ beamSize: Int) -> Bool {
+        guard let handle = handle, (1...8).contains(beamSize) else { return false }
+        return flow_set_whisper_beam_size(handle, UInt8(beamSize))
+    }
+
+    // MARK: - Custom Vocabulary
+
+    /// Terms (names, products, jargon) used to prompt transcription
+    public var vocabularyTerms: [String]? {
+        guard let handle = handle else { return nil }
+        guard let cString = flow_get_vocabulary_terms(handle) else { return nil }
+        let json = String(cString: cString)
+        flow_free_string(cString)
+        guard let data = json.data(using: .utf8) else { return nil }
+        return try? JSONDecoder().decode([String].self, from: data)
+    }
+
+    /// Add a term to the custom vocabulary
+    /// - Returns: true on success
+    public func addVocabularyTerm(_ term: String) -> Bool {
+        guard let handle = handle else { return false }
+        return term.withCString { cTerm in
+            flow_add_vocabulary_term(handle, cTerm)
+        }
+    }
+
+    /// Remove a term from the custom vocabulary
+    /// - Returns: true if the term was removed
+    public func removeVocabularyTerm(_ term: String) -> Bool {
+        guard let handle = handle else { return false }
+        return term.withCString { cTerm in
+            flow_remove_vocabulary_term(handle, cTerm)
+        }
+    }
+
+    // MARK: - Whisper Model Registry
+
+    /// Install a Whisper checkpoint in the background (poll `modelInstallProgress`)
+    /// - Parameter source: "owner/name" or "owner/name@revision" on Hugging Face, or a local directory path
+    /// - Returns: The model id, or nil if the source is invalid or another install is running
+    public func installWhisperModel(from source: String) -> String? {
+        guard let handle = handle else { return nil }
+        guard let cString = source.withCString({ flow_install_whisper_model(handle, $0) }) else {
+            return nil
+        }
+        let modelId = String(cString: cString)
+        flow_free_string(cString)
+        return modelId
+    }
+
+    /// Progress of the current or last model install
+    public var modelInstallProgress: ModelInstallProgress? {
+        guard let handle = handle else { return nil }
+        guard let cString = flow_get_model_install_progress_json(handle) else { return nil }
+        let json = String(cString: cString)
+        flow_free_string(cString)
+        guard let data = json.data(using: .utf8) else { return nil }
+        return try? JSONDecoder().decode(ModelInstallProgress.self, from: data)
+    }
+
+    /// Whisper models installed in the models directory
+    public var installedWhisperModels: [InstalledWhisperModel] {
+        guard let handle = handle else { return [] }
+        guard let cString = flow_get_installed_whisper_models_json(handle) else { return [] }
+        let json = String(cString: cString)
+        flow_free_string(cString)
+        guard let data = json.data(using: .utf8) else { return [] }
+        let decoder = JSONDecoder()
+        decoder.dateDecodingStrategy = .flowISO8601
+        return (try? decoder.decode([InstalledWhisperModel].self, from: data)) ?? []
+    }
+
+    /// Delete an installed Whisper model
+    /// - Returns: true if the model was removed
+    public func deleteWhisperModel(_ modelId: String) -> Bool {
+        guard let handle = handle else { return false }
+        return modelId.withCString { flow_delete_whisper_model(handle, $0) }
+    }
+
+    /// Check an installed model's files against their recorded SHA-256
+    /// - Returns: true if every file matches
+    public func verifyWhisperModel(_ modelId: String) -> Bool {
+        guard let handle = handle else { return false }
+        return modelId.withCString { flow_verify_whisper_model(handle, $0) }
+    }
+
+    /// Switch to local transcription with an installed model
+    /// - Returns: true on success
+    public func useWhisperModel(_ modelId: String) -> Bool {
+        guard let handle = handle else { return false }
+        return modelId.withCString { flow_use_whisper_model(handle, $0) }
+    }
+
+    // MARK: - Offline Mode
+
+    /// Whether offline mode is on (no network; local Whisper and on-device formatting)
+    public var isOfflineMode: Bool {
+        guard let handle = handle else { return false }
+        return flow_is_offline_mode(handle)
+    }
+
+    /// Turn offline mode on or off; the selected local Whisper model must already be installed
+    /// - Returns: true on success
+    public func setOfflineMode(_ enabled: Bool) -> Bool {
+        guard let handle = handle else { return false }
+        return flow_set_offline_mode(handle, enabled)
+    }
+
     // Configuration persistence is handled in the core database.
 }
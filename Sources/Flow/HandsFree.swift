@@ -0,0 +1,56 @@
+//
+// HandsFree.swift
+// Flow
+//
+// Hands-free recordings that end by themselves after trailing silence.
+//
+
+import Foundation
+
+/// Hands-free recording settings
+public struct HandsFreeSettings: Codable, Equatable {
+    public var enabled: Bool
+    /// Silence after speech that ends a recording
+    public var trailingSilenceMs: UInt32
+    /// Silence never ends a shorter recording
+    public var minDurationMs: UInt32
+    /// Recordings end at this length regardless
+    public var maxDurationMs: UInt32
+
+    public init(
+        enabled: Bool = false,
+        trailingSilenceMs: UInt32 = 1500,
+        minDurationMs: UInt32 = 1000,
+        maxDurationMs: UInt32 = 120_000
+    ) {
+        self.enabled = enabled
+        self.trailingSilenceMs = trailingSilenceMs
+        self.minDurationMs = minDurationMs
+        self.maxDurationMs = maxDurationMs
+    }
+
+    enum CodingKeys: String, CodingKey {
+        case enabled
+        case trailingSilenceMs = "trailing_silence_ms"
+        case minDurationMs = "min_duration_ms"
+        case maxDurationMs = "max_duration_ms"
+    }
+}
+
+/// A hands-free recording that ended by itself
+public struct AutoStop: Codable, Equatable {
+    public enum Reason: String, Codable {
+        /// The speaker went quiet
+        case silence
+        /// The recording reached its maximum length
+        case maxDuration = "max_duration"
+    }
+
+    public let reason: Reason
+    public let durationMs: UInt64
+
+    enum CodingKeys: String, CodingKey {
+        case reason
+        case durationMs = "duration_ms"
+    }
+}
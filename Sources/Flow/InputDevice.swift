@@ -0,0 +1,76 @@
+//
+// InputDevice.swift
+// Flow
+//
+// Input devices and device changes during capture.
+//
+
+import Foundation
+
+/// A range of sample rates an input device supports for one channel count and format
+public struct InputConfigRange: Codable, Equatable {
+    public let channels: UInt16
+    public let minSampleRate: UInt32
+    public let maxSampleRate: UInt32
+    public let sampleFormat: String
+
+    enum CodingKeys: String, CodingKey {
+        case channels
+        case minSampleRate = "min_sample_rate"
+        case maxSampleRate = "max_sample_rate"
+        case sampleFormat = "sample_format"
+    }
+}
+
+/// A connected input device
+public struct InputDevice: Identifiable, Codable, Equatable {
+    /// Stable identifier where the host provides one
+    public let deviceId: String?
+    public let name: String
+    public let isDefault: Bool
+    public let configs: [InputConfigRange]
+
+    /// Value to pass to `Flow.setInputDevice`
+    public var id: String { deviceId ?? name }
+
+    enum CodingKeys: String, CodingKey {
+        case deviceId = "id"
+        case name
+        case isDefault = "is_default"
+        case configs
+    }
+}
+
+/// A change of input device during capture
+public enum AudioDeviceEvent: Decodable, Equatable {
+    /// Recording moved to the default input because `from` was unavailable
+    case failedOver(from: String, to: String, reason: String)
+    /// The input device went away and no other could be opened
+    case captureFailed(device: String, reason: String)
+
+    enum CodingKeys: String, CodingKey {
+        case type
+        case from
+        case to
+        case device
+        case reason
+    }
+
+    public init(from decoder: Decoder) throws {
+        let container = try decoder.container(keyedBy: CodingKeys.self)
+        let reason = try container.decode(String.self, forKey: .reason)
+        switch try container.decode(String.self, forKey: .type) {
+        case "failed_over":
+            self = .failedOver(
+                from: try container.decode(String.self, forKey: .from),
+                to: try container.decode(String.self, forKey: .to),
+                reason: reason
+            )
+        case "capture_failed":
+            self = .captureFailed(device: try container.decode(String.self, forKey: .device), reason: reason)
+        default:
+            throw DecodingError.dataCorruptedError(
+                forKey: .type, in: container, debugDescription: "Unknown device event")
+        }
+    }
+}
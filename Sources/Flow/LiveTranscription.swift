@@ -0,0 +1,27 @@
+//
+// LiveTranscription.swift
+// Flow
+//
+// Text transcribed from a recording while it is still being captured.
+//
+
+import Foundation
+
+/// What has been transcribed of the current recording so far
+public struct PartialTranscription: Codable, Equatable {
+    /// Everything heard so far, including a guess at the speech still going on
+    public let text: String
+    /// The start of `text` that won't change any more
+    public let stableText: String
+    /// Length of the audio the text covers
+    public let audioMs: UInt64
+    /// Increases whenever `text` changes
+    public let revision: UInt64
+
+    enum CodingKeys: String, CodingKey {
+        case text
+        case stableText = "stable_text"
+        case audioMs = "audio_ms"
+        case revision
+    }
+}
@@ -0,0 +1,22 @@
+//
+// TranscriptionQueue.swift
+// Flow
+//
+// Transcriptions that failed without network and are retried in the background.
+//
+
+import Foundation
+
+/// A queued transcription that went through or was given up
+public struct QueuedTranscriptionResult: Codable {
+    /// History entry the result was written to
+    public let id: String
+    public let status: TranscriptionStatus
+    /// Processed text, when it went through
+    public let text: String?
+    public let error: String?
+
+    public var succeeded: Bool {
+        status == .success
+    }
+}
@@ -12,6 +12,70 @@ public enum TranscriptionStatus: String, Codable {
     case failed
 }
 
+/// Timing of a single word within a segment
+public struct WordTiming: Codable, Equatable {
+    public let text: String
+    public let startMs: UInt64
+    public let endMs: UInt64
+    /// Probability (0-1) that the word was recognised correctly
+    public let confidence: Float?
+
+    enum CodingKeys: String, CodingKey {
+        case text
+        case startMs = "start_ms"
+        case endMs = "end_ms"
+        case confidence
+    }
+}
+
+/// A run of words that might be misrecognised
+public struct ConfidenceSpan: Codable, Equatable {
+    public let text: String
+    /// Start offset into the raw text, in Unicode scalars
+    public let start: Int
+    /// End offset (exclusive) into the raw text, in Unicode scalars
+    public let end: Int
+    public let startMs: UInt64
+    public let endMs: UInt64
+    /// Lowest word confidence in the span
+    public let confidence: Float
+
+    enum CodingKeys: String, CodingKey {
+        case text
+        case start
+        case end
+        case startMs = "start_ms"
+        case endMs = "end_ms"
+        case confidence
+    }
+}
+
+/// A timed span of the transcript
+public struct TranscriptionSegment: Codable, Equatable {
+    public let text: String
+    public let startMs: UInt64
+    public let endMs: UInt64
+    public let confidence: Float?
+    public let words: [WordTiming]
+
+    enum CodingKeys: String, CodingKey {
+        case text
+        case startMs = "start_ms"
+        case endMs = "end_ms"
+        case confidence
+        case words
+    }
+
+    public init(from decoder: Decoder) throws {
+        let container = try decoder.container(keyedBy: CodingKeys.self)
+        text = try container.decode(String.self, forKey: .text)
+        startMs = try container.decode(UInt64.self, forKey: .startMs)
+        endMs = try container.decode(UInt64.self, forKey: .endMs)
+        confidence = try container.decodeIfPresent(Float.self, forKey: .confidence)
+        words = try container.decodeIfPresent([WordTiming].self, forKey: .words) ?? []
+    }
+}
+
 public struct TranscriptionSummary: Identifiable, Codable {
     public let id: String
     public let status: TranscriptionStatus
@@ -21,6 +85,16 @@ public struct TranscriptionSummary: Identifiable, Codable {
     public let durationMs: UInt64
     public let createdAt: Date
     public let appName: String?
+    /// Provider that served the transcription
+    public let provider: String?
+    /// Overall confidence (0-1), when the provider reported one
+    public let confidence: Float?
+    /// Timed segments (empty when the provider returned none)
+    public let segments: [TranscriptionSegment]
+    /// Words in rawText that might be misrecognised, for highlighting
+    public let lowConfidenceSpans: [ConfidenceSpan]
+    /// The recording is in the audio archive, for replay and re-transcription
+    public let hasAudio: Bool
 
     public init(
         id: String,
@@ -30,7 +104,12 @@ public struct TranscriptionSummary: Identifiable, Codable {
         error: String?,
         durationMs: UInt64,
         createdAt: Date,
-        appName: String?
+        appName: String?,
+        provider: String? = nil,
+        confidence: Float? = nil,
+        segments: [TranscriptionSegment] = [],
+        lowConfidenceSpans: [ConfidenceSpan] = [],
+        hasAudio: Bool = false
     ) {
         self.id = id
         self.status = status
@@ -40,6 +119,11 @@ public struct TranscriptionSummary: Identifiable, Codable {
         self.durationMs = durationMs
         self.createdAt = createdAt
         self.appName = appName
+        self.provider = provider
+        self.confidence = confidence
+        self.segments = segments
+        self.lowConfidenceSpans = lowConfidenceSpans
+        self.hasAudio = hasAudio
     }
 
     enum CodingKeys: String, CodingKey {
@@ -51,6 +135,11 @@ public struct TranscriptionSummary: Identifiable, Codable {
         case durationMs = "duration_ms"
         case createdAt = "created_at"
         case appName = "app_name"
+        case provider
+        case confidence
+        case segments
+        case lowConfidenceSpans = "low_confidence_spans"
+        case hasAudio = "has_audio"
     }
 
     public init(from decoder: Decoder) throws {
@@ -63,5 +152,10 @@ public struct TranscriptionSummary: Identifiable, Codable {
         durationMs = try container.decode(UInt64.self, forKey: .durationMs)
         createdAt = try container.decode(Date.self, forKey: .createdAt)
         appName = try container.decodeIfPresent(String.self, forKey: .appName)
+        provider = try container.decodeIfPresent(String.self, forKey: .provider)
+        confidence = try container.decodeIfPresent(Float.self, forKey: .confidence)
+        segments = try container.decodeIfPresent([TranscriptionSegment].self, forKey: .segments) ?? []
+        lowConfidenceSpans = try container.decodeIfPresent([ConfidenceSpan].self, forKey: .lowConfidenceSpans) ?? []
+        hasAudio = try container.decodeIfPresent(Bool.self, forKey: .hasAudio) ?? false
     }
 }
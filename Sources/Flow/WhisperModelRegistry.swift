@@ -0,0 +1,109 @@
+//
+// WhisperModelRegistry.swift
+// Flow
+//
+// Installed Whisper models and install progress.
+//
+
+import Foundation
+
+/// Where an installed model came from
+public struct WhisperModelSource: Codable, Equatable {
+    /// "hugging_face" or "local"
+    public let type: String
+    public let repo: String?
+    public let revision: String?
+    public let path: String?
+}
+
+/// A file of an installed model with its recorded checksum
+public struct WhisperModelFile: Codable, Equatable {
+    /// "config", "tokenizer" or "weights"
+    public let role: String
+    public let name: String
+    public let sizeBytes: UInt64
+    public let sha256: String
+
+    enum CodingKeys: String, CodingKey {
+        case role
+        case name
+        case sizeBytes = "size_bytes"
+        case sha256
+    }
+}
+
+/// A Whisper model installed in the models directory
+public struct InstalledWhisperModel: Identifiable, Codable, Equatable {
+    public let id: String
+    public let source: WhisperModelSource
+    public let quantized: Bool
+    public let files: [WhisperModelFile]
+    public let installedAt: Date
+    /// Bytes used on disk
+    public let sizeBytes: UInt64
+
+    enum CodingKeys: String, CodingKey {
+        case id
+        case source
+        case quantized
+        case files
+        case installedAt = "installed_at"
+        case sizeBytes = "size_bytes"
+    }
+}
+
+/// Progress of a model download or import
+public struct ModelInstallProgress: Codable, Equatable {
+    public enum State: String, Codable {
+        case idle
+        case downloading
+        case verifying
+        case installed
+        case failed
+    }
+
+    public let modelId: String?
+    public let state: State
+    public let downloadedBytes: UInt64
+    public let totalBytes: UInt64
+    public let error: String?
+
+    /// Fraction downloaded (0-1), or nil while the total size is unknown
+    public var fractionCompleted: Double? {
+        guard totalBytes > 0 else { return nil }
+        return min(Double(downloadedBytes) / Double(totalBytes), 1)
+    }
+
+    enum CodingKeys: String, CodingKey {
+        case modelId = "model_id"
+        case state
+        case downloadedBytes = "downloaded_bytes"
+        case totalBytes = "total_bytes"
+        case error
+    }
+}
+
+extension JSONDecoder.DateDecodingStrategy {
+    /// RFC 3339 dates as written by the core, with or without fractional seconds
+    static let flowISO8601 = JSONDecoder.DateDecodingStrategy.custom { decoder in
+        let container = try decoder.singleValueContainer()
+        let dateString = try container.decode(String.self)
+
+        let fractionalFormatter = ISO8601DateFormatter()
+        fractionalFormatter.formatOptions = [.withInternetDateTime, .withFractionalSeconds]
+        if let date = fractionalFormatter.date(from: dateString) {
+            return date
+        }
+
+        let standardFormatter = ISO8601DateFormatter()
+        standardFormatter.formatOptions = [.withInternetDateTime]
+        if let date = standardFormatter.date(from: dateString) {
+            return date
+        }
+
+        throw DecodingError.dataCorruptedError(
+            in: container,
+            debugDescription: "Invalid date: \(dateString)"
+        )
+    }
+}
@@ -32,6 +32,10 @@ final class AppState: ObservableObject {
     /// Last transcribed text
     @Published var lastTranscription: String?
 
+    /// Text transcribed so far while recording (live transcription)
+    @Published var partialTranscription: String?
+    private var partialRevision: UInt64 = 0
+
     /// Current writing mode
     @Published var currentMode: WritingMode = .casual
 
@@ -97,6 +101,7 @@ final class AppState: ObservableObject {
         setupLifecycleObserver()
         setupWorkspaceObserver()
         setupModelLoadingPoller()
+        setupTranscriptionQueue()
         updateCurrentApp()
         refreshHistory()
 
@@ -145,7 +150,8 @@ final class AppState: ObservableObject {
                 startRecording()
             }
         case .released:
-            if isRecording {
+            // Hands-free recordings run until the speaker stops
+            if isRecording && !engine.handsFree.enabled {
                 stopRecording()
             }
         case .toggle:
@@ -161,6 +167,8 @@ final class AppState: ObservableObject {
         ) { _ in
             Task { @MainActor [weak self] in
                 self?.refreshAccessibilityStatus()
+                self?.warmMicrophoneIfNeeded()
+                self?.retryQueuedTranscriptions()
             }
         }
 
@@ -175,6 +183,33 @@ final class AppState: ObservableObject {
         }
     }
 
+    /// Refresh the history as dictations that failed without network are recovered
+    private func setupTranscriptionQueue() {
+        // The engine keeps the handler, so it must not keep self alive
+        engine.setTranscriptionQueueHandler { [weak self] result in
+            Task { @MainActor in
+                guard let self else { return }
+                log("📮 [QUEUE] Queued transcription \(result.id) \(result.status.rawValue)")
+                Analytics.shared.track("Queued Transcription Resolved", eventProperties: [
+                    "status": result.status.rawValue
+                ])
+                self.refreshHistory()
+            }
+        }
+    }
+
+    /// Coming back to the app is a good moment to retry dictations queued while offline
+    private func retryQueuedTranscriptions() {
+        guard engine.queuedTranscriptionCount > 0 else { return }
+        engine.processTranscriptionQueue()
+    }
+
+    /// Open the microphone early when pre-roll is on, so the next recording keeps its first syllable
+    private func warmMicrophoneIfNeeded() {
+        guard engine.preRollMs > 0, !isRecording else { return }
+        engine.warmMicrophone()
+    }
+
     private func setupModelLoadingPoller() {
         // Poll model loading state every 0.5 seconds
         modelLoadingTimer = Timer.scheduledTimer(withTimeInterval: 0.5, repeats: true) { [weak self] _ in
@@ -443,6 +478,7 @@ final class AppState: ObservableObject {
             updateRecordingIndicatorVisibility()
             recordingDuration = 0
             log("✅ [RECORDING] Recording started successfully")
+            handleAudioDeviceEvents()
 
             Analytics.shared.track("Recording Started", eventProperties: [
                 "app_name": currentApp,
@@ -454,6 +490,11 @@ final class AppState: ObservableObject {
                 Task { @MainActor [weak self] in
                     guard let self, self.isRecording else { return }
                     self.recordingDuration += 100
+                    self.handleAudioDeviceEvents()
+                    if let stop = self.engine.autoStop {
+                        self.log("🤫 [RECORDING] Hands-free recording ended (\(stop.reason.rawValue), \(stop.durationMs)ms)")
+                        self.stopRecording()
+                    }
                 }
             }
 
@@ -466,6 +507,10 @@ final class AppState: ObservableObject {
                     // Higher smoothing factor = smoother but slower response
                     let smoothingFactor: Float = 0.3
                     self.smoothedAudioLevel = self.smoothedAudioLevel * (1 - smoothingFactor) + newLevel * smoothingFactor
+                    if let partial = self.engine.partialTranscription, partial.revision != self.partialRevision {
+                        self.partialRevision = partial.revision
+                        self.partialTranscription = partial.text
+                    }
                 }
             }
         } else {
@@ -474,6 +519,20 @@ final class AppState: ObservableObject {
         }
     }
 
+    /// Surface input device failover (polled while recording; the level poll does the failover)
+    private func handleAudioDeviceEvents() {
+        for event in engine.takeAudioDeviceEvents() {
+            switch event {
+            case .failedOver(let from, let to, let reason):
+                log("🎙️ [RECORDING] Input device \(from) unavailable (\(reason)), using \(to)")
+                errorMessage = "\(from) is unavailable, recording from \(to)"
+            case .captureFailed(let device, let reason):
+                log("❌ [RECORDING] Input device \(device) lost: \(reason)")
+                errorMessage = "Lost input device \(device): \(reason)"
+            }
+        }
+    }
+
     func stopRecording() {
         log("⏹️ [RECORDING] Stopping recording - Duration: \(recordingDuration)ms")
         recordingTimer?.invalidate()
@@ -482,6 +541,8 @@ final class AppState: ObservableObject {
         audioLevelTimer = nil
         audioLevel = 0.0
         smoothedAudioLevel = 0.0
+        partialTranscription = nil
+        partialRevision = 0
 
         let duration = engine.stopRecording()
         isRecording = false
@@ -493,7 +554,14 @@ final class AppState: ObservableObject {
             self?.resumeMediaPlayback()
         }
 
-        if duration > 0 {
+        if duration == Flow.noSpeech {
+            log("🔇 [RECORDING] No speech detected - skipping transcription")
+            Analytics.shared.track("Recording Without Speech", eventProperties: [
+                "duration_ms": recordingDuration,
+                "app_name": currentApp
+            ])
+            updateRecordingIndicatorVisibility()
+        } else if duration > 0 {
             log("✅ [RECORDING] Recording stopped successfully - Duration: \(duration)ms")
             Analytics.shared.track("Recording Stopped", eventProperties: [
                 "duration_ms": recordingDuration,
@@ -527,7 +595,11 @@ final class AppState: ObservableObject {
 
             await MainActor.run { [weak self] in
                 guard let self else { return }
-                if let text = result {
+                if let text = result, text.isEmpty {
+                    self.log("🔇 [TRANSCRIBE] No speech detected - nothing to paste")
+                    self.errorMessage = nil
+                    self.finishProcessing()
+                } else if let text = result {
                     self.log("✅ [TRANSCRIBE] Transcription completed - Length: \(text.count) chars")
                     self.log("📝 [TRANSCRIBE] Result: \(text.prefix(100))...")
                     self.lastTranscription = text
@@ -584,7 +656,11 @@ final class AppState: ObservableObject {
 
             await MainActor.run { [weak self] in
                 guard let self else { return }
-                if let text = result {
+                if let text = result, text.isEmpty {
+                    self.log("🔇 [TRANSCRIBE] No speech detected in retried recording")
+                    self.errorMessage = nil
+                    self.finishProcessing()
+                } else if let text = result {
                     self.lastTranscription = text
                     self.errorMessage = nil
                     NSPasteboard.general.clearContents()
@@ -617,6 +693,39 @@ final class AppState: ObservableObject {
         }
     }
 
+    /// Transcribe an archived history entry again, e.g. one that failed or with another model
+    /// The entry is updated in place; nothing is pasted
+    func retranscribe(_ entry: TranscriptionSummary, provider: RetranscriptionProvider? = nil, model: String? = nil) {
+        guard entry.hasAudio else { return }
+        setProcessing(true)
+
+        Analytics.shared.track("Transcription Re-transcribe Attempted", eventProperties: [
+            "provider": provider?.rawValue ?? "active",
+            "previous_status": entry.status.rawValue
+        ])
+
+        Task.detached { [weak self] in
+            guard let self else { return }
+            let result = await Task {
+                self.engine.retranscribe(entry.id, provider: provider, model: model)
+            }.value
+
+            await MainActor.run { [weak self] in
+                guard let self else { return }
+                if let text = result {
+                    if !text.isEmpty {
+                        self.lastTranscription = text
+                    }
+                    self.errorMessage = nil
+                } else {
+                    self.errorMessage = self.engine.lastError ?? "Re-transcription failed"
+                }
+                self.refreshHistory()
+                self.finishProcessing()
+            }
+        }
+    }
+
     private func pasteText() {
         log("📌 [PASTE] Sending paste command (Cmd+V) to app: \(targetApplication?.localizedName ?? "Unknown")")
         let source = CGEventSource(stateID: .hidSystemState)
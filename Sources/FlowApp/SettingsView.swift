@@ -205,10 +205,12 @@ private struct APIKeysSection: View {
     @State private var openAIKey = ""
     @State private var geminiKey = ""
     @State private var openRouterKey = ""
+    @State private var anthropicKey = ""
     @State private var selectedProvider: CompletionProvider = .openAI
     @State private var existingOpenAIKey: String?
     @State private var existingGeminiKey: String?
     @State private var existingOpenRouterKey: String?
+    @State private var existingAnthropicKey: String?
     @State private var showSavedFeedback = false
 
     private var currentProviderHasKey: Bool {
@@ -228,7 +230,7 @@ private struct APIKeysSection: View {
 
                     FWSegmentedControl(
                         selection: $selectedProvider,
-                        options: [CompletionProvider.openAI, CompletionProvider.gemini, CompletionProvider.openRouter],
+                        options: [CompletionProvider.openAI, CompletionProvider.gemini, CompletionProvider.openRouter, CompletionProvider.anthropic],
                         label: { $0.displayName }
                     )
                     .onChange(of: selectedProvider) { _, newProvider in
@@ -269,6 +271,7 @@ private struct APIKeysSection: View {
         existingOpenAIKey = appState.engine.maskedOpenAIKey
         existingGeminiKey = appState.engine.maskedGeminiKey
         existingOpenRouterKey = appState.engine.maskedOpenRouterKey
+        existingAnthropicKey = appState.engine.maskedAnthropicKey
     }
 
     private var currentKeyBinding: Binding<String> {
@@ -276,6 +279,7 @@ private struct APIKeysSection: View {
         case .openAI: return $openAIKey
         case .gemini: return $geminiKey
         case .openRouter: return $openRouterKey
+        case .anthropic: return $anthropicKey
         }
     }
 
@@ -284,6 +288,7 @@ private struct APIKeysSection: View {
         case .openAI: return existingOpenAIKey
         case .gemini: return existingGeminiKey
         case .openRouter: return existingOpenRouterKey
+        case .anthropic: return existingAnthropicKey
         }
     }
 
@@ -295,6 +300,7 @@ private struct APIKeysSection: View {
         case .openAI: openAIKey = ""
         case .gemini: geminiKey = ""
         case .openRouter: openRouterKey = ""
+        case .anthropic: anthropicKey = ""
         }
 
         withAnimation {
@@ -354,6 +360,7 @@ private extension CompletionProvider {
         case .openAI: return "sk-..."
         case .gemini: return "AI..."
         case .openRouter: return "sk-or-v1-..."
+        case .anthropic: return "sk-ant-..."
         }
     }
 }
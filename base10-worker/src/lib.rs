@@ -27,6 +27,19 @@ struct WhisperInput {
 #[derive(Debug, Deserialize)]
 struct AudioInput {
     audio_b64: String,
+    /// Format of the encoded audio (clients without compression send WAV)
+    #[serde(default)]
+    codec: AudioCodec,
+}
+
+/// Containers Base10 Whisper decodes; the audio is forwarded unchanged
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum AudioCodec {
+    #[default]
+    Wav,
+    Flac,
+    Opus,
 }
 
 #[derive(Debug, Deserialize)]
@@ -44,10 +57,21 @@ struct CompletionParams {
     app_context: Option<String>,
     #[serde(default)]
     shortcuts_triggered: Vec<String>,
+    /// Shortcuts to expand in the transcription before it is formatted
+    #[serde(default)]
+    shortcuts: Vec<ShortcutExpansion>,
     #[serde(default)]
     voice_instruction: Option<String>,
 }
 
+#[derive(Debug, Deserialize)]
+struct ShortcutExpansion {
+    trigger: String,
+    replacement: String,
+    #[serde(default)]
+    case_sensitive: bool,
+}
+
 // ============ Base10 Types ============
 
 #[derive(Debug, Serialize)]
@@ -177,6 +201,57 @@ fn build_system_prompt(mode: &str, app_context: Option<&str>, shortcuts: &[Strin
     prompt
 }
 
+/// Replace every shortcut trigger in `text` with its expansion, longest trigger first
+/// Returns the expanded text and the expansions made, each listed once
+fn expand_shortcuts(text: &str, shortcuts: &[ShortcutExpansion]) -> (String, Vec<String>) {
+    let mut expanded = String::with_capacity(text.len());
+    let mut triggered: Vec<String> = Vec::new();
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        let matched = shortcuts
+            .iter()
+            .filter(|shortcut| !shortcut.trigger.is_empty())
+            .filter_map(|shortcut| {
+                trigger_len(rest, &shortcut.trigger, shortcut.case_sensitive)
+                    .map(|len| (len, shortcut))
+            })
+            .max_by_key(|(len, _)| *len);
+        match matched {
+            Some((len, shortcut)) => {
+                expanded.push_str(&shortcut.replacement);
+                if !triggered.contains(&shortcut.replacement) {
+                    triggered.push(shortcut.replacement.clone());
+                }
+                rest = &rest[len..];
+            }
+            None => {
+                expanded.push(c);
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+    (expanded, triggered)
+}
+
+/// Byte length of the prefix of `text` that spells `trigger`, if it starts with it
+fn trigger_len(text: &str, trigger: &str, case_sensitive: bool) -> Option<usize> {
+    if case_sensitive {
+        return text.starts_with(trigger).then_some(trigger.len());
+    }
+    let mut wanted = trigger.chars().flat_map(char::to_lowercase).peekable();
+    for (i, c) in text.char_indices() {
+        for lower in c.to_lowercase() {
+            if wanted.next() != Some(lower) {
+                return None;
+            }
+        }
+        if wanted.peek().is_none() {
+            return Some(i + c.len_utf8());
+        }
+    }
+    None
+}
+
 fn get_mode_prompt(mode: &str) -> &'static str {
     match mode {
         "formal" => {
@@ -641,6 +716,12 @@ pub async fn main(mut req: Request, env: Env, _ctx: worker::Context) -> Result<R
         Err(e) => return Response::error(format!("Invalid JSON: {}", e), 400),
     };
 
+    worker::console_log!(
+        "[DEBUG] audio codec={:?}, {} base64 bytes",
+        request.whisper_input.audio.codec,
+        request.whisper_input.audio.audio_b64.len()
+    );
+
     // Step 1: Transcribe
     let transcription = call_base10(
         &env,
@@ -672,12 +753,20 @@ pub async fn main(mut req: Request, env: Env, _ctx: worker::Context) -> Result<R
     } else {
         // Normal formatting mode
         worker::console_log!("[DEBUG] Using normal formatting mode with mode={}", &request.completion.mode);
+        // Expand shortcuts first so the prompt only names expansions the text really holds
+        let (expanded, mut shortcuts) =
+            expand_shortcuts(&transcription, &request.completion.shortcuts);
+        for replacement in &request.completion.shortcuts_triggered {
+            if expanded.contains(replacement.as_str()) && !shortcuts.contains(replacement) {
+                shortcuts.push(replacement.clone());
+            }
+        }
         call_openrouter(
             &env,
-            &transcription,
+            &expanded,
             &request.completion.mode,
             request.completion.app_context.as_deref(),
-            &request.completion.shortcuts_triggered,
+            &shortcuts,
         )
         .await?
     };
@@ -0,0 +1,300 @@
+//! On-disk archive of recorded audio
+//!
+//! Each recording is kept as a FLAC file named by the id of its
+//! `transcription_history` entry, so any past dictation can be played back,
+//! exported as WAV or transcribed again. Retention caps the archive by total
+//! size and by age, dropping the oldest recordings first.
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use serde::Serialize;
+use tracing::{debug, info, warn};
+
+use crate::AudioData;
+use crate::audio::{AudioCodec, encode_audio, pcm_to_wav};
+use crate::error::{Error, Result};
+use crate::types::TranscriptionId;
+
+const ARCHIVE_EXTENSION: &str = "flac";
+
+/// Extension of recordings still being written
+const PARTIAL_EXTENSION: &str = "part";
+
+/// How much archived audio to keep
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    /// Total size of the archive (None: unlimited)
+    pub max_bytes: Option<u64>,
+    /// Age of the oldest recording kept (None: unlimited)
+    pub max_age: Option<Duration>,
+}
+
+impl RetentionPolicy {
+    pub const DEFAULT_MAX_BYTES: u64 = 500 * 1024 * 1024;
+    pub const DEFAULT_MAX_AGE: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        Self {
+            max_bytes: Some(Self::DEFAULT_MAX_BYTES),
+            max_age: Some(Self::DEFAULT_MAX_AGE),
+        }
+    }
+}
+
+/// A recording in the archive
+#[derive(Debug, Clone, Serialize)]
+pub struct ArchivedRecording {
+    pub id: TranscriptionId,
+    pub path: PathBuf,
+    pub bytes: u64,
+    #[serde(skip)]
+    pub modified: SystemTime,
+}
+
+/// Recordings stored in one directory, keyed by history id
+#[derive(Debug, Clone)]
+pub struct AudioArchive {
+    dir: PathBuf,
+}
+
+impl AudioArchive {
+    /// Archive in `dir`, which is created on the first save
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Where the recording for `id` is (or would be) stored
+    pub fn path(&self, id: &TranscriptionId) -> PathBuf {
+        self.dir.join(format!("{id}.{ARCHIVE_EXTENSION}"))
+    }
+
+    pub fn contains(&self, id: &TranscriptionId) -> bool {
+        self.path(id).is_file()
+    }
+
+    /// Store mono 16-bit PCM for `id`, replacing any earlier recording
+    pub fn save(&self, id: &TranscriptionId, pcm: &[u8], sample_rate: u32) -> Result<PathBuf> {
+        let flac = encode_audio(pcm, sample_rate, AudioCodec::Flac)?;
+        std::fs::create_dir_all(&self.dir)?;
+
+        let path = self.path(id);
+        let partial = path.with_extension(format!("{ARCHIVE_EXTENSION}.{PARTIAL_EXTENSION}"));
+        std::fs::write(&partial, &flac)?;
+        std::fs::rename(&partial, &path)?;
+        debug!("Archived {} bytes of audio for {}", flac.len(), id);
+        Ok(path)
+    }
+
+    /// Read back the recording for `id` as mono 16-bit PCM and its sample rate
+    pub fn load(&self, id: &TranscriptionId) -> Result<(AudioData, u32)> {
+        let path = self.existing_path(id)?;
+        let flac_error =
+            |e: claxon::Error| Error::Audio(format!("Failed to read archived audio {id}: {e}"));
+        let mut reader = claxon::FlacReader::open(&path).map_err(flac_error)?;
+        let sample_rate = reader.streaminfo().sample_rate;
+
+        // Recordings are stored as 16-bit mono, so the samples are the PCM as captured
+        let mut pcm = Vec::new();
+        for sample in reader.samples() {
+            pcm.extend_from_slice(&(sample.map_err(flac_error)? as i16).to_le_bytes());
+        }
+        Ok((pcm, sample_rate))
+    }
+
+    /// Write the recording for `id` to `dest` as a WAV file
+    pub fn export_wav(&self, id: &TranscriptionId, dest: &Path) -> Result<()> {
+        let (pcm, sample_rate) = self.load(id)?;
+        std::fs::write(dest, pcm_to_wav(&pcm, sample_rate, 1))?;
+        Ok(())
+    }
+
+    /// Delete the recording for `id`; returns false if there was none
+    pub fn remove(&self, id: &TranscriptionId) -> Result<bool> {
+        match std::fs::remove_file(self.path(id)) {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// All archived recordings, oldest first
+    pub fn recordings(&self) -> Result<Vec<ArchivedRecording>> {
+        let entries = match std::fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut recordings = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some(ARCHIVE_EXTENSION) {
+                continue;
+            }
+            let Some(id) = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .and_then(|stem| TranscriptionId::parse_str(stem).ok())
+            else {
+                continue;
+            };
+            let metadata = std::fs::metadata(&path)?;
+            recordings.push(ArchivedRecording {
+                id,
+                bytes: metadata.len(),
+                modified: metadata.modified()?,
+                path,
+            });
+        }
+        recordings.sort_by_key(|recording| recording.modified);
+        Ok(recordings)
+    }
+
+    /// Delete recordings outside `policy`, returning how many were removed
+    pub fn prune(&self, policy: &RetentionPolicy) -> Result<usize> {
+        self.prune_at(policy, SystemTime::now())
+    }
+
+    fn prune_at(&self, policy: &RetentionPolicy, now: SystemTime) -> Result<usize> {
+        let recordings = self.recordings()?;
+        let mut total: u64 = recordings.iter().map(|recording| recording.bytes).sum();
+        let mut removed = 0;
+
+        for recording in recordings {
+            let expired = policy.max_age.is_some_and(|max_age| {
+                now.duration_since(recording.modified)
+                    .is_ok_and(|age| age > max_age)
+            });
+            let over_size = policy.max_bytes.is_some_and(|max_bytes| total > max_bytes);
+            if !expired && !over_size {
+                // Everything after this is newer, and the archive already fits
+                break;
+            }
+
+            match std::fs::remove_file(&recording.path) {
+                Ok(()) => {
+                    total -= recording.bytes;
+                    removed += 1;
+                }
+                Err(e) => warn!("Failed to remove archived audio {}: {}", recording.id, e),
+            }
+        }
+
+        if removed > 0 {
+            info!("Pruned {} archived recordings", removed);
+        }
+        Ok(removed)
+    }
+
+    fn existing_path(&self, id: &TranscriptionId) -> Result<PathBuf> {
+        let path = self.path(id);
+        if path.is_file() {
+            Ok(path)
+        } else {
+            Err(Error::Audio(format!("No archived audio for {id}")))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::{pcm_to_samples, read_wav, samples_to_pcm};
+    use std::fs::File;
+    use uuid::Uuid;
+
+    fn tone(samples: usize) -> AudioData {
+        let samples: Vec<f32> = (0..samples)
+            .map(|i| 0.3 * (i as f32 * 440.0 * std::f32::consts::TAU / 16000.0).sin())
+            .collect();
+        samples_to_pcm(&samples)
+    }
+
+    #[test]
+    fn test_archive_roundtrip_and_export() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive = AudioArchive::new(dir.path().join("recordings"));
+        let id = Uuid::new_v4();
+        assert!(!archive.contains(&id));
+        assert!(archive.load(&id).is_err());
+
+        let pcm = tone(16000);
+        archive.save(&id, &pcm, 16000).unwrap();
+        assert!(archive.contains(&id));
+        // FLAC is lossless
+        assert_eq!(archive.load(&id).unwrap(), (pcm.clone(), 16000));
+
+        let wav = dir.path().join("export.wav");
+        archive.export_wav(&id, &wav).unwrap();
+        let (samples, rate) = read_wav(&wav).unwrap();
+        assert_eq!(rate, 16000);
+        assert_eq!(samples, pcm_to_samples(&pcm));
+
+        assert!(archive.remove(&id).unwrap());
+        assert!(!archive.remove(&id).unwrap());
+        assert!(archive.recordings().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_prune_by_age_then_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive = AudioArchive::new(dir.path());
+        let now = SystemTime::now();
+        let day = Duration::from_secs(24 * 60 * 60);
+
+        // Four recordings made 10, 3, 2 and 1 days ago
+        let ids: Vec<TranscriptionId> = [10u32, 3, 2, 1]
+            .iter()
+            .map(|&days| {
+                let id = Uuid::new_v4();
+                let path = archive.save(&id, &tone(8000), 16000).unwrap();
+                File::options()
+                    .write(true)
+                    .open(path)
+                    .unwrap()
+                    .set_modified(now - day * days)
+                    .unwrap();
+                id
+            })
+            .collect();
+        std::fs::write(dir.path().join("notes.txt"), "not a recording").unwrap();
+        let recordings = archive.recordings().unwrap();
+        assert_eq!(
+            recordings.iter().map(|r| r.id).collect::<Vec<_>>(),
+            ids,
+            "oldest first"
+        );
+        let size = recordings[1].bytes + recordings[2].bytes + recordings[3].bytes;
+
+        let unlimited = RetentionPolicy {
+            max_bytes: None,
+            max_age: None,
+        };
+        assert_eq!(archive.prune_at(&unlimited, now).unwrap(), 0);
+
+        // A week removes the 10-day-old recording
+        let week = RetentionPolicy {
+            max_bytes: None,
+            max_age: Some(day * 7),
+        };
+        assert_eq!(archive.prune_at(&week, now).unwrap(), 1);
+        assert!(!archive.contains(&ids[0]));
+
+        // Shrinking the budget removes the oldest of the rest
+        let small = RetentionPolicy {
+            max_bytes: Some(size - 1),
+            max_age: None,
+        };
+        assert_eq!(archive.prune_at(&small, now).unwrap(), 1);
+        let kept: Vec<_> = archive.recordings().unwrap().iter().map(|r| r.id).collect();
+        assert_eq!(kept, ids[2..]);
+        assert!(dir.path().join("notes.txt").exists());
+    }
+}
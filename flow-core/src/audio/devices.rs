@@ -0,0 +1,193 @@
+//! Input device discovery and selection
+
+use cpal::traits::{DeviceTrait, HostTrait};
+use cpal::{Device, Host, SupportedStreamConfigRange};
+use serde::Serialize;
+
+use crate::error::{Error, Result};
+
+/// An input device and the stream configurations it supports
+#[derive(Debug, Clone, Serialize)]
+pub struct InputDevice {
+    /// Stable identifier that survives reconnects and reboots where the host allows it
+    pub id: Option<String>,
+    pub name: String,
+    pub is_default: bool,
+    pub configs: Vec<InputConfigRange>,
+}
+
+/// A range of sample rates supported for one channel count and sample format
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct InputConfigRange {
+    pub channels: u16,
+    pub min_sample_rate: u32,
+    pub max_sample_rate: u32,
+    pub sample_format: String,
+}
+
+impl From<SupportedStreamConfigRange> for InputConfigRange {
+    fn from(range: SupportedStreamConfigRange) -> Self {
+        Self {
+            channels: range.channels(),
+            min_sample_rate: range.min_sample_rate(),
+            max_sample_rate: range.max_sample_rate(),
+            sample_format: range.sample_format().to_string(),
+        }
+    }
+}
+
+/// A change of input device during capture
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AudioDeviceEvent {
+    /// Capture moved to the default input because `from` was unavailable
+    FailedOver {
+        from: String,
+        to: String,
+        reason: String,
+    },
+    /// The input device went away and no other could be opened
+    CaptureFailed { device: String, reason: String },
+}
+
+/// List the input devices of the default host
+pub fn list_input_devices() -> Result<Vec<InputDevice>> {
+    let host = cpal::default_host();
+    let default = host
+        .default_input_device()
+        .map(|device| device_key(&device));
+
+    let devices = host
+        .input_devices()
+        .map_err(|e| Error::Audio(format!("Failed to list input devices: {e}")))?;
+
+    Ok(devices
+        .map(|device| {
+            let configs = device
+                .supported_input_configs()
+                .map(|ranges| ranges.map(InputConfigRange::from).collect())
+                .unwrap_or_default();
+            InputDevice {
+                is_default: default.as_ref() == Some(&device_key(&device)),
+                id: device_id(&device),
+                name: device_name(&device),
+                configs,
+            }
+        })
+        .collect())
+}
+
+/// Find an input device by id, or failing that by name (case-insensitive)
+pub fn find_input_device(host: &Host, wanted: &str) -> Option<Device> {
+    let devices: Vec<Device> = host.input_devices().ok()?.collect();
+    let id_match = devices
+        .iter()
+        .position(|device| device_id(device).as_deref() == Some(wanted));
+    let index = id_match.or_else(|| {
+        devices
+            .iter()
+            .position(|device| name_matches(wanted, &device_name(device)))
+    })?;
+    devices.into_iter().nth(index)
+}
+
+/// Pick the device `wanted` refers to: an exact id match, else a name match
+pub fn select_input_device<'a>(
+    devices: &'a [InputDevice],
+    wanted: &str,
+) -> Option<&'a InputDevice> {
+    devices
+        .iter()
+        .find(|device| device.id.as_deref() == Some(wanted))
+        .or_else(|| {
+            devices
+                .iter()
+                .find(|device| name_matches(wanted, &device.name))
+        })
+}
+
+/// The host's default input device
+pub(super) fn default_input_device(host: &Host) -> Result<Device> {
+    host.default_input_device()
+        .ok_or_else(|| Error::Audio("No input device available".to_string()))
+}
+
+pub(super) fn device_name(device: &Device) -> String {
+    device
+        .description()
+        .map(|description| description.name().to_string())
+        .unwrap_or_else(|_| "Unknown".to_string())
+}
+
+fn device_id(device: &Device) -> Option<String> {
+    device.id().ok().map(|id| id.to_string())
+}
+
+/// Identifies a device for comparison, falling back to its name without an id
+fn device_key(device: &Device) -> String {
+    device_id(device).unwrap_or_else(|| device_name(device))
+}
+
+fn name_matches(wanted: &str, name: &str) -> bool {
+    wanted.trim().eq_ignore_ascii_case(name.trim())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_name_matches() {
+        assert!(name_matches("USB Headset", "usb headset"));
+        assert!(name_matches(
+            " MacBook Pro Microphone",
+            "MacBook Pro Microphone "
+        ));
+        assert!(!name_matches("USB Headset", "USB Headset 2"));
+    }
+
+    fn device(id: Option<&str>, name: &str) -> InputDevice {
+        InputDevice {
+            id: id.map(str::to_string),
+            name: name.to_string(),
+            is_default: false,
+            configs: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_select_prefers_id() {
+        let devices = [
+            device(Some("coreaudio:headset-a"), "USB Headset"),
+            device(Some("coreaudio:headset-b"), "USB Headset"),
+            device(None, "coreaudio:headset-b"),
+        ];
+        let selected = select_input_device(&devices, "coreaudio:headset-b").unwrap();
+        assert_eq!(selected.name, "USB Headset");
+
+        let selected = select_input_device(&devices, "usb headset").unwrap();
+        assert_eq!(selected.id.as_deref(), Some("coreaudio:headset-a"));
+        assert!(select_input_device(&devices, "Webcam").is_none());
+    }
+
+    #[test]
+    fn test_event_json() {
+        let event = AudioDeviceEvent::FailedOver {
+            from: "USB Headset".to_string(),
+            to: "MacBook Pro Microphone".to_string(),
+            reason: "unplugged".to_string(),
+        };
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["type"], "failed_over");
+        assert_eq!(json["from"], "USB Headset");
+
+        let event = AudioDeviceEvent::CaptureFailed {
+            device: "USB Headset".to_string(),
+            reason: "No input device available".to_string(),
+        };
+        assert_eq!(
+            serde_json::to_value(&event).unwrap()["type"],
+            "capture_failed"
+        );
+    }
+}
@@ -0,0 +1,363 @@
+//! Clean-up applied to captured speech before it reaches a provider
+//!
+//! Three stages run in order, each switchable on its own: a high-pass filter
+//! removing rumble and mains hum, spectral noise suppression subtracting the
+//! steady background measured in the quietest frames, and automatic gain
+//! control bringing speech to a consistent level, with a peak limiter so
+//! boosted audio never clips.
+
+use realfft::RealFftPlanner;
+use realfft::num_complex::Complex;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+/// Corner frequency of the high-pass filter; speech carries little below it
+const HIGH_PASS_CUTOFF_HZ: f64 = 100.0;
+/// Section Q factors of a 4th-order Butterworth filter
+const HIGH_PASS_Q: [f64; 2] = [0.541_196_1, 1.306_563];
+
+/// Noise suppression analysis frame (32ms at 16kHz), hopped by half
+const NOISE_FRAME: usize = 512;
+/// Share of the quietest frames taken to be background noise
+const NOISE_PROFILE_FRACTION: f64 = 0.1;
+/// Noise power subtracted, as a multiple of the estimate, to cover its variance
+const NOISE_OVER_SUBTRACTION: f32 = 2.0;
+/// Most a frequency bin is attenuated (-20 dB); deeper cuts leave "musical" artefacts
+const NOISE_GAIN_FLOOR: f32 = 0.1;
+/// Neighbouring bins either side whose power is averaged before the gain is chosen,
+/// together with the previous frame, so random noise peaks aren't mistaken for signal
+const NOISE_SMOOTHING_BINS: usize = 2;
+
+/// Speech level the gain control aims for (RMS dBFS)
+const AGC_TARGET_DB: f32 = -20.0;
+const AGC_MAX_GAIN_DB: f32 = 20.0;
+const AGC_MIN_GAIN_DB: f32 = -20.0;
+/// Blocks quieter than this (dBFS) don't move the measured level, so pauses aren't boosted
+const AGC_GATE_DB: f32 = -50.0;
+const AGC_BLOCK_MS: u32 = 10;
+/// How quickly the measured level follows speech getting louder and quieter
+const AGC_ATTACK_MS: f32 = 50.0;
+const AGC_RELEASE_MS: f32 = 1500.0;
+/// Smoothing of gain changes between blocks
+const AGC_GAIN_SMOOTHING_MS: f32 = 10.0;
+/// Peak ceiling of the limiter (-1 dBFS)
+const LIMITER_CEILING: f32 = 0.891;
+const LIMITER_RELEASE_MS: f32 = 50.0;
+
+/// Which stages of the processing chain run on captured audio
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AudioProcessing {
+    /// Remove rumble and hum below 100Hz (on by default)
+    pub high_pass: bool,
+    /// Suppress steady background noise such as fans and air conditioning (off by default)
+    pub noise_suppression: bool,
+    /// Bring speech to a consistent level and limit peaks (on by default)
+    pub agc: bool,
+}
+
+impl Default for AudioProcessing {
+    fn default() -> Self {
+        Self {
+            high_pass: true,
+            noise_suppression: false,
+            agc: true,
+        }
+    }
+}
+
+impl AudioProcessing {
+    /// Every stage off
+    pub fn none() -> Self {
+        Self {
+            high_pass: false,
+            noise_suppression: false,
+            agc: false,
+        }
+    }
+
+    /// Run the enabled stages over mono audio in place
+    pub fn apply(&self, samples: &mut [f32], sample_rate: u32) {
+        if samples.is_empty() || sample_rate == 0 {
+            return;
+        }
+        if self.high_pass {
+            high_pass(samples, sample_rate);
+        }
+        if self.noise_suppression {
+            suppress_noise(samples);
+        }
+        if self.agc {
+            automatic_gain(samples, sample_rate);
+        }
+    }
+}
+
+/// 4th-order Butterworth high-pass filter at `HIGH_PASS_CUTOFF_HZ`
+pub fn high_pass(samples: &mut [f32], sample_rate: u32) {
+    let w0 = std::f64::consts::TAU * HIGH_PASS_CUTOFF_HZ / sample_rate as f64;
+    if w0 >= std::f64::consts::PI {
+        return;
+    }
+    let cos = w0.cos();
+
+    for q in HIGH_PASS_Q {
+        // RBJ cookbook biquad, normalised by a0
+        let alpha = w0.sin() / (2.0 * q);
+        let a0 = 1.0 + alpha;
+        let b0 = (1.0 + cos) / 2.0 / a0;
+        let b1 = -(1.0 + cos) / a0;
+        let b2 = b0;
+        let a1 = -2.0 * cos / a0;
+        let a2 = (1.0 - alpha) / a0;
+
+        // Transposed direct form II
+        let (mut z1, mut z2) = (0.0f64, 0.0f64);
+        for sample in samples.iter_mut() {
+            let x = *sample as f64;
+            let y = b0 * x + z1;
+            z1 = b1 * x - a1 * y + z2;
+            z2 = b2 * x - a2 * y;
+            *sample = y as f32;
+        }
+    }
+}
+
+/// Spectral subtraction against the background heard in the quietest frames
+///
+/// Frames overlap by half under a square-root Hann window on both analysis and
+/// synthesis, so bins left alone come back unchanged.
+pub fn suppress_noise(samples: &mut [f32]) {
+    let hop = NOISE_FRAME / 2;
+    if samples.len() < NOISE_FRAME {
+        return;
+    }
+
+    let window: Vec<f32> = (0..NOISE_FRAME)
+        .map(|n| (std::f32::consts::PI * n as f32 / NOISE_FRAME as f32).sin())
+        .collect();
+
+    // Pad so every sample falls under two frames
+    let frames = samples.len().div_ceil(hop) + 1;
+    let mut padded = vec![0.0f32; (frames + 1) * hop];
+    padded[hop..hop + samples.len()].copy_from_slice(samples);
+
+    let mut planner = RealFftPlanner::<f32>::new();
+    let forward = planner.plan_fft_forward(NOISE_FRAME);
+    let inverse = planner.plan_fft_inverse(NOISE_FRAME);
+
+    let mut frame = forward.make_input_vec();
+    let spectra: Vec<Vec<Complex<f32>>> = (0..frames)
+        .map(|index| {
+            let start = index * hop;
+            for ((slot, sample), w) in frame
+                .iter_mut()
+                .zip(&padded[start..start + NOISE_FRAME])
+                .zip(&window)
+            {
+                *slot = sample * w;
+            }
+            let mut spectrum = forward.make_output_vec();
+            let _ = forward.process(&mut frame, &mut spectrum);
+            spectrum
+        })
+        .collect();
+
+    // Average power spectrum of the quietest frames
+    let energy = |spectrum: &[Complex<f32>]| spectrum.iter().map(|c| c.norm_sqr()).sum::<f32>();
+    let mut by_energy: Vec<usize> = (0..frames).collect();
+    by_energy.sort_by(|&a, &b| energy(&spectra[a]).total_cmp(&energy(&spectra[b])));
+    let quiet = ((frames as f64 * NOISE_PROFILE_FRACTION).ceil() as usize).max(1);
+    let bins = NOISE_FRAME / 2 + 1;
+    let mut noise = vec![0.0f32; bins];
+    for &index in &by_energy[..quiet] {
+        for (power, bin) in noise.iter_mut().zip(&spectra[index]) {
+            *power += bin.norm_sqr() / quiet as f32;
+        }
+    }
+
+    let mut output = vec![0.0f32; padded.len()];
+    let mut time = inverse.make_output_vec();
+    let mut previous = vec![0.0f32; bins];
+    for (index, mut spectrum) in spectra.into_iter().enumerate() {
+        let current: Vec<f32> = spectrum.iter().map(|bin| bin.norm_sqr()).collect();
+        let gains: Vec<f32> = (0..bins)
+            .map(|k| {
+                let near = k.saturating_sub(NOISE_SMOOTHING_BINS)
+                    ..(k + NOISE_SMOOTHING_BINS + 1).min(bins);
+                let count = (2 * near.len()) as f32;
+                let power = (current[near.clone()].iter().sum::<f32>()
+                    + previous[near].iter().sum::<f32>())
+                    / count;
+                if power > 0.0 {
+                    (1.0 - NOISE_OVER_SUBTRACTION * noise[k] / power)
+                        .max(NOISE_GAIN_FLOOR * NOISE_GAIN_FLOOR)
+                        .sqrt()
+                } else {
+                    NOISE_GAIN_FLOOR
+                }
+            })
+            .collect();
+        for (bin, gain) in spectrum.iter_mut().zip(gains) {
+            *bin *= gain;
+        }
+        previous = current;
+        // Real input leaves no imaginary part at DC or Nyquist; rounding might
+        spectrum[0].im = 0.0;
+        spectrum[bins - 1].im = 0.0;
+
+        if let Err(e) = inverse.process(&mut spectrum, &mut time) {
+            warn!("Noise suppression failed, leaving audio as is: {}", e);
+            return;
+        }
+        let start = index * hop;
+        for ((out, sample), w) in output[start..start + NOISE_FRAME]
+            .iter_mut()
+            .zip(&time)
+            .zip(&window)
+        {
+            // The inverse transform is unnormalised
+            *out += sample * w / NOISE_FRAME as f32;
+        }
+    }
+
+    samples.copy_from_slice(&output[hop..hop + samples.len()]);
+}
+
+/// Level speech to `AGC_TARGET_DB`, then keep peaks under `LIMITER_CEILING`
+///
+/// The level is measured over short blocks, rising quickly as speech gets
+/// louder and falling slowly through pauses; blocks below the gate are ignored
+/// so silence and faint background are never pulled up to speech level.
+pub fn automatic_gain(samples: &mut [f32], sample_rate: u32) {
+    let block = (sample_rate * AGC_BLOCK_MS / 1000).max(1) as usize;
+    let per_block = |ms: f32| (-(AGC_BLOCK_MS as f32) / ms).exp();
+    let per_sample = |ms: f32| (-1000.0 / (ms * sample_rate as f32)).exp();
+    let (attack, release) = (per_block(AGC_ATTACK_MS), per_block(AGC_RELEASE_MS));
+    let smoothing = per_sample(AGC_GAIN_SMOOTHING_MS);
+    let limiter_release = per_sample(LIMITER_RELEASE_MS);
+
+    let mut level_db: Option<f32> = None;
+    let mut gain = 1.0f32;
+    let mut limiter = 1.0f32;
+
+    for chunk in samples.chunks_mut(block) {
+        let rms = (chunk.iter().map(|s| s * s).sum::<f32>() / chunk.len() as f32).sqrt();
+        let block_db = 20.0 * rms.max(1e-9).log10();
+        if block_db > AGC_GATE_DB {
+            level_db = Some(match level_db {
+                None => block_db,
+                Some(level) => {
+                    let coeff = if block_db > level { attack } else { release };
+                    block_db + (level - block_db) * coeff
+                }
+            });
+        }
+        let target_gain = level_db.map_or(1.0, |level| {
+            10f32.powf((AGC_TARGET_DB - level).clamp(AGC_MIN_GAIN_DB, AGC_MAX_GAIN_DB) / 20.0)
+        });
+
+        for sample in chunk.iter_mut() {
+            gain = target_gain + (gain - target_gain) * smoothing;
+            let boosted = *sample * gain;
+            if (boosted * limiter).abs() > LIMITER_CEILING {
+                limiter = LIMITER_CEILING / boosted.abs();
+            }
+            *sample = boosted * limiter;
+            limiter = 1.0 + (limiter - 1.0) * limiter_release;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::tests::{db, sine, tone_amplitude};
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    const RATE: u32 = 16000;
+
+    fn noise(amplitude: f32, len: usize) -> Vec<f32> {
+        let mut rng = StdRng::seed_from_u64(7);
+        (0..len)
+            .map(|_| rng.random_range(-amplitude..amplitude))
+            .collect()
+    }
+
+    fn rms(samples: &[f32]) -> f32 {
+        (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt()
+    }
+
+    #[test]
+    fn test_high_pass_removes_hum() {
+        let mut hum = sine(50.0, RATE, 1000);
+        high_pass(&mut hum, RATE);
+        assert!(db(tone_amplitude(&hum, RATE, 50.0) / 0.5) < -20.0);
+
+        let mut voice = sine(1000.0, RATE, 1000);
+        high_pass(&mut voice, RATE);
+        assert!(db(tone_amplitude(&voice, RATE, 1000.0) / 0.5).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_noise_suppression_keeps_tone() {
+        // Fan noise throughout, a "voice" in the middle second
+        let mut samples = noise(0.05, 2 * RATE as usize);
+        let tone = sine(1000.0, RATE, 1000);
+        let middle = RATE as usize / 2..RATE as usize * 3 / 2;
+        for (sample, tone) in samples[middle.clone()].iter_mut().zip(&tone) {
+            *sample += tone;
+        }
+        let quiet = 0..RATE as usize * 2 / 5;
+        let noise_before = rms(&samples[quiet.clone()]);
+
+        suppress_noise(&mut samples);
+
+        let reduction = db(rms(&samples[quiet]) / noise_before);
+        assert!(reduction < -12.0, "noise only down {reduction} dB");
+        let tone_gain = db(tone_amplitude(&samples[middle], RATE, 1000.0) / 0.5);
+        assert!(tone_gain.abs() < 1.0, "tone changed by {tone_gain} dB");
+    }
+
+    #[test]
+    fn test_agc_levels_quiet_speech() {
+        let mut quiet: Vec<f32> = sine(1000.0, RATE, 2000).iter().map(|s| s * 0.06).collect();
+        automatic_gain(&mut quiet, RATE);
+        let level = db(rms(&quiet[RATE as usize..]));
+        assert!((level - AGC_TARGET_DB).abs() < 1.0, "level {level} dBFS");
+
+        // Background below the gate is left alone
+        let mut hiss = noise(0.0005, RATE as usize);
+        let before = hiss.clone();
+        automatic_gain(&mut hiss, RATE);
+        assert_eq!(hiss, before);
+    }
+
+    #[test]
+    fn test_limiter_catches_sudden_peaks() {
+        // A shout straight after quiet speech, while the gain is still high
+        let mut samples: Vec<f32> = sine(1000.0, RATE, 1000).iter().map(|s| s * 0.06).collect();
+        samples.extend(sine(1000.0, RATE, 500).iter().map(|s| s * 1.9));
+        automatic_gain(&mut samples, RATE);
+        let peak = samples.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+        assert!(peak <= LIMITER_CEILING + 1e-6, "peak {peak}");
+    }
+
+    #[test]
+    fn test_stages_toggle_independently() {
+        let original = noise(0.2, RATE as usize);
+        let mut samples = original.clone();
+        AudioProcessing::none().apply(&mut samples, RATE);
+        assert_eq!(samples, original);
+
+        let only_high_pass = AudioProcessing {
+            high_pass: true,
+            ..AudioProcessing::none()
+        };
+        only_high_pass.apply(&mut samples, RATE);
+        let mut expected = original.clone();
+        high_pass(&mut expected, RATE);
+        assert_eq!(samples, expected);
+    }
+}
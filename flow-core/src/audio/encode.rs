@@ -0,0 +1,316 @@
+//! Encoding recordings for upload
+//!
+//! Cloud providers are sent FLAC (lossless, roughly half the size of WAV) or
+//! Opus in Ogg (a fraction of that) rather than raw PCM, depending on what
+//! each API accepts.
+
+use flacenc::component::BitRepr;
+use flacenc::error::Verify;
+use ogg::writing::{PacketWriteEndInfo, PacketWriter};
+use opus_rs::{Application, OpusEncoder};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+use crate::error::{Error, Result};
+
+use super::{SPEECH_SAMPLE_RATE, pcm_to_samples, resample};
+
+/// Format of uploaded audio
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AudioCodec {
+    /// 16-bit PCM in a WAV container
+    #[default]
+    Wav,
+    /// Lossless FLAC
+    Flac,
+    /// Opus in an Ogg container
+    Opus,
+}
+
+impl AudioCodec {
+    pub fn mime_type(self) -> &'static str {
+        match self {
+            Self::Wav => "audio/wav",
+            Self::Flac => "audio/flac",
+            Self::Opus => "audio/ogg",
+        }
+    }
+
+    /// File name for multipart uploads, whose extension identifies the format
+    pub fn file_name(self) -> &'static str {
+        match self {
+            Self::Wav => "audio.wav",
+            Self::Flac => "audio.flac",
+            Self::Opus => "audio.ogg",
+        }
+    }
+}
+
+/// Opus bitrate, transparent for speech at 16kHz
+const OPUS_BITRATE: i32 = 24_000;
+const OPUS_FRAME_MS: u32 = 20;
+/// Rates the Opus encoder takes directly; others are resampled to 16kHz
+const OPUS_SAMPLE_RATES: [u32; 5] = [8000, 12000, 16000, 24000, 48000];
+/// Encoder lookahead to skip on decode, in 48kHz samples
+const OPUS_PRE_SKIP: u16 = 312;
+/// Ogg granule positions count 48kHz samples whatever the input rate
+const OPUS_GRANULE_RATE: u64 = 48_000;
+/// Ogg logical stream serial number
+const OGG_SERIAL: u32 = 0x466c_6f77;
+
+/// Encode mono 16-bit PCM with `codec`
+pub fn encode_audio(pcm: &[u8], sample_rate: u32, codec: AudioCodec) -> Result<Vec<u8>> {
+    match codec {
+        AudioCodec::Wav => Ok(pcm_to_wav(pcm, sample_rate, 1)),
+        AudioCodec::Flac => encode_flac(pcm, sample_rate),
+        AudioCodec::Opus => encode_opus(pcm, sample_rate),
+    }
+}
+
+/// Encode mono 16-bit PCM for upload, falling back to WAV if `codec` fails
+///
+/// Returns the encoded audio and the codec actually used.
+pub fn encode_for_upload(pcm: &[u8], sample_rate: u32, codec: AudioCodec) -> (Vec<u8>, AudioCodec) {
+    match encode_audio(pcm, sample_rate, codec) {
+        Ok(data) => {
+            debug!(
+                "Encoded {} bytes of PCM as {:?}: {} bytes",
+                pcm.len(),
+                codec,
+                data.len()
+            );
+            (data, codec)
+        }
+        Err(e) => {
+            warn!("Failed to encode audio as {:?}, sending WAV: {}", codec, e);
+            (pcm_to_wav(pcm, sample_rate, 1), AudioCodec::Wav)
+        }
+    }
+}
+
+/// Wrap 16-bit PCM in a WAV header
+pub fn pcm_to_wav(pcm: &[u8], sample_rate: u32, channels: u16) -> Vec<u8> {
+    let bits_per_sample: u16 = 16;
+    let byte_rate = sample_rate * u32::from(channels) * u32::from(bits_per_sample) / 8;
+    let block_align = channels * bits_per_sample / 8;
+    let data_size = pcm.len() as u32;
+    let file_size = 36 + data_size;
+
+    let mut wav = Vec::with_capacity(44 + pcm.len());
+
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&file_size.to_le_bytes());
+    wav.extend_from_slice(b"WAVE");
+    wav.extend_from_slice(b"fmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes());
+    wav.extend_from_slice(&channels.to_le_bytes());
+    wav.extend_from_slice(&sample_rate.to_le_bytes());
+    wav.extend_from_slice(&byte_rate.to_le_bytes());
+    wav.extend_from_slice(&block_align.to_le_bytes());
+    wav.extend_from_slice(&bits_per_sample.to_le_bytes());
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_size.to_le_bytes());
+    wav.extend_from_slice(pcm);
+
+    wav
+}
+
+fn encode_flac(pcm: &[u8], sample_rate: u32) -> Result<Vec<u8>> {
+    if pcm.len() < 2 {
+        return Err(Error::Audio("No audio to encode".to_string()));
+    }
+
+    let samples: Vec<i32> = pcm
+        .chunks_exact(2)
+        .map(|bytes| i16::from_le_bytes([bytes[0], bytes[1]]) as i32)
+        .collect();
+
+    let config = flacenc::config::Encoder::default()
+        .into_verified()
+        .map_err(|(_, e)| Error::Audio(format!("Invalid FLAC encoder config: {e}")))?;
+    let source = flacenc::source::MemSource::from_samples(&samples, 1, 16, sample_rate as usize);
+    let stream = flacenc::encode_with_fixed_block_size(&config, source, config.block_size)
+        .map_err(|e| Error::Audio(format!("Failed to encode FLAC: {e}")))?;
+
+    let mut sink = flacenc::bitsink::ByteSink::new();
+    stream
+        .write(&mut sink)
+        .map_err(|e| Error::Audio(format!("Failed to write FLAC: {e}")))?;
+    Ok(sink.into_inner())
+}
+
+fn encode_opus(pcm: &[u8], sample_rate: u32) -> Result<Vec<u8>> {
+    let samples = pcm_to_samples(pcm);
+    if samples.is_empty() {
+        return Err(Error::Audio("No audio to encode".to_string()));
+    }
+    let (samples, sample_rate) = if OPUS_SAMPLE_RATES.contains(&sample_rate) {
+        (samples, sample_rate)
+    } else {
+        (
+            resample(&samples, sample_rate, SPEECH_SAMPLE_RATE),
+            SPEECH_SAMPLE_RATE,
+        )
+    };
+
+    let opus_error = |e: &str| Error::Audio(format!("Failed to encode Opus: {e}"));
+    let mut encoder =
+        OpusEncoder::new(sample_rate as i32, 1, Application::Voip).map_err(opus_error)?;
+    encoder.bitrate_bps = OPUS_BITRATE;
+    // Constant bitrate keeps the upload size predictable
+    encoder.use_cbr = true;
+
+    let ogg_error = |e: std::io::Error| Error::Audio(format!("Failed to write Ogg: {e}"));
+    let mut writer = PacketWriter::new(Vec::new());
+    writer
+        .write_packet(
+            opus_head(sample_rate),
+            OGG_SERIAL,
+            PacketWriteEndInfo::EndPage,
+            0,
+        )
+        .map_err(ogg_error)?;
+    writer
+        .write_packet(opus_tags(), OGG_SERIAL, PacketWriteEndInfo::EndPage, 0)
+        .map_err(ogg_error)?;
+
+    let frame_len = (sample_rate * OPUS_FRAME_MS / 1000) as usize;
+    let frame_granules = OPUS_GRANULE_RATE * OPUS_FRAME_MS as u64 / 1000;
+    // The last page's granule position marks where the audio really ends
+    let end_granule =
+        OPUS_PRE_SKIP as u64 + samples.len() as u64 * OPUS_GRANULE_RATE / sample_rate as u64;
+    let frame_count = samples.len().div_ceil(frame_len);
+
+    let mut frame = vec![0.0f32; frame_len];
+    let mut packet = [0u8; 1500];
+    for (index, chunk) in samples.chunks(frame_len).enumerate() {
+        frame[..chunk.len()].copy_from_slice(chunk);
+        frame[chunk.len()..].fill(0.0);
+        let len = encoder
+            .encode(&frame, frame_len, &mut packet)
+            .map_err(opus_error)?;
+
+        let (end, granule) = if index + 1 == frame_count {
+            (PacketWriteEndInfo::EndStream, end_granule)
+        } else {
+            (
+                PacketWriteEndInfo::NormalPacket,
+                OPUS_PRE_SKIP as u64 + (index as u64 + 1) * frame_granules,
+            )
+        };
+        writer
+            .write_packet(packet[..len].to_vec(), OGG_SERIAL, end, granule)
+            .map_err(ogg_error)?;
+    }
+
+    Ok(writer.into_inner())
+}
+
+/// Ogg Opus identification header (RFC 7845 section 5.1)
+fn opus_head(sample_rate: u32) -> Vec<u8> {
+    let mut head = b"OpusHead".to_vec();
+    head.push(1); // version
+    head.push(1); // channels
+    head.extend(OPUS_PRE_SKIP.to_le_bytes());
+    head.extend(sample_rate.to_le_bytes());
+    head.extend(0i16.to_le_bytes()); // output gain
+    head.push(0); // mono/stereo channel mapping
+    head
+}
+
+/// Ogg Opus comment header with no tags (RFC 7845 section 5.2)
+fn opus_tags() -> Vec<u8> {
+    let vendor = b"flow";
+    let mut tags = b"OpusTags".to_vec();
+    tags.extend((vendor.len() as u32).to_le_bytes());
+    tags.extend(vendor);
+    tags.extend(0u32.to_le_bytes());
+    tags
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::samples_to_pcm;
+    use std::io::Cursor;
+
+    /// Two seconds of a 16kHz voiced-sound stand-in: a few harmonics of 180 Hz
+    fn speech_like() -> Vec<u8> {
+        let samples: Vec<f32> = (0..32000)
+            .map(|i| {
+                let t = i as f32 / 16000.0;
+                (1..=4)
+                    .map(|h| (t * 180.0 * h as f32 * std::f32::consts::TAU).sin() * 0.2 / h as f32)
+                    .sum()
+            })
+            .collect();
+        samples_to_pcm(&samples)
+    }
+
+    #[test]
+    fn test_flac_is_lossless() {
+        let pcm = speech_like();
+        let flac = encode_audio(&pcm, 16000, AudioCodec::Flac).unwrap();
+        assert_eq!(&flac[..4], b"fLaC");
+        assert!(flac.len() < pcm.len() / 2, "{} bytes", flac.len());
+
+        let mut reader = claxon::FlacReader::new(Cursor::new(flac)).unwrap();
+        assert_eq!(reader.streaminfo().sample_rate, 16000);
+        let decoded: Vec<i32> = reader.samples().map(|s| s.unwrap()).collect();
+        let original: Vec<i32> = pcm
+            .chunks_exact(2)
+            .map(|b| i16::from_le_bytes([b[0], b[1]]) as i32)
+            .collect();
+        assert_eq!(decoded, original);
+    }
+
+    #[test]
+    fn test_opus_in_ogg() {
+        let pcm = speech_like();
+        let ogg = encode_audio(&pcm, 16000, AudioCodec::Opus).unwrap();
+        assert!(ogg.len() < pcm.len() / 8, "{} bytes", ogg.len());
+
+        let mut reader = ogg::PacketReader::new(Cursor::new(ogg));
+        let head = reader.read_packet().unwrap().unwrap();
+        assert_eq!(&head.data[..8], b"OpusHead");
+        assert_eq!(
+            u32::from_le_bytes(head.data[12..16].try_into().unwrap()),
+            16000
+        );
+        let tags = reader.read_packet().unwrap().unwrap();
+        assert_eq!(&tags.data[..8], b"OpusTags");
+
+        let mut decoder = opus_rs::OpusDecoder::new(16000, 1).unwrap();
+        let mut decoded = Vec::new();
+        let mut frame = [0.0f32; 320];
+        let mut last_granule = 0;
+        while let Some(packet) = reader.read_packet().unwrap() {
+            let len = decoder.decode(&packet.data, 320, &mut frame).unwrap();
+            decoded.extend_from_slice(&frame[..len]);
+            last_granule = packet.absgp_page();
+        }
+        assert_eq!(decoded.len(), 32000);
+        assert_eq!(last_granule, OPUS_PRE_SKIP as u64 + 96000);
+
+        // Lossy, but the level survives
+        let rms = |samples: &[f32]| {
+            (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt()
+        };
+        let original = rms(&pcm_to_samples(&pcm));
+        let ratio = rms(&decoded[4000..]) / original;
+        assert!((0.7..1.3).contains(&ratio), "level ratio {ratio}");
+    }
+
+    #[test]
+    fn test_upload_falls_back_to_wav() {
+        let (data, codec) = encode_for_upload(&[], 16000, AudioCodec::Flac);
+        assert_eq!(codec, AudioCodec::Wav);
+        assert_eq!(&data[..4], b"RIFF");
+
+        let (_, codec) = encode_for_upload(&speech_like(), 44100, AudioCodec::Opus);
+        assert_eq!(codec, AudioCodec::Opus);
+        assert_eq!(serde_json::to_value(codec).unwrap(), "opus");
+    }
+}
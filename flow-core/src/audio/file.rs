@@ -0,0 +1,377 @@
+//! Audio files as an input source
+//!
+//! Decodes WAV (via hound) and FLAC (via claxon) into mono f32 samples so a
+//! recording can be dictated, or the pipeline exercised without a microphone.
+
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use crate::AudioData;
+use crate::error::{Error, Result};
+
+use super::{
+    AudioFrame, AudioSource, AutoStop, CaptureState, FrameSink, HandsFreeConfig,
+    SPEECH_SAMPLE_RATE, SilenceMonitor, downmix, resample, rms_level, samples_to_pcm,
+};
+
+/// Container formats `FileSource` can read
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AudioFileFormat {
+    Wav,
+    Flac,
+}
+
+impl AudioFileFormat {
+    /// Identify a file by its magic bytes
+    pub fn detect(path: &Path) -> Result<Self> {
+        let mut magic = [0u8; 4];
+        File::open(path)
+            .and_then(|mut file| file.read_exact(&mut magic))
+            .map_err(|e| Error::Audio(format!("Failed to read {}: {e}", path.display())))?;
+
+        match &magic {
+            b"RIFF" => Ok(Self::Wav),
+            b"fLaC" => Ok(Self::Flac),
+            _ => Err(Error::Audio(format!(
+                "Unsupported audio file {}: expected WAV or FLAC",
+                path.display()
+            ))),
+        }
+    }
+}
+
+/// Plays back a decoded audio file as if it had just been recorded
+///
+/// The whole file counts as captured as soon as the source starts, so `stop`
+/// returns all of it, resampled to 16kHz like microphone audio (and a frame
+/// sink receives it at once). In hands-free mode the recording ends where
+/// trailing silence would have ended it live.
+pub struct FileSource {
+    samples: Vec<f32>,
+    state: CaptureState,
+    hands_free: Option<SilenceMonitor>,
+    /// Samples the current recording holds
+    recorded: usize,
+    frames: Option<FrameSink>,
+}
+
+impl FileSource {
+    /// Decode a WAV or FLAC file, mixing it down to mono
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let (samples, sample_rate) = match AudioFileFormat::detect(path)? {
+            AudioFileFormat::Wav => read_wav(path)?,
+            AudioFileFormat::Flac => read_flac(path)?,
+        };
+        Ok(Self::from_samples(samples, sample_rate))
+    }
+
+    /// Use mono samples already in memory, recorded at `sample_rate`
+    pub fn from_samples(samples: Vec<f32>, sample_rate: u32) -> Self {
+        let samples = if sample_rate == SPEECH_SAMPLE_RATE {
+            samples
+        } else {
+            resample(&samples, sample_rate, SPEECH_SAMPLE_RATE)
+        };
+        Self {
+            samples,
+            state: CaptureState::Idle,
+            hands_free: None,
+            recorded: 0,
+            frames: None,
+        }
+    }
+
+    /// End recordings by themselves after trailing silence, as `AudioCapture` does
+    pub fn with_hands_free(mut self, config: Option<HandsFreeConfig>) -> Self {
+        self.hands_free = config.map(|config| SilenceMonitor::new(config, SPEECH_SAMPLE_RATE));
+        self
+    }
+
+    pub fn samples(&self) -> &[f32] {
+        &self.samples
+    }
+
+    pub fn duration_ms(&self) -> u64 {
+        self.samples.len() as u64 * 1000 / SPEECH_SAMPLE_RATE as u64
+    }
+}
+
+impl AudioSource for FileSource {
+    fn start(&mut self) -> Result<()> {
+        self.state = CaptureState::Recording;
+        self.recorded = self.samples.len();
+
+        if let Some(monitor) = &mut self.hands_free {
+            monitor.reset();
+            if let Some(stop) = monitor.push(&self.samples) {
+                let end = stop.duration_ms * SPEECH_SAMPLE_RATE as u64 / 1000;
+                self.recorded = (end as usize).min(self.samples.len());
+                self.state = CaptureState::Paused;
+            }
+        }
+
+        if let Some(sink) = &self.frames {
+            let frame = AudioFrame {
+                samples: self.samples[..self.recorded].to_vec(),
+                sample_rate: SPEECH_SAMPLE_RATE,
+            };
+            if sink.send(frame).is_err() {
+                self.frames = None;
+            }
+        }
+        Ok(())
+    }
+
+    fn stop(&mut self) -> Result<AudioData> {
+        self.state = CaptureState::Idle;
+        Ok(samples_to_pcm(&self.samples[..self.recorded]))
+    }
+
+    fn state(&self) -> CaptureState {
+        self.state
+    }
+
+    fn sample_rate(&self) -> u32 {
+        SPEECH_SAMPLE_RATE
+    }
+
+    fn buffer_duration_ms(&self) -> u64 {
+        match self.state {
+            CaptureState::Idle => 0,
+            CaptureState::Recording | CaptureState::Paused => {
+                self.recorded as u64 * 1000 / SPEECH_SAMPLE_RATE as u64
+            }
+        }
+    }
+
+    fn current_audio_level(&self) -> f32 {
+        match self.state {
+            CaptureState::Recording => {
+                rms_level(&self.samples[..self.recorded], SPEECH_SAMPLE_RATE)
+            }
+            CaptureState::Idle | CaptureState::Paused => 0.0,
+        }
+    }
+
+    fn auto_stop(&self) -> Option<AutoStop> {
+        match self.state {
+            CaptureState::Paused => self.hands_free.as_ref().and_then(SilenceMonitor::stopped),
+            CaptureState::Idle | CaptureState::Recording => None,
+        }
+    }
+
+    fn set_frame_sink(&mut self, sink: Option<FrameSink>) {
+        self.frames = sink;
+    }
+}
+
+/// Decode a WAV file to mono samples and its sample rate
+pub fn read_wav(path: &Path) -> Result<(Vec<f32>, u32)> {
+    let wav_error =
+        |e: hound::Error| Error::Audio(format!("Failed to read WAV {}: {e}", path.display()));
+    let reader = hound::WavReader::open(path).map_err(wav_error)?;
+    let spec = reader.spec();
+
+    let interleaved = match spec.sample_format {
+        hound::SampleFormat::Float => reader
+            .into_samples::<f32>()
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(wav_error)?,
+        hound::SampleFormat::Int => {
+            let scale = int_scale(spec.bits_per_sample as u32);
+            reader
+                .into_samples::<i32>()
+                .map(|sample| sample.map(|s| s as f32 / scale))
+                .collect::<std::result::Result<Vec<_>, _>>()
+                .map_err(wav_error)?
+        }
+    };
+
+    Ok((downmix(&interleaved, spec.channels), spec.sample_rate))
+}
+
+/// Decode a FLAC file to mono samples and its sample rate
+pub fn read_flac(path: &Path) -> Result<(Vec<f32>, u32)> {
+    let flac_error =
+        |e: claxon::Error| Error::Audio(format!("Failed to read FLAC {}: {e}", path.display()));
+    let mut reader = claxon::FlacReader::open(path).map_err(flac_error)?;
+    let info = reader.streaminfo();
+
+    let scale = int_scale(info.bits_per_sample);
+    let interleaved = reader
+        .samples()
+        .map(|sample| sample.map(|s| s as f32 / scale))
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(flac_error)?;
+
+    Ok((
+        downmix(&interleaved, info.channels as u16),
+        info.sample_rate,
+    ))
+}
+
+/// Full scale of a signed integer sample
+fn int_scale(bits_per_sample: u32) -> f32 {
+    (1u64 << bits_per_sample.clamp(1, 32).saturating_sub(1)) as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tone(len: usize) -> Vec<i16> {
+        (0..len)
+            .map(|i| ((i as f32 * 0.05).sin() * 12000.0) as i16)
+            .collect()
+    }
+
+    #[test]
+    fn test_reads_stereo_wav_as_mono() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("stereo.wav");
+        let spec = hound::WavSpec {
+            channels: 2,
+            sample_rate: 44100,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+        for _ in 0..441 {
+            writer.write_sample(16384i16).unwrap();
+            writer.write_sample(0i16).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let mut source = FileSource::open(&path).unwrap();
+        assert_eq!(source.sample_rate(), 16000);
+        assert_eq!(source.duration_ms(), 10);
+        assert_eq!(source.samples().len(), 160);
+        // Resampling a constant only softens the zero-padded edges
+        assert!(
+            source.samples()[40..120]
+                .iter()
+                .all(|&s| (s - 0.25).abs() < 1e-3)
+        );
+
+        assert_eq!(source.buffer_duration_ms(), 0);
+        source.start().unwrap();
+        assert_eq!(source.state(), CaptureState::Recording);
+        assert_eq!(source.buffer_duration_ms(), 10);
+        let pcm = source.stop().unwrap();
+        assert_eq!(pcm.len(), 160 * 2);
+        assert_eq!(source.state(), CaptureState::Idle);
+    }
+
+    #[test]
+    fn test_hands_free_ends_at_trailing_silence() {
+        // 1s of speech then 2s of silence, as a hands-free recording hears it
+        let mut samples: Vec<f32> = tone(16000).iter().map(|&s| s as f32 / 32768.0).collect();
+        samples.resize(48000, 0.0);
+        let config = HandsFreeConfig {
+            trailing_silence_ms: 600,
+            min_duration_ms: 0,
+            max_duration_ms: 60_000,
+        };
+        let mut source = FileSource::from_samples(samples, 16000).with_hands_free(Some(config));
+
+        source.start().unwrap();
+        let stop = source.auto_stop().expect("should stop by itself");
+        assert_eq!(stop.reason, crate::audio::AutoStopReason::Silence);
+        assert_eq!(source.state(), CaptureState::Paused);
+        assert_eq!(source.buffer_duration_ms(), stop.duration_ms);
+        assert!((1600..=1650).contains(&stop.duration_ms), "{stop:?}");
+
+        let pcm = source.stop().unwrap();
+        assert_eq!(pcm.len() as u64, stop.duration_ms * 16 * 2);
+        assert_eq!(source.auto_stop(), None);
+    }
+
+    /// Write a single-frame, 16-bit mono FLAC file with a verbatim subframe
+    fn write_flac(path: &Path, sample_rate: u32, samples: &[i16]) {
+        fn crc8(data: &[u8]) -> u8 {
+            data.iter().fold(0u8, |mut crc, &byte| {
+                crc ^= byte;
+                for _ in 0..8 {
+                    crc = if crc & 0x80 != 0 {
+                        (crc << 1) ^ 0x07
+                    } else {
+                        crc << 1
+                    };
+                }
+                crc
+            })
+        }
+        fn crc16(data: &[u8]) -> u16 {
+            data.iter().fold(0u16, |mut crc, &byte| {
+                crc ^= (byte as u16) << 8;
+                for _ in 0..8 {
+                    crc = if crc & 0x8000 != 0 {
+                        (crc << 1) ^ 0x8005
+                    } else {
+                        crc << 1
+                    };
+                }
+                crc
+            })
+        }
+
+        let count = samples.len() as u64;
+        let block = samples.len() as u16;
+        let mut out = b"fLaC".to_vec();
+        // Last metadata block: STREAMINFO, 34 bytes
+        out.extend([0x80, 0, 0, 34]);
+        out.extend(block.to_be_bytes());
+        out.extend(block.to_be_bytes());
+        out.extend([0; 6]);
+        // 20-bit rate, 3-bit channels - 1, 5-bit bits per sample - 1, 36-bit total samples
+        let packed = (sample_rate as u64) << 44 | 15u64 << 36 | count;
+        out.extend(packed.to_be_bytes());
+        out.extend([0; 16]);
+
+        // Frame header: fixed blocking, 16-bit block size at the end, rate from
+        // STREAMINFO, mono, 16 bits per sample, frame number 0
+        let mut frame = vec![0xFF, 0xF8, 0x70, 0x08, 0x00];
+        frame.extend((block - 1).to_be_bytes());
+        frame.push(crc8(&frame));
+        // Verbatim subframe
+        frame.push(0x02);
+        for sample in samples {
+            frame.extend(sample.to_be_bytes());
+        }
+        let crc = crc16(&frame);
+        frame.extend(crc.to_be_bytes());
+        out.extend(frame);
+
+        std::fs::write(path, out).unwrap();
+    }
+
+    #[test]
+    fn test_reads_flac() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tone.flac");
+        let samples = tone(1600);
+        write_flac(&path, 16000, &samples);
+
+        assert_eq!(
+            AudioFileFormat::detect(&path).unwrap(),
+            AudioFileFormat::Flac
+        );
+        let source = FileSource::open(&path).unwrap();
+        assert_eq!(source.sample_rate(), 16000);
+        assert_eq!(source.duration_ms(), 100);
+        for (decoded, original) in source.samples().iter().zip(&samples) {
+            assert!((decoded - *original as f32 / 32768.0).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_rejects_other_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes.txt");
+        std::fs::write(&path, "not audio").unwrap();
+        assert!(matches!(FileSource::open(&path), Err(Error::Audio(_))));
+        assert!(FileSource::open(dir.path().join("missing.wav")).is_err());
+    }
+}
@@ -0,0 +1,269 @@
+//! Hands-free recording: ending a recording by itself once the speaker stops
+//!
+//! The monitor sees audio as it is captured, classifies short frames as
+//! speech or silence against an adaptive noise floor (the same rule voice
+//! activity detection uses), and reports when trailing silence or the
+//! maximum duration ends the recording.
+
+use serde::{Deserialize, Serialize};
+
+use super::{MAX_SPEECH_THRESHOLD_DB, VadConfig, frame_energy_db};
+
+/// Limits for hands-free recordings
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HandsFreeConfig {
+    /// Silence after speech that ends the recording
+    pub trailing_silence_ms: u32,
+    /// Silence never ends a recording shorter than this
+    pub min_duration_ms: u32,
+    /// Recordings end at this length whatever is heard
+    pub max_duration_ms: u32,
+}
+
+impl Default for HandsFreeConfig {
+    fn default() -> Self {
+        Self {
+            trailing_silence_ms: 1500,
+            min_duration_ms: 1000,
+            max_duration_ms: 120_000,
+        }
+    }
+}
+
+/// Why a hands-free recording ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AutoStopReason {
+    /// The speaker went quiet for `trailing_silence_ms`
+    Silence,
+    /// The recording reached `max_duration_ms`
+    MaxDuration,
+}
+
+/// A hands-free recording that ended by itself
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct AutoStop {
+    pub reason: AutoStopReason,
+    /// Length of the recording when it ended
+    pub duration_ms: u64,
+}
+
+/// Watches a recording as it is captured and decides when it is over
+///
+/// Nothing ends on silence until some speech has been heard, so a recording
+/// waits for the speaker (up to `max_duration_ms`).
+#[derive(Debug, Clone)]
+pub struct SilenceMonitor {
+    config: HandsFreeConfig,
+    vad: VadConfig,
+    frame_len: usize,
+    frame_ms: f64,
+    /// Samples of the frame being filled
+    frame: Vec<f32>,
+    elapsed_ms: f64,
+    silence_ms: f64,
+    heard_speech: bool,
+    noise_floor_db: Option<f32>,
+    stopped: Option<AutoStop>,
+}
+
+impl SilenceMonitor {
+    /// How fast the noise floor may rise per frame, so it follows a noisier room
+    /// without climbing onto sustained speech
+    const NOISE_FLOOR_RISE_DB: f32 = 0.05;
+
+    pub fn new(config: HandsFreeConfig, sample_rate: u32) -> Self {
+        let vad = VadConfig::default();
+        let mut monitor = Self {
+            config,
+            vad,
+            frame_len: 1,
+            frame_ms: 0.0,
+            frame: Vec::new(),
+            elapsed_ms: 0.0,
+            silence_ms: 0.0,
+            heard_speech: false,
+            noise_floor_db: None,
+            stopped: None,
+        };
+        monitor.set_sample_rate(sample_rate);
+        monitor
+    }
+
+    pub fn config(&self) -> &HandsFreeConfig {
+        &self.config
+    }
+
+    /// Follow the audio at a new rate (after failing over to another device)
+    pub fn set_sample_rate(&mut self, sample_rate: u32) {
+        let sample_rate = sample_rate.max(1);
+        self.frame_len = (sample_rate as u64 * self.vad.frame_ms as u64 / 1000).max(1) as usize;
+        self.frame_ms = self.frame_len as f64 * 1000.0 / sample_rate as f64;
+        self.frame.clear();
+    }
+
+    /// Forget the recording so far, for the next one
+    pub fn reset(&mut self) {
+        self.frame.clear();
+        self.elapsed_ms = 0.0;
+        self.silence_ms = 0.0;
+        self.heard_speech = false;
+        self.noise_floor_db = None;
+        self.stopped = None;
+    }
+
+    /// Why the recording ended, once it has
+    pub fn stopped(&self) -> Option<AutoStop> {
+        self.stopped
+    }
+
+    /// Feed mono audio as it arrives; returns the stop once the recording is over
+    ///
+    /// Keeps returning the same stop until `reset`.
+    pub fn push(&mut self, samples: &[f32]) -> Option<AutoStop> {
+        for &sample in samples {
+            if self.stopped.is_some() {
+                break;
+            }
+            self.frame.push(sample);
+            if self.frame.len() == self.frame_len {
+                let db = frame_energy_db(&self.frame);
+                self.frame.clear();
+                self.stopped = self.end_frame(db);
+            }
+        }
+        self.stopped
+    }
+
+    fn end_frame(&mut self, db: f32) -> Option<AutoStop> {
+        let floor = match self.noise_floor_db {
+            Some(floor) => db.min(floor + Self::NOISE_FLOOR_RISE_DB),
+            None => db,
+        };
+        self.noise_floor_db = Some(floor);
+        let threshold = (floor + self.vad.energy_margin_db)
+            .clamp(self.vad.min_energy_db, MAX_SPEECH_THRESHOLD_DB);
+
+        self.elapsed_ms += self.frame_ms;
+        if db >= threshold {
+            self.heard_speech = true;
+            self.silence_ms = 0.0;
+        } else {
+            self.silence_ms += self.frame_ms;
+        }
+
+        let stop = |reason| {
+            Some(AutoStop {
+                reason,
+                duration_ms: self.elapsed_ms.round() as u64,
+            })
+        };
+        if self.elapsed_ms >= self.config.max_duration_ms as f64 {
+            stop(AutoStopReason::MaxDuration)
+        } else if self.heard_speech
+            && self.silence_ms >= self.config.trailing_silence_ms as f64
+            && self.elapsed_ms >= self.config.min_duration_ms as f64
+        {
+            stop(AutoStopReason::Silence)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    /// Scripted capture: (milliseconds, amplitude) of a 220Hz "voice" over room noise
+    fn script(rate: u32, parts: &[(u32, f32)], noise: f32) -> Vec<f32> {
+        let mut rng = StdRng::seed_from_u64(3);
+        let mut samples = Vec::new();
+        for &(ms, amplitude) in parts {
+            for _ in 0..rate as u64 * ms as u64 / 1000 {
+                let t = samples.len() as f32 / rate as f32;
+                let voice = amplitude * (t * 220.0 * std::f32::consts::TAU).sin();
+                let room = if noise > 0.0 {
+                    rng.random_range(-noise..noise)
+                } else {
+                    0.0
+                };
+                samples.push(voice + room);
+            }
+        }
+        samples
+    }
+
+    /// Feed a script in 10ms callbacks, as a stream would
+    fn drive(monitor: &mut SilenceMonitor, rate: u32, samples: &[f32]) -> Option<AutoStop> {
+        samples
+            .chunks((rate / 100) as usize)
+            .find_map(|chunk| monitor.push(chunk))
+    }
+
+    fn config(trailing: u32, min: u32, max: u32) -> HandsFreeConfig {
+        HandsFreeConfig {
+            trailing_silence_ms: trailing,
+            min_duration_ms: min,
+            max_duration_ms: max,
+        }
+    }
+
+    #[test]
+    fn test_stops_after_trailing_silence() {
+        let mut monitor = SilenceMonitor::new(config(800, 500, 30_000), 48000);
+        let samples = script(48000, &[(300, 0.0), (2000, 0.3), (3000, 0.0)], 0.003);
+        let stop = drive(&mut monitor, 48000, &samples).expect("should stop");
+        assert_eq!(stop.reason, AutoStopReason::Silence);
+        assert!((3050..=3200).contains(&stop.duration_ms), "{stop:?}");
+
+        // Pauses shorter than the trailing silence don't end it
+        monitor.reset();
+        let samples = script(
+            48000,
+            &[(1000, 0.3), (500, 0.0), (1000, 0.3), (1000, 0.0)],
+            0.0,
+        );
+        let stop = drive(&mut monitor, 48000, &samples).expect("should stop");
+        assert!(stop.duration_ms >= 3250, "{stop:?}");
+    }
+
+    #[test]
+    fn test_waits_for_speech_and_min_duration() {
+        // Silence alone never ends the recording before the maximum
+        let mut monitor = SilenceMonitor::new(config(500, 0, 4000), 16000);
+        let silence = script(16000, &[(3000, 0.0)], 0.003);
+        assert_eq!(drive(&mut monitor, 16000, &silence), None);
+
+        // A short word is kept open until the minimum duration
+        let mut monitor = SilenceMonitor::new(config(300, 2000, 30_000), 16000);
+        let samples = script(16000, &[(400, 0.3), (3000, 0.0)], 0.0);
+        let stop = drive(&mut monitor, 16000, &samples).expect("should stop");
+        assert_eq!(stop.reason, AutoStopReason::Silence);
+        assert!((2000..=2030).contains(&stop.duration_ms), "{stop:?}");
+    }
+
+    #[test]
+    fn test_stops_at_max_duration() {
+        let mut monitor = SilenceMonitor::new(config(500, 0, 2000), 16000);
+        let samples = script(16000, &[(5000, 0.3)], 0.01);
+        let stop = drive(&mut monitor, 16000, &samples).expect("should stop");
+        assert_eq!(stop.reason, AutoStopReason::MaxDuration);
+        assert!((2000..=2030).contains(&stop.duration_ms), "{stop:?}");
+        // Stays stopped
+        assert_eq!(monitor.push(&samples[..160]), Some(stop));
+    }
+
+    #[test]
+    fn test_follows_noisy_room() {
+        // Open-office background well above digital silence
+        let mut monitor = SilenceMonitor::new(config(1000, 0, 30_000), 16000);
+        let samples = script(16000, &[(500, 0.0), (1500, 0.4), (4000, 0.0)], 0.03);
+        let stop = drive(&mut monitor, 16000, &samples).expect("should stop");
+        assert_eq!(stop.reason, AutoStopReason::Silence);
+        assert!((2950..=3100).contains(&stop.duration_ms), "{stop:?}");
+    }
+}
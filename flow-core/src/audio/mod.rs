@@ -0,0 +1,1521 @@
+//! Audio capture module using CPAL for cross-platform audio input
+//!
+//! Also holds the voice activity detection used to trim silence from recordings
+//! and skip ones with no speech before they reach a provider.
+
+use candle_core::{Device as TensorDevice, Tensor};
+use candle_nn::{Linear, Module};
+use candle_transformers::models::whisper;
+use cpal::traits::{DeviceTrait, StreamTrait};
+use cpal::{Device, Sample, SampleFormat, SizedSample, Stream, StreamConfig};
+use parking_lot::Mutex;
+use std::ops::Range;
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::mpsc::Sender;
+use std::time::Duration;
+use tracing::{debug, error, info, warn};
+
+use crate::AudioData;
+use crate::error::{Error, Result};
+
+mod devices;
+mod dsp;
+mod encode;
+mod file;
+mod hands_free;
+mod pre_roll;
+
+pub use devices::{
+    AudioDeviceEvent, InputConfigRange, InputDevice, find_input_device, list_input_devices,
+    select_input_device,
+};
+pub use dsp::AudioProcessing;
+pub use encode::{AudioCodec, encode_audio, encode_for_upload, pcm_to_wav};
+pub use file::{AudioFileFormat, FileSource, read_flac, read_wav};
+pub use hands_free::{AutoStop, AutoStopReason, HandsFreeConfig, SilenceMonitor};
+
+use devices::{default_input_device, device_name};
+use pre_roll::{PreRoll, WarmStream};
+
+/// Sample rate every audio source delivers, as expected by speech models
+pub const SPEECH_SAMPLE_RATE: u32 = 16000;
+
+/// Longest pre-roll kept, bounding the memory held by a warm device
+pub const MAX_PRE_ROLL_MS: u32 = 2000;
+
+/// How long a warm device stays open without a recording
+pub const DEFAULT_WARM_IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Audio capture configuration
+#[derive(Debug, Clone)]
+pub struct AudioCaptureConfig {
+    /// Sample rate of captured audio in Hz (default: 16000 for speech recognition),
+    /// resampled from whatever rate the device runs at
+    pub sample_rate: u32,
+    /// Number of channels (default: 1 for mono)
+    pub channels: u16,
+    /// Buffer size in samples
+    pub buffer_size: usize,
+    /// Input device id or name (default input device when unset or not connected)
+    pub device: Option<String>,
+    /// Audio from just before `start` to prepend to each recording, in
+    /// milliseconds (default: 0, off; capped at `MAX_PRE_ROLL_MS`). When set,
+    /// the device stays open between recordings.
+    pub pre_roll_ms: u32,
+    /// Close the device after this long without a recording while pre-roll
+    /// keeps it open
+    pub warm_idle_timeout: Duration,
+    /// Clean-up applied to captured audio before it is returned
+    pub processing: AudioProcessing,
+    /// End recordings by themselves after trailing silence (default: off)
+    pub hands_free: Option<HandsFreeConfig>,
+}
+
+impl Default for AudioCaptureConfig {
+    fn default() -> Self {
+        Self {
+            sample_rate: SPEECH_SAMPLE_RATE,
+            channels: 1,
+            buffer_size: 4096,
+            device: None,
+            pre_roll_ms: 0,
+            warm_idle_timeout: DEFAULT_WARM_IDLE_TIMEOUT,
+            processing: AudioProcessing::default(),
+            hands_free: None,
+        }
+    }
+}
+
+/// Mono audio handed out while a recording is captured, at the rate it was heard
+#[derive(Debug, Clone, PartialEq)]
+pub struct AudioFrame {
+    pub samples: Vec<f32>,
+    pub sample_rate: u32,
+}
+
+/// Where a source streams the audio it records, for work that starts before `stop`
+pub type FrameSink = Sender<AudioFrame>;
+
+/// State of the audio capture
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptureState {
+    Idle,
+    Recording,
+    Paused,
+}
+
+/// Where dictated audio comes from
+///
+/// The microphone (`AudioCapture`) is one source; `FileSource` plays back a
+/// recorded file so the pipeline runs without an input device.
+pub trait AudioSource {
+    /// Start capturing, discarding anything buffered before
+    fn start(&mut self) -> Result<()>;
+
+    /// Stop capturing and take the buffered audio as mono 16-bit PCM
+    fn stop(&mut self) -> Result<AudioData>;
+
+    fn state(&self) -> CaptureState;
+
+    /// Sample rate of the audio returned by `stop`
+    fn sample_rate(&self) -> u32;
+
+    /// Duration of the audio buffered so far
+    fn buffer_duration_ms(&self) -> u64;
+
+    /// Level of the most recent audio, between 0.0 and 1.0
+    fn current_audio_level(&self) -> f32;
+
+    /// Reconnect if the underlying device went away mid-recording
+    fn recover(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Take the device changes since the last call
+    fn take_device_events(&mut self) -> Vec<AudioDeviceEvent> {
+        Vec::new()
+    }
+
+    /// Why the current hands-free recording ended by itself, once it has
+    ///
+    /// Capture is paused from that point; `stop` still returns the recording.
+    fn auto_stop(&self) -> Option<AutoStop> {
+        None
+    }
+
+    /// Open the device ahead of `start` so pre-roll audio is available
+    fn warm(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Whether the device is held open between recordings
+    fn is_warm(&self) -> bool {
+        false
+    }
+
+    /// Stream recorded audio to `sink` as it is captured (None to stop)
+    ///
+    /// Set before `start` to also receive the audio a recording begins with.
+    fn set_frame_sink(&mut self, _sink: Option<FrameSink>) {}
+}
+
+/// An opened input device and the stream format chosen for it
+#[derive(Clone)]
+struct InputStreamSetup {
+    device: Device,
+    name: String,
+    stream_config: StreamConfig,
+    channels: u16,
+    sample_format: SampleFormat,
+    sample_rate: u32,
+}
+
+impl InputStreamSetup {
+    fn open(device: Device, preferred_rate: u32, preferred_channels: u16) -> Result<Self> {
+        let name = device_name(&device);
+        info!("Using input device: {}", name);
+
+        let supported_configs: Vec<_> = device
+            .supported_input_configs()
+            .map_err(|e| Error::Audio(format!("Failed to get supported configs: {e}")))?
+            .collect();
+
+        if supported_configs.is_empty() {
+            return Err(Error::Audio("No supported input configs".to_string()));
+        }
+
+        let (supported_config, channels, sample_format, sample_rate) =
+            select_supported_config(&supported_configs, preferred_rate, preferred_channels)
+                .ok_or_else(|| Error::Audio("No supported input config found".to_string()))?;
+
+        let stream_config = supported_config.config();
+
+        debug!(
+            "Stream config: {:?} (input channels: {}, format: {:?})",
+            stream_config, channels, sample_format
+        );
+
+        Ok(Self {
+            device,
+            name,
+            stream_config,
+            channels,
+            sample_format,
+            sample_rate,
+        })
+    }
+
+    /// Build and start a stream on this device feeding `shared`
+    fn play(&self, shared: &StreamShared) -> Result<Stream> {
+        let stream = match self.sample_format {
+            SampleFormat::F32 => self.build_stream::<f32>(shared.clone())?,
+            SampleFormat::I16 => self.build_stream::<i16>(shared.clone())?,
+            SampleFormat::U16 => self.build_stream::<u16>(shared.clone())?,
+            SampleFormat::I24 => self.build_stream::<cpal::I24>(shared.clone())?,
+            SampleFormat::U24 => self.build_stream::<cpal::U24>(shared.clone())?,
+            SampleFormat::I32 => self.build_stream::<i32>(shared.clone())?,
+            SampleFormat::U32 => self.build_stream::<u32>(shared.clone())?,
+            SampleFormat::I8 => self.build_stream::<i8>(shared.clone())?,
+            SampleFormat::U8 => self.build_stream::<u8>(shared.clone())?,
+            SampleFormat::F64 => self.build_stream::<f64>(shared.clone())?,
+            SampleFormat::I64 => self.build_stream::<i64>(shared.clone())?,
+            SampleFormat::U64 => self.build_stream::<u64>(shared.clone())?,
+            _ => {
+                return Err(Error::Audio(format!(
+                    "Unsupported sample format: {:?}",
+                    self.sample_format
+                )));
+            }
+        };
+
+        stream
+            .play()
+            .map_err(|e| Error::Audio(format!("Failed to start stream: {e}")))?;
+
+        Ok(stream)
+    }
+
+    fn build_stream<T>(&self, shared: StreamShared) -> Result<Stream>
+    where
+        T: Sample + SizedSample,
+        f32: cpal::FromSample<T>,
+    {
+        let channels = self.channels as usize;
+        let sample_rate = self.sample_rate;
+
+        let device_lost = Arc::clone(&shared.device_lost);
+        let err_fn = move |err: cpal::StreamError| {
+            error!("Audio stream error: {}", err);
+            if matches!(
+                err,
+                cpal::StreamError::DeviceNotAvailable | cpal::StreamError::StreamInvalidated
+            ) {
+                *device_lost.lock() = Some(err.to_string());
+            }
+        };
+
+        self.device
+            .build_input_stream(
+                &self.stream_config,
+                move |data: &[T], _: &cpal::InputCallbackInfo| {
+                    // Hold the state lock so `start` can move the pre-roll into the
+                    // buffer without a callback landing in between
+                    let mut state = shared.state.lock();
+                    match *state {
+                        CaptureState::Recording => {
+                            let mut buffer = shared.buffer.lock();
+                            let start = buffer.len();
+                            downmix_into(data, channels, &mut *buffer);
+                            send_frame(&shared.frames, &buffer[start..], sample_rate);
+                            if let Some(monitor) = shared.hands_free.lock().as_mut()
+                                && monitor.push(&buffer[start..]).is_some()
+                            {
+                                // Over by itself; hold the audio for `stop`
+                                *state = CaptureState::Paused;
+                            }
+                        }
+                        CaptureState::Idle => shared.pre_roll.lock().push(data, channels),
+                        CaptureState::Paused => {}
+                    }
+                },
+                err_fn,
+                None,
+            )
+            .map_err(|e| Error::Audio(format!("Failed to build stream: {e}")))
+    }
+}
+
+/// State shared between an `AudioCapture` and its stream callback
+#[derive(Clone)]
+struct StreamShared {
+    state: Arc<Mutex<CaptureState>>,
+    buffer: Arc<Mutex<Vec<f32>>>,
+    /// Most recent audio heard while idle on a warm device
+    pre_roll: Arc<Mutex<PreRoll>>,
+    /// Set by the stream error callback when the device disappears
+    device_lost: Arc<Mutex<Option<String>>>,
+    /// Watches recordings for their end in hands-free mode
+    hands_free: Arc<Mutex<Option<SilenceMonitor>>>,
+    /// Receives recorded audio as it arrives
+    frames: Arc<Mutex<Option<FrameSink>>>,
+}
+
+/// Hand newly recorded audio to the sink, forgetting a sink nobody reads any more
+fn send_frame(sink: &Mutex<Option<FrameSink>>, samples: &[f32], sample_rate: u32) {
+    let mut sink = sink.lock();
+    if samples.is_empty() {
+        return;
+    }
+    let frame = AudioFrame {
+        samples: samples.to_vec(),
+        sample_rate,
+    };
+    if sink.as_ref().is_some_and(|sink| sink.send(frame).is_err()) {
+        *sink = None;
+    }
+}
+
+/// Handles audio capture from the selected (or default) input device
+///
+/// Whatever rate and channel layout the device runs at, captured audio is
+/// mixed down to mono as it arrives and resampled to the configured rate
+/// (16kHz by default) when it is taken.
+///
+/// With `pre_roll_ms` set the device is kept warm: it stays open between
+/// recordings, remembering only the last `pre_roll_ms` of audio, and closes
+/// after `warm_idle_timeout` without a recording.
+///
+/// With `hands_free` set a recording pauses itself after trailing silence or
+/// at its maximum length, reported by `auto_stop`.
+pub struct AudioCapture {
+    input: InputStreamSetup,
+    config: AudioCaptureConfig,
+    shared: StreamShared,
+    stream: Option<Stream>,
+    warm: Option<WarmStream>,
+    events: Vec<AudioDeviceEvent>,
+}
+
+impl AudioCapture {
+    /// Create a new AudioCapture with default settings
+    pub fn new() -> Result<Self> {
+        Self::with_config(AudioCaptureConfig::default())
+    }
+
+    /// Create a new AudioCapture with custom configuration
+    ///
+    /// Falls back to the default input device, with a `FailedOver` event, when
+    /// the configured device is not connected.
+    pub fn with_config(config: AudioCaptureConfig) -> Result<Self> {
+        let host = cpal::default_host();
+        let mut events = Vec::new();
+
+        let device = match config.device.as_deref() {
+            Some(wanted) => match find_input_device(&host, wanted) {
+                Some(device) => device,
+                None => {
+                    let device = default_input_device(&host)?;
+                    warn!("Input device {} not found, using the default", wanted);
+                    events.push(AudioDeviceEvent::FailedOver {
+                        from: wanted.to_string(),
+                        to: device_name(&device),
+                        reason: "Selected input device is not connected".to_string(),
+                    });
+                    device
+                }
+            },
+            None => default_input_device(&host)?,
+        };
+
+        let input = InputStreamSetup::open(device, config.sample_rate, config.channels)?;
+
+        let mut config = config;
+        config.channels = 1;
+        config.pre_roll_ms = config.pre_roll_ms.min(MAX_PRE_ROLL_MS);
+
+        let pre_roll = PreRoll::new(pre_roll_capacity(&config, input.sample_rate));
+        let hands_free = config
+            .hands_free
+            .map(|hands_free| SilenceMonitor::new(hands_free, input.sample_rate));
+
+        Ok(Self {
+            input,
+            config,
+            shared: StreamShared {
+                state: Arc::new(Mutex::new(CaptureState::Idle)),
+                buffer: Arc::new(Mutex::new(Vec::new())),
+                pre_roll: Arc::new(Mutex::new(pre_roll)),
+                device_lost: Arc::new(Mutex::new(None)),
+                hands_free: Arc::new(Mutex::new(hands_free)),
+                frames: Arc::new(Mutex::new(None)),
+            },
+            stream: None,
+            warm: None,
+            events,
+        })
+    }
+
+    /// Name of the input device in use
+    pub fn device_name(&self) -> &str {
+        &self.input.name
+    }
+
+    /// Start recording audio
+    ///
+    /// When warm, the recording begins with the pre-roll heard just before.
+    pub fn start(&mut self) -> Result<()> {
+        if self.state() == CaptureState::Recording {
+            return Ok(());
+        }
+
+        // a warm device may have gone away since the last recording
+        self.recover()?;
+
+        if self.config.pre_roll_ms > 0 {
+            self.warm()?;
+            let mut state = self.shared.state.lock();
+            let mut buffer = self.shared.buffer.lock();
+            *buffer = self.shared.pre_roll.lock().take();
+            self.reset_hands_free(&buffer);
+            send_frame(&self.shared.frames, &buffer, self.input.sample_rate);
+            *state = CaptureState::Recording;
+        } else {
+            self.shared.buffer.lock().clear();
+            self.reset_hands_free(&[]);
+            self.stream = Some(self.input.play(&self.shared)?);
+            *self.shared.state.lock() = CaptureState::Recording;
+        }
+
+        info!("Audio capture started");
+        Ok(())
+    }
+
+    /// Open the device and keep it open between recordings, filling the pre-roll
+    ///
+    /// Closes again after `warm_idle_timeout` without a recording; calling this
+    /// while warm restarts the timeout. Fails when pre-roll is off.
+    pub fn warm(&mut self) -> Result<()> {
+        if self.config.pre_roll_ms == 0 {
+            return Err(Error::Audio("Pre-roll is disabled".to_string()));
+        }
+        if let Some(warm) = self.warm.as_ref().filter(|warm| warm.is_open()) {
+            warm.touch();
+            return Ok(());
+        }
+
+        self.stream = None;
+        self.warm = None;
+        self.warm = Some(WarmStream::spawn(
+            self.input.clone(),
+            self.shared.clone(),
+            self.config.warm_idle_timeout,
+        )?);
+        info!("Input device {} kept warm", self.input.name);
+        Ok(())
+    }
+
+    /// Whether the device is held open between recordings
+    pub fn is_warm(&self) -> bool {
+        self.warm.as_ref().is_some_and(WarmStream::is_open)
+    }
+
+    /// Close a warm device now, forgetting the pre-roll
+    pub fn cool(&mut self) {
+        if self.warm.take().is_some() {
+            self.shared.pre_roll.lock().clear();
+            info!("Input device {} closed", self.input.name);
+        }
+    }
+
+    /// Fail over to the default input device if the current one went away
+    ///
+    /// Audio captured so far is kept (resampled to the new device's rate) and
+    /// capture carries on where it was. Does nothing while the device is fine.
+    pub fn recover(&mut self) -> Result<()> {
+        let Some(reason) = self.shared.device_lost.lock().take() else {
+            return Ok(());
+        };
+        let lost = self.input.name.clone();
+        warn!("Input device {} lost: {}", lost, reason);
+        self.stream = None;
+        self.cool();
+
+        let result = default_input_device(&cpal::default_host())
+            .and_then(|device| {
+                InputStreamSetup::open(device, self.config.sample_rate, self.config.channels)
+            })
+            .and_then(|input| {
+                if input.sample_rate != self.input.sample_rate {
+                    let mut buffer = self.shared.buffer.lock();
+                    *buffer = resample(&buffer, self.input.sample_rate, input.sample_rate);
+                    *self.shared.pre_roll.lock() =
+                        PreRoll::new(pre_roll_capacity(&self.config, input.sample_rate));
+                    if let Some(monitor) = self.shared.hands_free.lock().as_mut() {
+                        monitor.set_sample_rate(input.sample_rate);
+                    }
+                }
+                self.events.push(AudioDeviceEvent::FailedOver {
+                    from: lost.clone(),
+                    to: input.name.clone(),
+                    reason: reason.clone(),
+                });
+                self.input = input;
+
+                if self.config.pre_roll_ms > 0 {
+                    self.warm()
+                } else if self.state() == CaptureState::Idle {
+                    Ok(())
+                } else {
+                    self.stream = Some(self.input.play(&self.shared)?);
+                    Ok(())
+                }
+            });
+
+        if let Err(e) = &result {
+            error!("Failed to recover audio capture: {}", e);
+            *self.shared.state.lock() = CaptureState::Idle;
+            self.events.push(AudioDeviceEvent::CaptureFailed {
+                device: lost,
+                reason: format!("{reason} ({e})"),
+            });
+        }
+        result
+    }
+
+    /// Take the device changes since the last call
+    pub fn take_device_events(&mut self) -> Vec<AudioDeviceEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    /// Why the current hands-free recording ended by itself, once it has
+    pub fn auto_stop(&self) -> Option<AutoStop> {
+        match self.state() {
+            CaptureState::Paused => self
+                .shared
+                .hands_free
+                .lock()
+                .as_ref()
+                .and_then(SilenceMonitor::stopped),
+            CaptureState::Idle | CaptureState::Recording => None,
+        }
+    }
+
+    /// Start watching a new recording, beginning with the audio already buffered
+    fn reset_hands_free(&self, buffered: &[f32]) {
+        if let Some(monitor) = self.shared.hands_free.lock().as_mut() {
+            monitor.reset();
+            monitor.push(buffered);
+        }
+    }
+
+    /// Stop recording and return the captured audio data
+    ///
+    /// A warm device stays open, collecting pre-roll for the next recording.
+    pub fn stop(&mut self) -> Result<AudioData> {
+        *self.shared.state.lock() = CaptureState::Idle;
+
+        // drop the stream to stop recording
+        self.stream = None;
+        if let Some(warm) = &self.warm {
+            warm.touch();
+        }
+
+        let audio_data = self.take_buffered_audio();
+
+        info!("Audio capture stopped, {} bytes captured", audio_data.len());
+        Ok(audio_data)
+    }
+
+    /// Stop recording without draining the buffer
+    pub fn stop_stream(&mut self) -> Result<()> {
+        *self.shared.state.lock() = CaptureState::Idle;
+        self.stream = None;
+        self.cool();
+        info!("Audio capture stopped (buffer retained)");
+        Ok(())
+    }
+
+    /// Drain buffered audio into PCM data without touching the stream
+    ///
+    /// The configured `processing` runs on the resampled audio.
+    pub fn take_buffered_audio(&mut self) -> AudioData {
+        let samples = std::mem::take(&mut *self.shared.buffer.lock());
+        let mut samples = resample(&samples, self.input.sample_rate, self.config.sample_rate);
+        self.config
+            .processing
+            .apply(&mut samples, self.config.sample_rate);
+        samples_to_pcm(&samples)
+    }
+
+    /// Pause recording (keeps stream alive but stops buffering)
+    pub fn pause(&mut self) {
+        *self.shared.state.lock() = CaptureState::Paused;
+        debug!("Audio capture paused");
+    }
+
+    /// Resume recording after pause
+    pub fn resume(&mut self) {
+        *self.shared.state.lock() = CaptureState::Recording;
+        debug!("Audio capture resumed");
+    }
+
+    /// Get current capture state
+    pub fn state(&self) -> CaptureState {
+        *self.shared.state.lock()
+    }
+
+    /// Get current buffer duration in milliseconds
+    pub fn buffer_duration_ms(&self) -> u64 {
+        let samples = self.shared.buffer.lock().len();
+        (samples as u64 * 1000) / self.input.sample_rate.max(1) as u64
+    }
+
+    /// Sample rate of the audio returned by `stop`, whatever rate the device runs at
+    pub fn sample_rate(&self) -> u32 {
+        self.config.sample_rate
+    }
+
+    /// Get current audio level (RMS amplitude) from the last 50ms of audio
+    /// Returns a value between 0.0 and 1.0
+    pub fn current_audio_level(&self) -> f32 {
+        rms_level(&self.shared.buffer.lock(), self.input.sample_rate)
+    }
+
+    /// Stream recorded audio to `sink` as it arrives, at the device's rate (None to stop)
+    ///
+    /// A recording started afterwards sends its pre-roll first.
+    pub fn set_frame_sink(&mut self, sink: Option<FrameSink>) {
+        *self.shared.frames.lock() = sink;
+    }
+}
+
+/// Samples of pre-roll kept at the device's rate
+fn pre_roll_capacity(config: &AudioCaptureConfig, sample_rate: u32) -> usize {
+    (config.pre_roll_ms.min(MAX_PRE_ROLL_MS) as u64 * sample_rate as u64 / 1000) as usize
+}
+
+impl AudioSource for AudioCapture {
+    fn start(&mut self) -> Result<()> {
+        AudioCapture::start(self)
+    }
+
+    fn stop(&mut self) -> Result<AudioData> {
+        AudioCapture::stop(self)
+    }
+
+    fn state(&self) -> CaptureState {
+        AudioCapture::state(self)
+    }
+
+    fn sample_rate(&self) -> u32 {
+        AudioCapture::sample_rate(self)
+    }
+
+    fn buffer_duration_ms(&self) -> u64 {
+        AudioCapture::buffer_duration_ms(self)
+    }
+
+    fn current_audio_level(&self) -> f32 {
+        AudioCapture::current_audio_level(self)
+    }
+
+    fn recover(&mut self) -> Result<()> {
+        AudioCapture::recover(self)
+    }
+
+    fn take_device_events(&mut self) -> Vec<AudioDeviceEvent> {
+        AudioCapture::take_device_events(self)
+    }
+
+    fn auto_stop(&self) -> Option<AutoStop> {
+        AudioCapture::auto_stop(self)
+    }
+
+    fn warm(&mut self) -> Result<()> {
+        AudioCapture::warm(self)
+    }
+
+    fn is_warm(&self) -> bool {
+        AudioCapture::is_warm(self)
+    }
+
+    fn set_frame_sink(&mut self, sink: Option<FrameSink>) {
+        AudioCapture::set_frame_sink(self, sink)
+    }
+}
+
+impl Drop for AudioCapture {
+    fn drop(&mut self) {
+        *self.shared.state.lock() = CaptureState::Idle;
+        self.stream = None;
+        self.warm = None;
+    }
+}
+
+fn select_supported_config(
+    ranges: &[cpal::SupportedStreamConfigRange],
+    preferred_rate: u32,
+    preferred_channels: u16,
+) -> Option<(cpal::SupportedStreamConfig, u16, SampleFormat, u32)> {
+    let preferred_formats = [
+        SampleFormat::F32,
+        SampleFormat::I16,
+        SampleFormat::U16,
+        SampleFormat::I32,
+        SampleFormat::U32,
+        SampleFormat::F64,
+        SampleFormat::I24,
+        SampleFormat::U24,
+        SampleFormat::I8,
+        SampleFormat::U8,
+        SampleFormat::I64,
+        SampleFormat::U64,
+    ];
+
+    for format in preferred_formats {
+        let mut candidates: Vec<_> = ranges
+            .iter()
+            .copied()
+            .filter(|range| {
+                range.sample_format() == format && range.channels() == preferred_channels
+            })
+            .collect();
+
+        if candidates.is_empty() {
+            candidates = ranges
+                .iter()
+                .copied()
+                .filter(|range| range.sample_format() == format)
+                .collect();
+        }
+
+        if candidates.is_empty() {
+            continue;
+        }
+
+        let best = candidates
+            .into_iter()
+            .min_by_key(|range| sample_rate_distance(*range, preferred_rate))?;
+
+        let sample_rate = choose_sample_rate(best, preferred_rate);
+        let supported = best.with_sample_rate(sample_rate);
+
+        return Some((supported, best.channels(), format, sample_rate));
+    }
+
+    None
+}
+
+fn sample_rate_distance(range: cpal::SupportedStreamConfigRange, preferred_rate: u32) -> u32 {
+    let min_rate = range.min_sample_rate();
+    let max_rate = range.max_sample_rate();
+    if preferred_rate < min_rate {
+        min_rate.saturating_sub(preferred_rate)
+    } else if preferred_rate > max_rate {
+        preferred_rate.saturating_sub(max_rate)
+    } else {
+        0
+    }
+}
+
+fn choose_sample_rate(range: cpal::SupportedStreamConfigRange, preferred_rate: u32) -> u32 {
+    let min_rate = range.min_sample_rate();
+    let max_rate = range.max_sample_rate();
+    if preferred_rate < min_rate {
+        min_rate
+    } else if preferred_rate > max_rate {
+        max_rate
+    } else {
+        preferred_rate
+    }
+}
+
+/// Level (RMS amplitude) of the last 50ms of `samples`, between 0.0 and 1.0
+fn rms_level(samples: &[f32], sample_rate: u32) -> f32 {
+    if samples.is_empty() {
+        return 0.0;
+    }
+
+    // Calculate how many samples represent 50ms
+    let samples_per_50ms = (sample_rate as usize / 20).max(1);
+    let start_idx = samples.len().saturating_sub(samples_per_50ms);
+    let recent_samples = &samples[start_idx..];
+
+    // Calculate RMS (root mean square) for perceived loudness
+    let sum_squares: f32 = recent_samples.iter().map(|&s| s * s).sum();
+    let rms = (sum_squares / recent_samples.len() as f32).sqrt();
+
+    // Amplify a bit for visual effect (typical speech is quite quiet)
+    (rms * 3.0).min(1.0)
+}
+
+/// Convert f32 samples to 16-bit PCM bytes
+pub fn samples_to_pcm(samples: &[f32]) -> AudioData {
+    samples
+        .iter()
+        .flat_map(|&sample| {
+            // clamp and convert to i16
+            let clamped = sample.clamp(-1.0, 1.0);
+            let pcm = (clamped * 32767.0) as i16;
+            pcm.to_le_bytes()
+        })
+        .collect()
+}
+
+/// Convert 16-bit little-endian PCM bytes to f32 samples in [-1, 1)
+pub fn pcm_to_samples(audio: &[u8]) -> Vec<f32> {
+    audio
+        .chunks_exact(2)
+        .map(|chunk| i16::from_le_bytes([chunk[0], chunk[1]]) as f32 / 32768.0)
+        .collect()
+}
+
+/// Zero crossings of the resampling kernel on each side of its centre
+const RESAMPLE_ZERO_CROSSINGS: f64 = 16.0;
+/// Kaiser window shape for the resampling kernel (about 80 dB stopband)
+const RESAMPLE_KAISER_BETA: f64 = 8.0;
+/// Passband edge as a fraction of the lower Nyquist frequency, leaving room
+/// for the transition band so nothing above Nyquist aliases back
+const RESAMPLE_CUTOFF: f64 = 0.9;
+/// Most kernel phases precomputed; finer fractional positions use the nearest
+const RESAMPLE_MAX_PHASES: u64 = 1024;
+
+/// Resample audio with a Kaiser-windowed sinc low-pass filter
+///
+/// The ratio is reduced to `up / down` and one kernel is precomputed per
+/// output phase (polyphase), so common conversions such as 48kHz or 44.1kHz
+/// to 16kHz are exact. Content above the lower Nyquist frequency is filtered
+/// out rather than aliased.
+pub fn resample(samples: &[f32], from_rate: u32, to_rate: u32) -> Vec<f32> {
+    if from_rate == to_rate || from_rate == 0 || to_rate == 0 || samples.is_empty() {
+        return samples.to_vec();
+    }
+
+    let divisor = gcd(from_rate, to_rate) as u64;
+    let up = to_rate as u64 / divisor;
+    let down = from_rate as u64 / divisor;
+
+    // Cutoff as a fraction of the input Nyquist frequency
+    let cutoff = RESAMPLE_CUTOFF * (up as f64 / down as f64).min(1.0);
+    let half = (RESAMPLE_ZERO_CROSSINGS / cutoff).ceil() as usize;
+    let phases = up.min(RESAMPLE_MAX_PHASES);
+    let window_norm = bessel_i0(RESAMPLE_KAISER_BETA);
+
+    // Kernel for output positions `phase / phases` of the way past an input sample
+    let kernels: Vec<Vec<f32>> = (0..phases)
+        .map(|phase| {
+            let frac = phase as f64 / phases as f64;
+            let taps: Vec<f64> = (0..2 * half)
+                .map(|j| {
+                    let t = frac + half as f64 - 1.0 - j as f64;
+                    let x = (t / half as f64).clamp(-1.0, 1.0);
+                    let window =
+                        bessel_i0(RESAMPLE_KAISER_BETA * (1.0 - x * x).sqrt()) / window_norm;
+                    sinc(cutoff * t) * window
+                })
+                .collect();
+            // Unity gain at DC for every phase
+            let sum: f64 = taps.iter().sum();
+            taps.iter().map(|tap| (tap / sum) as f32).collect()
+        })
+        .collect();
+
+    let output_len = (samples.len() as u64 * up).div_ceil(down);
+    (0..output_len)
+        .map(|n| {
+            let position = n * down;
+            let mut index = position / up;
+            let mut phase = (position % up * phases + up / 2) / up;
+            if phase == phases {
+                index += 1;
+                phase = 0;
+            }
+            let kernel = &kernels[phase as usize];
+
+            // Input samples under the kernel, zero-padded past either end
+            let first = index as i64 - half as i64 + 1;
+            let start = first.max(0) as usize;
+            let end = (first + kernel.len() as i64).min(samples.len() as i64);
+            if end <= start as i64 {
+                return 0.0;
+            }
+            let taps = &kernel[(start as i64 - first) as usize..];
+            samples[start..end as usize]
+                .iter()
+                .zip(taps)
+                .map(|(sample, tap)| sample * tap)
+                .sum()
+        })
+        .collect()
+}
+
+fn gcd(a: u32, b: u32) -> u32 {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+fn sinc(x: f64) -> f64 {
+    if x.abs() < 1e-9 {
+        1.0
+    } else {
+        let x = x * std::f64::consts::PI;
+        x.sin() / x
+    }
+}
+
+/// Zeroth-order modified Bessel function of the first kind, for the Kaiser window
+fn bessel_i0(x: f64) -> f64 {
+    let mut sum = 1.0;
+    let mut term = 1.0;
+    let half_x = x / 2.0;
+    for k in 1..64 {
+        term *= (half_x / k as f64).powi(2);
+        sum += term;
+        if term < sum * 1e-12 {
+            break;
+        }
+    }
+    sum
+}
+
+/// Mix interleaved frames down to mono by averaging their channels
+pub fn downmix(interleaved: &[f32], channels: u16) -> Vec<f32> {
+    let mut mono = Vec::with_capacity(interleaved.len() / channels.max(1) as usize);
+    downmix_into(interleaved, channels as usize, &mut mono);
+    mono
+}
+
+/// Average each interleaved frame into one mono sample, appending to `out`
+///
+/// Averaging keeps a source picked up equally on every channel at its original
+/// level; a trailing partial frame is dropped.
+fn downmix_into<T>(interleaved: &[T], channels: usize, out: &mut impl Extend<f32>)
+where
+    T: Sample,
+    f32: cpal::FromSample<T>,
+{
+    if channels <= 1 {
+        out.extend(interleaved.iter().map(|sample| sample.to_sample::<f32>()));
+        return;
+    }
+    out.extend(interleaved.chunks_exact(channels).map(|frame| {
+        frame
+            .iter()
+            .map(|sample| sample.to_sample::<f32>())
+            .sum::<f32>()
+            / channels as f32
+    }));
+}
+
+/// Upper bound for the adaptive speech threshold, so recordings that are
+/// speech from start to finish don't raise it above quiet words
+const MAX_SPEECH_THRESHOLD_DB: f32 = -30.0;
+
+/// Voice activity detection settings
+#[derive(Debug, Clone)]
+pub struct VadConfig {
+    /// Analysis frame length in milliseconds
+    pub frame_ms: u32,
+    /// Frames this far above the estimated noise floor (dB) count as speech
+    pub energy_margin_db: f32,
+    /// Frames quieter than this (dBFS) are always silence
+    pub min_energy_db: f32,
+    /// Zero-crossing rate above which barely-loud frames are treated as hiss
+    pub max_noise_zcr: f32,
+    /// Speech shorter than this is ignored (clicks, key presses)
+    pub min_speech_ms: u32,
+    /// Pauses shorter than this don't end a speech segment
+    pub min_silence_ms: u32,
+    /// Audio kept either side of detected speech
+    pub speech_pad_ms: u32,
+    /// Longest chunk produced when splitting at pauses
+    pub max_chunk_ms: u32,
+    /// Probability the neural model must report for a frame to count as speech
+    pub neural_threshold: f32,
+}
+
+impl Default for VadConfig {
+    fn default() -> Self {
+        Self {
+            frame_ms: 30,
+            energy_margin_db: 10.0,
+            min_energy_db: -50.0,
+            max_noise_zcr: 0.35,
+            min_speech_ms: 150,
+            min_silence_ms: 400,
+            speech_pad_ms: 200,
+            max_chunk_ms: 30_000,
+            neural_threshold: 0.5,
+        }
+    }
+}
+
+/// Small frame classifier over log-mel features, run with candle on the CPU
+///
+/// Expects a safetensors file with `fc1.weight` (hidden x 80), `fc1.bias`,
+/// `fc2.weight` (1 x hidden) and `fc2.bias`. Each VAD frame is scored on its
+/// mean Whisper log-mel spectrum at 16kHz.
+pub struct NeuralVad {
+    fc1: Linear,
+    fc2: Linear,
+    mel_filters: Vec<f32>,
+}
+
+impl NeuralVad {
+    /// Mel bins in the model input
+    const MEL_BINS: usize = 80;
+
+    /// Load model weights from a safetensors file
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let mut tensors =
+            candle_core::safetensors::load(path, &TensorDevice::Cpu).map_err(|e| {
+                Error::Model(format!("Failed to load VAD model {}: {e}", path.display()))
+            })?;
+        let mut take = |name: &str| {
+            tensors
+                .remove(name)
+                .ok_or_else(|| Error::Model(format!("VAD model is missing {name}")))
+        };
+        let fc1 = Linear::new(take("fc1.weight")?, Some(take("fc1.bias")?));
+        let fc2 = Linear::new(take("fc2.weight")?, Some(take("fc2.bias")?));
+
+        let input_dims = fc1.weight().dims2().map(|(_, inputs)| inputs);
+        let output_dims = fc2.weight().dims2().map(|(outputs, _)| outputs);
+        if input_dims.ok() != Some(Self::MEL_BINS) || output_dims.ok() != Some(1) {
+            return Err(Error::Model(format!(
+                "VAD model must map {} mel bins to 1 output",
+                Self::MEL_BINS
+            )));
+        }
+
+        Ok(Self {
+            fc1,
+            fc2,
+            mel_filters: crate::providers::mel_filters(),
+        })
+    }
+
+    /// Speech probability for each `frame_len`-sample frame of `samples`
+    pub fn speech_probabilities(
+        &self,
+        samples: &[f32],
+        sample_rate: u32,
+        frame_len: usize,
+    ) -> Result<Vec<f32>> {
+        let frames = samples.len() / frame_len;
+        if frames == 0 {
+            return Ok(Vec::new());
+        }
+
+        let samples = resample(samples, sample_rate, whisper::SAMPLE_RATE as u32);
+        let mel = whisper::audio::log_mel_spectrogram_(
+            &samples,
+            &self.mel_filters,
+            whisper::N_FFT,
+            whisper::HOP_LENGTH,
+            Self::MEL_BINS,
+            false,
+        );
+        // Laid out bin-major, padded past the end of the audio
+        let mel_len = mel.len() / Self::MEL_BINS;
+        let hops = (samples.len() / whisper::HOP_LENGTH).clamp(1, mel_len);
+        let hops_per_frame = hops as f64 / frames as f64;
+
+        let mut features = Vec::with_capacity(frames * Self::MEL_BINS);
+        for frame in 0..frames {
+            let start = ((frame as f64 * hops_per_frame) as usize).min(hops - 1);
+            let end = (((frame + 1) as f64 * hops_per_frame) as usize).clamp(start + 1, hops);
+            for bin in 0..Self::MEL_BINS {
+                let row = &mel[bin * mel_len..(bin + 1) * mel_len];
+                features.push(row[start..end].iter().sum::<f32>() / (end - start) as f32);
+            }
+        }
+
+        let run = || -> candle_core::Result<Vec<f32>> {
+            let input = Tensor::from_vec(features, (frames, Self::MEL_BINS), &TensorDevice::Cpu)?;
+            let hidden = self.fc1.forward(&input)?.relu()?;
+            let logits = self.fc2.forward(&hidden)?;
+            candle_nn::ops::sigmoid(&logits)?.flatten_all()?.to_vec1()
+        };
+        run().map_err(|e| Error::Model(format!("VAD model failed: {e}")))
+    }
+}
+
+/// Finds speech in recorded audio
+///
+/// Frames are classified by energy against an adaptive noise floor, with the
+/// zero-crossing rate used to reject hiss; a `NeuralVad` model, when set, must
+/// also agree before a frame counts as speech.
+#[derive(Default)]
+pub struct VoiceActivityDetector {
+    config: VadConfig,
+    model: Option<NeuralVad>,
+}
+
+impl VoiceActivityDetector {
+    pub fn new(config: VadConfig) -> Self {
+        Self {
+            config,
+            model: None,
+        }
+    }
+
+    /// Confirm speech frames with a neural model
+    pub fn with_model(mut self, model: NeuralVad) -> Self {
+        self.model = Some(model);
+        self
+    }
+
+    /// Replace or remove the neural model
+    pub fn set_model(&mut self, model: Option<NeuralVad>) {
+        self.model = model;
+    }
+
+    pub fn has_model(&self) -> bool {
+        self.model.is_some()
+    }
+
+    pub fn config(&self) -> &VadConfig {
+        &self.config
+    }
+
+    /// Sample ranges containing speech, padded and with short pauses bridged
+    pub fn detect(&self, samples: &[f32], sample_rate: u32) -> Result<Vec<Range<usize>>> {
+        let ms_to_samples = |ms: u32| (sample_rate as u64 * ms as u64 / 1000) as usize;
+        let frame_len = ms_to_samples(self.config.frame_ms).max(1);
+        let frames: Vec<&[f32]> = samples.chunks(frame_len).collect();
+        if frames.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let energy: Vec<f32> = frames.iter().map(|frame| frame_energy_db(frame)).collect();
+        let mut sorted = energy.clone();
+        sorted.sort_by(f32::total_cmp);
+        let noise_floor = sorted[sorted.len() / 10];
+        let threshold = (noise_floor + self.config.energy_margin_db)
+            .clamp(self.config.min_energy_db, MAX_SPEECH_THRESHOLD_DB);
+
+        let mut speech: Vec<bool> = frames
+            .iter()
+            .zip(&energy)
+            .map(|(frame, &db)| {
+                let hiss = zero_crossing_rate(frame) > self.config.max_noise_zcr
+                    && db < threshold + self.config.energy_margin_db;
+                db >= threshold && !hiss
+            })
+            .collect();
+
+        if let Some(model) = &self.model
+            && speech.contains(&true)
+        {
+            let probabilities = model.speech_probabilities(samples, sample_rate, frame_len)?;
+            for (is_speech, probability) in speech.iter_mut().zip(probabilities) {
+                *is_speech &= probability >= self.config.neural_threshold;
+            }
+        }
+
+        // Runs of speech frames, bridging pauses shorter than min_silence_ms
+        let frames_for = |ms: u32| (ms as usize).div_ceil(self.config.frame_ms.max(1) as usize);
+        let min_silence = frames_for(self.config.min_silence_ms);
+        let mut runs: Vec<Range<usize>> = Vec::new();
+        for (index, _) in speech.iter().enumerate().filter(|(_, s)| **s) {
+            match runs.last_mut() {
+                Some(run) if index - run.end < min_silence => run.end = index + 1,
+                _ => runs.push(index..index + 1),
+            }
+        }
+
+        let min_speech = frames_for(self.config.min_speech_ms);
+        let pad = ms_to_samples(self.config.speech_pad_ms);
+        let mut segments: Vec<Range<usize>> = Vec::new();
+        for run in runs.into_iter().filter(|run| run.len() >= min_speech) {
+            let start = (run.start * frame_len).saturating_sub(pad);
+            let end = (run.end * frame_len + pad).min(samples.len());
+            match segments.last_mut() {
+                Some(last) if start <= last.end => last.end = end,
+                _ => segments.push(start..end),
+            }
+        }
+        Ok(segments)
+    }
+
+    /// The span from the first to the last speech, or None when there is none
+    pub fn trim(&self, samples: &[f32], sample_rate: u32) -> Result<Option<Range<usize>>> {
+        let segments = self.detect(samples, sample_rate)?;
+        Ok(match (segments.first(), segments.last()) {
+            (Some(first), Some(last)) => Some(first.start..last.end),
+            _ => None,
+        })
+    }
+
+    /// Group speech segments into chunks of at most `max_chunk_ms`, cutting in
+    /// the pauses between them
+    ///
+    /// A single segment longer than the limit is cut at its quietest frame.
+    pub fn split_at_pauses(
+        &self,
+        samples: &[f32],
+        sample_rate: u32,
+        segments: &[Range<usize>],
+    ) -> Vec<Range<usize>> {
+        let max_len = (sample_rate as u64 * self.config.max_chunk_ms as u64 / 1000) as usize;
+        let frame_len = (sample_rate as u64 * self.config.frame_ms as u64 / 1000).max(1) as usize;
+        if max_len < frame_len * 2 {
+            return segments.to_vec();
+        }
+
+        let mut chunks: Vec<Range<usize>> = Vec::new();
+        for segment in segments {
+            let mut start = segment.start;
+            // Join the previous chunk when both fit; the cut lands mid-pause
+            if let Some(last) = chunks.last_mut()
+                && segment.end - last.start <= max_len
+            {
+                last.end = segment.end;
+                continue;
+            }
+            if let Some(last) = chunks.last_mut() {
+                let cut = (last.end + start) / 2;
+                last.end = cut;
+                start = cut;
+            }
+
+            while segment.end - start > max_len {
+                // Look for the quietest frame in the last third of the window
+                let search = start + max_len * 2 / 3..start + max_len - frame_len;
+                let cut = search
+                    .step_by(frame_len)
+                    .map(|at| (at, frame_energy_db(&samples[at..at + frame_len])))
+                    .min_by(|a, b| a.1.total_cmp(&b.1))
+                    .map_or(start + max_len, |(at, _)| at + frame_len / 2);
+                chunks.push(start..cut);
+                start = cut;
+            }
+            chunks.push(start..segment.end);
+        }
+        chunks
+    }
+}
+
+/// RMS level of a frame in dBFS
+fn frame_energy_db(frame: &[f32]) -> f32 {
+    if frame.is_empty() {
+        return f32::NEG_INFINITY;
+    }
+    let mean_square = frame.iter().map(|&s| s * s).sum::<f32>() / frame.len() as f32;
+    10.0 * mean_square.max(1e-12).log10()
+}
+
+/// Fraction of adjacent samples that change sign
+fn zero_crossing_rate(frame: &[f32]) -> f32 {
+    if frame.len() < 2 {
+        return 0.0;
+    }
+    let crossings = frame
+        .windows(2)
+        .filter(|pair| (pair[0] >= 0.0) != (pair[1] >= 0.0))
+        .count();
+    crossings as f32 / (frame.len() - 1) as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_config() {
+        let config = AudioCaptureConfig::default();
+        assert_eq!(config.sample_rate, 16000);
+        assert_eq!(config.channels, 1);
+    }
+
+    #[test]
+    fn test_samples_to_pcm() {
+        let samples = [0.0f32, 0.5, -0.5, 1.0, -1.0];
+        let pcm = samples_to_pcm(&samples);
+
+        // 5 samples * 2 bytes each = 10 bytes
+        assert_eq!(pcm.len(), 10);
+
+        // check silence (0.0 -> 0)
+        assert_eq!(i16::from_le_bytes([pcm[0], pcm[1]]), 0);
+
+        // check 0.5 -> ~16383
+        let half_pos = i16::from_le_bytes([pcm[2], pcm[3]]);
+        assert!((half_pos - 16383).abs() < 2);
+
+        // check -0.5 -> ~-16383
+        let half_neg = i16::from_le_bytes([pcm[4], pcm[5]]);
+        assert!((half_neg + 16383).abs() < 2);
+
+        let round_trip = pcm_to_samples(&pcm);
+        for (original, converted) in samples.iter().zip(round_trip) {
+            assert!((original - converted).abs() < 1e-3);
+        }
+    }
+
+    pub(super) fn sine(freq: f32, rate: u32, ms: u32) -> Vec<f32> {
+        (0..rate * ms / 1000)
+            .map(|i| 0.5 * (i as f32 * freq * std::f32::consts::TAU / rate as f32).sin())
+            .collect()
+    }
+
+    /// Amplitude of the `freq` component (Goertzel), skipping filter edges
+    pub(super) fn tone_amplitude(samples: &[f32], rate: u32, freq: f32) -> f32 {
+        let samples = &samples[samples.len() / 10..samples.len() * 9 / 10];
+        let coeff = 2.0 * (std::f64::consts::TAU * freq as f64 / rate as f64).cos();
+        let (mut s1, mut s2) = (0.0f64, 0.0f64);
+        for &sample in samples {
+            let s0 = sample as f64 + coeff * s1 - s2;
+            s2 = s1;
+            s1 = s0;
+        }
+        let power = s1 * s1 + s2 * s2 - coeff * s1 * s2;
+        (2.0 * power.sqrt() / samples.len() as f64) as f32
+    }
+
+    pub(super) fn db(ratio: f32) -> f32 {
+        20.0 * ratio.max(1e-9).log10()
+    }
+
+    #[test]
+    fn test_resample_keeps_passband() {
+        for from in [44100, 48000, 8000] {
+            let output = resample(&sine(1000.0, from, 500), from, 16000);
+            assert_eq!(output.len(), 8000, "{from} Hz");
+            let gain = tone_amplitude(&output, 16000, 1000.0) / 0.5;
+            assert!(db(gain).abs() < 0.1, "{from} Hz: {} dB", db(gain));
+        }
+    }
+
+    #[test]
+    fn test_resample_rejects_aliases() {
+        // Above the 8kHz output Nyquist frequency; a naive decimator folds these
+        // back to 4kHz and 6kHz
+        for (from, freq, alias) in [(48000, 12000.0, 4000.0), (44100, 10000.0, 6000.0)] {
+            let output = resample(&sine(freq, from, 500), from, 16000);
+            let leak = tone_amplitude(&output, 16000, alias) / 0.5;
+            assert!(
+                db(leak) < -60.0,
+                "{freq} Hz from {from} Hz: {} dB",
+                db(leak)
+            );
+
+            let naive = resample_linear(&sine(freq, from, 500), from, 16000);
+            assert!(db(tone_amplitude(&naive, 16000, alias) / 0.5) > -20.0);
+        }
+
+        // Upsampling must not create an image of the tone mirrored at 4kHz
+        let output = resample(&sine(1000.0, 8000, 500), 8000, 16000);
+        let image = tone_amplitude(&output, 16000, 7000.0) / 0.5;
+        assert!(db(image) < -60.0, "image at {} dB", db(image));
+    }
+
+    /// Point sampling at the output rate, for comparison
+    fn resample_linear(samples: &[f32], from_rate: u32, to_rate: u32) -> Vec<f32> {
+        let step = from_rate as f64 / to_rate as f64;
+        (0..(samples.len() as f64 / step) as usize)
+            .map(|i| samples[(i as f64 * step) as usize])
+            .collect()
+    }
+
+    #[test]
+    fn test_downmix() {
+        let stereo = [0.5f32, -0.5, 0.25, 0.75, 1.0];
+        assert_eq!(downmix(&stereo, 2), vec![0.0, 0.5]);
+        assert_eq!(downmix(&stereo, 1), stereo.to_vec());
+
+        let mut mono = Vec::new();
+        downmix_into(&[i16::MAX, i16::MAX, i16::MAX, 0, 0, 0], 3, &mut mono);
+        assert!((mono[0] - 1.0).abs() < 1e-3);
+        assert_eq!(mono[1], 0.0);
+    }
+
+    const RATE: u32 = 16000;
+
+    /// Quiet background noise (about -60 dBFS)
+    fn noise(ms: u32, seed: u32) -> Vec<f32> {
+        let mut state = seed.wrapping_mul(747796405).wrapping_add(1);
+        (0..RATE * ms / 1000)
+            .map(|_| {
+                state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+                (state >> 8) as f32 / (1 << 24) as f32 * 0.002 - 0.001
+            })
+            .collect()
+    }
+
+    /// Voiced sound stand-in: a 220 Hz tone over the background noise
+    fn voice(ms: u32) -> Vec<f32> {
+        noise(ms, 7)
+            .into_iter()
+            .enumerate()
+            .map(|(i, n)| n + 0.3 * (i as f32 * 220.0 * std::f32::consts::TAU / RATE as f32).sin())
+            .collect()
+    }
+
+    fn recording(parts: &[(bool, u32)]) -> Vec<f32> {
+        parts
+            .iter()
+            .enumerate()
+            .flat_map(|(i, &(speech, ms))| {
+                if speech {
+                    voice(ms)
+                } else {
+                    noise(ms, i as u32)
+                }
+            })
+            .collect()
+    }
+
+    fn ms(samples: usize) -> usize {
+        samples * 1000 / RATE as usize
+    }
+
+    #[test]
+    fn test_vad_trims_silence() {
+        let vad = VoiceActivityDetector::default();
+        let audio = recording(&[(false, 1000), (true, 1200), (false, 1500)]);
+
+        let speech = vad.trim(&audio, RATE).unwrap().unwrap();
+        // Padded by 200ms either side of the voice
+        assert!((780..=810).contains(&ms(speech.start)), "{speech:?}");
+        assert!((2380..=2440).contains(&ms(speech.end)), "{speech:?}");
+
+        // Short pauses stay inside one segment
+        let audio = recording(&[(true, 800), (false, 200), (true, 800)]);
+        assert_eq!(vad.detect(&audio, RATE).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_vad_finds_no_speech() {
+        let vad = VoiceActivityDetector::default();
+        assert_eq!(vad.trim(&noise(2000, 1), RATE).unwrap(), None);
+        assert_eq!(vad.trim(&[], RATE).unwrap(), None);
+
+        // A click from the hotkey is too short to be speech
+        let audio = recording(&[(false, 1000), (true, 60), (false, 1000)]);
+        assert_eq!(vad.trim(&audio, RATE).unwrap(), None);
+
+        // Hiss is rejected by its zero-crossing rate
+        let hiss: Vec<f32> = noise(1500, 3).iter().map(|s| s * 10.0).collect();
+        let audio = [noise(1000, 4), hiss, noise(1000, 5)].concat();
+        assert_eq!(vad.trim(&audio, RATE).unwrap(), None);
+    }
+
+    #[test]
+    fn test_vad_splits_at_pauses() {
+        let vad = VoiceActivityDetector::new(VadConfig {
+            max_chunk_ms: 2500,
+            ..VadConfig::default()
+        });
+        let audio = recording(&[
+            (true, 1000),
+            (false, 1000),
+            (true, 1000),
+            (false, 1000),
+            (true, 3000),
+        ]);
+        let segments = vad.detect(&audio, RATE).unwrap();
+        assert_eq!(segments.len(), 3);
+
+        let chunks = vad.split_at_pauses(&audio, RATE, &segments);
+        // Cuts land between the first two segments; the last is split by length
+        assert_eq!(chunks.len(), 4);
+        assert!((1400..=1600).contains(&ms(chunks[0].end)), "{chunks:?}");
+        for pair in chunks.windows(2) {
+            assert_eq!(pair[0].end, pair[1].start);
+        }
+        for chunk in &chunks {
+            assert!(ms(chunk.len()) <= 2500, "{chunks:?}");
+        }
+        assert_eq!(chunks.last().unwrap().end, segments[2].end);
+    }
+
+    fn write_model(dir: &Path, output_bias: f32, inputs: usize) -> std::path::PathBuf {
+        let device = TensorDevice::Cpu;
+        let tensors = std::collections::HashMap::from([
+            (
+                "fc1.weight".to_string(),
+                Tensor::zeros((4, inputs), candle_core::DType::F32, &device).unwrap(),
+            ),
+            (
+                "fc1.bias".to_string(),
+                Tensor::zeros(4, candle_core::DType::F32, &device).unwrap(),
+            ),
+            (
+                "fc2.weight".to_string(),
+                Tensor::zeros((1, 4), candle_core::DType::F32, &device).unwrap(),
+            ),
+            (
+                "fc2.bias".to_string(),
+                Tensor::new(&[output_bias], &device).unwrap(),
+            ),
+        ]);
+        let path = dir.join(format!("vad-{output_bias}-{inputs}.safetensors"));
+        candle_core::safetensors::save(&tensors, &path).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_neural_vad_confirms_speech() {
+        let dir = tempfile::tempdir().unwrap();
+        let audio = recording(&[(false, 500), (true, 1000), (false, 500)]);
+
+        let accepting = NeuralVad::load(write_model(dir.path(), 5.0, 80)).unwrap();
+        let probabilities = accepting.speech_probabilities(&audio, RATE, 480).unwrap();
+        assert_eq!(probabilities.len(), audio.len() / 480);
+        let vad = VoiceActivityDetector::default().with_model(accepting);
+        assert!(vad.trim(&audio, RATE).unwrap().is_some());
+
+        let rejecting = NeuralVad::load(write_model(dir.path(), -5.0, 80)).unwrap();
+        let vad = VoiceActivityDetector::default().with_model(rejecting);
+        assert_eq!(vad.trim(&audio, RATE).unwrap(), None);
+
+        assert!(matches!(
+            NeuralVad::load(write_model(dir.path(), 0.0, 40)),
+            Err(Error::Model(_))
+        ));
+        assert!(NeuralVad::load(dir.path().join("missing.safetensors")).is_err());
+    }
+}
@@ -0,0 +1,177 @@
+//! Warm capture for pre-roll
+//!
+//! With pre-roll on, the input device stays open between recordings and the
+//! last few hundred milliseconds heard are kept, so a recording can start
+//! with the word spoken while the hotkey was still going down. The stream
+//! lives on its own thread, which closes the device once it has sat idle for
+//! the configured timeout.
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use cpal::Sample;
+use tracing::{error, info};
+
+use crate::error::{Error, Result};
+
+use super::{CaptureState, InputStreamSetup, StreamShared, downmix_into};
+
+/// Bounded history of the mono audio heard while idle
+pub(super) struct PreRoll {
+    samples: VecDeque<f32>,
+    capacity: usize,
+}
+
+impl PreRoll {
+    pub(super) fn new(capacity: usize) -> Self {
+        Self {
+            samples: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// Append interleaved frames, forgetting the oldest audio beyond capacity
+    pub(super) fn push<T>(&mut self, data: &[T], channels: usize)
+    where
+        T: Sample,
+        f32: cpal::FromSample<T>,
+    {
+        if self.capacity == 0 {
+            return;
+        }
+        downmix_into(data, channels, &mut self.samples);
+        let excess = self.samples.len().saturating_sub(self.capacity);
+        self.samples.drain(..excess);
+    }
+
+    pub(super) fn take(&mut self) -> Vec<f32> {
+        self.samples.drain(..).collect()
+    }
+
+    pub(super) fn clear(&mut self) {
+        self.samples.clear();
+    }
+}
+
+enum WarmControl {
+    /// A recording started or stopped; restart the idle timeout
+    Activity,
+    Close,
+}
+
+/// An input stream kept open on its own thread
+///
+/// Closes when dropped, or by itself after `idle_timeout` without recording.
+pub(super) struct WarmStream {
+    control: Sender<WarmControl>,
+    thread: Option<JoinHandle<()>>,
+    open: Arc<AtomicBool>,
+}
+
+impl WarmStream {
+    /// Open the device and start filling `shared`, returning once the stream runs
+    pub(super) fn spawn(
+        input: InputStreamSetup,
+        shared: StreamShared,
+        idle_timeout: Duration,
+    ) -> Result<Self> {
+        let (control, commands) = mpsc::channel();
+        let (ready_tx, ready_rx) = mpsc::sync_channel(1);
+        let open = Arc::new(AtomicBool::new(true));
+        let thread_open = Arc::clone(&open);
+
+        let thread = std::thread::Builder::new()
+            .name("flow-warm-capture".to_string())
+            .spawn(move || {
+                let stream = match input.play(&shared) {
+                    Ok(stream) => {
+                        let _ = ready_tx.send(Ok(()));
+                        stream
+                    }
+                    Err(e) => {
+                        thread_open.store(false, Ordering::SeqCst);
+                        let _ = ready_tx.send(Err(e));
+                        return;
+                    }
+                };
+
+                loop {
+                    match commands.recv_timeout(idle_timeout) {
+                        Ok(WarmControl::Activity) => {}
+                        Ok(WarmControl::Close) | Err(RecvTimeoutError::Disconnected) => break,
+                        Err(RecvTimeoutError::Timeout) => {
+                            if *shared.state.lock() == CaptureState::Idle {
+                                info!("Closing input device after {:?} idle", idle_timeout);
+                                break;
+                            }
+                        }
+                    }
+                }
+
+                thread_open.store(false, Ordering::SeqCst);
+                drop(stream);
+                shared.pre_roll.lock().clear();
+            })
+            .map_err(|e| Error::Audio(format!("Failed to start capture thread: {e}")))?;
+
+        match ready_rx.recv() {
+            Ok(Ok(())) => Ok(Self {
+                control,
+                thread: Some(thread),
+                open,
+            }),
+            Ok(Err(e)) => {
+                let _ = thread.join();
+                Err(e)
+            }
+            Err(_) => {
+                error!("Capture thread exited before the stream started");
+                let _ = thread.join();
+                Err(Error::Audio(
+                    "Capture thread exited unexpectedly".to_string(),
+                ))
+            }
+        }
+    }
+
+    /// Whether the device is still open (false once the idle timeout closed it)
+    pub(super) fn is_open(&self) -> bool {
+        self.open.load(Ordering::SeqCst)
+    }
+
+    /// Restart the idle timeout
+    pub(super) fn touch(&self) {
+        let _ = self.control.send(WarmControl::Activity);
+    }
+}
+
+impl Drop for WarmStream {
+    fn drop(&mut self) {
+        let _ = self.control.send(WarmControl::Close);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pre_roll_keeps_latest_audio() {
+        let mut pre_roll = PreRoll::new(4);
+        pre_roll.push(&[0.1f32, 0.2, 0.3], 1);
+        pre_roll.push(&[0.5f32, 0.5, 0.25, 0.75, 0.0, 0.5], 2);
+        assert_eq!(pre_roll.take(), vec![0.3, 0.5, 0.5, 0.25]);
+        assert!(pre_roll.take().is_empty());
+
+        let mut disabled = PreRoll::new(0);
+        disabled.push(&[0.1f32; 16], 1);
+        assert!(disabled.take().is_empty());
+    }
+}
@@ -17,6 +17,20 @@ pub enum Error {
     #[error("Completion failed: {0}")]
     Completion(String),
 
+    /// A transcription API answered with an error status
+    #[error("Transcription failed: {message}")]
+    TranscriptionApi {
+        status: reqwest::StatusCode,
+        message: String,
+    },
+
+    /// A completion API answered with an error status
+    #[error("Completion failed: {message}")]
+    CompletionApi {
+        status: reqwest::StatusCode,
+        message: String,
+    },
+
     #[error("Storage error: {0}")]
     Storage(#[from] rusqlite::Error),
 
@@ -35,6 +49,22 @@ pub enum Error {
     #[error("Feature requires subscription tier: {0}")]
     SubscriptionRequired(String),
 
+    #[error("No speech detected")]
+    NoSpeech,
+
+    #[error("Offline mode is on: {0} needs network access")]
+    Offline(String),
+
+    #[error("Model error: {0}")]
+    Model(String),
+
+    #[error("Checksum mismatch for {file}: expected {expected}, got {actual}")]
+    ChecksumMismatch {
+        file: String,
+        expected: String,
+        actual: String,
+    },
+
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
 }
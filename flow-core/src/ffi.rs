@@ -7,36 +7,66 @@
 #![allow(clippy::not_unsafe_ptr_arg_deref)]
 
 use std::ffi::{CStr, CString};
+use std::ops::Range;
 use std::os::raw::{c_char, c_void};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::ptr;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 
+use chrono::Utc;
+use futures::StreamExt;
 use parking_lot::Mutex;
 use serde::Serialize;
 use tokio::runtime::Runtime;
-use tracing::{debug, error};
+use tracing::{debug, error, warn};
 
 use crate::apps::AppTracker;
-use crate::audio::{AudioCapture, CaptureState};
+use crate::archive::{AudioArchive, RetentionPolicy};
+use crate::audio::{
+    AudioCapture, AudioCaptureConfig, AudioDeviceEvent, AudioProcessing, AudioSource, CaptureState,
+    DEFAULT_WARM_IDLE_TIMEOUT, FileSource, HandsFreeConfig, MAX_PRE_ROLL_MS, NeuralVad,
+    SPEECH_SAMPLE_RATE, VoiceActivityDetector,
+};
 use crate::contacts::{ContactClassifier, ContactInput};
+use crate::error::Error;
 use crate::learning::LearningEngine;
+use crate::live::{LiveConfig, LiveSession, LiveTranscript};
 use crate::macos_messages::MessagesDetector;
 use crate::modes::{StyleLearner, WritingMode, WritingModeEngine};
 use crate::providers::{
-    Base10TranscriptionProvider, CompletionProvider, GeminiCompletionProvider,
-    GeminiTranscriptionProvider, LocalWhisperTranscriptionProvider, OpenAICompletionProvider,
-    OpenAITranscriptionProvider, OpenRouterCompletionProvider, TranscriptionCompletionParams,
-    TranscriptionProvider, TranscriptionRequest, WhisperModel,
+    AnthropicCompletionProvider, Base10TranscriptionProvider, CompletionProvider,
+    CompletionRequest, ConfidenceSpan, DecodingOptions, FallbackCompletionProvider,
+    FallbackTranscriptionProvider, GeminiCompletionProvider, GeminiTranscriptionProvider,
+    LOW_CONFIDENCE_THRESHOLD, LocalFormattingProvider, LocalWhisperTranscriptionProvider,
+    OpenAICompletionProvider, OpenAITranscriptionProvider, OpenRouterCompletionProvider,
+    ShortcutExpansion, TranscriptionCompletionParams, TranscriptionProvider, TranscriptionRequest,
+    TranscriptionSegment, WhisperModel, format_text, low_confidence_spans, whisper_language_code,
+};
+use crate::queue::{MAX_QUEUE_ATTEMPTS, QueueWorker, retry_delay};
+use crate::shortcuts::{
+    ShortcutsEngine, TriggeredShortcut, preservation_instruction, restore_expansions,
 };
-use crate::shortcuts::ShortcutsEngine;
 use crate::storage::{
+    SETTING_ANTHROPIC_API_KEY, SETTING_AUDIO_ARCHIVE, SETTING_AUDIO_ARCHIVE_MAX_AGE_DAYS,
+    SETTING_AUDIO_ARCHIVE_MAX_MB, SETTING_AUTOMATIC_GAIN_CONTROL,
     SETTING_CLOUD_TRANSCRIPTION_PROVIDER, SETTING_COMPLETION_PROVIDER, SETTING_GEMINI_API_KEY,
-    SETTING_LOCAL_WHISPER_MODEL, SETTING_OPENAI_API_KEY, SETTING_OPENROUTER_API_KEY,
-    SETTING_USE_LOCAL_TRANSCRIPTION, Storage,
+    SETTING_HANDS_FREE, SETTING_HANDS_FREE_MAX_DURATION_MS, SETTING_HANDS_FREE_MIN_DURATION_MS,
+    SETTING_HANDS_FREE_TRAILING_SILENCE_MS, SETTING_HIGH_PASS_FILTER, SETTING_INPUT_DEVICE,
+    SETTING_LIVE_TRANSCRIPTION, SETTING_LOCAL_WHISPER_MODEL, SETTING_NOISE_SUPPRESSION,
+    SETTING_OFFLINE_MODE, SETTING_OPENAI_API_KEY, SETTING_OPENAI_BASE_URL,
+    SETTING_OPENAI_COMPLETION_MODEL, SETTING_OPENAI_TRANSCRIPTION_MODEL,
+    SETTING_OPENROUTER_API_KEY, SETTING_PRE_ROLL_IDLE_TIMEOUT_SECS, SETTING_PRE_ROLL_MS,
+    SETTING_TRANSCRIPTION_LANGUAGE, SETTING_TRANSLATE_TO_ENGLISH, SETTING_USE_LOCAL_TRANSCRIPTION,
+    SETTING_VAD_ENABLED, SETTING_VAD_MODEL_PATH, SETTING_WHISPER_BEAM_SIZE, Storage,
 };
-use crate::types::{Shortcut, Transcription, TranscriptionHistoryEntry, TranscriptionStatus};
+use crate::types::{
+    QueuedTranscription, Shortcut, Transcription, TranscriptionHistoryEntry, TranscriptionId,
+    TranscriptionStatus,
+};
+use crate::vocabulary::VocabularySources;
+use crate::whisper_models::{self, DownloadProgress, ModelSpec};
 
 /// Log with timestamp
 macro_rules! log_with_time {
@@ -52,9 +82,23 @@ macro_rules! log_with_time {
 pub struct FlowHandle {
     runtime: Runtime,
     storage: Storage,
-    audio: Mutex<Option<AudioCapture>>,
+    audio: Mutex<Option<Box<dyn AudioSource>>>,
+    /// Record from this file instead of the microphone
+    input_file: Mutex<Option<PathBuf>>,
+    /// Input device changes not yet collected by the app
+    audio_events: Mutex<Vec<AudioDeviceEvent>>,
     last_audio: Mutex<Option<crate::AudioData>>,
     last_audio_sample_rate: Mutex<Option<u32>>,
+    /// Recordings kept with their history entries, when the archive is on
+    archive: AudioArchive,
+    /// Retries transcriptions that failed for lack of network
+    queue_worker: Mutex<Option<QueueWorker>>,
+    /// Told when a queued transcription is resolved (callback and its context)
+    queue_callback: Mutex<Option<(ResultCallback, usize)>>,
+    /// Queued job for the recording in `last_audio`, if its transcription was queued
+    last_queued: Mutex<Option<TranscriptionId>>,
+    /// Transcribes the current recording while it is captured, when live transcription is on
+    live: Mutex<Option<LiveSession>>,
     last_error: Mutex<Option<String>>,
     transcription: Arc<dyn TranscriptionProvider>,
     completion: Arc<dyn CompletionProvider>,
@@ -64,7 +108,13 @@ pub struct FlowHandle {
     app_tracker: AppTracker,
     style_learner: Mutex<StyleLearner>,
     is_model_loading: Arc<AtomicBool>,
+    /// Progress of the current or last Whisper model install
+    model_download: Arc<DownloadProgress>,
+    /// Local Whisper used in offline mode when cloud transcription is configured
+    offline_transcription: Mutex<Option<Arc<dyn TranscriptionProvider>>>,
     contact_classifier: ContactClassifier,
+    /// Finds speech in recordings before they are transcribed
+    vad: Mutex<VoiceActivityDetector>,
     /// Captured contact name at recording start (for Messages.app context)
     captured_contact: Mutex<Option<String>>,
     /// Temporary storage for audio between stop and transcribe (ensures mic is fully released)
@@ -82,25 +132,106 @@ struct TranscriptionSummary {
     duration_ms: u64,
     created_at: String,
     app_name: Option<String>,
+    provider: Option<String>,
+    completion_provider: Option<String>,
+    confidence: Option<f32>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    segments: Vec<TranscriptionSegment>,
+    /// Words that might be misrecognised, with offsets into `raw_text`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    low_confidence_spans: Vec<ConfidenceSpan>,
+    /// The recording is in the audio archive
+    has_audio: bool,
 }
 
 /// Result callback type for async operations
 pub type ResultCallback = extern "C" fn(success: bool, result: *const c_char, context: *mut c_void);
 
+/// Invoke a result callback with a temporary C string (only valid during the call)
+fn invoke_callback(callback: ResultCallback, context: *mut c_void, success: bool, text: &str) {
+    let text = CString::new(text.replace('\0', "")).unwrap_or_default();
+    callback(success, text.as_ptr(), context);
+}
+
 fn set_last_error(handle: &FlowHandle, message: impl Into<String>) {
     *handle.last_error.lock() = Some(message.into());
 }
 
-/// Check if Whisper model files exist in the models directory
-fn check_model_files_exist(model: WhisperModel, models_dir: &std::path::Path) -> bool {
-    let (model_id, _) = model.model_id();
-    let model_name = model_id.split('/').next_back().unwrap();
+/// Reported as the Whisper model when a custom registry model is selected
+const CUSTOM_WHISPER_MODEL: u8 = 255;
+
+/// Maximum beam size accepted for local Whisper decoding
+const MAX_WHISPER_BEAM_SIZE: u8 = 8;
+
+/// Build the local Whisper provider with the stored decoding settings
+fn local_whisper_provider(
+    handle: &FlowHandle,
+    spec: ModelSpec,
+    models_dir: PathBuf,
+) -> LocalWhisperTranscriptionProvider {
+    let beam_size = non_empty_setting(&handle.storage, SETTING_WHISPER_BEAM_SIZE)
+        .and_then(|s| s.parse::<usize>().ok())
+        .unwrap_or(1);
+    LocalWhisperTranscriptionProvider::from_spec(spec, models_dir)
+        .with_decoding_options(DecodingOptions::default().with_beam_size(beam_size))
+        .with_progress(Arc::clone(&handle.model_download))
+}
+
+/// Resolve the stored Whisper model setting: a built-in model name or an installed model id
+fn stored_whisper_spec(name: Option<&str>, models_dir: &std::path::Path) -> ModelSpec {
+    let builtin = name.and_then(|name| {
+        WhisperModel::parse(name).or_else(|| {
+            // Older versions stored the repo id
+            WhisperModel::all()
+                .iter()
+                .find(|m| m.model_id().0 == name)
+                .copied()
+        })
+    });
+    if let Some(model) = builtin {
+        return model.spec(models_dir);
+    }
+
+    let installed =
+        name.and_then(|id| whisper_models::read_manifest(models_dir, id).ok().flatten());
+    match installed.map(|manifest| manifest.spec()) {
+        Some(Ok(spec)) => spec,
+        _ => WhisperModel::Quality.spec(models_dir),
+    }
+}
+
+/// Transcription provider for the next request
+/// In offline mode cloud providers are replaced by local Whisper with the stored model,
+/// which must already be installed
+fn active_transcription_provider(
+    handle: &FlowHandle,
+) -> crate::error::Result<Arc<dyn TranscriptionProvider>> {
+    if !crate::offline::is_offline() || handle.transcription.is_local() {
+        return Ok(Arc::clone(&handle.transcription));
+    }
+
+    let mut offline = handle.offline_transcription.lock();
+    if let Some(provider) = offline.as_ref() {
+        return Ok(Arc::clone(provider));
+    }
 
-    let config_path = models_dir.join(format!("{}-config.json", model_name));
-    let tokenizer_path = models_dir.join(format!("{}-tokenizer.json", model_name));
-    let weights_path = models_dir.join(format!("{}-model.safetensors", model_name));
+    let models_dir = whisper_models::get_models_dir()?;
+    let model = non_empty_setting(&handle.storage, SETTING_LOCAL_WHISPER_MODEL);
+    let spec = stored_whisper_spec(model.as_deref(), &models_dir);
+    debug!("Offline mode: transcribing locally with {}", spec.id);
+    let provider: Arc<dyn TranscriptionProvider> =
+        Arc::new(local_whisper_provider(handle, spec, models_dir));
+    *offline = Some(Arc::clone(&provider));
+    Ok(provider)
+}
 
-    config_path.exists() && tokenizer_path.exists() && weights_path.exists()
+/// Completion provider for the next request (local formatting in offline mode)
+fn active_completion_provider(handle: &FlowHandle) -> Arc<dyn CompletionProvider> {
+    if crate::offline::is_offline() {
+        Arc::new(LocalFormattingProvider::new())
+    } else {
+        Arc::clone(&handle.completion)
+    }
 }
 
 fn clear_last_error(handle: &FlowHandle) {
@@ -112,6 +243,246 @@ fn estimate_duration_ms(bytes: usize, sample_rate: u32) -> u64 {
     (samples as u64 * 1000) / sample_rate as u64
 }
 
+/// Read a non-empty setting value
+fn non_empty_setting(storage: &Storage, key: &str) -> Option<String> {
+    storage
+        .get_setting(key)
+        .ok()
+        .flatten()
+        .filter(|value| !value.is_empty())
+}
+
+/// Whether recordings are checked for speech before transcription (on by default)
+fn vad_enabled(storage: &Storage) -> bool {
+    non_empty_setting(storage, SETTING_VAD_ENABLED).is_none_or(|v| v == "true")
+}
+
+/// Audio processing stages switched on, with unset ones at their defaults
+fn audio_processing(storage: &Storage) -> AudioProcessing {
+    let defaults = AudioProcessing::default();
+    let flag =
+        |key: &str, default: bool| non_empty_setting(storage, key).map_or(default, |v| v == "true");
+    AudioProcessing {
+        high_pass: flag(SETTING_HIGH_PASS_FILTER, defaults.high_pass),
+        noise_suppression: flag(SETTING_NOISE_SUPPRESSION, defaults.noise_suppression),
+        agc: flag(SETTING_AUTOMATIC_GAIN_CONTROL, defaults.agc),
+    }
+}
+
+/// Hands-free recording limits, with unset ones at their defaults
+fn hands_free_limits(storage: &Storage) -> HandsFreeConfig {
+    let defaults = HandsFreeConfig::default();
+    let limit = |key: &str, default: u32| {
+        non_empty_setting(storage, key)
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(default)
+    };
+    HandsFreeConfig {
+        trailing_silence_ms: limit(
+            SETTING_HANDS_FREE_TRAILING_SILENCE_MS,
+            defaults.trailing_silence_ms,
+        ),
+        min_duration_ms: limit(SETTING_HANDS_FREE_MIN_DURATION_MS, defaults.min_duration_ms),
+        max_duration_ms: limit(SETTING_HANDS_FREE_MAX_DURATION_MS, defaults.max_duration_ms),
+    }
+}
+
+/// Hands-free limits when the mode is on (off by default)
+fn hands_free_config(storage: &Storage) -> Option<HandsFreeConfig> {
+    (non_empty_setting(storage, SETTING_HANDS_FREE).as_deref() == Some("true"))
+        .then(|| hands_free_limits(storage))
+}
+
+/// Whether recordings are transcribed while they are captured (off by default)
+fn live_transcription_enabled(storage: &Storage) -> bool {
+    non_empty_setting(storage, SETTING_LIVE_TRANSCRIPTION).as_deref() == Some("true")
+}
+
+/// Microphone settings for the next capture: input device, pre-roll, processing and hands-free
+fn capture_config(storage: &Storage) -> AudioCaptureConfig {
+    let pre_roll_ms = non_empty_setting(storage, SETTING_PRE_ROLL_MS)
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(0);
+    let warm_idle_timeout = non_empty_setting(storage, SETTING_PRE_ROLL_IDLE_TIMEOUT_SECS)
+        .and_then(|v| v.parse().ok())
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_WARM_IDLE_TIMEOUT);
+
+    AudioCaptureConfig {
+        device: non_empty_setting(storage, SETTING_INPUT_DEVICE),
+        pre_roll_ms,
+        warm_idle_timeout,
+        processing: audio_processing(storage),
+        hands_free: hands_free_config(storage),
+        ..Default::default()
+    }
+}
+
+/// Create the audio source for the next recording, unless one is already open
+fn ensure_audio_source(
+    handle: &FlowHandle,
+    audio: &mut Option<Box<dyn AudioSource>>,
+) -> crate::error::Result<()> {
+    if audio.is_some() {
+        return Ok(());
+    }
+    let source = match handle.input_file.lock().as_deref() {
+        Some(path) => {
+            Box::new(FileSource::open(path)?.with_hands_free(hands_free_config(&handle.storage)))
+                as Box<dyn AudioSource>
+        }
+        None => Box::new(AudioCapture::with_config(capture_config(&handle.storage))?),
+    };
+    *audio = Some(source);
+    Ok(())
+}
+
+/// Fail over to the default input device if the one in use was disconnected
+/// Device changes are queued for flow_take_audio_device_events_json
+fn recover_audio_source(handle: &FlowHandle, source: &mut dyn AudioSource) -> bool {
+    let recovered = source.recover();
+    handle
+        .audio_events
+        .lock()
+        .extend(source.take_device_events());
+    match recovered {
+        Ok(()) => true,
+        Err(e) => {
+            set_last_error(handle, format!("Input device lost: {e}"));
+            false
+        }
+    }
+}
+
+/// Close an idle audio source (a warm microphone) so the next recording picks up new settings
+fn reset_idle_audio_source(handle: &FlowHandle) {
+    let mut audio = handle.audio.lock();
+    if audio
+        .as_ref()
+        .is_some_and(|source| source.state() == CaptureState::Idle)
+    {
+        *audio = None;
+    }
+}
+
+/// Sample ranges of a recording to transcribe, with leading and trailing silence removed
+/// With `split`, long recordings are cut into chunks at pauses
+/// Fails with `Error::NoSpeech` when the recording holds no speech
+fn speech_ranges(
+    handle: &FlowHandle,
+    samples: &[f32],
+    sample_rate: u32,
+    split: bool,
+) -> crate::error::Result<Vec<Range<usize>>> {
+    let whole = std::iter::once(0..samples.len()).collect();
+    if !vad_enabled(&handle.storage) {
+        return Ok(whole);
+    }
+
+    let vad = handle.vad.lock();
+    let segments = match vad.detect(samples, sample_rate) {
+        Ok(segments) => segments,
+        Err(e) => {
+            warn!(
+                "Voice activity detection failed, using the whole recording: {}",
+                e
+            );
+            return Ok(whole);
+        }
+    };
+    let (Some(first), Some(last)) = (segments.first(), segments.last()) else {
+        return Err(Error::NoSpeech);
+    };
+    if split {
+        Ok(vad.split_at_pauses(samples, sample_rate, &segments))
+    } else {
+        Ok(std::iter::once(first.start..last.end).collect())
+    }
+}
+
+/// Build the OpenAI transcription provider with the persisted base URL and model
+fn openai_transcription_provider(
+    storage: &Storage,
+    api_key: Option<String>,
+) -> OpenAITranscriptionProvider {
+    let mut provider = OpenAITranscriptionProvider::new(api_key);
+    if let Some(base_url) = non_empty_setting(storage, SETTING_OPENAI_BASE_URL) {
+        provider = provider.with_base_url(base_url);
+    }
+    if let Some(model) = non_empty_setting(storage, SETTING_OPENAI_TRANSCRIPTION_MODEL) {
+        provider = provider.with_model(model);
+    }
+    provider
+}
+
+/// Build the OpenAI completion provider with the persisted base URL and model
+fn openai_completion_provider(
+    storage: &Storage,
+    api_key: Option<String>,
+) -> OpenAICompletionProvider {
+    let mut provider = OpenAICompletionProvider::new(api_key);
+    if let Some(base_url) = non_empty_setting(storage, SETTING_OPENAI_BASE_URL) {
+        provider = provider.with_base_url(base_url);
+    }
+    if let Some(model) = non_empty_setting(storage, SETTING_OPENAI_COMPLETION_MODEL) {
+        provider = provider.with_model(model);
+    }
+    provider
+}
+
+/// Build the Auto cloud transcription provider
+/// The worker handles transcription and completion; with an OpenAI key set, OpenAI Whisper
+/// takes over when the worker is unreachable (output is then formatted by the completion
+/// provider)
+fn auto_transcription_provider(storage: &Storage) -> Arc<dyn TranscriptionProvider> {
+    let worker: Arc<dyn TranscriptionProvider> = Arc::new(Base10TranscriptionProvider::new(None));
+    match non_empty_setting(storage, SETTING_OPENAI_API_KEY) {
+        Some(key) => Arc::new(FallbackTranscriptionProvider::new(vec![
+            worker,
+            Arc::new(openai_transcription_provider(storage, Some(key))),
+        ])),
+        None => worker,
+    }
+}
+
+/// Build the completion provider selected in settings
+/// Other providers with an API key set take over, in a fixed order, when it fails with a
+/// network or server error
+fn completion_provider(storage: &Storage) -> Arc<dyn CompletionProvider> {
+    let mut providers: Vec<Arc<dyn CompletionProvider>> = vec![
+        Arc::new(openai_completion_provider(
+            storage,
+            non_empty_setting(storage, SETTING_OPENAI_API_KEY),
+        )),
+        Arc::new(GeminiCompletionProvider::new(non_empty_setting(
+            storage,
+            SETTING_GEMINI_API_KEY,
+        ))),
+        Arc::new(OpenRouterCompletionProvider::new(non_empty_setting(
+            storage,
+            SETTING_OPENROUTER_API_KEY,
+        ))),
+        Arc::new(AnthropicCompletionProvider::new(non_empty_setting(
+            storage,
+            SETTING_ANTHROPIC_API_KEY,
+        ))),
+    ];
+    let selected = match non_empty_setting(storage, SETTING_COMPLETION_PROVIDER).as_deref() {
+        Some("gemini") => 1,
+        Some("openrouter") => 2,
+        Some("anthropic") => 3,
+        _ => 0,
+    };
+
+    let primary = providers.remove(selected);
+    providers.retain(|provider| provider.is_configured());
+    if providers.is_empty() {
+        return primary;
+    }
+    providers.insert(0, primary);
+    Arc::new(FallbackCompletionProvider::new(providers))
+}
+
 fn load_persisted_configuration(handle: &mut FlowHandle) {
     // Load all API keys
     let openai_key = handle
@@ -129,6 +500,11 @@ fn load_persisted_configuration(handle: &mut FlowHandle) {
         .get_setting(SETTING_OPENROUTER_API_KEY)
         .ok()
         .flatten();
+    let anthropic_key = handle
+        .storage
+        .get_setting(SETTING_ANTHROPIC_API_KEY)
+        .ok()
+        .flatten();
 
     // Load saved provider preferences
     let saved_completion_provider = handle
@@ -169,6 +545,14 @@ fn load_persisted_configuration(handle: &mut FlowHandle) {
             "NONE"
         }
     );
+    tracing::info!(
+        "  Anthropic key: {}",
+        if anthropic_key.is_some() {
+            "SET"
+        } else {
+            "NONE"
+        }
+    );
     tracing::info!(
         "  Saved completion provider: {:?}",
         saved_completion_provider
@@ -180,20 +564,11 @@ fn load_persisted_configuration(handle: &mut FlowHandle) {
     tracing::info!("  Use local transcription: {}", use_local_transcription);
 
     // Initialize completion provider based on saved preference
-    match saved_completion_provider.as_deref() {
-        Some("gemini") => {
-            debug!("Restoring Gemini completion provider from database");
-            handle.completion = Arc::new(GeminiCompletionProvider::new(gemini_key.clone()));
-        }
-        Some("openrouter") => {
-            debug!("Restoring OpenRouter completion provider from database");
-            handle.completion = Arc::new(OpenRouterCompletionProvider::new(openrouter_key));
-        }
-        _ => {
-            debug!("Restoring OpenAI completion provider from database");
-            handle.completion = Arc::new(OpenAICompletionProvider::new(openai_key.clone()));
-        }
-    }
+    handle.completion = completion_provider(&handle.storage);
+    debug!(
+        "Restored {} completion provider from database",
+        handle.completion.name()
+    );
 
     // Initialize transcription provider separately
     if use_local_transcription {
@@ -206,12 +581,13 @@ fn load_persisted_configuration(handle: &mut FlowHandle) {
         match saved_cloud_transcription.as_deref() {
             Some("openai") => {
                 debug!("Restoring OpenAI transcription provider from database");
-                handle.transcription = Arc::new(OpenAITranscriptionProvider::new(openai_key));
+                handle.transcription =
+                    Arc::new(openai_transcription_provider(&handle.storage, openai_key));
             }
             _ => {
                 // Default to Auto (worker handles transcription + completion)
                 debug!("Using Auto transcription provider (default)");
-                handle.transcription = Arc::new(Base10TranscriptionProvider::new(None));
+                handle.transcription = auto_transcription_provider(&handle.storage);
             }
         }
     }
@@ -274,8 +650,15 @@ pub extern "C" fn flow_init(db_path: *const c_char) -> *mut FlowHandle {
         runtime,
         storage,
         audio: Mutex::new(None),
+        input_file: Mutex::new(None),
+        audio_events: Mutex::new(Vec::new()),
         last_audio: Mutex::new(None),
         last_audio_sample_rate: Mutex::new(None),
+        archive: AudioArchive::new(db_path.with_file_name("recordings")),
+        queue_worker: Mutex::new(None),
+        queue_callback: Mutex::new(None),
+        last_queued: Mutex::new(None),
+        live: Mutex::new(None),
         last_error: Mutex::new(None),
         transcription: Arc::new(OpenAITranscriptionProvider::new(None)),
         completion: Arc::new(OpenAICompletionProvider::new(None)),
@@ -285,7 +668,10 @@ pub extern "C" fn flow_init(db_path: *const c_char) -> *mut FlowHandle {
         app_tracker,
         style_learner: Mutex::new(style_learner),
         is_model_loading: Arc::new(AtomicBool::new(false)),
+        model_download: Arc::new(DownloadProgress::new()),
+        offline_transcription: Mutex::new(None),
         contact_classifier,
+        vad: Mutex::new(VoiceActivityDetector::default()),
         captured_contact: Mutex::new(None),
         pending_audio: Mutex::new(None),
         pending_sample_rate: Mutex::new(None),
@@ -293,6 +679,20 @@ pub extern "C" fn flow_init(db_path: *const c_char) -> *mut FlowHandle {
 
     load_persisted_configuration(&mut handle);
 
+    let offline =
+        non_empty_setting(&handle.storage, SETTING_OFFLINE_MODE).is_some_and(|v| v == "true");
+    crate::offline::set_offline(offline);
+    if offline {
+        log_with_time!("✈️ [INIT] Offline mode: transcribing and formatting locally");
+    }
+
+    if let Some(path) = non_empty_setting(&handle.storage, SETTING_VAD_MODEL_PATH) {
+        match NeuralVad::load(&path) {
+            Ok(model) => handle.vad.lock().set_model(Some(model)),
+            Err(e) => warn!("Failed to load VAD model, using energy detection: {}", e),
+        }
+    }
+
     // Load transcription mode (local vs remote Whisper)
     let use_local = handle
         .storage
@@ -309,21 +709,14 @@ pub extern "C" fn flow_init(db_path: *const c_char) -> *mut FlowHandle {
             .get_setting(SETTING_LOCAL_WHISPER_MODEL)
             .ok()
             .flatten();
-        let model = WhisperModel::all()
-            .iter()
-            .find(|m| {
-                let (id, _) = m.model_id();
-                Some(id) == model_str.as_deref()
-            })
-            .copied()
-            .unwrap_or(WhisperModel::Quality);
 
         // Get models directory
         match crate::whisper_models::get_models_dir() {
             Ok(models_dir) => {
-                handle.transcription =
-                    Arc::new(LocalWhisperTranscriptionProvider::new(model, models_dir));
-                log_with_time!("✅ [INIT] Using local Whisper model: {:?}", model);
+                let spec = stored_whisper_spec(model_str.as_deref(), &models_dir);
+                let model_id = spec.id.clone();
+                handle.transcription = Arc::new(local_whisper_provider(&handle, spec, models_dir));
+                log_with_time!("✅ [INIT] Using local Whisper model: {}", model_id);
             }
             Err(e) => {
                 error!("Failed to get models directory: {}", e);
@@ -339,16 +732,19 @@ pub extern "C" fn flow_init(db_path: *const c_char) -> *mut FlowHandle {
 
     debug!("Flow engine initialized");
 
-    Box::into_raw(Box::new(handle))
+    let handle = Box::into_raw(Box::new(handle));
+    start_queue_worker(handle);
+    handle
 }
 
 /// Destroy the Flow engine and free resources
 #[unsafe(no_mangle)]
 pub extern "C" fn flow_destroy(handle: *mut FlowHandle) {
     if !handle.is_null() {
-        unsafe {
-            drop(Box::from_raw(handle));
-        }
+        let handle = unsafe { Box::from_raw(handle) };
+        // The worker uses the handle until it stops
+        drop(handle.queue_worker.lock().take());
+        drop(handle);
         debug!("Flow engine destroyed");
     }
 }
@@ -399,26 +795,37 @@ pub extern "C" fn flow_start_recording(handle: *mut FlowHandle) -> bool {
 
     let mut audio_lock = handle.audio.lock();
 
-    // create new audio capture if needed
-    if audio_lock.is_none() {
-        match AudioCapture::new() {
-            Ok(capture) => *audio_lock = Some(capture),
-            Err(e) => {
-                let message = format!("Failed to create audio capture: {e}");
-                error!("{message}");
-                set_last_error(handle, message);
-                return false;
-            }
-        }
+    // create new audio capture if needed (a warm microphone is reused)
+    if let Err(e) = ensure_audio_source(handle, &mut audio_lock) {
+        let message = format!("Failed to create audio capture: {e}");
+        error!("{message}");
+        set_last_error(handle, message);
+        return false;
     }
 
     if let Some(ref mut capture) = *audio_lock {
-        match capture.start() {
+        // A warm microphone may have been unplugged since the last recording
+        if !recover_audio_source(handle, capture.as_mut()) {
+            return false;
+        }
+
+        // The source streams to the live session from the first frame, pre-roll included
+        let live = start_live_session(handle);
+        capture.set_frame_sink(live.as_ref().map(LiveSession::sink));
+        let started = capture.start();
+        handle
+            .audio_events
+            .lock()
+            .extend(capture.take_device_events());
+        match started {
             Ok(_) => {
+                *handle.live.lock() = live;
                 clear_last_error(handle);
                 true
             }
             Err(e) => {
+                capture.set_frame_sink(None);
+                *handle.live.lock() = None;
                 let message = format!("Failed to start recording: {e}");
                 error!("{message}");
                 set_last_error(handle, message);
@@ -431,9 +838,14 @@ pub extern "C" fn flow_start_recording(handle: *mut FlowHandle) -> bool {
     }
 }
 
+/// Returned by `flow_stop_recording` when the recording holds no speech
+pub const FLOW_NO_SPEECH: u64 = u64::MAX;
+
 /// Stop audio recording and get the duration
-/// Returns duration in milliseconds, or 0 on failure
-/// This function extracts audio data and fully releases the microphone device
+/// Returns duration in milliseconds, 0 on failure, or FLOW_NO_SPEECH when voice activity
+/// detection found no speech (the audio is discarded and there is nothing to transcribe)
+/// This function extracts audio data and fully releases the microphone device, unless
+/// pre-roll keeps it warm for the next recording (see flow_set_pre_roll)
 #[unsafe(no_mangle)]
 pub extern "C" fn flow_stop_recording(handle: *mut FlowHandle) -> u64 {
     let handle = unsafe { &*handle };
@@ -442,30 +854,50 @@ pub extern "C" fn flow_stop_recording(handle: *mut FlowHandle) -> u64 {
     // Take ownership of AudioCapture (removes it from the Option)
     // This causes it to be dropped after this block, releasing the CPAL device
     if let Some(mut capture) = audio_lock.take() {
+        // A device lost mid-recording fails over here even if the level was never polled;
+        // the audio captured before it went away is kept either way
+        recover_audio_source(handle, capture.as_mut());
         let duration = capture.buffer_duration_ms();
+        let sample_rate = capture.sample_rate();
+        let stopped = capture.stop();
+        capture.set_frame_sink(None);
+        handle
+            .audio_events
+            .lock()
+            .extend(capture.take_device_events());
+
+        // A warm microphone stays open, collecting pre-roll for the next recording;
+        // otherwise AudioCapture is dropped here - CPAL device fully released
+        if capture.is_warm() {
+            *audio_lock = Some(capture);
+        } else {
+            drop(capture);
+        }
 
-        match capture.stop_stream() {
-            Ok(_) => {
-                // Extract and cache audio data before dropping AudioCapture
-                let sample_rate = capture.sample_rate();
-                let audio_data = capture.take_buffered_audio();
+        match stopped {
+            Ok(audio_data) => {
+                // Accidental hotkey presses never reach a provider
+                let samples = crate::audio::pcm_to_samples(&audio_data);
+                if let Err(Error::NoSpeech) = speech_ranges(handle, &samples, sample_rate, false) {
+                    log_with_time!("🔇 [RUST] No speech detected, discarding recording");
+                    *handle.live.lock() = None;
+                    *handle.pending_audio.lock() = None;
+                    *handle.pending_sample_rate.lock() = None;
+                    clear_last_error(handle);
+                    return FLOW_NO_SPEECH;
+                }
 
                 *handle.pending_audio.lock() = Some(audio_data);
                 *handle.pending_sample_rate.lock() = Some(sample_rate);
 
-                // AudioCapture is dropped here - CPAL device fully released
-                drop(capture);
-
                 clear_last_error(handle);
                 duration
             }
             Err(e) => {
+                *handle.live.lock() = None;
                 let message = format!("Failed to stop recording: {e}");
                 error!("{message}");
                 set_last_error(handle, message);
-
-                // Still drop capture even on error
-                drop(capture);
                 0
             }
         }
@@ -490,12 +922,16 @@ pub extern "C" fn flow_is_recording(handle: *mut FlowHandle) -> bool {
 
 /// Get current audio level (RMS amplitude) from the recording
 /// Returns a value between 0.0 and 1.0, or 0.0 if not recording
+/// Also fails over to the default input device when the one in use was disconnected, as
+/// starting and stopping a recording do; see flow_take_audio_device_events_json
 #[unsafe(no_mangle)]
 pub extern "C" fn flow_get_audio_level(handle: *mut FlowHandle) -> f32 {
     let handle = unsafe { &*handle };
-    let audio_lock = handle.audio.lock();
+    let mut audio_lock = handle.audio.lock();
+
+    if let Some(ref mut capture) = *audio_lock {
+        recover_audio_source(handle, capture.as_mut());
 
-    if let Some(ref capture) = *audio_lock {
         if capture.state() == CaptureState::Recording {
             capture.current_audio_level()
         } else {
@@ -506,1608 +942,3982 @@ pub extern "C" fn flow_get_audio_level(handle: *mut FlowHandle) -> f32 {
     }
 }
 
-// ============ Transcription ============
-
-fn transcribe_with_audio(
-    handle: &FlowHandle,
-    audio_data: crate::AudioData,
-    sample_rate: u32,
-    app_name: Option<String>,
-) -> crate::error::Result<String> {
-    // Determine writing mode - use contact captured at recording start for Messages
-    let mode = if let Some(ref name) = app_name {
-        // Check if this is Messages.app
-        if name.to_lowercase().contains("messages") || name == "com.apple.MobileSMS" {
-            // Use the contact that was captured when recording started
-            // This avoids race conditions where the window focus changes during recording
-            let captured = handle.captured_contact.lock().clone();
+// ============ Input Devices ============
 
-            if let Some(contact_name) = captured {
-                debug!("Using captured Messages contact: {}", contact_name);
+/// Get the available input devices as JSON
+/// [{"id", "name", "is_default", "configs": [{"channels", "min_sample_rate", "max_sample_rate", "sample_format"}]}]
+/// Caller must free with flow_free_string; returns null on error
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_get_input_devices_json(handle: *mut FlowHandle) -> *mut c_char {
+    let handle = unsafe { &*handle };
 
-                // Classify the contact
-                let input = ContactInput {
-                    name: contact_name.clone(),
-                    organization: String::new(),
-                };
-                let category = handle.contact_classifier.classify(&input);
-                let contact_mode = category.suggested_writing_mode();
+    let devices = match crate::audio::list_input_devices() {
+        Ok(devices) => devices,
+        Err(e) => {
+            set_last_error(handle, e.to_string());
+            return ptr::null_mut();
+        }
+    };
 
-                debug!(
-                    "Contact '{}' classified as {:?}, using mode {:?}",
-                    contact_name, category, contact_mode
-                );
+    clear_last_error(handle);
+    match CString::new(serde_json::to_string(&devices).unwrap_or_default()) {
+        Ok(cstr) => cstr.into_raw(),
+        Err(_) => ptr::null_mut(),
+    }
+}
 
-                // Record the interaction
-                handle.contact_classifier.record_interaction(&contact_name);
+/// Record from an input device, given its id or name (persisted)
+/// Pass NULL or an empty string to follow the system default input
+/// The device must be connected; it is saved by id when the host provides one
+/// Returns true on success
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_set_input_device(handle: *mut FlowHandle, device: *const c_char) -> bool {
+    let handle = unsafe { &*handle };
 
-                contact_mode
-            } else {
-                debug!("No contact was captured at recording start, using app default");
-                let mut modes = handle.modes.lock();
-                modes.get_mode_with_storage(name, &handle.storage)
+    let wanted = if device.is_null() {
+        ""
+    } else {
+        match unsafe { CStr::from_ptr(device) }.to_str() {
+            Ok(device) => device.trim(),
+            Err(_) => {
+                set_last_error(handle, "Invalid device name");
+                return false;
             }
-        } else {
-            // Not Messages - use app-based mode
-            let mut modes = handle.modes.lock();
-            modes.get_mode_with_storage(name, &handle.storage)
         }
+    };
+
+    let value = if wanted.is_empty() {
+        String::new()
     } else {
-        WritingMode::Casual
+        let devices = match crate::audio::list_input_devices() {
+            Ok(devices) => devices,
+            Err(e) => {
+                set_last_error(handle, e.to_string());
+                return false;
+            }
+        };
+        match crate::audio::select_input_device(&devices, wanted) {
+            Some(device) => device.id.clone().unwrap_or_else(|| device.name.clone()),
+            None => {
+                set_last_error(handle, format!("Input device not found: {wanted}"));
+                return false;
+            }
+        }
     };
 
-    let transcription_provider = Arc::clone(&handle.transcription);
-    let app_context = handle.app_tracker.current_app();
+    if let Err(e) = handle.storage.set_setting(SETTING_INPUT_DEVICE, &value) {
+        let message = format!("Failed to save input device: {e}");
+        error!("{message}");
+        set_last_error(handle, message);
+        return false;
+    }
 
-    // Check if using local transcription
-    let use_local_transcription = handle
-        .storage
-        .get_setting(SETTING_USE_LOCAL_TRANSCRIPTION)
-        .ok()
-        .flatten()
-        .map(|s| s == "true")
-        .unwrap_or(false);
+    reset_idle_audio_source(handle);
+    clear_last_error(handle);
+    true
+}
 
-    // Build mode string for worker
-    let mode_str = match mode {
-        WritingMode::Formal => "formal",
-        WritingMode::Casual => "casual",
-        WritingMode::VeryCasual => "very_casual",
-        WritingMode::Excited => "excited",
-    };
+/// Get the selected input device id or name, or null when following the system default
+/// Caller must free with flow_free_string
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_get_input_device(handle: *mut FlowHandle) -> *mut c_char {
+    let handle = unsafe { &*handle };
 
-    // For cloud transcription (auto mode), worker handles everything
-    let completion_params = if !use_local_transcription {
-        log_with_time!("🚀 [RUST] Using auto mode (worker handles transcription+completion)");
-        Some(TranscriptionCompletionParams {
-            mode: mode_str.to_string(),
-            app_context: app_name.clone(),
-            shortcuts_triggered: Vec::new(),
-            voice_instruction: None, // Worker auto-detects from transcription
-        })
-    } else {
-        None
-    };
+    match non_empty_setting(&handle.storage, SETTING_INPUT_DEVICE).map(CString::new) {
+        Some(Ok(cstr)) => cstr.into_raw(),
+        _ => ptr::null_mut(),
+    }
+}
 
-    // Perform transcription
-    let transcription = handle.runtime.block_on(async {
-        let mut request = TranscriptionRequest::new(audio_data, sample_rate);
-        if let Some(params) = completion_params {
-            request = request.with_completion(params);
-        }
-        transcription_provider.transcribe(request).await
-    })?;
+/// Take input device changes since the last call as JSON
+/// [{"type": "failed_over", "from", "to", "reason"} | {"type": "capture_failed", "device", "reason"}]
+/// Caller must free with flow_free_string
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_take_audio_device_events_json(handle: *mut FlowHandle) -> *mut c_char {
+    let handle = unsafe { &*handle };
 
-    // Process shortcuts and corrections on raw transcription
-    let (text_with_shortcuts, triggered) = handle.shortcuts.process(&transcription.text);
-    let (text_with_corrections, _applied) = handle.learning.apply_corrections(&text_with_shortcuts);
+    let events = std::mem::take(&mut *handle.audio_events.lock());
+    match CString::new(serde_json::to_string(&events).unwrap_or_default()) {
+        Ok(cstr) => cstr.into_raw(),
+        Err(_) => ptr::null_mut(),
+    }
+}
 
-    // Use worker completion if available, otherwise use corrected transcription
-    let processed_text = if let Some(completed_text) = transcription.completed_text {
-        log_with_time!(
-            "✅ [RUST/AI] Worker completion received - Output: {} chars",
-            completed_text.len()
-        );
-        completed_text
-    } else {
-        // Local transcription mode - use corrected text directly (no separate completion)
-        log_with_time!(
-            "📝 [RUST] Local transcription mode - using corrected text: {} chars",
-            text_with_corrections.len()
-        );
-        text_with_corrections.clone()
-    };
+// ============ Pre-roll ============
 
-    // Suppress unused warning for triggered shortcuts (used by worker)
-    let _ = triggered;
+/// Keep the microphone open between recordings so each one starts with the audio
+/// heard just before flow_start_recording (persisted)
+/// pre_roll_ms: audio to prepend, capped at 2000; 0 turns pre-roll off and closes the device
+/// idle_timeout_secs: close the device after this long without a recording (0 for the default, 30)
+/// Returns true on success
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_set_pre_roll(
+    handle: *mut FlowHandle,
+    pre_roll_ms: u32,
+    idle_timeout_secs: u32,
+) -> bool {
+    let handle = unsafe { &*handle };
 
-    let mut record = Transcription::new(
-        transcription.text,
-        processed_text.clone(),
-        transcription.confidence.unwrap_or(0.0),
-        transcription.duration_ms,
-    );
-    if let Some(context) = app_context {
-        record.app_context = Some(context);
-    }
-    if let Err(e) = handle.storage.save_transcription(&record) {
-        error!("Failed to save transcription: {}", e);
-    }
+    let pre_roll_ms = pre_roll_ms.min(MAX_PRE_ROLL_MS);
+    let idle_timeout_secs = if idle_timeout_secs == 0 {
+        DEFAULT_WARM_IDLE_TIMEOUT.as_secs()
+    } else {
+        idle_timeout_secs as u64
+    };
 
-    let mut history = TranscriptionHistoryEntry::success(
-        record.raw_text.clone(),
-        processed_text.clone(),
-        record.duration_ms,
-    );
-    history.app_context = record.app_context.clone();
-    if let Err(e) = handle.storage.save_history_entry(&history) {
-        error!("Failed to save transcription history: {}", e);
+    let saved = handle
+        .storage
+        .set_setting(SETTING_PRE_ROLL_MS, &pre_roll_ms.to_string())
+        .and_then(|_| {
+            handle.storage.set_setting(
+                SETTING_PRE_ROLL_IDLE_TIMEOUT_SECS,
+                &idle_timeout_secs.to_string(),
+            )
+        });
+    if let Err(e) = saved {
+        let message = format!("Failed to save pre-roll setting: {e}");
+        error!("{message}");
+        set_last_error(handle, message);
+        return false;
     }
 
-    Ok(processed_text)
+    reset_idle_audio_source(handle);
+    clear_last_error(handle);
+    true
 }
 
-/// Transcribe the recorded audio and process it
-/// Returns the processed text (caller must free with flow_free_string)
-/// Returns null on failure
+/// Get the pre-roll length in milliseconds (0 when off)
 #[unsafe(no_mangle)]
-pub extern "C" fn flow_transcribe(handle: *mut FlowHandle, app_name: *const c_char) -> *mut c_char {
+pub extern "C" fn flow_get_pre_roll_ms(handle: *mut FlowHandle) -> u32 {
     let handle = unsafe { &*handle };
+    capture_config(&handle.storage)
+        .pre_roll_ms
+        .min(MAX_PRE_ROLL_MS)
+}
 
-    // Get cached audio data (don't touch handle.audio at all)
-    // This ensures the microphone device was already released by flow_stop_recording
-    let (audio_data, sample_rate) = {
-        let audio_data = handle.pending_audio.lock().take();
-        let sample_rate = handle.pending_sample_rate.lock().take();
-
-        match (audio_data, sample_rate) {
-            (Some(data), Some(rate)) => (data, rate),
-            _ => {
-                set_last_error(
-                    handle,
-                    "No audio data pending - must call stop_recording first",
-                );
-                return ptr::null_mut();
-            }
-        }
-    };
+/// Open the microphone ahead of a recording so pre-roll audio is available, e.g. when
+/// the app becomes active; calling again while warm restarts the idle timeout
+/// Fails when pre-roll is off or audio comes from a file
+/// Returns true on success
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_warm_microphone(handle: *mut FlowHandle) -> bool {
+    let handle = unsafe { &*handle };
 
-    if audio_data.is_empty() {
-        set_last_error(handle, "No audio captured");
-        return ptr::null_mut();
+    if capture_config(&handle.storage).pre_roll_ms == 0 {
+        set_last_error(handle, "Pre-roll is disabled");
+        return false;
+    }
+    if handle.input_file.lock().is_some() {
+        set_last_error(handle, "Recording from a file");
+        return false;
     }
 
-    // get app name
-    let app = if !app_name.is_null() {
-        unsafe { CStr::from_ptr(app_name) }
-            .to_str()
-            .ok()
-            .map(String::from)
-    } else {
-        None
-    };
-
-    let duration_ms = estimate_duration_ms(audio_data.len(), sample_rate);
-    *handle.last_audio.lock() = Some(audio_data.clone());
-    *handle.last_audio_sample_rate.lock() = Some(sample_rate);
-    let result = transcribe_with_audio(handle, audio_data, sample_rate, app);
-
-    // Clear the captured contact after transcription (whether success or failure)
-    *handle.captured_contact.lock() = None;
+    let mut audio_lock = handle.audio.lock();
+    let warmed = ensure_audio_source(handle, &mut audio_lock).and_then(|_| match *audio_lock {
+        Some(ref mut capture) if capture.state() == CaptureState::Idle => capture.warm(),
+        _ => Ok(()),
+    });
+    if let Some(ref mut capture) = *audio_lock {
+        handle
+            .audio_events
+            .lock()
+            .extend(capture.take_device_events());
+    }
 
-    match result {
-        Ok(text) => {
+    match warmed {
+        Ok(()) => {
             clear_last_error(handle);
-            *handle.last_audio.lock() = None;
-            *handle.last_audio_sample_rate.lock() = None;
-            match CString::new(text) {
-                Ok(cstr) => cstr.into_raw(),
-                Err(_) => ptr::null_mut(),
-            }
+            true
         }
         Err(e) => {
-            let message = format!("Transcription failed: {e}");
+            let message = format!("Failed to open microphone: {e}");
             error!("{message}");
-            set_last_error(handle, message.clone());
-            let mut history = TranscriptionHistoryEntry::failure(message, duration_ms);
-            history.app_context = handle.app_tracker.current_app();
-            if let Err(e) = handle.storage.save_history_entry(&history) {
-                error!("Failed to save transcription history: {}", e);
-            }
-            ptr::null_mut()
+            set_last_error(handle, message);
+            *audio_lock = None;
+            false
         }
     }
 }
 
-/// Retry the last transcription using cached audio
-/// Returns processed text (caller must free with flow_free_string), or null on failure
+/// Check whether the microphone is being kept open for pre-roll
 #[unsafe(no_mangle)]
-pub extern "C" fn flow_retry_last_transcription(
+pub extern "C" fn flow_is_microphone_warm(handle: *mut FlowHandle) -> bool {
+    let handle = unsafe { &*handle };
+    handle
+        .audio
+        .lock()
+        .as_ref()
+        .is_some_and(|capture| capture.is_warm())
+}
+
+// ============ Hands-free ============
+
+/// Let recordings end by themselves once the speaker stops (persisted, off by default)
+/// trailing_silence_ms: silence after speech that ends a recording (0 for the default, 1500)
+/// min_duration_ms: silence never ends a shorter recording (0 for the default, 1000)
+/// max_duration_ms: recordings end at this length regardless (0 for the default, 120000)
+/// Poll flow_get_auto_stop_json while recording to learn when one has ended
+/// Returns true on success; fails when the minimum exceeds the maximum
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_set_hands_free(
     handle: *mut FlowHandle,
-    app_name: *const c_char,
-) -> *mut c_char {
+    enabled: bool,
+    trailing_silence_ms: u32,
+    min_duration_ms: u32,
+    max_duration_ms: u32,
+) -> bool {
     let handle = unsafe { &*handle };
-    let (audio_data, sample_rate) = {
-        let last_audio = handle.last_audio.lock();
-        let last_sample_rate = handle.last_audio_sample_rate.lock();
-        match last_audio.as_ref() {
-            Some(data) => (data.clone(), last_sample_rate.unwrap_or(16_000)),
-            None => {
-                set_last_error(handle, "No previous recording to retry");
-                return ptr::null_mut();
-            }
-        }
-    };
 
-    let app = if !app_name.is_null() {
-        unsafe { CStr::from_ptr(app_name) }
-            .to_str()
-            .ok()
-            .map(String::from)
-    } else {
-        None
+    let defaults = HandsFreeConfig::default();
+    let or_default = |value: u32, default: u32| if value == 0 { default } else { value };
+    let config = HandsFreeConfig {
+        trailing_silence_ms: or_default(trailing_silence_ms, defaults.trailing_silence_ms),
+        min_duration_ms: or_default(min_duration_ms, defaults.min_duration_ms),
+        max_duration_ms: or_default(max_duration_ms, defaults.max_duration_ms),
     };
+    if config.min_duration_ms > config.max_duration_ms {
+        set_last_error(handle, "Minimum recording length exceeds the maximum");
+        return false;
+    }
 
-    let duration_ms = estimate_duration_ms(audio_data.len(), sample_rate);
-    let result = transcribe_with_audio(handle, audio_data, sample_rate, app);
-
-    match result {
-        Ok(text) => {
-            clear_last_error(handle);
-            *handle.last_audio.lock() = None;
-            *handle.last_audio_sample_rate.lock() = None;
-            match CString::new(text) {
-                Ok(cstr) => cstr.into_raw(),
-                Err(_) => ptr::null_mut(),
-            }
-        }
-        Err(e) => {
-            let message = format!("Transcription failed: {e}");
+    let settings = [
+        (SETTING_HANDS_FREE, enabled.to_string()),
+        (
+            SETTING_HANDS_FREE_TRAILING_SILENCE_MS,
+            config.trailing_silence_ms.to_string(),
+        ),
+        (
+            SETTING_HANDS_FREE_MIN_DURATION_MS,
+            config.min_duration_ms.to_string(),
+        ),
+        (
+            SETTING_HANDS_FREE_MAX_DURATION_MS,
+            config.max_duration_ms.to_string(),
+        ),
+    ];
+    for (key, value) in settings {
+        if let Err(e) = handle.storage.set_setting(key, &value) {
+            let message = format!("Failed to save hands-free setting: {e}");
             error!("{message}");
-            set_last_error(handle, message.clone());
-            let mut history = TranscriptionHistoryEntry::failure(message, duration_ms);
-            history.app_context = handle.app_tracker.current_app();
-            if let Err(e) = handle.storage.save_history_entry(&history) {
-                error!("Failed to save transcription history: {}", e);
-            }
-            ptr::null_mut()
+            set_last_error(handle, message);
+            return false;
         }
     }
-}
 
-// ============ Shortcuts ============
+    reset_idle_audio_source(handle);
+    clear_last_error(handle);
+    true
+}
 
-/// Add a voice shortcut
-/// Returns true on success
+/// Get the hands-free settings as JSON
+/// {"enabled", "trailing_silence_ms", "min_duration_ms", "max_duration_ms"}
+/// Caller must free with flow_free_string
 #[unsafe(no_mangle)]
-pub extern "C" fn flow_add_shortcut(
-    handle: *mut FlowHandle,
-    trigger: *const c_char,
-    replacement: *const c_char,
-) -> bool {
-    if trigger.is_null() || replacement.is_null() {
-        return false;
+pub extern "C" fn flow_get_hands_free_json(handle: *mut FlowHandle) -> *mut c_char {
+    let handle = unsafe { &*handle };
+
+    let enabled = hands_free_config(&handle.storage).is_some();
+    let config = hands_free_limits(&handle.storage);
+    let json = serde_json::json!({
+        "enabled": enabled,
+        "trailing_silence_ms": config.trailing_silence_ms,
+        "min_duration_ms": config.min_duration_ms,
+        "max_duration_ms": config.max_duration_ms,
+    });
+    match CString::new(json.to_string()) {
+        Ok(cstr) => cstr.into_raw(),
+        Err(_) => ptr::null_mut(),
     }
+}
 
+/// Check whether the current hands-free recording has ended by itself
+/// Returns JSON {"reason": "silence" | "max_duration", "duration_ms"} once it has, or null
+/// Capture is paused from then on; call flow_stop_recording to collect the recording
+/// Caller must free with flow_free_string
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_get_auto_stop_json(handle: *mut FlowHandle) -> *mut c_char {
     let handle = unsafe { &*handle };
 
-    let trigger_str = match unsafe { CStr::from_ptr(trigger) }.to_str() {
-        Ok(s) => s.to_string(),
-        Err(_) => return false,
+    let Some(stop) = handle
+        .audio
+        .lock()
+        .as_ref()
+        .and_then(|source| source.auto_stop())
+    else {
+        return ptr::null_mut();
     };
+    match CString::new(serde_json::to_string(&stop).unwrap_or_default()) {
+        Ok(cstr) => cstr.into_raw(),
+        Err(_) => ptr::null_mut(),
+    }
+}
 
-    let replacement_str = match unsafe { CStr::from_ptr(replacement) }.to_str() {
-        Ok(s) => s.to_string(),
-        Err(_) => return false,
+// ============ Live Transcription ============
+
+/// Start transcribing the next recording as it is captured, when live transcription is on
+/// Providers that format whole recordings themselves (the worker) get the recording at the end
+fn start_live_session(handle: &FlowHandle) -> Option<LiveSession> {
+    if !live_transcription_enabled(&handle.storage) {
+        return None;
+    }
+    let provider = match active_transcription_provider(handle) {
+        Ok(provider) if !provider.completes_text() => provider,
+        Ok(provider) => {
+            debug!(
+                "{} needs whole recordings, not transcribing live",
+                provider.name()
+            );
+            return None;
+        }
+        Err(e) => {
+            warn!("Live transcription unavailable: {}", e);
+            return None;
+        }
     };
 
-    let shortcut = Shortcut::new(trigger_str, replacement_str);
+    // Each speculative refresh sends the pending speech again, which a cloud provider bills
+    let speculative = provider.is_local();
+    let template = transcription_request_template(handle);
+    let runtime = handle.runtime.handle().clone();
+    let transcribe = Box::new(move |samples: &[f32]| {
+        let mut request = template.clone();
+        request.audio = crate::audio::samples_to_pcm(samples);
+        runtime.block_on(provider.transcribe(request))
+    });
 
-    if let Err(e) = handle.storage.save_shortcut(&shortcut) {
-        error!("Failed to save shortcut: {}", e);
-        return false;
+    // Audio files are transcribed as they are, microphone audio after its clean-up
+    let processing = if handle.input_file.lock().is_some() {
+        AudioProcessing::none()
+    } else {
+        audio_processing(&handle.storage)
+    };
+    let config = LiveConfig {
+        speculative,
+        vad: handle.vad.lock().config().clone(),
+        processing,
+        ..Default::default()
+    };
+    match LiveSession::spawn(config, transcribe) {
+        Ok(session) => Some(session),
+        Err(e) => {
+            warn!("{}", e);
+            None
+        }
     }
-
-    handle.shortcuts.add_shortcut(shortcut);
-    true
 }
 
-/// Remove a voice shortcut
+/// Transcribe recordings while they are captured (persisted, off by default)
+/// Each stretch of speech is transcribed once a pause ends it, so flow_transcribe only has
+/// the tail left; partial results are available from flow_get_partial_transcription_json,
+/// which only guess at the speech not yet closed when transcribing locally
+/// Recordings for a provider that formats text itself (the worker) are still sent whole
 /// Returns true on success
 #[unsafe(no_mangle)]
-pub extern "C" fn flow_remove_shortcut(handle: *mut FlowHandle, trigger: *const c_char) -> bool {
-    if trigger.is_null() {
-        return false;
-    }
-
+pub extern "C" fn flow_set_live_transcription(handle: *mut FlowHandle, enabled: bool) -> bool {
     let handle = unsafe { &*handle };
 
-    let trigger_str = match unsafe { CStr::from_ptr(trigger) }.to_str() {
-        Ok(s) => s,
-        Err(_) => return false,
-    };
+    if let Err(e) = handle
+        .storage
+        .set_setting(SETTING_LIVE_TRANSCRIPTION, &enabled.to_string())
+    {
+        let message = format!("Failed to save live transcription setting: {e}");
+        error!("{message}");
+        set_last_error(handle, message);
+        return false;
+    }
 
-    handle.shortcuts.remove_shortcut(trigger_str);
+    clear_last_error(handle);
     true
 }
 
-/// Get the number of shortcuts
+/// Check whether recordings are transcribed while they are captured
 #[unsafe(no_mangle)]
-pub extern "C" fn flow_shortcut_count(handle: *mut FlowHandle) -> usize {
+pub extern "C" fn flow_is_live_transcription_enabled(handle: *mut FlowHandle) -> bool {
     let handle = unsafe { &*handle };
-    handle.shortcuts.count()
+    live_transcription_enabled(&handle.storage)
 }
 
-// ============ Writing Modes ============
+/// Get what has been transcribed of the current recording so far
+/// Returns JSON {"text", "stable_text", "audio_ms", "revision"}, or null when the recording
+/// is not being transcribed live. "stable_text" is the start of "text" that won't change;
+/// "revision" increases whenever "text" does. Available until flow_transcribe
+/// Caller must free with flow_free_string
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_get_partial_transcription_json(handle: *mut FlowHandle) -> *mut c_char {
+    let handle = unsafe { &*handle };
 
-/// Set the writing mode for an app
-/// mode: 0 = Formal, 1 = Casual, 2 = VeryCasual, 3 = Excited
+    let Some(partial) = handle.live.lock().as_ref().map(LiveSession::partial) else {
+        return ptr::null_mut();
+    };
+    match CString::new(serde_json::to_string(&partial).unwrap_or_default()) {
+        Ok(cstr) => cstr.into_raw(),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+// ============ Audio Processing ============
+
+/// Choose the clean-up applied to recordings before transcription (persisted)
+/// high_pass: remove rumble and hum below 100Hz (on by default)
+/// noise_suppression: suppress steady background noise (off by default)
+/// agc: level speech and limit peaks (on by default)
 /// Returns true on success
 #[unsafe(no_mangle)]
-pub extern "C" fn flow_set_app_mode(
+pub extern "C" fn flow_set_audio_processing(
     handle: *mut FlowHandle,
-    app_name: *const c_char,
-    mode: u8,
+    high_pass: bool,
+    noise_suppression: bool,
+    agc: bool,
 ) -> bool {
-    if app_name.is_null() {
-        return false;
-    }
-
     let handle = unsafe { &*handle };
 
-    let app = match unsafe { CStr::from_ptr(app_name) }.to_str() {
-        Ok(s) => s,
-        Err(_) => return false,
-    };
-
-    let writing_mode = match mode {
-        0 => WritingMode::Formal,
-        1 => WritingMode::Casual,
-        2 => WritingMode::VeryCasual,
-        3 => WritingMode::Excited,
-        _ => return false,
-    };
-
-    let mut modes = handle.modes.lock();
-    if let Err(e) = modes.set_mode_with_storage(app, writing_mode, &handle.storage) {
-        error!("Failed to save app mode: {}", e);
+    let flag = |enabled: bool| if enabled { "true" } else { "false" };
+    let saved = handle
+        .storage
+        .set_setting(SETTING_HIGH_PASS_FILTER, flag(high_pass))
+        .and_then(|_| {
+            handle
+                .storage
+                .set_setting(SETTING_NOISE_SUPPRESSION, flag(noise_suppression))
+        })
+        .and_then(|_| {
+            handle
+                .storage
+                .set_setting(SETTING_AUTOMATIC_GAIN_CONTROL, flag(agc))
+        });
+    if let Err(e) = saved {
+        let message = format!("Failed to save audio processing settings: {e}");
+        error!("{message}");
+        set_last_error(handle, message);
         return false;
     }
 
+    reset_idle_audio_source(handle);
+    clear_last_error(handle);
     true
 }
 
-/// Get the writing mode for an app
-/// Returns: 0 = Formal, 1 = Casual, 2 = VeryCasual, 3 = Excited
+/// Get the audio processing stages as JSON: {"high_pass", "noise_suppression", "agc"}
+/// Caller must free with flow_free_string
 #[unsafe(no_mangle)]
-pub extern "C" fn flow_get_app_mode(handle: *mut FlowHandle, app_name: *const c_char) -> u8 {
-    if app_name.is_null() {
-        return 1; // default to casual
-    }
-
+pub extern "C" fn flow_get_audio_processing_json(handle: *mut FlowHandle) -> *mut c_char {
     let handle = unsafe { &*handle };
 
-    let app = match unsafe { CStr::from_ptr(app_name) }.to_str() {
-        Ok(s) => s,
-        Err(_) => return 1,
-    };
-
-    let mut modes = handle.modes.lock();
-    let mode = modes.get_mode_with_storage(app, &handle.storage);
-
-    match mode {
-        WritingMode::Formal => 0,
-        WritingMode::Casual => 1,
-        WritingMode::VeryCasual => 2,
-        WritingMode::Excited => 3,
+    let processing = audio_processing(&handle.storage);
+    match CString::new(serde_json::to_string(&processing).unwrap_or_default()) {
+        Ok(cstr) => cstr.into_raw(),
+        Err(_) => ptr::null_mut(),
     }
 }
 
-// ============ Learning ============
+// ============ Voice Activity Detection ============
 
-/// Report a user edit to learn from
+/// Turn voice activity detection on or off (persisted, on by default)
+/// While on, silence is trimmed before transcription and recordings without speech are skipped
 /// Returns true on success
 #[unsafe(no_mangle)]
-pub extern "C" fn flow_learn_from_edit(
-    handle: *mut FlowHandle,
-    original: *const c_char,
-    edited: *const c_char,
-) -> bool {
-    if original.is_null() || edited.is_null() {
-        return false;
-    }
-
+pub extern "C" fn flow_set_vad_enabled(handle: *mut FlowHandle, enabled: bool) -> bool {
     let handle = unsafe { &*handle };
 
-    let original_str = match unsafe { CStr::from_ptr(original) }.to_str() {
-        Ok(s) => s,
-        Err(_) => return false,
-    };
-
-    let edited_str = match unsafe { CStr::from_ptr(edited) }.to_str() {
-        Ok(s) => s,
-        Err(_) => return false,
-    };
-
-    match handle
-        .learning
-        .learn_from_edit(original_str, edited_str, &handle.storage)
+    if let Err(e) = handle
+        .storage
+        .set_setting(SETTING_VAD_ENABLED, if enabled { "true" } else { "false" })
     {
-        Ok(learned) => {
-            debug!("Learned {} corrections from edit", learned.len());
-            true
-        }
-        Err(e) => {
-            error!("Failed to learn from edit: {}", e);
-            false
-        }
+        let message = format!("Failed to save voice activity detection setting: {e}");
+        error!("{message}");
+        set_last_error(handle, message);
+        return false;
     }
+
+    clear_last_error(handle);
+    true
 }
 
-/// Get the number of learned corrections
+/// Check whether voice activity detection is on
 #[unsafe(no_mangle)]
-pub extern "C" fn flow_correction_count(handle: *mut FlowHandle) -> usize {
+pub extern "C" fn flow_is_vad_enabled(handle: *mut FlowHandle) -> bool {
     let handle = unsafe { &*handle };
-    handle.learning.cache_size()
+    vad_enabled(&handle.storage)
 }
 
-/// Get all corrections as JSON
-/// Returns JSON array: [{"id": "...", "original": "...", "corrected": "...", "occurrences": N, "confidence": N.N}, ...]
-/// Caller must free the returned string with flow_free_string
+/// Set the neural model that confirms speech (safetensors file, persisted)
+/// path: model file, or null/empty to use energy detection only
+/// Returns true on success; on failure the previous model is kept
 #[unsafe(no_mangle)]
-pub extern "C" fn flow_get_corrections_json(handle: *mut FlowHandle) -> *mut c_char {
+pub extern "C" fn flow_set_vad_model(handle: *mut FlowHandle, path: *const c_char) -> bool {
     let handle = unsafe { &*handle };
 
-    let corrections = match handle.storage.get_all_corrections() {
-        Ok(c) => c,
-        Err(e) => {
-            error!("Failed to get corrections: {}", e);
-            return ptr::null_mut();
+    let path = if path.is_null() {
+        ""
+    } else {
+        match unsafe { CStr::from_ptr(path) }.to_str() {
+            Ok(path) => path.trim(),
+            Err(_) => {
+                set_last_error(handle, "Invalid model path");
+                return false;
+            }
         }
     };
 
-    let json_array: Vec<serde_json::Value> = corrections
-        .into_iter()
-        .map(|c| {
-            serde_json::json!({
-                "id": c.id.to_string(),
-                "original": c.original,
-                "corrected": c.corrected,
-                "occurrences": c.occurrences,
-                "confidence": c.confidence,
-                "source": format!("{:?}", c.source),
-                "created_at": c.created_at.to_rfc3339(),
-                "updated_at": c.updated_at.to_rfc3339(),
-            })
-        })
-        .collect();
+    let model = if path.is_empty() {
+        None
+    } else {
+        match NeuralVad::load(path) {
+            Ok(model) => Some(model),
+            Err(e) => {
+                let message = format!("Failed to load VAD model: {e}");
+                error!("{message}");
+                set_last_error(handle, message);
+                return false;
+            }
+        }
+    };
 
-    match CString::new(serde_json::to_string(&json_array).unwrap_or_default()) {
-        Ok(cstr) => cstr.into_raw(),
-        Err(_) => ptr::null_mut(),
+    if let Err(e) = handle.storage.set_setting(SETTING_VAD_MODEL_PATH, path) {
+        let message = format!("Failed to save VAD model: {e}");
+        error!("{message}");
+        set_last_error(handle, message);
+        return false;
     }
+
+    handle.vad.lock().set_model(model);
+    clear_last_error(handle);
+    true
 }
 
-/// Delete a correction by ID
-/// Returns true if the correction was deleted, false if not found or on error
+/// Check whether a neural VAD model is loaded
 #[unsafe(no_mangle)]
-pub extern "C" fn flow_delete_correction(handle: *mut FlowHandle, id: *const c_char) -> bool {
-    if id.is_null() {
-        return false;
+pub extern "C" fn flow_has_vad_model(handle: *mut FlowHandle) -> bool {
+    let handle = unsafe { &*handle };
+    handle.vad.lock().has_model()
+}
+
+// ============ Transcription ============
+
+/// Whether recordings are kept in the audio archive (off by default)
+fn audio_archive_enabled(storage: &Storage) -> bool {
+    non_empty_setting(storage, SETTING_AUDIO_ARCHIVE).as_deref() == Some("true")
+}
+
+/// Stored archive retention; 0 lifts a limit
+fn archive_retention(storage: &Storage) -> RetentionPolicy {
+    let limit = |key| non_empty_setting(storage, key).and_then(|v| v.parse::<u64>().ok());
+    let defaults = RetentionPolicy::default();
+    RetentionPolicy {
+        max_bytes: match limit(SETTING_AUDIO_ARCHIVE_MAX_MB) {
+            Some(0) => None,
+            Some(mb) => Some(mb * 1024 * 1024),
+            None => defaults.max_bytes,
+        },
+        max_age: match limit(SETTING_AUDIO_ARCHIVE_MAX_AGE_DAYS) {
+            Some(0) => None,
+            Some(days) => Some(Duration::from_secs(days * 24 * 60 * 60)),
+            None => defaults.max_age,
+        },
     }
+}
 
-    let handle = unsafe { &*handle };
+/// Whether recordings are transcribed locally (always the case offline), without a worker completion
+fn uses_local_transcription(storage: &Storage) -> bool {
+    crate::offline::is_offline()
+        || storage
+            .get_setting(SETTING_USE_LOCAL_TRANSCRIPTION)
+            .ok()
+            .flatten()
+            .map(|s| s == "true")
+            .unwrap_or(false)
+}
 
-    let id_str = match unsafe { CStr::from_ptr(id) }.to_str() {
-        Ok(s) => s,
-        Err(_) => return false,
-    };
+/// Keep a recording with its history entry when the archive is on
+fn archive_recording(handle: &FlowHandle, id: &TranscriptionId, audio: &[u8], sample_rate: u32) {
+    if !audio_archive_enabled(&handle.storage) {
+        return;
+    }
+    if let Err(e) = handle.archive.save(id, audio, sample_rate) {
+        warn!("Failed to archive recording: {}", e);
+        return;
+    }
+    if let Err(e) = handle.archive.prune(&archive_retention(&handle.storage)) {
+        warn!("Failed to prune audio archive: {}", e);
+    }
+}
 
-    let uuid = match uuid::Uuid::parse_str(id_str) {
-        Ok(u) => u,
-        Err(_) => {
-            error!("Invalid UUID: {}", id_str);
-            return false;
-        }
-    };
+/// A recording to transcribe, with the writing mode and app it was dictated in
+struct RecordingRequest<'a> {
+    audio: &'a [u8],
+    sample_rate: u32,
+    mode: WritingMode,
+    app_name: Option<String>,
+    /// What was transcribed while it was recorded, leaving only the tail
+    live: Option<LiveTranscript>,
+}
 
-    // First, get the correction to find the original word (for cache removal)
-    if let Ok(corrections) = handle.storage.get_all_corrections() {
-        if let Some(correction) = corrections.iter().find(|c| c.id == uuid) {
-            let original = correction.original.clone();
+/// Prepare a recording for transcription, choosing its writing mode from the app
+/// (in Messages, from the contact captured when recording started)
+fn recording_request<'a>(
+    handle: &FlowHandle,
+    audio: &'a [u8],
+    sample_rate: u32,
+    app_name: Option<String>,
+) -> RecordingRequest<'a> {
+    // Determine writing mode - use contact captured at recording start for Messages
+    let mode = if let Some(ref name) = app_name {
+        // Check if this is Messages.app
+        if name.to_lowercase().contains("messages") || name == "com.apple.MobileSMS" {
+            // Use the contact that was captured when recording started
+            // This avoids race conditions where the window focus changes during recording
+            let captured = handle.captured_contact.lock().clone();
 
-            // Delete from storage
-            match handle.storage.delete_correction(&uuid) {
-                Ok(deleted) => {
-                    if deleted {
-                        // Remove from cache
-                        handle.learning.remove_from_cache(&original);
-                        debug!(
-                            "Deleted correction: {} -> {}",
-                            original, correction.corrected
-                        );
-                    }
-                    deleted
-                }
-                Err(e) => {
-                    error!("Failed to delete correction: {}", e);
-                    false
-                }
+            if let Some(contact_name) = captured {
+                debug!("Using captured Messages contact: {}", contact_name);
+
+                // Classify the contact
+                let input = ContactInput {
+                    name: contact_name.clone(),
+                    organization: String::new(),
+                };
+                let category = handle.contact_classifier.classify(&input);
+                let contact_mode = category.suggested_writing_mode();
+
+                debug!(
+                    "Contact '{}' classified as {:?}, using mode {:?}",
+                    contact_name, category, contact_mode
+                );
+
+                // Record the interaction
+                handle.contact_classifier.record_interaction(&contact_name);
+
+                contact_mode
+            } else {
+                debug!("No contact was captured at recording start, using app default");
+                let mut modes = handle.modes.lock();
+                modes.get_mode_with_storage(name, &handle.storage)
             }
         } else {
-            false
+            // Not Messages - use app-based mode
+            let mut modes = handle.modes.lock();
+            modes.get_mode_with_storage(name, &handle.storage)
         }
     } else {
-        false
+        WritingMode::Casual
+    };
+
+    RecordingRequest {
+        audio,
+        sample_rate,
+        mode,
+        app_name,
+        live: None,
     }
 }
 
-/// Delete all corrections
-/// Returns the number of corrections deleted
-#[unsafe(no_mangle)]
-pub extern "C" fn flow_delete_all_corrections(handle: *mut FlowHandle) -> usize {
-    let handle = unsafe { &*handle };
-
-    match handle.storage.delete_all_corrections() {
-        Ok(count) => {
-            // Clear the cache
-            handle.learning.clear_cache();
-            debug!("Deleted all {} corrections", count);
-            count
-        }
-        Err(e) => {
-            error!("Failed to delete all corrections: {}", e);
-            0
-        }
+/// Save the statistics record for a successful transcription
+fn record_transcription(handle: &FlowHandle, history: &TranscriptionHistoryEntry) {
+    let mut record = Transcription::new(
+        history.raw_text.clone(),
+        history.text.clone(),
+        history.confidence.unwrap_or(0.0),
+        history.duration_ms,
+    );
+    record.app_context = history.app_context.clone();
+    if let Err(e) = handle.storage.save_transcription(&record) {
+        error!("Failed to save transcription: {}", e);
     }
 }
 
-/// Validate corrections using AI (async, returns JSON)
-/// Input: JSON array of {"original": "...", "corrected": "..."} pairs
-/// Output: JSON array of {"original": "...", "corrected": "...", "valid": bool, "reason": "..."}
-/// Caller must free the returned string with flow_free_string
-#[unsafe(no_mangle)]
-pub extern "C" fn flow_validate_corrections(
-    _handle: *mut FlowHandle,
-    corrections_json: *const c_char,
-) -> *mut c_char {
-    if corrections_json.is_null() {
-        return ptr::null_mut();
+/// Transcribe a recording with the active provider, record it in the history and statistics,
+/// and archive its audio
+fn transcribe_with_audio(
+    handle: &FlowHandle,
+    request: &RecordingRequest,
+) -> crate::error::Result<String> {
+    let transcription_provider = active_transcription_provider(handle)?;
+    let history = run_transcription(
+        handle,
+        transcription_provider.as_ref(),
+        uses_local_transcription(&handle.storage),
+        request,
+    )?;
+
+    record_transcription(handle, &history);
+    if let Err(e) = handle.storage.save_history_entry(&history) {
+        error!("Failed to save transcription history: {}", e);
     }
+    archive_recording(handle, &history.id, request.audio, request.sample_rate);
 
-    let json_str = match unsafe { CStr::from_ptr(corrections_json) }.to_str() {
-        Ok(s) => s,
-        Err(_) => return ptr::null_mut(),
-    };
+    Ok(history.text)
+}
 
-    // Parse input JSON
-    let pairs: Vec<crate::providers::CorrectionPair> = match serde_json::from_str(json_str) {
-        Ok(p) => p,
-        Err(e) => {
-            error!("Failed to parse corrections JSON: {}", e);
-            return ptr::null_mut();
+/// Transcription options from the settings (language, translation and the vocabulary
+/// prompt), for requests that only lack their audio
+fn transcription_request_template(handle: &FlowHandle) -> TranscriptionRequest {
+    let mut request = TranscriptionRequest::new(Vec::new(), SPEECH_SAMPLE_RATE);
+    if let Some(language) = non_empty_setting(&handle.storage, SETTING_TRANSCRIPTION_LANGUAGE)
+        .filter(|language| language != "auto")
+    {
+        request = request.with_language(language);
+    }
+    if non_empty_setting(&handle.storage, SETTING_TRANSLATE_TO_ENGLISH).as_deref() == Some("true") {
+        request = request.with_translation();
+    }
+
+    // Bias recognition toward the user's names and terms
+    match VocabularySources::collect(
+        &handle.storage,
+        &handle.shortcuts,
+        &handle.learning,
+        &handle.contact_classifier,
+    ) {
+        Ok(sources) => {
+            if let Some(vocabulary) = sources.build_prompt() {
+                request = request.with_prompt(vocabulary);
+            }
         }
+        Err(e) => warn!("Failed to collect vocabulary: {}", e),
+    }
+    request
+}
+
+/// Run a recording through transcription and post-processing
+/// Returns the history entry for the result, not yet saved
+fn run_transcription(
+    handle: &FlowHandle,
+    transcription_provider: &dyn TranscriptionProvider,
+    use_local_transcription: bool,
+    request: &RecordingRequest,
+) -> crate::error::Result<TranscriptionHistoryEntry> {
+    let RecordingRequest {
+        audio: audio_data,
+        sample_rate,
+        mode,
+        ref app_name,
+        ref live,
+    } = *request;
+    let app_context = handle.app_tracker.current_app();
+    let offline = crate::offline::is_offline();
+
+    // Build mode string for worker
+    let mode_str = match mode {
+        WritingMode::Formal => "formal",
+        WritingMode::Casual => "casual",
+        WritingMode::VeryCasual => "very_casual",
+        WritingMode::Excited => "excited",
     };
 
-    // Run async validation
-    let runtime = match tokio::runtime::Runtime::new() {
-        Ok(rt) => rt,
-        Err(e) => {
-            error!("Failed to create tokio runtime: {}", e);
-            return ptr::null_mut();
-        }
+    // For cloud transcription (auto mode), worker handles everything
+    let completion_params = if !use_local_transcription {
+        log_with_time!("🚀 [RUST] Using auto mode (worker handles transcription+completion)");
+        Some(TranscriptionCompletionParams {
+            mode: mode_str.to_string(),
+            app_context: app_name.clone(),
+            // Which shortcuts were said is only known once the worker has transcribed,
+            // so it expands them itself and only tells the formatter about those it expanded
+            shortcuts_triggered: Vec::new(),
+            shortcuts: handle
+                .shortcuts
+                .get_all()
+                .into_iter()
+                .filter(|shortcut| shortcut.enabled)
+                .map(|shortcut| ShortcutExpansion {
+                    trigger: shortcut.trigger,
+                    replacement: shortcut.replacement,
+                    case_sensitive: shortcut.case_sensitive,
+                })
+                .collect(),
+            voice_instruction: None, // Worker auto-detects from transcription
+        })
+    } else {
+        None
     };
 
-    let results = match runtime.block_on(crate::providers::validate_corrections(pairs)) {
-        Ok(r) => r,
-        Err(e) => {
-            error!("Validation failed: {}", e);
-            return ptr::null_mut();
+    let template = transcription_request_template(handle);
+
+    // Chunks transcribed while recording are kept; only the audio after them is left
+    let samples = crate::audio::pcm_to_samples(audio_data);
+    let (tail_start, transcribed) = match live {
+        Some(live) if !transcription_provider.completes_text() => {
+            let tail_start = (live.tail_start_ms * sample_rate as u64 / 1000) as usize;
+            (tail_start.min(samples.len()), live.committed.clone())
         }
+        _ => (0, None),
     };
+    if tail_start > 0 {
+        log_with_time!(
+            "⚡ [RUST] Live transcription covered {}ms, transcribing the rest",
+            tail_start as u64 * 1000 / sample_rate as u64
+        );
+    }
 
-    // Return as JSON
-    match CString::new(serde_json::to_string(&results).unwrap_or_default()) {
-        Ok(cstr) => cstr.into_raw(),
-        Err(_) => ptr::null_mut(),
+    // Skip silence; without a worker completion, long recordings are sent in chunks cut at pauses
+    let ranges = match speech_ranges(
+        handle,
+        &samples[tail_start..],
+        sample_rate,
+        completion_params.is_none(),
+    ) {
+        Ok(ranges) => ranges
+            .into_iter()
+            .map(|range| range.start + tail_start..range.end + tail_start)
+            .filter(|range| tail_start == 0 || !range.is_empty())
+            .collect(),
+        Err(Error::NoSpeech) if transcribed.is_some() => Vec::new(),
+        Err(e) => return Err(e),
+    };
+    if ranges.len() > 1 {
+        debug!("Transcribing {} chunks split at pauses", ranges.len());
     }
-}
 
-// ============ Stats ============
+    // Perform transcription
+    let transcription = handle.runtime.block_on(async {
+        let mut merged = transcribed;
+        for range in ranges {
+            let offset_ms = range.start as u64 * 1000 / sample_rate as u64;
+            let chunk = audio_data[range.start * 2..range.end * 2].to_vec();
+            let mut request = template.clone();
+            request.audio = chunk;
+            request.sample_rate = sample_rate;
+            if let Some(params) = &completion_params {
+                request = request.with_completion(params.clone());
+            }
+            let response = transcription_provider
+                .transcribe(request)
+                .await?
+                .offset_by(offset_ms);
+            match merged.as_mut() {
+                Some(merged) => merged.append(response),
+                None => merged = Some(response),
+            }
+        }
+        merged.ok_or(Error::NoSpeech)
+    })?;
 
-/// Get total transcription time in minutes
-#[unsafe(no_mangle)]
-pub extern "C" fn flow_total_transcription_minutes(handle: *mut FlowHandle) -> u64 {
-    let handle = unsafe { &*handle };
-    handle
-        .storage
-        .get_total_transcription_time_ms()
-        .unwrap_or(0)
-        / 60000
-}
+    // Process shortcuts and corrections on raw transcription
+    let (text_with_shortcuts, triggered) = handle.shortcuts.process(&transcription.text);
+    let (text_with_corrections, _applied) = handle.learning.apply_corrections(&text_with_shortcuts);
 
-/// Get total transcription count
-#[unsafe(no_mangle)]
-pub extern "C" fn flow_transcription_count(handle: *mut FlowHandle) -> u64 {
-    let handle = unsafe { &*handle };
-    handle.storage.get_transcription_count().unwrap_or(0)
-}
+    let served_by = transcription
+        .provider
+        .unwrap_or_else(|| transcription_provider.name().to_string());
 
-// ============ Utilities ============
+    // Use worker completion if available, otherwise use corrected transcription
+    let (processed_text, completion_provider) =
+        if let Some(completed_text) = transcription.completed_text {
+            log_with_time!(
+                "✅ [RUST/AI] Worker completion received - Output: {} chars",
+                completed_text.len()
+            );
+            (completed_text, Some(served_by.clone()))
+        } else if offline {
+            // Offline mode - format on-device instead of calling an LLM
+            let formatted = format_text(&text_with_corrections, mode);
+            log_with_time!(
+                "✈️ [RUST] Offline mode - formatted locally: {} chars",
+                formatted.len()
+            );
+            (formatted, None)
+        } else if use_local_transcription || transcription_provider.completes_text() {
+            // Local transcription mode, or the worker failed over to a provider that only
+            // transcribes - format with the completion provider. Other cloud providers
+            // never format, so their text is used as corrected
+            complete_local_transcription(
+                handle,
+                &text_with_corrections,
+                mode,
+                app_name.as_deref(),
+                &triggered,
+            )
+        } else {
+            log_with_time!(
+                "📝 [RUST] No completion from the provider - using corrected text: {} chars",
+                text_with_corrections.len()
+            );
+            (text_with_corrections, None)
+        };
 
-/// Free a string returned by flow functions
-#[unsafe(no_mangle)]
-pub extern "C" fn flow_free_string(s: *mut c_char) {
-    if !s.is_null() {
-        unsafe {
-            drop(CString::from_raw(s));
-        }
-    }
+    // Expansions must come through formatting word for word
+    let processed_text = restore_expansions(&processed_text, &transcription.text, &triggered);
+
+    let mut history = TranscriptionHistoryEntry::success(
+        transcription.text,
+        processed_text,
+        transcription.duration_ms,
+    );
+    history.app_context = app_context;
+    history.confidence = transcription.confidence;
+    history.segments = transcription.segments.unwrap_or_default();
+    history.provider = Some(served_by);
+    history.completion_provider = completion_provider;
+
+    Ok(history)
 }
 
-/// Check if the transcription provider is configured
-#[unsafe(no_mangle)]
-pub extern "C" fn flow_is_configured(handle: *mut FlowHandle) -> bool {
-    let handle = unsafe { &*handle };
+/// Format a local transcription for its writing mode and app with the completion provider
+/// Returns the text and the provider that formatted it
+/// Falls back to the corrected text when the provider is not configured or fails
+fn complete_local_transcription(
+    handle: &FlowHandle,
+    text: &str,
+    mode: WritingMode,
+    app_name: Option<&str>,
+    triggered: &[TriggeredShortcut],
+) -> (String, Option<String>) {
+    let completion = active_completion_provider(handle);
+    if text.trim().is_empty() || !completion.is_configured() {
+        log_with_time!(
+            "📝 [RUST] Local transcription mode - using corrected text: {} chars",
+            text.len()
+        );
+        return (text.to_string(), None);
+    }
 
-    // Base10 ("Auto (Cloud)") handles both transcription and completion internally,
-    // so we don't need a separate completion provider configured
-    if handle.transcription.name() == "Auto (Cloud)" {
-        return handle.transcription.is_configured();
+    let mut request = CompletionRequest::new(text.to_string(), mode);
+    if let Some(name) = app_name {
+        request = request.with_app_context(name);
+    }
+    if let Some(instruction) = preservation_instruction(triggered) {
+        request = request.with_shortcut_preservation(instruction);
     }
 
-    handle.transcription.is_configured() && handle.completion.is_configured()
+    match handle.runtime.block_on(completion.complete(request)) {
+        Ok(response) if !response.text.trim().is_empty() => {
+            let provider = response
+                .provider
+                .unwrap_or_else(|| completion.name().to_string());
+            log_with_time!(
+                "✅ [RUST/AI] Local transcription formatted by {} - Output: {} chars",
+                provider,
+                response.text.len()
+            );
+            (response.text, Some(provider))
+        }
+        Ok(_) => {
+            warn!("Completion returned no text, using corrected transcription");
+            (text.to_string(), None)
+        }
+        Err(e) => {
+            warn!("Completion failed, using corrected transcription: {}", e);
+            (text.to_string(), None)
+        }
+    }
 }
 
-// ============ App Tracking ============
-
-/// Set the currently active app (call from Swift when app switches)
-/// Returns the suggested writing mode for the app
-#[unsafe(no_mangle)]
-pub extern "C" fn flow_set_active_app(
-    handle: *mut FlowHandle,
-    app_name: *const c_char,
-    bundle_id: *const c_char,
-    window_title: *const c_char,
-) -> u8 {
-    if app_name.is_null() {
-        return 1; // default to casual
+/// Hand a transcription result to the caller as a C string
+/// No speech becomes an empty string; failures are recorded in the history (with the
+/// recording, when given and the archive is on) and return null
+/// A recording that failed for lack of network is queued to be retried in the background
+fn transcription_result(
+    handle: &FlowHandle,
+    result: crate::error::Result<String>,
+    request: Option<&RecordingRequest>,
+) -> *mut c_char {
+    match result {
+        Ok(text) => {
+            clear_last_error(handle);
+            match CString::new(text) {
+                Ok(cstr) => cstr.into_raw(),
+                Err(_) => ptr::null_mut(),
+            }
+        }
+        Err(Error::NoSpeech) => {
+            log_with_time!("🔇 [RUST] No speech detected, nothing to transcribe");
+            clear_last_error(handle);
+            CString::default().into_raw()
+        }
+        Err(e) => {
+            let message = format!("Transcription failed: {e}");
+            error!("{message}");
+            set_last_error(handle, message.clone());
+            let duration_ms = request.map_or(0, |request| {
+                estimate_duration_ms(request.audio.len(), request.sample_rate)
+            });
+            let mut history = TranscriptionHistoryEntry::failure(message, duration_ms);
+            history.app_context = handle.app_tracker.current_app();
+            if let Err(e) = handle.storage.save_history_entry(&history) {
+                error!("Failed to save transcription history: {}", e);
+            }
+            if let Some(request) = request {
+                archive_recording(handle, &history.id, request.audio, request.sample_rate);
+                if matches!(e, Error::Network(_)) {
+                    queue_transcription(handle, &history.id, request);
+                }
+            }
+            ptr::null_mut()
+        }
     }
+}
 
+/// Transcribe the recorded audio and process it
+/// Returns the processed text (caller must free with flow_free_string)
+/// Returns an empty string when the recording holds no speech (no provider is called)
+/// Returns null on failure
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_transcribe(handle: *mut FlowHandle, app_name: *const c_char) -> *mut c_char {
     let handle = unsafe { &*handle };
 
-    let name = match unsafe { CStr::from_ptr(app_name) }.to_str() {
-        Ok(s) => s.to_string(),
-        Err(_) => return 1,
-    };
+    // Get cached audio data (don't touch handle.audio at all)
+    // This ensures the microphone device was already released by flow_stop_recording
+    let (audio_data, sample_rate) = {
+        let audio_data = handle.pending_audio.lock().take();
+        let sample_rate = handle.pending_sample_rate.lock().take();
 
-    let bid = if bundle_id.is_null() {
-        None
-    } else {
-        unsafe { CStr::from_ptr(bundle_id) }
-            .to_str()
-            .ok()
-            .map(String::from)
+        match (audio_data, sample_rate) {
+            (Some(data), Some(rate)) => (data, rate),
+            _ => {
+                set_last_error(
+                    handle,
+                    "No audio data pending - must call stop_recording first",
+                );
+                return ptr::null_mut();
+            }
+        }
     };
 
-    let title = if window_title.is_null() {
-        None
-    } else {
-        unsafe { CStr::from_ptr(window_title) }
+    if audio_data.is_empty() {
+        set_last_error(handle, "No audio captured");
+        return ptr::null_mut();
+    }
+
+    // get app name
+    let app = if !app_name.is_null() {
+        unsafe { CStr::from_ptr(app_name) }
             .to_str()
             .ok()
             .map(String::from)
+    } else {
+        None
     };
 
-    let _context = handle.app_tracker.set_active_app(name, bid, title);
+    *handle.last_audio.lock() = Some(audio_data.clone());
+    *handle.last_audio_sample_rate.lock() = Some(sample_rate);
+    *handle.last_queued.lock() = None;
+    let mut request = recording_request(handle, &audio_data, sample_rate, app);
+    request.live = handle.live.lock().take().map(LiveSession::finish);
+    let result = transcribe_with_audio(handle, &request);
 
-    // return suggested mode
-    match handle.app_tracker.suggested_mode() {
-        WritingMode::Formal => 0,
-        WritingMode::Casual => 1,
-        WritingMode::VeryCasual => 2,
-        WritingMode::Excited => 3,
+    // Clear the captured contact after transcription (whether success or failure)
+    *handle.captured_contact.lock() = None;
+
+    // Keep the audio for a retry only when the provider failed
+    if !matches!(result, Err(ref e) if !matches!(e, Error::NoSpeech)) {
+        *handle.last_audio.lock() = None;
+        *handle.last_audio_sample_rate.lock() = None;
     }
+    transcription_result(handle, result, Some(&request))
 }
 
-/// Get the current app's category
-/// Returns: 0=Email, 1=Slack, 2=Code, 3=Documents, 4=Social, 5=Browser, 6=Terminal, 7=Unknown
+/// Retry the last transcription using cached audio
+/// Returns processed text (caller must free with flow_free_string), an empty string when the
+/// audio holds no speech, or null on failure
 #[unsafe(no_mangle)]
-pub extern "C" fn flow_get_app_category(handle: *mut FlowHandle) -> u8 {
+pub extern "C" fn flow_retry_last_transcription(
+    handle: *mut FlowHandle,
+    app_name: *const c_char,
+) -> *mut c_char {
     let handle = unsafe { &*handle };
-
-    use crate::types::AppCategory;
-    match handle.app_tracker.current_category() {
-        AppCategory::Email => 0,
-        AppCategory::Slack => 1,
-        AppCategory::Code => 2,
-        AppCategory::Documents => 3,
-        AppCategory::Social => 4,
-        AppCategory::Browser => 5,
-        AppCategory::Terminal => 6,
-        AppCategory::Unknown => 7,
+    let (audio_data, sample_rate) = {
+        let last_audio = handle.last_audio.lock();
+        let last_sample_rate = handle.last_audio_sample_rate.lock();
+        match last_audio.as_ref() {
+            Some(data) => (data.clone(), last_sample_rate.unwrap_or(16_000)),
+            None => {
+                set_last_error(handle, "No previous recording to retry");
+                return ptr::null_mut();
+            }
+        }
+    };
+
+    let app = if !app_name.is_null() {
+        unsafe { CStr::from_ptr(app_name) }
+            .to_str()
+            .ok()
+            .map(String::from)
+    } else {
+        None
+    };
+
+    // This retry takes over from the background queue
+    if let Some(id) = handle.last_queued.lock().take()
+        && let Err(e) = handle.storage.delete_queued_transcription(&id)
+    {
+        warn!("Failed to remove queued transcription: {}", e);
+    }
+
+    let request = recording_request(handle, &audio_data, sample_rate, app);
+    let result = transcribe_with_audio(handle, &request);
+
+    // Keep the audio for a retry only when the provider failed
+    if !matches!(result, Err(ref e) if !matches!(e, Error::NoSpeech)) {
+        *handle.last_audio.lock() = None;
+        *handle.last_audio_sample_rate.lock() = None;
     }
+    transcription_result(handle, result, Some(&request))
 }
 
-/// Get current app name (caller must free with flow_free_string)
+/// Transcribe a WAV or FLAC file through the same pipeline as a recording:
+/// voice activity detection, shortcuts, corrections and the app's writing mode
+/// Fails with `Error::NoSpeech` when the file holds no speech
+pub fn transcribe_file(
+    handle: &FlowHandle,
+    path: &Path,
+    app_name: Option<String>,
+) -> crate::error::Result<String> {
+    let source = FileSource::open(path)?;
+    let audio_data = crate::audio::samples_to_pcm(source.samples());
+    let request = recording_request(handle, &audio_data, source.sample_rate(), app_name);
+    transcribe_with_audio(handle, &request)
+}
+
+/// Transcribe an audio file (WAV or FLAC) and process it like a recording
+/// Returns the processed text (caller must free with flow_free_string), an empty string when
+/// the file holds no speech, or null on failure
 #[unsafe(no_mangle)]
-pub extern "C" fn flow_get_current_app(handle: *mut FlowHandle) -> *mut c_char {
+pub extern "C" fn flow_transcribe_file(
+    handle: *mut FlowHandle,
+    path: *const c_char,
+    app_name: *const c_char,
+) -> *mut c_char {
     let handle = unsafe { &*handle };
 
-    match handle.app_tracker.current_app() {
-        Some(ctx) => match CString::new(ctx.app_name) {
-            Ok(cstr) => cstr.into_raw(),
-            Err(_) => ptr::null_mut(),
-        },
-        None => ptr::null_mut(),
-    }
-}
+    let path = if path.is_null() {
+        None
+    } else {
+        unsafe { CStr::from_ptr(path) }.to_str().ok()
+    };
+    let Some(path) = path else {
+        set_last_error(handle, "Invalid file path");
+        return ptr::null_mut();
+    };
 
-// ============ Style Learning ============
+    let app = if !app_name.is_null() {
+        unsafe { CStr::from_ptr(app_name) }
+            .to_str()
+            .ok()
+            .map(String::from)
+    } else {
+        None
+    };
 
-/// Report edited text to learn user's style for current app
+    let result = transcribe_file(handle, Path::new(path), app);
+    transcription_result(handle, result, None)
+}
+
+/// Record from an audio file (WAV or FLAC) instead of the microphone, e.g. to exercise
+/// the recording pipeline on machines without an input device
+/// path: file to play back, or null to use the microphone again
+/// Returns true on success
 #[unsafe(no_mangle)]
-pub extern "C" fn flow_learn_style(handle: *mut FlowHandle, edited_text: *const c_char) -> bool {
-    if edited_text.is_null() {
+pub extern "C" fn flow_set_input_file(handle: *mut FlowHandle, path: *const c_char) -> bool {
+    let handle = unsafe { &*handle };
+
+    if path.is_null() {
+        *handle.input_file.lock() = None;
+        reset_idle_audio_source(handle);
+        clear_last_error(handle);
+        return true;
+    }
+
+    let Ok(path) = unsafe { CStr::from_ptr(path) }.to_str() else {
+        set_last_error(handle, "Invalid file path");
+        return false;
+    };
+    // Fail now rather than when recording starts
+    if let Err(e) = crate::audio::AudioFileFormat::detect(Path::new(path)) {
+        set_last_error(handle, e.to_string());
         return false;
     }
 
+    *handle.input_file.lock() = Some(PathBuf::from(path));
+    reset_idle_audio_source(handle);
+    clear_last_error(handle);
+    true
+}
+
+/// Format text with the active completion provider, streaming output as it is generated
+/// callback receives (true, chunk) for each chunk of text, or (false, error) on failure.
+/// Chunk strings are only valid for the duration of the callback.
+/// Providers without streaming support deliver the whole result as a single chunk.
+/// Returns the full formatted text (caller must free with flow_free_string), or null on failure
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_complete_streaming(
+    handle: *mut FlowHandle,
+    text: *const c_char,
+    app_name: *const c_char,
+    callback: ResultCallback,
+    context: *mut c_void,
+) -> *mut c_char {
     let handle = unsafe { &*handle };
 
-    let text = match unsafe { CStr::from_ptr(edited_text) }.to_str() {
-        Ok(s) => s,
-        Err(_) => return false,
+    let text = if text.is_null() {
+        None
+    } else {
+        unsafe { CStr::from_ptr(text) }.to_str().ok()
+    };
+    let Some(text) = text else {
+        set_last_error(handle, "Invalid text");
+        invoke_callback(callback, context, false, "Invalid text");
+        return ptr::null_mut();
     };
 
-    let app_name = match handle.app_tracker.current_app() {
-        Some(ctx) => ctx.app_name,
-        None => return false,
+    let app = if !app_name.is_null() {
+        unsafe { CStr::from_ptr(app_name) }
+            .to_str()
+            .ok()
+            .map(String::from)
+    } else {
+        None
     };
 
-    let mut learner = handle.style_learner.lock();
-    learner.observe_with_storage(&app_name, text, &handle.storage);
+    let mode = match &app {
+        Some(name) => handle
+            .modes
+            .lock()
+            .get_mode_with_storage(name, &handle.storage),
+        None => WritingMode::Casual,
+    };
 
-    true
+    let mut request = CompletionRequest::new(text.to_string(), mode);
+    if let Some(name) = app {
+        request = request.with_app_context(name);
+    }
+
+    let completion = active_completion_provider(handle);
+    let result = handle.runtime.block_on(async {
+        let Some(streaming) = completion.as_streaming() else {
+            let response = completion.complete(request).await?;
+            invoke_callback(callback, context, true, &response.text);
+            return Ok(response.text);
+        };
+
+        let mut stream = streaming.complete_stream(request).await?;
+        let mut output = String::new();
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk?;
+            if !chunk.text.is_empty() {
+                invoke_callback(callback, context, true, &chunk.text);
+                output.push_str(&chunk.text);
+            }
+        }
+        Ok::<_, crate::error::Error>(output)
+    });
+
+    match result {
+        Ok(output) => {
+            clear_last_error(handle);
+            match CString::new(output) {
+                Ok(cstr) => cstr.into_raw(),
+                Err(_) => ptr::null_mut(),
+            }
+        }
+        Err(e) => {
+            let message = format!("Completion failed: {e}");
+            error!("{message}");
+            set_last_error(handle, message.clone());
+            invoke_callback(callback, context, false, &message);
+            ptr::null_mut()
+        }
+    }
 }
 
-/// Get suggested mode based on learned style for current app
-/// Returns: 0=Formal, 1=Casual, 2=VeryCasual, 3=Excited, 255=no suggestion
+// ============ Audio Archive ============
+
+/// Read a transcription history id argument
+fn history_id_arg(id: *const c_char) -> Option<TranscriptionId> {
+    if id.is_null() {
+        return None;
+    }
+    let id = unsafe { CStr::from_ptr(id) }.to_str().ok()?;
+    TranscriptionId::parse_str(id.trim()).ok()
+}
+
+/// Transcription provider picked for a re-transcription, and whether it is local
+/// provider: "auto", "openai" or "local" (None: the active provider)
+/// model: OpenAI model name, or a built-in or installed Whisper model for "local"
+fn chosen_transcription_provider(
+    handle: &FlowHandle,
+    provider: Option<&str>,
+    model: Option<&str>,
+) -> crate::error::Result<(Arc<dyn TranscriptionProvider>, bool)> {
+    let offline = crate::offline::is_offline();
+    match (provider, model) {
+        (None, None) => Ok((
+            active_transcription_provider(handle)?,
+            uses_local_transcription(&handle.storage),
+        )),
+        (None, Some(_)) => Err(Error::Config("Choose a provider for the model".to_string())),
+        (Some("auto"), None) if offline => Err(Error::Offline("Auto transcription".to_string())),
+        (Some("auto"), None) => Ok((auto_transcription_provider(&handle.storage), false)),
+        (Some("auto"), Some(_)) => Err(Error::Config(
+            "Auto transcription has no model choice".to_string(),
+        )),
+        (Some("openai"), _) if offline => Err(Error::Offline("OpenAI transcription".to_string())),
+        (Some("openai"), model) => {
+            let key =
+                non_empty_setting(&handle.storage, SETTING_OPENAI_API_KEY).ok_or_else(|| {
+                    Error::ProviderNotConfigured("OpenAI API key not configured".to_string())
+                })?;
+            let mut provider = openai_transcription_provider(&handle.storage, Some(key));
+            if let Some(model) = model {
+                provider = provider.with_model(model);
+            }
+            Ok((Arc::new(provider), false))
+        }
+        (Some("local"), model) => {
+            let models_dir = whisper_models::get_models_dir()?;
+            let spec = match model {
+                None => {
+                    let stored = non_empty_setting(&handle.storage, SETTING_LOCAL_WHISPER_MODEL);
+                    stored_whisper_spec(stored.as_deref(), &models_dir)
+                }
+                Some(model) => match WhisperModel::parse(model) {
+                    Some(builtin) => builtin.spec(&models_dir),
+                    None => whisper_models::read_manifest(&models_dir, model)?
+                        .ok_or_else(|| Error::Model(format!("Model {model} is not installed")))?
+                        .spec()?,
+                },
+            };
+            Ok((
+                Arc::new(local_whisper_provider(handle, spec, models_dir)),
+                true,
+            ))
+        }
+        (Some(other), _) => Err(Error::Config(format!(
+            "Unknown transcription provider: {other}"
+        ))),
+    }
+}
+
+/// Transcribe an archived recording again, replacing the outcome of its history entry
+/// The entry keeps its id, app and time; statistics are not counted twice
+fn retranscribe(
+    handle: &FlowHandle,
+    id: &TranscriptionId,
+    provider: Option<&str>,
+    model: Option<&str>,
+) -> crate::error::Result<String> {
+    let original = handle
+        .storage
+        .get_history_entry(id)?
+        .ok_or_else(|| Error::Config(format!("No transcription {id} in the history")))?;
+    let (audio_data, sample_rate) = handle.archive.load(id)?;
+    let (provider, use_local_transcription) =
+        chosen_transcription_provider(handle, provider, model)?;
+
+    let app_name = original
+        .app_context
+        .as_ref()
+        .map(|context| context.app_name.clone());
+    let request = recording_request(handle, &audio_data, sample_rate, app_name);
+    let mut history =
+        run_transcription(handle, provider.as_ref(), use_local_transcription, &request)?;
+    history.id = original.id;
+    history.app_context = original.app_context;
+    history.created_at = original.created_at;
+    handle.storage.update_history_entry(&history)?;
+    // A queued retry of the same recording would only redo this
+    handle.storage.delete_queued_transcription(id)?;
+    Ok(history.text)
+}
+
+/// Keep recordings on disk with their history entries, for replay and re-transcription
+/// (persisted, off by default)
+/// max_mb: archive size before the oldest recordings are deleted (0 for no limit)
+/// max_age_days: days recordings are kept (0 for no limit)
+/// Recordings outside the new limits are deleted right away, even when turning the archive off
+/// Returns true on success
 #[unsafe(no_mangle)]
-pub extern "C" fn flow_get_style_suggestion(handle: *mut FlowHandle) -> u8 {
+pub extern "C" fn flow_set_audio_archive(
+    handle: *mut FlowHandle,
+    enabled: bool,
+    max_mb: u32,
+    max_age_days: u32,
+) -> bool {
     let handle = unsafe { &*handle };
 
-    let app_name = match handle.app_tracker.current_app() {
-        Some(ctx) => ctx.app_name,
-        None => return 255,
-    };
+    let settings = [
+        (SETTING_AUDIO_ARCHIVE, enabled.to_string()),
+        (SETTING_AUDIO_ARCHIVE_MAX_MB, max_mb.to_string()),
+        (SETTING_AUDIO_ARCHIVE_MAX_AGE_DAYS, max_age_days.to_string()),
+    ];
+    for (key, value) in settings {
+        if let Err(e) = handle.storage.set_setting(key, &value) {
+            let message = format!("Failed to save audio archive setting: {e}");
+            error!("{message}");
+            set_last_error(handle, message);
+            return false;
+        }
+    }
 
-    let learner = handle.style_learner.lock();
-    match learner.suggest_mode(&app_name) {
-        Some(suggestion) => match suggestion.suggested_mode {
-            WritingMode::Formal => 0,
-            WritingMode::Casual => 1,
-            WritingMode::VeryCasual => 2,
-            WritingMode::Excited => 3,
-        },
-        None => 255,
+    if let Err(e) = handle.archive.prune(&archive_retention(&handle.storage)) {
+        warn!("Failed to prune audio archive: {}", e);
     }
+    clear_last_error(handle);
+    true
 }
 
-// ============ Extended Stats ============
-
-/// Get user stats as JSON (caller must free with flow_free_string)
+/// Get the audio archive settings and usage as JSON
+/// {"enabled", "max_mb", "max_age_days", "recordings", "total_bytes"} (0 limits mean none)
+/// Caller must free with flow_free_string
 #[unsafe(no_mangle)]
-pub extern "C" fn flow_get_stats_json(handle: *mut FlowHandle) -> *mut c_char {
+pub extern "C" fn flow_get_audio_archive_json(handle: *mut FlowHandle) -> *mut c_char {
     let handle = unsafe { &*handle };
 
-    let stats = serde_json::json!({
-        "total_transcriptions": handle.storage.get_transcription_count().unwrap_or(0),
-        "total_duration_ms": handle.storage.get_total_transcription_time_ms().unwrap_or(0),
-        "total_words_dictated": handle.storage.get_total_words_dictated().unwrap_or(0),
-        "shortcut_count": handle.shortcuts.count(),
-        "correction_count": handle.learning.cache_size(),
+    let retention = archive_retention(&handle.storage);
+    let recordings = handle.archive.recordings().unwrap_or_else(|e| {
+        warn!("Failed to list audio archive: {}", e);
+        Vec::new()
     });
-
-    match CString::new(stats.to_string()) {
+    let json = serde_json::json!({
+        "enabled": audio_archive_enabled(&handle.storage),
+        "max_mb": retention.max_bytes.map_or(0, |bytes| bytes / (1024 * 1024)),
+        "max_age_days": retention.max_age.map_or(0, |age| age.as_secs() / (24 * 60 * 60)),
+        "recordings": recordings.len(),
+        "total_bytes": recordings.iter().map(|recording| recording.bytes).sum::<u64>(),
+    });
+    match CString::new(json.to_string()) {
         Ok(cstr) => cstr.into_raw(),
         Err(_) => ptr::null_mut(),
     }
 }
 
-/// Get recent transcriptions as JSON (caller must free with flow_free_string)
+/// Get the archived recording of a history entry for playback (a FLAC file)
+/// Returns the file path (caller must free with flow_free_string), or null if there is none
 #[unsafe(no_mangle)]
-pub extern "C" fn flow_get_recent_transcriptions_json(
+pub extern "C" fn flow_get_archived_audio_path(
     handle: *mut FlowHandle,
-    limit: usize,
+    id: *const c_char,
 ) -> *mut c_char {
     let handle = unsafe { &*handle };
-    let transcriptions = match handle.storage.get_recent_history(limit) {
-        Ok(items) => items,
-        Err(e) => {
-            error!("Failed to load transcriptions: {}", e);
-            return ptr::null_mut();
-        }
-    };
-
-    let summaries: Vec<TranscriptionSummary> = transcriptions
-        .into_iter()
-        .map(|item| TranscriptionSummary {
-            id: item.id.to_string(),
-            status: item.status,
-            text: item.text,
-            raw_text: item.raw_text,
-            error: item.error,
-            duration_ms: item.duration_ms,
-            created_at: item.created_at.to_rfc3339(),
-            app_name: item.app_context.map(|ctx| ctx.app_name),
-        })
-        .collect();
 
-    let json = match serde_json::to_string(&summaries) {
-        Ok(value) => value,
-        Err(e) => {
-            error!("Failed to serialize transcriptions: {}", e);
-            return ptr::null_mut();
-        }
+    let Some(id) = history_id_arg(id) else {
+        set_last_error(handle, "Invalid transcription id");
+        return ptr::null_mut();
     };
-
-    match CString::new(json) {
+    if !handle.archive.contains(&id) {
+        set_last_error(handle, format!("No archived audio for {id}"));
+        return ptr::null_mut();
+    }
+    match CString::new(handle.archive.path(&id).to_string_lossy().into_owned()) {
         Ok(cstr) => cstr.into_raw(),
         Err(_) => ptr::null_mut(),
     }
 }
 
-/// Get the last error message (caller must free with flow_free_string)
+/// Export the archived recording of a history entry as a 16-bit mono WAV file
+/// Returns true on success
 #[unsafe(no_mangle)]
-pub extern "C" fn flow_get_last_error(handle: *mut FlowHandle) -> *mut c_char {
+pub extern "C" fn flow_export_archived_audio(
+    handle: *mut FlowHandle,
+    id: *const c_char,
+    path: *const c_char,
+) -> bool {
     let handle = unsafe { &*handle };
-    let message = handle.last_error.lock().clone();
-    match message {
-        Some(text) => match CString::new(text) {
-            Ok(cstr) => cstr.into_raw(),
-            Err(_) => ptr::null_mut(),
-        },
-        None => ptr::null_mut(),
+
+    let Some(id) = history_id_arg(id) else {
+        set_last_error(handle, "Invalid transcription id");
+        return false;
+    };
+    let path = if path.is_null() {
+        None
+    } else {
+        unsafe { CStr::from_ptr(path) }.to_str().ok()
+    };
+    let Some(path) = path else {
+        set_last_error(handle, "Invalid file path");
+        return false;
+    };
+
+    match handle.archive.export_wav(&id, Path::new(path)) {
+        Ok(()) => {
+            clear_last_error(handle);
+            true
+        }
+        Err(e) => {
+            let message = format!("Failed to export recording: {e}");
+            error!("{message}");
+            set_last_error(handle, message);
+            false
+        }
     }
 }
 
-// ============ Provider Configuration ============
-
-/// Switch completion provider (loads API key from database)
-/// provider: 0 = OpenAI, 1 = Gemini, 2 = OpenRouter
-/// Returns true if provider was switched successfully
+/// Delete the archived recording of a history entry (the entry itself is kept)
+/// Returns true if a recording was deleted
 #[unsafe(no_mangle)]
-pub extern "C" fn flow_switch_completion_provider(handle: *mut FlowHandle, provider: u8) -> bool {
-    let handle = unsafe { &mut *handle };
+pub extern "C" fn flow_delete_archived_audio(handle: *mut FlowHandle, id: *const c_char) -> bool {
+    let handle = unsafe { &*handle };
 
-    let (setting_key, provider_name) = match provider {
-        0 => (SETTING_OPENAI_API_KEY, "openai"),
-        1 => (SETTING_GEMINI_API_KEY, "gemini"),
-        2 => (SETTING_OPENROUTER_API_KEY, "openrouter"),
-        _ => {
-            set_last_error(handle, "Invalid provider");
-            return false;
+    let Some(id) = history_id_arg(id) else {
+        set_last_error(handle, "Invalid transcription id");
+        return false;
+    };
+    match handle.archive.remove(&id) {
+        Ok(removed) => removed,
+        Err(e) => {
+            set_last_error(handle, format!("Failed to delete recording: {e}"));
+            false
         }
+    }
+}
+
+/// Transcribe the archived recording of a history entry again, e.g. after it failed or with a
+/// better model; the entry is updated with the new result
+/// provider: "auto", "openai" or "local", or null for the active provider
+/// model: OpenAI model name, or a built-in or installed Whisper model id with "local"
+/// (null for the provider's configured model)
+/// Returns the processed text (caller must free with flow_free_string), an empty string when
+/// the recording holds no speech, or null on failure (the entry is left unchanged)
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_retranscribe(
+    handle: *mut FlowHandle,
+    id: *const c_char,
+    provider: *const c_char,
+    model: *const c_char,
+) -> *mut c_char {
+    let handle = unsafe { &*handle };
+
+    let Some(id) = history_id_arg(id) else {
+        set_last_error(handle, "Invalid transcription id");
+        return ptr::null_mut();
     };
 
-    // Load the API key from the database
-    let api_key = match handle.storage.get_setting(setting_key) {
-        Ok(Some(key)) if !key.is_empty() => key,
-        Ok(Some(_)) | Ok(None) => {
-            let message = format!("No API key configured for {}", provider_name);
-            error!("{message}");
-            set_last_error(handle, message);
-            return false;
+    match retranscribe(handle, &id, model_id_arg(provider), model_id_arg(model)) {
+        Ok(text) => {
+            clear_last_error(handle);
+            match CString::new(text) {
+                Ok(cstr) => cstr.into_raw(),
+                Err(_) => ptr::null_mut(),
+            }
+        }
+        Err(Error::NoSpeech) => {
+            clear_last_error(handle);
+            CString::default().into_raw()
         }
         Err(e) => {
-            let message = format!("Failed to load API key for {}: {}", provider_name, e);
+            let message = format!("Re-transcription failed: {e}");
             error!("{message}");
             set_last_error(handle, message);
-            return false;
+            ptr::null_mut()
         }
-    };
+    }
+}
 
-    // Save the provider preference
-    if let Err(e) = handle
-        .storage
-        .set_setting(SETTING_COMPLETION_PROVIDER, provider_name)
-    {
-        let message = format!("Failed to save completion provider: {e}");
-        error!("{message}");
-        set_last_error(handle, message);
-        return false;
+// ============ Transcription Queue ============
+
+/// Handle pointer for the queue worker, which is stopped before the handle is freed
+struct WorkerHandle(*const FlowHandle);
+
+// SAFETY: FlowHandle is used from any thread the host calls in on; flow_destroy joins the
+// worker before freeing it
+unsafe impl Send for WorkerHandle {}
+
+impl WorkerHandle {
+    fn get(&self) -> &FlowHandle {
+        unsafe { &*self.0 }
     }
+}
 
-    // Initialize the provider
-    match provider {
-        0 => {
-            handle.transcription =
-                Arc::new(OpenAITranscriptionProvider::new(Some(api_key.clone())));
-            handle.completion = Arc::new(OpenAICompletionProvider::new(Some(api_key)));
-            debug!("Switched completion provider to OpenAI");
-        }
-        1 => {
-            handle.transcription =
-                Arc::new(GeminiTranscriptionProvider::new(Some(api_key.clone())));
-            handle.completion = Arc::new(GeminiCompletionProvider::new(Some(api_key)));
-            debug!("Switched completion provider to Gemini");
-        }
-        2 => {
-            // OpenRouter only handles completion, keep existing transcription provider
-            handle.completion = Arc::new(OpenRouterCompletionProvider::new(Some(api_key)));
-            debug!("Switched completion provider to OpenRouter");
-        }
-        _ => unreachable!(),
+fn start_queue_worker(handle: *mut FlowHandle) {
+    let worker = WorkerHandle(handle);
+    match QueueWorker::spawn(move || process_transcription_queue(worker.get())) {
+        Ok(worker) => *unsafe { &*handle }.queue_worker.lock() = Some(worker),
+        Err(e) => error!("Failed to start transcription queue: {}", e),
     }
+}
 
-    clear_last_error(handle);
-    true
+/// Queue a recording whose transcription failed for lack of network
+fn queue_transcription(handle: &FlowHandle, id: &TranscriptionId, request: &RecordingRequest) {
+    let mut job = QueuedTranscription::new(
+        *id,
+        request.audio.to_vec(),
+        request.sample_rate,
+        request.mode,
+        request.app_name.clone(),
+    );
+    job.next_attempt_at = Utc::now() + retry_delay(1);
+    if let Err(e) = handle.storage.enqueue_transcription(&job) {
+        error!("Failed to queue transcription: {}", e);
+        return;
+    }
+    log_with_time!(
+        "📥 [QUEUE] No network - transcription queued for retry in {:?}",
+        retry_delay(1)
+    );
+    *handle.last_queued.lock() = Some(*id);
+    if let Some(worker) = handle.queue_worker.lock().as_ref() {
+        worker.wake();
+    }
 }
 
-/// Set completion provider with API key (saves both)
-/// provider: 0 = OpenAI, 1 = Gemini, 2 = OpenRouter
-/// api_key: The API key for the provider
-#[unsafe(no_mangle)]
-pub extern "C" fn flow_set_completion_provider(
-    handle: *mut FlowHandle,
-    provider: u8,
-    api_key: *const c_char,
-) -> bool {
-    if api_key.is_null() {
-        return false;
+/// Tell the host how a queued transcription was resolved
+fn notify_queue_result(handle: &FlowHandle, success: bool, result: serde_json::Value) {
+    if let Some((callback, context)) = *handle.queue_callback.lock() {
+        invoke_callback(
+            callback,
+            context as *mut c_void,
+            success,
+            &result.to_string(),
+        );
     }
+}
 
-    let handle = unsafe { &mut *handle };
+/// Retry the queued transcriptions that are due; returns how long until the next one is
+/// Nothing is sent while offline mode is on
+fn process_transcription_queue(handle: &FlowHandle) -> Option<Duration> {
+    if crate::offline::is_offline() {
+        return None;
+    }
 
-    let key = match unsafe { CStr::from_ptr(api_key) }.to_str() {
-        Ok(s) => s.to_string(),
-        Err(_) => return false,
+    match handle.storage.get_due_transcriptions(Utc::now()) {
+        Ok(jobs) => {
+            for job in jobs {
+                process_queued_transcription(handle, job);
+            }
+        }
+        Err(e) => error!("Failed to read transcription queue: {}", e),
+    }
+
+    match handle.storage.next_queued_transcription_at() {
+        Ok(next) => next.map(|at| (at - Utc::now()).to_std().unwrap_or_default()),
+        Err(e) => {
+            error!("Failed to read transcription queue: {}", e);
+            None
+        }
+    }
+}
+
+/// Retry one queued transcription, resolving its history entry once it goes through
+/// Network failures are retried with backoff; anything else fails the entry for good
+fn process_queued_transcription(handle: &FlowHandle, job: QueuedTranscription) {
+    let request = RecordingRequest {
+        audio: &job.audio,
+        sample_rate: job.sample_rate,
+        mode: job.mode,
+        app_name: job.app_name.clone(),
+        live: None,
     };
+    let result = active_transcription_provider(handle).and_then(|provider| {
+        run_transcription(
+            handle,
+            provider.as_ref(),
+            uses_local_transcription(&handle.storage),
+            &request,
+        )
+    });
 
-    match provider {
-        0 => {
-            if let Err(e) = handle.storage.set_setting(SETTING_OPENAI_API_KEY, &key) {
-                let message = format!("Failed to save OpenAI API key: {e}");
-                error!("{message}");
-                set_last_error(handle, message);
-                return false;
-            }
-            if let Err(e) = handle
-                .storage
-                .set_setting(SETTING_COMPLETION_PROVIDER, "openai")
-            {
-                let message = format!("Failed to save completion provider: {e}");
-                error!("{message}");
-                set_last_error(handle, message);
-                return false;
+    let original = handle.storage.get_history_entry(&job.id).ok().flatten();
+    let outcome = match result {
+        Ok(mut history) => {
+            history.id = job.id;
+            if let Some(original) = original {
+                history.app_context = original.app_context;
+                history.created_at = original.created_at;
             }
-            handle.transcription = Arc::new(OpenAITranscriptionProvider::new(Some(key.clone())));
-            handle.completion = Arc::new(OpenAICompletionProvider::new(Some(key)));
-            debug!("Set completion provider to OpenAI");
+            record_transcription(handle, &history);
+            log_with_time!("📤 [QUEUE] Queued transcription {} went through", job.id);
+            Ok(history)
         }
-        1 => {
-            if let Err(e) = handle.storage.set_setting(SETTING_GEMINI_API_KEY, &key) {
-                let message = format!("Failed to save Gemini API key: {e}");
-                error!("{message}");
-                set_last_error(handle, message);
-                return false;
-            }
-            if let Err(e) = handle
-                .storage
-                .set_setting(SETTING_COMPLETION_PROVIDER, "gemini")
-            {
-                let message = format!("Failed to save completion provider: {e}");
-                error!("{message}");
-                set_last_error(handle, message);
-                return false;
+        Err(Error::Network(e)) if job.attempts + 1 < MAX_QUEUE_ATTEMPTS => {
+            let attempts = job.attempts + 1;
+            let delay = retry_delay(attempts + 1);
+            debug!(
+                "Queued transcription {} failed again ({}), retrying in {:?}",
+                job.id, e, delay
+            );
+            if let Err(e) = handle.storage.reschedule_transcription(
+                &job.id,
+                attempts,
+                Utc::now() + delay,
+                &e.to_string(),
+            ) {
+                error!("Failed to reschedule queued transcription: {}", e);
             }
-            handle.transcription = Arc::new(GeminiTranscriptionProvider::new(Some(key.clone())));
-            handle.completion = Arc::new(GeminiCompletionProvider::new(Some(key)));
-            debug!("Set completion provider to Gemini");
+            return;
         }
-        2 => {
-            if let Err(e) = handle.storage.set_setting(SETTING_OPENROUTER_API_KEY, &key) {
-                let message = format!("Failed to save OpenRouter API key: {e}");
-                error!("{message}");
-                set_last_error(handle, message);
-                return false;
-            }
-            if let Err(e) = handle
-                .storage
-                .set_setting(SETTING_COMPLETION_PROVIDER, "openrouter")
-            {
-                let message = format!("Failed to save completion provider: {e}");
-                error!("{message}");
-                set_last_error(handle, message);
-                return false;
-            }
-            // OpenRouter only handles completion, keep transcription provider as-is
-            handle.completion = Arc::new(OpenRouterCompletionProvider::new(Some(key)));
-            debug!("Set completion provider to OpenRouter");
+        Err(e) => {
+            warn!("Giving up on queued transcription {}: {}", job.id, e);
+            let message = format!("Transcription failed: {e}");
+            let duration_ms = estimate_duration_ms(job.audio.len(), job.sample_rate);
+            let mut history = TranscriptionHistoryEntry::failure(message, duration_ms);
+            history.id = job.id;
+            Err(history)
         }
-        _ => return false,
-    }
+    };
 
-    true
+    let (success, history) = match outcome {
+        Ok(history) => (true, history),
+        Err(history) => (false, history),
+    };
+    if let Err(e) = handle.storage.update_history_entry(&history) {
+        error!("Failed to update transcription history: {}", e);
+    }
+    if let Err(e) = handle.storage.delete_queued_transcription(&job.id) {
+        error!("Failed to remove queued transcription: {}", e);
+    }
+    notify_queue_result(
+        handle,
+        success,
+        serde_json::json!({
+            "id": job.id.to_string(),
+            "status": history.status,
+            "text": history.text,
+            "error": history.error,
+        }),
+    );
 }
 
-/// Get the current completion provider name
-/// Returns: 0 = OpenAI, 1 = Gemini, 2 = OpenRouter, 255 = Unknown
+/// Be told when a queued transcription is resolved
+/// A transcription is queued when the provider can't be reached; it is retried in the
+/// background with backoff until it goes through (updating its history entry) or fails for good
+/// callback: called from a background thread with (true, {"id", "status": "success", "text"})
+/// or (false, {"id", "status": "failed", "error"}); null to stop notifications
+/// context: passed through to callback
 #[unsafe(no_mangle)]
-pub extern "C" fn flow_get_completion_provider(handle: *mut FlowHandle) -> u8 {
+pub extern "C" fn flow_set_transcription_queue_callback(
+    handle: *mut FlowHandle,
+    callback: Option<ResultCallback>,
+    context: *mut c_void,
+) {
     let handle = unsafe { &*handle };
-
-    match handle.completion.name() {
-        "OpenAI GPT" => 0,
-        "Gemini" => 1,
-        "OpenRouter" => 2,
-        _ => 255,
-    }
+    *handle.queue_callback.lock() = callback.map(|callback| (callback, context as usize));
 }
 
-/// Helper function to mask an API key for display
-/// Shows the prefix (e.g., "sk-" or "AI") and masks the rest with dots
-fn mask_api_key(key: &str) -> String {
-    if key.is_empty() {
-        return String::new();
-    }
-
-    // For OpenAI keys (sk-...)
-    if key.starts_with("sk-") {
-        return format!("sk-••••••••");
-    }
-    // For Gemini keys (AI...)
-    if key.starts_with("AI") {
-        return format!("AI••••••••");
-    }
-    // For other keys, just show dots
-    "••••••••".to_string()
+/// Number of transcriptions waiting to be retried
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_queued_transcription_count(handle: *mut FlowHandle) -> usize {
+    let handle = unsafe { &*handle };
+    handle
+        .storage
+        .queued_transcription_count()
+        .unwrap_or_else(|e| {
+            error!("Failed to read transcription queue: {}", e);
+            0
+        })
 }
 
-/// Get API key for a specific provider in masked form
-/// provider: 0 = OpenAI, 1 = Gemini, 2 = OpenRouter
-/// Returns null if no key is set, or a masked version like "sk-••••••••"
-/// Caller must free the returned string with flow_free_string
+/// Retry every queued transcription now instead of waiting out the backoff,
+/// e.g. when the host sees the network come back
+/// Returns the number of queued transcriptions
 #[unsafe(no_mangle)]
-pub extern "C" fn flow_get_api_key(handle: *mut FlowHandle, provider: u8) -> *mut c_char {
+pub extern "C" fn flow_process_transcription_queue(handle: *mut FlowHandle) -> usize {
     let handle = unsafe { &*handle };
-
-    let setting_key = match provider {
-        0 => SETTING_OPENAI_API_KEY,
-        1 => SETTING_GEMINI_API_KEY,
-        2 => SETTING_OPENROUTER_API_KEY,
-        _ => return ptr::null_mut(),
-    };
-
-    match handle.storage.get_setting(setting_key) {
-        Ok(Some(key)) => {
-            let masked = mask_api_key(&key);
-            CString::new(masked).unwrap().into_raw()
+    let queued = match handle.storage.retry_queued_transcriptions_now() {
+        Ok(queued) => queued,
+        Err(e) => {
+            set_last_error(handle, format!("Failed to read transcription queue: {e}"));
+            return 0;
         }
-        _ => ptr::null_mut(),
+    };
+    if let Some(worker) = handle.queue_worker.lock().as_ref() {
+        worker.wake();
     }
+    queued
 }
 
-/// Set transcription mode (local or remote)
-/// use_local: true for local Whisper, false for cloud provider
-/// whisper_model: Model selection (only used when use_local = true)
-///   0 = Turbo (~15MB) - quantized, ultra-fast, lowest memory
-///   1 = Fast (~39MB) - fast, lower accuracy
-///   2 = Balanced (~142MB) - good speed/accuracy balance
-///   3 = Quality (~400MB) - great accuracy, still fast [recommended]
-///   4 = Best (~750MB) - best quality available
-/// Returns true on success, false on failure
+// ============ Shortcuts ============
+
+/// Add a voice shortcut
+/// Returns true on success
 #[unsafe(no_mangle)]
-pub extern "C" fn flow_set_transcription_mode(
+pub extern "C" fn flow_add_shortcut(
     handle: *mut FlowHandle,
-    use_local: bool,
-    whisper_model: u8,
+    trigger: *const c_char,
+    replacement: *const c_char,
 ) -> bool {
-    let handle = unsafe { &mut *handle };
-
-    // Save setting to database
-    if let Err(e) = handle.storage.set_setting(
-        SETTING_USE_LOCAL_TRANSCRIPTION,
-        if use_local { "true" } else { "false" },
-    ) {
-        let message = format!("Failed to save transcription mode: {}", e);
-        error!("{}", message);
-        set_last_error(handle, message);
+    if trigger.is_null() || replacement.is_null() {
         return false;
     }
 
-    if use_local {
-        // Local Whisper transcription
-        let model = match whisper_model {
-            0 => WhisperModel::Turbo,
-            1 => WhisperModel::Fast,
-            2 => WhisperModel::Balanced,
+    let handle = unsafe { &*handle };
+
+    let trigger_str = match unsafe { CStr::from_ptr(trigger) }.to_str() {
+        Ok(s) => s.to_string(),
+        Err(_) => return false,
+    };
+
+    let replacement_str = match unsafe { CStr::from_ptr(replacement) }.to_str() {
+        Ok(s) => s.to_string(),
+        Err(_) => return false,
+    };
+
+    let shortcut = Shortcut::new(trigger_str, replacement_str);
+
+    if let Err(e) = handle.storage.save_shortcut(&shortcut) {
+        error!("Failed to save shortcut: {}", e);
+        return false;
+    }
+
+    handle.shortcuts.add_shortcut(shortcut);
+    true
+}
+
+/// Remove a voice shortcut
+/// Returns true on success
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_remove_shortcut(handle: *mut FlowHandle, trigger: *const c_char) -> bool {
+    if trigger.is_null() {
+        return false;
+    }
+
+    let handle = unsafe { &*handle };
+
+    let trigger_str = match unsafe { CStr::from_ptr(trigger) }.to_str() {
+        Ok(s) => s,
+        Err(_) => return false,
+    };
+
+    handle.shortcuts.remove_shortcut(trigger_str);
+    true
+}
+
+/// Get the number of shortcuts
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_shortcut_count(handle: *mut FlowHandle) -> usize {
+    let handle = unsafe { &*handle };
+    handle.shortcuts.count()
+}
+
+// ============ Writing Modes ============
+
+/// Set the writing mode for an app
+/// mode: 0 = Formal, 1 = Casual, 2 = VeryCasual, 3 = Excited
+/// Returns true on success
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_set_app_mode(
+    handle: *mut FlowHandle,
+    app_name: *const c_char,
+    mode: u8,
+) -> bool {
+    if app_name.is_null() {
+        return false;
+    }
+
+    let handle = unsafe { &*handle };
+
+    let app = match unsafe { CStr::from_ptr(app_name) }.to_str() {
+        Ok(s) => s,
+        Err(_) => return false,
+    };
+
+    let writing_mode = match mode {
+        0 => WritingMode::Formal,
+        1 => WritingMode::Casual,
+        2 => WritingMode::VeryCasual,
+        3 => WritingMode::Excited,
+        _ => return false,
+    };
+
+    let mut modes = handle.modes.lock();
+    if let Err(e) = modes.set_mode_with_storage(app, writing_mode, &handle.storage) {
+        error!("Failed to save app mode: {}", e);
+        return false;
+    }
+
+    true
+}
+
+/// Get the writing mode for an app
+/// Returns: 0 = Formal, 1 = Casual, 2 = VeryCasual, 3 = Excited
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_get_app_mode(handle: *mut FlowHandle, app_name: *const c_char) -> u8 {
+    if app_name.is_null() {
+        return 1; // default to casual
+    }
+
+    let handle = unsafe { &*handle };
+
+    let app = match unsafe { CStr::from_ptr(app_name) }.to_str() {
+        Ok(s) => s,
+        Err(_) => return 1,
+    };
+
+    let mut modes = handle.modes.lock();
+    let mode = modes.get_mode_with_storage(app, &handle.storage);
+
+    match mode {
+        WritingMode::Formal => 0,
+        WritingMode::Casual => 1,
+        WritingMode::VeryCasual => 2,
+        WritingMode::Excited => 3,
+    }
+}
+
+// ============ Learning ============
+
+/// Report a user edit to learn from
+/// Returns true on success
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_learn_from_edit(
+    handle: *mut FlowHandle,
+    original: *const c_char,
+    edited: *const c_char,
+) -> bool {
+    if original.is_null() || edited.is_null() {
+        return false;
+    }
+
+    let handle = unsafe { &*handle };
+
+    let original_str = match unsafe { CStr::from_ptr(original) }.to_str() {
+        Ok(s) => s,
+        Err(_) => return false,
+    };
+
+    let edited_str = match unsafe { CStr::from_ptr(edited) }.to_str() {
+        Ok(s) => s,
+        Err(_) => return false,
+    };
+
+    match handle
+        .learning
+        .learn_from_edit(original_str, edited_str, &handle.storage)
+    {
+        Ok(learned) => {
+            debug!("Learned {} corrections from edit", learned.len());
+            true
+        }
+        Err(e) => {
+            error!("Failed to learn from edit: {}", e);
+            false
+        }
+    }
+}
+
+/// Get the number of learned corrections
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_correction_count(handle: *mut FlowHandle) -> usize {
+    let handle = unsafe { &*handle };
+    handle.learning.cache_size()
+}
+
+/// Get all corrections as JSON
+/// Returns JSON array: [{"id": "...", "original": "...", "corrected": "...", "occurrences": N, "confidence": N.N}, ...]
+/// Caller must free the returned string with flow_free_string
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_get_corrections_json(handle: *mut FlowHandle) -> *mut c_char {
+    let handle = unsafe { &*handle };
+
+    let corrections = match handle.storage.get_all_corrections() {
+        Ok(c) => c,
+        Err(e) => {
+            error!("Failed to get corrections: {}", e);
+            return ptr::null_mut();
+        }
+    };
+
+    let json_array: Vec<serde_json::Value> = corrections
+        .into_iter()
+        .map(|c| {
+            serde_json::json!({
+                "id": c.id.to_string(),
+                "original": c.original,
+                "corrected": c.corrected,
+                "occurrences": c.occurrences,
+                "confidence": c.confidence,
+                "source": format!("{:?}", c.source),
+                "created_at": c.created_at.to_rfc3339(),
+                "updated_at": c.updated_at.to_rfc3339(),
+            })
+        })
+        .collect();
+
+    match CString::new(serde_json::to_string(&json_array).unwrap_or_default()) {
+        Ok(cstr) => cstr.into_raw(),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+/// Delete a correction by ID
+/// Returns true if the correction was deleted, false if not found or on error
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_delete_correction(handle: *mut FlowHandle, id: *const c_char) -> bool {
+    if id.is_null() {
+        return false;
+    }
+
+    let handle = unsafe { &*handle };
+
+    let id_str = match unsafe { CStr::from_ptr(id) }.to_str() {
+        Ok(s) => s,
+        Err(_) => return false,
+    };
+
+    let uuid = match uuid::Uuid::parse_str(id_str) {
+        Ok(u) => u,
+        Err(_) => {
+            error!("Invalid UUID: {}", id_str);
+            return false;
+        }
+    };
+
+    // First, get the correction to find the original word (for cache removal)
+    if let Ok(corrections) = handle.storage.get_all_corrections() {
+        if let Some(correction) = corrections.iter().find(|c| c.id == uuid) {
+            let original = correction.original.clone();
+
+            // Delete from storage
+            match handle.storage.delete_correction(&uuid) {
+                Ok(deleted) => {
+                    if deleted {
+                        // Remove from cache
+                        handle.learning.remove_from_cache(&original);
+                        debug!(
+                            "Deleted correction: {} -> {}",
+                            original, correction.corrected
+                        );
+                    }
+                    deleted
+                }
+                Err(e) => {
+                    error!("Failed to delete correction: {}", e);
+                    false
+                }
+            }
+        } else {
+            false
+        }
+    } else {
+        false
+    }
+}
+
+/// Delete all corrections
+/// Returns the number of corrections deleted
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_delete_all_corrections(handle: *mut FlowHandle) -> usize {
+    let handle = unsafe { &*handle };
+
+    match handle.storage.delete_all_corrections() {
+        Ok(count) => {
+            // Clear the cache
+            handle.learning.clear_cache();
+            debug!("Deleted all {} corrections", count);
+            count
+        }
+        Err(e) => {
+            error!("Failed to delete all corrections: {}", e);
+            0
+        }
+    }
+}
+
+/// Validate corrections using AI (async, returns JSON)
+/// Input: JSON array of {"original": "...", "corrected": "..."} pairs
+/// Output: JSON array of {"original": "...", "corrected": "...", "valid": bool, "reason": "..."}
+/// Caller must free the returned string with flow_free_string
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_validate_corrections(
+    _handle: *mut FlowHandle,
+    corrections_json: *const c_char,
+) -> *mut c_char {
+    if corrections_json.is_null() {
+        return ptr::null_mut();
+    }
+
+    let json_str = match unsafe { CStr::from_ptr(corrections_json) }.to_str() {
+        Ok(s) => s,
+        Err(_) => return ptr::null_mut(),
+    };
+
+    // Parse input JSON
+    let pairs: Vec<crate::providers::CorrectionPair> = match serde_json::from_str(json_str) {
+        Ok(p) => p,
+        Err(e) => {
+            error!("Failed to parse corrections JSON: {}", e);
+            return ptr::null_mut();
+        }
+    };
+
+    // Run async validation
+    let runtime = match tokio::runtime::Runtime::new() {
+        Ok(rt) => rt,
+        Err(e) => {
+            error!("Failed to create tokio runtime: {}", e);
+            return ptr::null_mut();
+        }
+    };
+
+    let results = match runtime.block_on(crate::providers::validate_corrections(pairs)) {
+        Ok(r) => r,
+        Err(e) => {
+            error!("Validation failed: {}", e);
+            return ptr::null_mut();
+        }
+    };
+
+    // Return as JSON
+    match CString::new(serde_json::to_string(&results).unwrap_or_default()) {
+        Ok(cstr) => cstr.into_raw(),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+// ============ Stats ============
+
+/// Get total transcription time in minutes
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_total_transcription_minutes(handle: *mut FlowHandle) -> u64 {
+    let handle = unsafe { &*handle };
+    handle
+        .storage
+        .get_total_transcription_time_ms()
+        .unwrap_or(0)
+        / 60000
+}
+
+/// Get total transcription count
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_transcription_count(handle: *mut FlowHandle) -> u64 {
+    let handle = unsafe { &*handle };
+    handle.storage.get_transcription_count().unwrap_or(0)
+}
+
+// ============ Utilities ============
+
+/// Free a string returned by flow functions
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_free_string(s: *mut c_char) {
+    if !s.is_null() {
+        unsafe {
+            drop(CString::from_raw(s));
+        }
+    }
+}
+
+/// Check if the transcription provider is configured
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_is_configured(handle: *mut FlowHandle) -> bool {
+    let handle = unsafe { &*handle };
+
+    // Base10 ("Auto (Cloud)") handles both transcription and completion internally,
+    // so we don't need a separate completion provider configured
+    if handle.transcription.name() == "Auto (Cloud)" {
+        return handle.transcription.is_configured();
+    }
+
+    handle.transcription.is_configured() && handle.completion.is_configured()
+}
+
+// ============ App Tracking ============
+
+/// Set the currently active app (call from Swift when app switches)
+/// Returns the suggested writing mode for the app
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_set_active_app(
+    handle: *mut FlowHandle,
+    app_name: *const c_char,
+    bundle_id: *const c_char,
+    window_title: *const c_char,
+) -> u8 {
+    if app_name.is_null() {
+        return 1; // default to casual
+    }
+
+    let handle = unsafe { &*handle };
+
+    let name = match unsafe { CStr::from_ptr(app_name) }.to_str() {
+        Ok(s) => s.to_string(),
+        Err(_) => return 1,
+    };
+
+    let bid = if bundle_id.is_null() {
+        None
+    } else {
+        unsafe { CStr::from_ptr(bundle_id) }
+            .to_str()
+            .ok()
+            .map(String::from)
+    };
+
+    let title = if window_title.is_null() {
+        None
+    } else {
+        unsafe { CStr::from_ptr(window_title) }
+            .to_str()
+            .ok()
+            .map(String::from)
+    };
+
+    let _context = handle.app_tracker.set_active_app(name, bid, title);
+
+    // return suggested mode
+    match handle.app_tracker.suggested_mode() {
+        WritingMode::Formal => 0,
+        WritingMode::Casual => 1,
+        WritingMode::VeryCasual => 2,
+        WritingMode::Excited => 3,
+    }
+}
+
+/// Get the current app's category
+/// Returns: 0=Email, 1=Slack, 2=Code, 3=Documents, 4=Social, 5=Browser, 6=Terminal, 7=Unknown
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_get_app_category(handle: *mut FlowHandle) -> u8 {
+    let handle = unsafe { &*handle };
+
+    use crate::types::AppCategory;
+    match handle.app_tracker.current_category() {
+        AppCategory::Email => 0,
+        AppCategory::Slack => 1,
+        AppCategory::Code => 2,
+        AppCategory::Documents => 3,
+        AppCategory::Social => 4,
+        AppCategory::Browser => 5,
+        AppCategory::Terminal => 6,
+        AppCategory::Unknown => 7,
+    }
+}
+
+/// Get current app name (caller must free with flow_free_string)
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_get_current_app(handle: *mut FlowHandle) -> *mut c_char {
+    let handle = unsafe { &*handle };
+
+    match handle.app_tracker.current_app() {
+        Some(ctx) => match CString::new(ctx.app_name) {
+            Ok(cstr) => cstr.into_raw(),
+            Err(_) => ptr::null_mut(),
+        },
+        None => ptr::null_mut(),
+    }
+}
+
+// ============ Style Learning ============
+
+/// Report edited text to learn user's style for current app
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_learn_style(handle: *mut FlowHandle, edited_text: *const c_char) -> bool {
+    if edited_text.is_null() {
+        return false;
+    }
+
+    let handle = unsafe { &*handle };
+
+    let text = match unsafe { CStr::from_ptr(edited_text) }.to_str() {
+        Ok(s) => s,
+        Err(_) => return false,
+    };
+
+    let app_name = match handle.app_tracker.current_app() {
+        Some(ctx) => ctx.app_name,
+        None => return false,
+    };
+
+    let mut learner = handle.style_learner.lock();
+    learner.observe_with_storage(&app_name, text, &handle.storage);
+
+    true
+}
+
+/// Get suggested mode based on learned style for current app
+/// Returns: 0=Formal, 1=Casual, 2=VeryCasual, 3=Excited, 255=no suggestion
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_get_style_suggestion(handle: *mut FlowHandle) -> u8 {
+    let handle = unsafe { &*handle };
+
+    let app_name = match handle.app_tracker.current_app() {
+        Some(ctx) => ctx.app_name,
+        None => return 255,
+    };
+
+    let learner = handle.style_learner.lock();
+    match learner.suggest_mode(&app_name) {
+        Some(suggestion) => match suggestion.suggested_mode {
+            WritingMode::Formal => 0,
+            WritingMode::Casual => 1,
+            WritingMode::VeryCasual => 2,
+            WritingMode::Excited => 3,
+        },
+        None => 255,
+    }
+}
+
+// ============ Extended Stats ============
+
+/// Get user stats as JSON (caller must free with flow_free_string)
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_get_stats_json(handle: *mut FlowHandle) -> *mut c_char {
+    let handle = unsafe { &*handle };
+
+    let stats = serde_json::json!({
+        "total_transcriptions": handle.storage.get_transcription_count().unwrap_or(0),
+        "total_duration_ms": handle.storage.get_total_transcription_time_ms().unwrap_or(0),
+        "total_words_dictated": handle.storage.get_total_words_dictated().unwrap_or(0),
+        "shortcut_count": handle.shortcuts.count(),
+        "correction_count": handle.learning.cache_size(),
+    });
+
+    match CString::new(stats.to_string()) {
+        Ok(cstr) => cstr.into_raw(),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+/// Get recent transcriptions as JSON (caller must free with flow_free_string)
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_get_recent_transcriptions_json(
+    handle: *mut FlowHandle,
+    limit: usize,
+) -> *mut c_char {
+    let handle = unsafe { &*handle };
+    let transcriptions = match handle.storage.get_recent_history(limit) {
+        Ok(items) => items,
+        Err(e) => {
+            error!("Failed to load transcriptions: {}", e);
+            return ptr::null_mut();
+        }
+    };
+
+    let summaries: Vec<TranscriptionSummary> = transcriptions
+        .into_iter()
+        .map(|item| TranscriptionSummary {
+            low_confidence_spans: low_confidence_spans(
+                &item.raw_text,
+                &item.segments,
+                LOW_CONFIDENCE_THRESHOLD,
+            ),
+            id: item.id.to_string(),
+            status: item.status,
+            text: item.text,
+            raw_text: item.raw_text,
+            error: item.error,
+            duration_ms: item.duration_ms,
+            created_at: item.created_at.to_rfc3339(),
+            app_name: item.app_context.map(|ctx| ctx.app_name),
+            provider: item.provider,
+            completion_provider: item.completion_provider,
+            confidence: item.confidence,
+            segments: item.segments,
+            has_audio: handle.archive.contains(&item.id),
+        })
+        .collect();
+
+    let json = match serde_json::to_string(&summaries) {
+        Ok(value) => value,
+        Err(e) => {
+            error!("Failed to serialize transcriptions: {}", e);
+            return ptr::null_mut();
+        }
+    };
+
+    match CString::new(json) {
+        Ok(cstr) => cstr.into_raw(),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+/// Get the last error message (caller must free with flow_free_string)
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_get_last_error(handle: *mut FlowHandle) -> *mut c_char {
+    let handle = unsafe { &*handle };
+    let message = handle.last_error.lock().clone();
+    match message {
+        Some(text) => match CString::new(text) {
+            Ok(cstr) => cstr.into_raw(),
+            Err(_) => ptr::null_mut(),
+        },
+        None => ptr::null_mut(),
+    }
+}
+
+// ============ Provider Configuration ============
+
+/// Switch completion provider (loads API key from database)
+/// provider: 0 = OpenAI, 1 = Gemini, 2 = OpenRouter, 3 = Anthropic
+/// Returns true if provider was switched successfully
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_switch_completion_provider(handle: *mut FlowHandle, provider: u8) -> bool {
+    let handle = unsafe { &mut *handle };
+
+    let (setting_key, provider_name) = match provider {
+        0 => (SETTING_OPENAI_API_KEY, "openai"),
+        1 => (SETTING_GEMINI_API_KEY, "gemini"),
+        2 => (SETTING_OPENROUTER_API_KEY, "openrouter"),
+        3 => (SETTING_ANTHROPIC_API_KEY, "anthropic"),
+        _ => {
+            set_last_error(handle, "Invalid provider");
+            return false;
+        }
+    };
+
+    // Load the API key from the database
+    let api_key = match handle.storage.get_setting(setting_key) {
+        Ok(Some(key)) if !key.is_empty() => key,
+        Ok(Some(_)) | Ok(None) => {
+            let message = format!("No API key configured for {}", provider_name);
+            error!("{message}");
+            set_last_error(handle, message);
+            return false;
+        }
+        Err(e) => {
+            let message = format!("Failed to load API key for {}: {}", provider_name, e);
+            error!("{message}");
+            set_last_error(handle, message);
+            return false;
+        }
+    };
+
+    // Save the provider preference
+    if let Err(e) = handle
+        .storage
+        .set_setting(SETTING_COMPLETION_PROVIDER, provider_name)
+    {
+        let message = format!("Failed to save completion provider: {e}");
+        error!("{message}");
+        set_last_error(handle, message);
+        return false;
+    }
+
+    // Initialize the provider
+    match provider {
+        0 => {
+            handle.transcription = Arc::new(openai_transcription_provider(
+                &handle.storage,
+                Some(api_key),
+            ));
+            debug!("Switched completion provider to OpenAI");
+        }
+        1 => {
+            handle.transcription = Arc::new(GeminiTranscriptionProvider::new(Some(api_key)));
+            debug!("Switched completion provider to Gemini");
+        }
+        2 => {
+            // OpenRouter only handles completion, keep existing transcription provider
+            debug!("Switched completion provider to OpenRouter");
+        }
+        3 => {
+            // Anthropic only handles completion, keep existing transcription provider
+            debug!("Switched completion provider to Anthropic");
+        }
+        _ => unreachable!(),
+    }
+    handle.completion = completion_provider(&handle.storage);
+
+    clear_last_error(handle);
+    true
+}
+
+/// Set completion provider with API key (saves both)
+/// provider: 0 = OpenAI, 1 = Gemini, 2 = OpenRouter, 3 = Anthropic
+/// api_key: The API key for the provider
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_set_completion_provider(
+    handle: *mut FlowHandle,
+    provider: u8,
+    api_key: *const c_char,
+) -> bool {
+    if api_key.is_null() {
+        return false;
+    }
+
+    let handle = unsafe { &mut *handle };
+
+    let key = match unsafe { CStr::from_ptr(api_key) }.to_str() {
+        Ok(s) => s.to_string(),
+        Err(_) => return false,
+    };
+
+    match provider {
+        0 => {
+            if let Err(e) = handle.storage.set_setting(SETTING_OPENAI_API_KEY, &key) {
+                let message = format!("Failed to save OpenAI API key: {e}");
+                error!("{message}");
+                set_last_error(handle, message);
+                return false;
+            }
+            if let Err(e) = handle
+                .storage
+                .set_setting(SETTING_COMPLETION_PROVIDER, "openai")
+            {
+                let message = format!("Failed to save completion provider: {e}");
+                error!("{message}");
+                set_last_error(handle, message);
+                return false;
+            }
+            handle.transcription =
+                Arc::new(openai_transcription_provider(&handle.storage, Some(key)));
+            debug!("Set completion provider to OpenAI");
+        }
+        1 => {
+            if let Err(e) = handle.storage.set_setting(SETTING_GEMINI_API_KEY, &key) {
+                let message = format!("Failed to save Gemini API key: {e}");
+                error!("{message}");
+                set_last_error(handle, message);
+                return false;
+            }
+            if let Err(e) = handle
+                .storage
+                .set_setting(SETTING_COMPLETION_PROVIDER, "gemini")
+            {
+                let message = format!("Failed to save completion provider: {e}");
+                error!("{message}");
+                set_last_error(handle, message);
+                return false;
+            }
+            handle.transcription = Arc::new(GeminiTranscriptionProvider::new(Some(key)));
+            debug!("Set completion provider to Gemini");
+        }
+        2 => {
+            if let Err(e) = handle.storage.set_setting(SETTING_OPENROUTER_API_KEY, &key) {
+                let message = format!("Failed to save OpenRouter API key: {e}");
+                error!("{message}");
+                set_last_error(handle, message);
+                return false;
+            }
+            if let Err(e) = handle
+                .storage
+                .set_setting(SETTING_COMPLETION_PROVIDER, "openrouter")
+            {
+                let message = format!("Failed to save completion provider: {e}");
+                error!("{message}");
+                set_last_error(handle, message);
+                return false;
+            }
+            // OpenRouter only handles completion, keep transcription provider as-is
+            debug!("Set completion provider to OpenRouter");
+        }
+        3 => {
+            if let Err(e) = handle.storage.set_setting(SETTING_ANTHROPIC_API_KEY, &key) {
+                let message = format!("Failed to save Anthropic API key: {e}");
+                error!("{message}");
+                set_last_error(handle, message);
+                return false;
+            }
+            if let Err(e) = handle
+                .storage
+                .set_setting(SETTING_COMPLETION_PROVIDER, "anthropic")
+            {
+                let message = format!("Failed to save completion provider: {e}");
+                error!("{message}");
+                set_last_error(handle, message);
+                return false;
+            }
+            // Anthropic only handles completion, keep transcription provider as-is
+            debug!("Set completion provider to Anthropic");
+        }
+        _ => return false,
+    }
+    handle.completion = completion_provider(&handle.storage);
+
+    true
+}
+
+/// Get the current completion provider name
+/// Returns: 0 = OpenAI, 1 = Gemini, 2 = OpenRouter, 3 = Anthropic, 255 = Unknown
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_get_completion_provider(handle: *mut FlowHandle) -> u8 {
+    let handle = unsafe { &*handle };
+
+    match handle.completion.name() {
+        "OpenAI GPT" => 0,
+        "Gemini" => 1,
+        "OpenRouter" => 2,
+        "Anthropic" => 3,
+        _ => 255,
+    }
+}
+
+/// Helper function to mask an API key for display
+/// Shows the prefix (e.g., "sk-" or "AI") and masks the rest with dots
+fn mask_api_key(key: &str) -> String {
+    if key.is_empty() {
+        return String::new();
+    }
+
+    // For OpenAI keys (sk-...)
+    if key.starts_with("sk-") {
+        return format!("sk-••••••••");
+    }
+    // For Gemini keys (AI...)
+    if key.starts_with("AI") {
+        return format!("AI••••••••");
+    }
+    // For other keys, just show dots
+    "••••••••".to_string()
+}
+
+/// Get API key for a specific provider in masked form
+/// provider: 0 = OpenAI, 1 = Gemini, 2 = OpenRouter, 3 = Anthropic
+/// Returns null if no key is set, or a masked version like "sk-••••••••"
+/// Caller must free the returned string with flow_free_string
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_get_api_key(handle: *mut FlowHandle, provider: u8) -> *mut c_char {
+    let handle = unsafe { &*handle };
+
+    let setting_key = match provider {
+        0 => SETTING_OPENAI_API_KEY,
+        1 => SETTING_GEMINI_API_KEY,
+        2 => SETTING_OPENROUTER_API_KEY,
+        3 => SETTING_ANTHROPIC_API_KEY,
+        _ => return ptr::null_mut(),
+    };
+
+    match handle.storage.get_setting(setting_key) {
+        Ok(Some(key)) => {
+            let masked = mask_api_key(&key);
+            CString::new(masked).unwrap().into_raw()
+        }
+        _ => ptr::null_mut(),
+    }
+}
+
+/// Set transcription mode (local or remote)
+/// Local transcriptions are formatted with the completion provider, when one is configured
+/// use_local: true for local Whisper, false for cloud provider
+/// whisper_model: Model selection (only used when use_local = true)
+///   0 = Turbo (~15MB) - quantized, ultra-fast, lowest memory
+///   1 = Fast (~39MB) - fast, lower accuracy
+///   2 = Balanced (~142MB) - good speed/accuracy balance
+///   3 = Quality (~400MB) - great accuracy, still fast [recommended]
+///   4 = Best (~750MB) - best quality available
+/// Returns true on success, false on failure
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_set_transcription_mode(
+    handle: *mut FlowHandle,
+    use_local: bool,
+    whisper_model: u8,
+) -> bool {
+    let handle = unsafe { &mut *handle };
+
+    // Save setting to database
+    if let Err(e) = handle.storage.set_setting(
+        SETTING_USE_LOCAL_TRANSCRIPTION,
+        if use_local { "true" } else { "false" },
+    ) {
+        let message = format!("Failed to save transcription mode: {}", e);
+        error!("{}", message);
+        set_last_error(handle, message);
+        return false;
+    }
+
+    if use_local {
+        // Local Whisper transcription
+        let model = match whisper_model {
+            0 => WhisperModel::Turbo,
+            1 => WhisperModel::Fast,
+            2 => WhisperModel::Balanced,
             3 => WhisperModel::Quality,
             4 => WhisperModel::Best,
             _ => {
-                set_last_error(handle, "Invalid Whisper model selection (0-4)");
+                set_last_error(handle, "Invalid Whisper model selection (0-4)");
+                return false;
+            }
+        };
+
+        // Save model choice using canonical name
+        let model_name = model.as_str();
+        if let Err(e) = handle
+            .storage
+            .set_setting(SETTING_LOCAL_WHISPER_MODEL, model_name)
+        {
+            let message = format!("Failed to save Whisper model: {}", e);
+            error!("{}", message);
+            set_last_error(handle, message);
+            return false;
+        }
+
+        // Get models directory
+        let models_dir = match crate::whisper_models::get_models_dir() {
+            Ok(dir) => dir,
+            Err(e) => {
+                let message = format!("Failed to get models directory: {}", e);
+                error!("{}", message);
+                set_last_error(handle, message);
+                return false;
+            }
+        };
+
+        // Check if model files already exist
+        let spec = model.spec(&models_dir);
+        let files_exist = whisper_models::is_installed(&spec, &models_dir);
+
+        // Set loading flag if this will require downloading
+        if !files_exist {
+            handle.is_model_loading.store(true, Ordering::SeqCst);
+            debug!(
+                "Model files not found, will download (~{}MB)",
+                model.size_mb()
+            );
+        }
+
+        // Create provider
+        let provider = Arc::new(local_whisper_provider(handle, spec, models_dir));
+
+        // Trigger model download/load asynchronously
+        let provider_clone = Arc::clone(&provider);
+        let loading_flag = Arc::clone(&handle.is_model_loading);
+        let should_clear_flag = !files_exist;
+
+        handle.runtime.spawn(async move {
+            if let Err(e) = provider_clone.load_model().await {
+                error!("Failed to load Whisper model: {}", e);
+            }
+            // Clear loading flag when done (only if we set it)
+            if should_clear_flag {
+                loading_flag.store(false, Ordering::SeqCst);
+                debug!("Model loading completed");
+            }
+        });
+
+        handle.transcription = provider;
+        debug!("Enabled local Whisper transcription with {:?} model", model);
+    } else {
+        // Remote transcription - use the cloud transcription provider setting
+        let cloud_provider = match handle
+            .storage
+            .get_setting(SETTING_CLOUD_TRANSCRIPTION_PROVIDER)
+        {
+            Ok(Some(name)) => name,
+            _ => "auto".to_string(), // default to Auto
+        };
+
+        match cloud_provider.as_str() {
+            "openai" => {
+                if let Ok(Some(key)) = handle.storage.get_setting(SETTING_OPENAI_API_KEY) {
+                    handle.transcription =
+                        Arc::new(openai_transcription_provider(&handle.storage, Some(key)));
+                    debug!("Enabled OpenAI remote transcription");
+                } else {
+                    set_last_error(handle, "OpenAI API key not configured");
+                    return false;
+                }
+            }
+            _ => {
+                // Default to Auto (worker handles transcription + completion)
+                handle.transcription = auto_transcription_provider(&handle.storage);
+                debug!("Enabled Auto transcription (worker handles everything)");
+            }
+        }
+    }
+
+    true
+}
+
+/// Get current transcription mode settings
+/// Returns use_local flag and whisper_model (0-4, or 255 for an installed custom model) via out parameters
+/// Returns false on database error, true on success
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_get_transcription_mode(
+    handle: *mut FlowHandle,
+    out_use_local: *mut bool,
+    out_whisper_model: *mut u8,
+) -> bool {
+    let handle = unsafe { &*handle };
+
+    // Read use_local setting from database
+    let use_local = match handle.storage.get_setting(SETTING_USE_LOCAL_TRANSCRIPTION) {
+        Ok(Some(value)) => value == "true",
+        Ok(None) => false, // Default to remote if not set
+        Err(e) => {
+            error!("Failed to read transcription mode: {}", e);
+            return false;
+        }
+    };
+
+    // Read whisper model setting from database
+    let whisper_model = if use_local {
+        match handle.storage.get_setting(SETTING_LOCAL_WHISPER_MODEL) {
+            Ok(Some(model_str)) => {
+                // Convert model name to enum
+                let model = WhisperModel::all()
+                    .iter()
+                    .find(|m| m.as_str() == model_str)
+                    .copied();
+
+                // Convert enum to u8
+                match model {
+                    Some(WhisperModel::Turbo) => 0,
+                    Some(WhisperModel::Fast) => 1,
+                    Some(WhisperModel::Balanced) => 2,
+                    Some(WhisperModel::Quality) => 3,
+                    Some(WhisperModel::Best) => 4,
+                    None => {
+                        let installed = whisper_models::get_models_dir()
+                            .and_then(|dir| whisper_models::read_manifest(&dir, &model_str))
+                            .is_ok_and(|manifest| manifest.is_some());
+                        if installed {
+                            CUSTOM_WHISPER_MODEL
+                        } else {
+                            2 // Default to Balanced
+                        }
+                    }
+                }
+            }
+            Ok(None) => 1, // Default to Balanced
+            Err(e) => {
+                error!("Failed to read Whisper model: {}", e);
                 return false;
             }
-        };
+        }
+    } else {
+        1 // Default value when using remote transcription
+    };
+
+    // Write to out parameters
+    unsafe {
+        *out_use_local = use_local;
+        *out_whisper_model = whisper_model;
+    }
+
+    true
+}
+
+/// Check if a Whisper model is currently being downloaded/initialized
+/// Returns true if model download/initialization is in progress
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_is_model_loading(handle: *mut FlowHandle) -> bool {
+    let handle = unsafe { &*handle };
+    handle.is_model_loading.load(Ordering::SeqCst)
+}
+
+/// Legacy function - prefer flow_set_transcription_mode
+/// Enable local Whisper transcription with Metal + Accelerate acceleration
+/// model: 0=Turbo, 1=Fast, 2=Balanced, 3=Quality, 4=Best
+/// Returns true on success, false on failure
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_enable_local_whisper(handle: *mut FlowHandle, model: u8) -> bool {
+    flow_set_transcription_mode(handle, true, model)
+}
+
+/// Get available Whisper models as JSON (caller must free with flow_free_string)
+/// Returns JSON array with model info including id, name, description, size, and flags
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_get_whisper_models_json() -> *mut c_char {
+    let models: Vec<serde_json::Value> = WhisperModel::all()
+        .iter()
+        .enumerate()
+        .map(|(id, model)| {
+            serde_json::json!({
+                "id": id,
+                "name": model.as_str(),
+                "description": model.description(),
+                "size_mb": model.size_mb(),
+                "is_quantized": model.is_quantized(),
+                "is_distilled": model.is_distilled(),
+            })
+        })
+        .collect();
+
+    let json = serde_json::to_string(&models).unwrap_or_else(|_| "[]".to_string());
+    CString::new(json).unwrap().into_raw()
+}
+
+/// Get all shortcuts as JSON (caller must free with flow_free_string)
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_get_shortcuts_json(handle: *mut FlowHandle) -> *mut c_char {
+    let handle = unsafe { &*handle };
+
+    let shortcuts: Vec<serde_json::Value> = handle
+        .shortcuts
+        .get_all()
+        .iter()
+        .map(|s| {
+            serde_json::json!({
+                "trigger": s.trigger,
+                "replacement": s.replacement,
+                "use_count": s.use_count,
+                "enabled": s.enabled,
+            })
+        })
+        .collect();
+
+    match CString::new(serde_json::to_string(&shortcuts).unwrap_or_default()) {
+        Ok(cstr) => cstr.into_raw(),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+// ============ Contact Categorization ============
+
+/// Get active contact name from Messages.app window
+/// Returns C string with contact name, or null if not available
+/// Caller must free with flow_free_string
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_get_active_messages_contact(handle: *mut FlowHandle) -> *mut c_char {
+    let handle = unsafe { &*handle };
+    clear_last_error(handle);
+
+    match MessagesDetector::get_active_contact() {
+        Ok(Some(name)) => match CString::new(name) {
+            Ok(cstr) => cstr.into_raw(),
+            Err(_) => {
+                set_last_error(handle, "Invalid UTF-8 in contact name");
+                ptr::null_mut()
+            }
+        },
+        Ok(None) => ptr::null_mut(),
+        Err(e) => {
+            set_last_error(handle, format!("Failed to get active contact: {}", e));
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Classify a contact given name and organization
+/// Returns JSON string with category
+/// Caller must free with flow_free_string
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_classify_contact(
+    handle: *mut FlowHandle,
+    name: *const c_char,
+    organization: *const c_char,
+) -> *mut c_char {
+    let handle = unsafe { &*handle };
+    clear_last_error(handle);
+
+    let name_str = unsafe {
+        if name.is_null() {
+            set_last_error(handle, "Name cannot be null");
+            return ptr::null_mut();
+        }
+        match CStr::from_ptr(name).to_str() {
+            Ok(s) => s,
+            Err(_) => {
+                set_last_error(handle, "Invalid UTF-8 in name");
+                return ptr::null_mut();
+            }
+        }
+    };
+
+    let org_str = unsafe {
+        if organization.is_null() {
+            String::new()
+        } else {
+            match CStr::from_ptr(organization).to_str() {
+                Ok(s) => s.to_string(),
+                Err(_) => String::new(),
+            }
+        }
+    };
+
+    let input = ContactInput {
+        name: name_str.to_string(),
+        organization: org_str,
+    };
+
+    let category = handle.contact_classifier.classify(&input);
+
+    let result = serde_json::json!({
+        "name": name_str,
+        "category": category,
+    });
+
+    match CString::new(result.to_string()) {
+        Ok(cstr) => cstr.into_raw(),
+        Err(_) => {
+            set_last_error(handle, "Failed to serialize result");
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Classify multiple contacts from JSON array
+/// Input format: [{"name": "...", "organization": "..."}]
+/// Output format: {"ContactName": "category", ...}
+/// Caller must free with flow_free_string
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_classify_contacts_batch(
+    handle: *mut FlowHandle,
+    contacts_json: *const c_char,
+) -> *mut c_char {
+    let handle = unsafe { &*handle };
+    clear_last_error(handle);
+
+    let json_str = unsafe {
+        if contacts_json.is_null() {
+            set_last_error(handle, "JSON cannot be null");
+            return ptr::null_mut();
+        }
+        match CStr::from_ptr(contacts_json).to_str() {
+            Ok(s) => s,
+            Err(_) => {
+                set_last_error(handle, "Invalid UTF-8 in JSON");
+                return ptr::null_mut();
+            }
+        }
+    };
+
+    let inputs: Vec<ContactInput> = match serde_json::from_str(json_str) {
+        Ok(i) => i,
+        Err(e) => {
+            set_last_error(handle, format!("Invalid JSON: {}", e));
+            return ptr::null_mut();
+        }
+    };
+
+    let result_json = handle.contact_classifier.classify_batch_json(&inputs);
+
+    match CString::new(result_json) {
+        Ok(cstr) => cstr.into_raw(),
+        Err(_) => {
+            set_last_error(handle, "Failed to create result string");
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Record interaction with a contact (updates frequency)
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_record_contact_interaction(handle: *mut FlowHandle, name: *const c_char) {
+    let handle = unsafe { &*handle };
+    clear_last_error(handle);
 
-        // Save model choice using canonical name
-        let model_name = model.as_str();
-        if let Err(e) = handle
-            .storage
-            .set_setting(SETTING_LOCAL_WHISPER_MODEL, model_name)
-        {
-            let message = format!("Failed to save Whisper model: {}", e);
-            error!("{}", message);
-            set_last_error(handle, message);
-            return false;
+    let name_str = unsafe {
+        if name.is_null() {
+            return;
+        }
+        match CStr::from_ptr(name).to_str() {
+            Ok(s) => s,
+            Err(_) => return,
         }
+    };
 
-        // Get models directory
-        let models_dir = match crate::whisper_models::get_models_dir() {
-            Ok(dir) => dir,
-            Err(e) => {
-                let message = format!("Failed to get models directory: {}", e);
-                error!("{}", message);
-                set_last_error(handle, message);
-                return false;
-            }
-        };
+    handle.contact_classifier.record_interaction(name_str);
+}
 
-        // Check if model files already exist
-        let files_exist = check_model_files_exist(model, &models_dir);
+/// Get frequent contacts as JSON array
+/// Returns: [{"name": "...", "category": "...", "frequency": N}, ...]
+/// Caller must free with flow_free_string
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_get_frequent_contacts(handle: *mut FlowHandle, limit: u32) -> *mut c_char {
+    let handle = unsafe { &*handle };
+    clear_last_error(handle);
 
-        // Set loading flag if this will require downloading
-        if !files_exist {
-            handle.is_model_loading.store(true, Ordering::SeqCst);
-            debug!(
-                "Model files not found, will download (~{}MB)",
-                model.size_mb()
+    let contacts = handle
+        .contact_classifier
+        .get_frequent_contacts(limit as usize);
+
+    let result: Vec<serde_json::Value> = contacts
+        .iter()
+        .map(|c| {
+            serde_json::json!({
+                "name": c.name,
+                "category": c.category,
+                "frequency": c.frequency,
+                "organization": c.organization,
+            })
+        })
+        .collect();
+
+    match CString::new(serde_json::to_string(&result).unwrap_or_default()) {
+        Ok(cstr) => cstr.into_raw(),
+        Err(_) => {
+            set_last_error(handle, "Failed to serialize contacts");
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Get suggested writing mode for a contact category
+/// Returns: 0=Formal, 1=Casual, 2=VeryCasual, 3=Excited
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_get_writing_mode_for_category(
+    handle: *mut FlowHandle,
+    category: u32,
+) -> u32 {
+    let handle = unsafe { &*handle };
+    clear_last_error(handle);
+
+    use crate::types::ContactCategory;
+
+    let contact_category = match category {
+        0 => ContactCategory::Professional,
+        1 => ContactCategory::CloseFamily,
+        2 => ContactCategory::CasualPeer,
+        3 => ContactCategory::Partner,
+        4 => ContactCategory::FormalNeutral,
+        _ => ContactCategory::FormalNeutral,
+    };
+
+    let writing_mode = contact_category.suggested_writing_mode();
+
+    match writing_mode {
+        WritingMode::Formal => 0,
+        WritingMode::Casual => 1,
+        WritingMode::VeryCasual => 2,
+        WritingMode::Excited => 3,
+    }
+}
+
+// ============ Cloud Transcription Provider ============
+
+/// Set cloud transcription provider (saves preference)
+/// provider: 0 = OpenAI, 1 = Auto (default)
+/// Returns true on success
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_set_cloud_transcription_provider(
+    handle: *mut FlowHandle,
+    provider: u8,
+) -> bool {
+    let handle = unsafe { &mut *handle };
+
+    let provider_name = match provider {
+        0 => "openai",
+        1 => "auto",
+        _ => {
+            set_last_error(
+                handle,
+                "Invalid cloud transcription provider (0=OpenAI, 1=Auto)",
             );
+            return false;
         }
+    };
 
-        // Create provider
-        let provider = Arc::new(LocalWhisperTranscriptionProvider::new(model, models_dir));
+    // Save the provider preference
+    if let Err(e) = handle
+        .storage
+        .set_setting(SETTING_CLOUD_TRANSCRIPTION_PROVIDER, provider_name)
+    {
+        let message = format!("Failed to save cloud transcription provider: {e}");
+        error!("{message}");
+        set_last_error(handle, message);
+        return false;
+    }
 
-        // Trigger model download/load asynchronously
-        let provider_clone = Arc::clone(&provider);
-        let loading_flag = Arc::clone(&handle.is_model_loading);
-        let should_clear_flag = !files_exist;
+    clear_last_error(handle);
+    true
+}
 
-        handle.runtime.spawn(async move {
-            if let Err(e) = provider_clone.load_model().await {
-                error!("Failed to load Whisper model: {}", e);
-            }
-            // Clear loading flag when done (only if we set it)
-            if should_clear_flag {
-                loading_flag.store(false, Ordering::SeqCst);
-                debug!("Model loading completed");
-            }
-        });
+/// Get the current cloud transcription provider
+/// Returns: 0 = OpenAI, 1 = Auto (default)
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_get_cloud_transcription_provider(handle: *mut FlowHandle) -> u8 {
+    let handle = unsafe { &*handle };
 
-        handle.transcription = provider;
-        debug!("Enabled local Whisper transcription with {:?} model", model);
+    match handle
+        .storage
+        .get_setting(SETTING_CLOUD_TRANSCRIPTION_PROVIDER)
+    {
+        Ok(Some(name)) => match name.as_str() {
+            "openai" => 0,
+            "auto" => 1,
+            _ => 1, // default to Auto
+        },
+        _ => 1, // default to Auto
+    }
+}
+
+// ============ OpenAI-Compatible Endpoint ============
+
+/// Rebuild any active OpenAI providers after their endpoint settings change
+fn refresh_openai_providers(handle: &mut FlowHandle) {
+    let api_key = non_empty_setting(&handle.storage, SETTING_OPENAI_API_KEY);
+
+    // The OpenAI provider may be selected or a fallback
+    handle.completion = completion_provider(&handle.storage);
+
+    match handle.transcription.name() {
+        "OpenAI Whisper" => {
+            handle.transcription =
+                Arc::new(openai_transcription_provider(&handle.storage, api_key));
+        }
+        // Auto mode chain includes OpenAI Whisper as its fallback
+        "Fallback" => handle.transcription = auto_transcription_provider(&handle.storage),
+        _ => {}
+    }
+}
+
+/// Set the base URL for the OpenAI providers, for self-hosted OpenAI-compatible servers
+/// (e.g. "http://localhost:11434/v1" for Ollama, "http://localhost:8080/v1" for whisper.cpp)
+/// base_url: null or empty to restore https://api.openai.com/v1
+/// Servers that don't check API keys accept any placeholder key
+/// Returns true on success
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_set_openai_base_url(
+    handle: *mut FlowHandle,
+    base_url: *const c_char,
+) -> bool {
+    let handle = unsafe { &mut *handle };
+
+    let url = if base_url.is_null() {
+        String::new()
     } else {
-        // Remote transcription - use the cloud transcription provider setting
-        let cloud_provider = match handle
-            .storage
-            .get_setting(SETTING_CLOUD_TRANSCRIPTION_PROVIDER)
-        {
-            Ok(Some(name)) => name,
-            _ => "auto".to_string(), // default to Auto
-        };
+        match unsafe { CStr::from_ptr(base_url) }.to_str() {
+            Ok(s) => s.trim().trim_end_matches('/').to_string(),
+            Err(_) => {
+                set_last_error(handle, "Invalid base URL");
+                return false;
+            }
+        }
+    };
 
-        match cloud_provider.as_str() {
-            "openai" => {
-                if let Ok(Some(key)) = handle.storage.get_setting(SETTING_OPENAI_API_KEY) {
-                    handle.transcription = Arc::new(OpenAITranscriptionProvider::new(Some(key)));
-                    debug!("Enabled OpenAI remote transcription");
-                } else {
-                    set_last_error(handle, "OpenAI API key not configured");
-                    return false;
-                }
+    if !url.is_empty() && !url.starts_with("http://") && !url.starts_with("https://") {
+        set_last_error(handle, "Base URL must start with http:// or https://");
+        return false;
+    }
+
+    if let Err(e) = handle.storage.set_setting(SETTING_OPENAI_BASE_URL, &url) {
+        let message = format!("Failed to save OpenAI base URL: {e}");
+        error!("{message}");
+        set_last_error(handle, message);
+        return false;
+    }
+
+    refresh_openai_providers(handle);
+    debug!("Set OpenAI base URL to {:?}", url);
+
+    clear_last_error(handle);
+    true
+}
+
+/// Get the custom base URL for the OpenAI providers
+/// Returns null when using https://api.openai.com/v1
+/// Caller must free the returned string with flow_free_string
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_get_openai_base_url(handle: *mut FlowHandle) -> *mut c_char {
+    let handle = unsafe { &*handle };
+
+    match non_empty_setting(&handle.storage, SETTING_OPENAI_BASE_URL) {
+        Some(url) => match CString::new(url) {
+            Ok(cstr) => cstr.into_raw(),
+            Err(_) => ptr::null_mut(),
+        },
+        None => ptr::null_mut(),
+    }
+}
+
+/// Set the spoken language hint for transcription
+/// language: ISO 639-1 code or English name (e.g. "de", "german"), or null/"auto" to auto-detect
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_set_transcription_language(
+    handle: *mut FlowHandle,
+    language: *const c_char,
+) -> bool {
+    let handle = unsafe { &mut *handle };
+
+    let hint = if language.is_null() {
+        "auto"
+    } else {
+        match unsafe { CStr::from_ptr(language) }.to_str() {
+            Ok(s) => s.trim(),
+            Err(_) => {
+                set_last_error(handle, "Invalid language");
+                return false;
             }
-            _ => {
-                // Default to Auto (worker handles transcription + completion)
-                handle.transcription = Arc::new(Base10TranscriptionProvider::new(None));
-                debug!("Enabled Auto transcription (worker handles everything)");
+        }
+    };
+
+    let code = if hint.is_empty() || hint.eq_ignore_ascii_case("auto") {
+        "auto"
+    } else {
+        match whisper_language_code(hint) {
+            Some(code) => code,
+            None => {
+                set_last_error(handle, format!("Unsupported language: {hint}"));
+                return false;
             }
         }
+    };
+
+    if let Err(e) = handle
+        .storage
+        .set_setting(SETTING_TRANSCRIPTION_LANGUAGE, code)
+    {
+        let message = format!("Failed to save transcription language: {e}");
+        error!("{message}");
+        set_last_error(handle, message);
+        return false;
     }
 
+    debug!("Set transcription language to {}", code);
+    clear_last_error(handle);
     true
 }
 
-/// Get current transcription mode settings
-/// Returns use_local flag and whisper_model (0-4) via out parameters
-/// Returns false on database error, true on success
+/// Get the spoken language hint for transcription
+/// Returns the ISO 639-1 code, or null when auto-detecting
+/// Caller must free the returned string with flow_free_string
 #[unsafe(no_mangle)]
-pub extern "C" fn flow_get_transcription_mode(
-    handle: *mut FlowHandle,
-    out_use_local: *mut bool,
-    out_whisper_model: *mut u8,
-) -> bool {
+pub extern "C" fn flow_get_transcription_language(handle: *mut FlowHandle) -> *mut c_char {
     let handle = unsafe { &*handle };
 
-    // Read use_local setting from database
-    let use_local = match handle.storage.get_setting(SETTING_USE_LOCAL_TRANSCRIPTION) {
-        Ok(Some(value)) => value == "true",
-        Ok(None) => false, // Default to remote if not set
-        Err(e) => {
-            error!("Failed to read transcription mode: {}", e);
-            return false;
-        }
-    };
-
-    // Read whisper model setting from database
-    let whisper_model = if use_local {
-        match handle.storage.get_setting(SETTING_LOCAL_WHISPER_MODEL) {
-            Ok(Some(model_str)) => {
-                // Convert model name to enum
-                let model = WhisperModel::all()
-                    .iter()
-                    .find(|m| m.as_str() == model_str)
-                    .copied()
-                    .unwrap_or(WhisperModel::Balanced); // Default to Balanced
+    match non_empty_setting(&handle.storage, SETTING_TRANSCRIPTION_LANGUAGE)
+        .filter(|language| language != "auto")
+    {
+        Some(language) => match CString::new(language) {
+            Ok(cstr) => cstr.into_raw(),
+            Err(_) => ptr::null_mut(),
+        },
+        None => ptr::null_mut(),
+    }
+}
 
-                // Convert enum to u8
-                match model {
-                    WhisperModel::Turbo => 0,
-                    WhisperModel::Fast => 1,
-                    WhisperModel::Balanced => 2,
-                    WhisperModel::Quality => 3,
-                    WhisperModel::Best => 4,
-                }
-            }
-            Ok(None) => 1, // Default to Balanced
-            Err(e) => {
-                error!("Failed to read Whisper model: {}", e);
-                return false;
-            }
-        }
-    } else {
-        1 // Default value when using remote transcription
-    };
+/// Enable or disable translating speech to English instead of transcribing it
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_set_translate_to_english(handle: *mut FlowHandle, enabled: bool) -> bool {
+    let handle = unsafe { &mut *handle };
 
-    // Write to out parameters
-    unsafe {
-        *out_use_local = use_local;
-        *out_whisper_model = whisper_model;
+    let value = if enabled { "true" } else { "false" };
+    if let Err(e) = handle
+        .storage
+        .set_setting(SETTING_TRANSLATE_TO_ENGLISH, value)
+    {
+        let message = format!("Failed to save translate setting: {e}");
+        error!("{message}");
+        set_last_error(handle, message);
+        return false;
     }
 
+    clear_last_error(handle);
     true
 }
 
-/// Check if a Whisper model is currently being downloaded/initialized
-/// Returns true if model download/initialization is in progress
+/// Check whether speech is translated to English
 #[unsafe(no_mangle)]
-pub extern "C" fn flow_is_model_loading(handle: *mut FlowHandle) -> bool {
+pub extern "C" fn flow_is_translate_to_english(handle: *mut FlowHandle) -> bool {
     let handle = unsafe { &*handle };
-    handle.is_model_loading.load(Ordering::SeqCst)
+
+    handle
+        .storage
+        .get_setting(SETTING_TRANSLATE_TO_ENGLISH)
+        .ok()
+        .flatten()
+        .is_some_and(|v| v == "true")
 }
 
-/// Legacy function - prefer flow_set_transcription_mode
-/// Enable local Whisper transcription with Metal + Accelerate acceleration
-/// model: 0=Turbo, 1=Fast, 2=Balanced, 3=Quality, 4=Best
-/// Returns true on success, false on failure
+/// Set the beam size for local Whisper decoding (1 = greedy, up to 8)
+/// Larger beams are more accurate on hard audio but proportionally slower
+/// Takes effect the next time local transcription is enabled
 #[unsafe(no_mangle)]
-pub extern "C" fn flow_enable_local_whisper(handle: *mut FlowHandle, model: u8) -> bool {
-    flow_set_transcription_mode(handle, true, model)
+pub extern "C" fn flow_set_whisper_beam_size(handle: *mut FlowHandle, beam_size: u8) -> bool {
+    let handle = unsafe { &mut *handle };
+
+    if beam_size == 0 || beam_size > MAX_WHISPER_BEAM_SIZE {
+        set_last_error(
+            handle,
+            format!("Invalid beam size (1-{})", MAX_WHISPER_BEAM_SIZE),
+        );
+        return false;
+    }
+
+    if let Err(e) = handle
+        .storage
+        .set_setting(SETTING_WHISPER_BEAM_SIZE, &beam_size.to_string())
+    {
+        let message = format!("Failed to save beam size: {e}");
+        error!("{message}");
+        set_last_error(handle, message);
+        return false;
+    }
+
+    debug!("Set Whisper beam size to {}", beam_size);
+    clear_last_error(handle);
+    true
 }
 
-/// Get available Whisper models as JSON (caller must free with flow_free_string)
-/// Returns JSON array with model info including id, name, description, size, and flags
+/// Get the beam size for local Whisper decoding
 #[unsafe(no_mangle)]
-pub extern "C" fn flow_get_whisper_models_json() -> *mut c_char {
-    let models: Vec<serde_json::Value> = WhisperModel::all()
-        .iter()
-        .enumerate()
-        .map(|(id, model)| {
-            serde_json::json!({
-                "id": id,
-                "name": model.as_str(),
-                "description": model.description(),
-                "size_mb": model.size_mb(),
-                "is_quantized": model.is_quantized(),
-                "is_distilled": model.is_distilled(),
-            })
-        })
-        .collect();
+pub extern "C" fn flow_get_whisper_beam_size(handle: *mut FlowHandle) -> u8 {
+    let handle = unsafe { &*handle };
 
-    let json = serde_json::to_string(&models).unwrap_or_else(|_| "[]".to_string());
-    CString::new(json).unwrap().into_raw()
+    non_empty_setting(&handle.storage, SETTING_WHISPER_BEAM_SIZE)
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(1)
 }
 
-/// Get all shortcuts as JSON (caller must free with flow_free_string)
+// ============ Custom Vocabulary ============
+
+/// Add a term (name, product, jargon) to the custom vocabulary used to prompt transcription
+/// Returns true on success
 #[unsafe(no_mangle)]
-pub extern "C" fn flow_get_shortcuts_json(handle: *mut FlowHandle) -> *mut c_char {
+pub extern "C" fn flow_add_vocabulary_term(handle: *mut FlowHandle, term: *const c_char) -> bool {
     let handle = unsafe { &*handle };
 
-    let shortcuts: Vec<serde_json::Value> = handle
-        .shortcuts
-        .get_all()
-        .iter()
-        .map(|s| {
-            serde_json::json!({
-                "trigger": s.trigger,
-                "replacement": s.replacement,
-                "use_count": s.use_count,
-                "enabled": s.enabled,
-            })
-        })
-        .collect();
+    let term = if term.is_null() {
+        ""
+    } else {
+        match unsafe { CStr::from_ptr(term) }.to_str() {
+            Ok(s) => s.trim(),
+            Err(_) => {
+                set_last_error(handle, "Invalid vocabulary term");
+                return false;
+            }
+        }
+    };
 
-    match CString::new(serde_json::to_string(&shortcuts).unwrap_or_default()) {
-        Ok(cstr) => cstr.into_raw(),
-        Err(_) => ptr::null_mut(),
+    if term.is_empty() {
+        set_last_error(handle, "Vocabulary term is empty");
+        return false;
+    }
+
+    if let Err(e) = handle.storage.add_vocabulary_term(term) {
+        let message = format!("Failed to save vocabulary term: {e}");
+        error!("{message}");
+        set_last_error(handle, message);
+        return false;
     }
+
+    clear_last_error(handle);
+    true
 }
 
-// ============ Contact Categorization ============
+/// Remove a term from the custom vocabulary (case-insensitive)
+/// Returns true if the term was removed
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_remove_vocabulary_term(
+    handle: *mut FlowHandle,
+    term: *const c_char,
+) -> bool {
+    if term.is_null() {
+        return false;
+    }
 
-/// Get active contact name from Messages.app window
-/// Returns C string with contact name, or null if not available
+    let handle = unsafe { &*handle };
+
+    let term = match unsafe { CStr::from_ptr(term) }.to_str() {
+        Ok(s) => s.trim(),
+        Err(_) => return false,
+    };
+
+    match handle.storage.remove_vocabulary_term(term) {
+        Ok(removed) => {
+            clear_last_error(handle);
+            removed
+        }
+        Err(e) => {
+            let message = format!("Failed to remove vocabulary term: {e}");
+            error!("{message}");
+            set_last_error(handle, message);
+            false
+        }
+    }
+}
+
+/// Get the custom vocabulary as a JSON array of strings
 /// Caller must free with flow_free_string
 #[unsafe(no_mangle)]
-pub extern "C" fn flow_get_active_messages_contact(handle: *mut FlowHandle) -> *mut c_char {
+pub extern "C" fn flow_get_vocabulary_terms(handle: *mut FlowHandle) -> *mut c_char {
     let handle = unsafe { &*handle };
-    clear_last_error(handle);
 
-    match MessagesDetector::get_active_contact() {
-        Ok(Some(name)) => match CString::new(name) {
-            Ok(cstr) => cstr.into_raw(),
-            Err(_) => {
-                set_last_error(handle, "Invalid UTF-8 in contact name");
-                ptr::null_mut()
-            }
-        },
-        Ok(None) => ptr::null_mut(),
+    let terms = match handle.storage.get_vocabulary_terms() {
+        Ok(terms) => terms,
         Err(e) => {
-            set_last_error(handle, format!("Failed to get active contact: {}", e));
-            ptr::null_mut()
+            set_last_error(handle, format!("Failed to load vocabulary: {e}"));
+            return ptr::null_mut();
         }
+    };
+
+    clear_last_error(handle);
+    match CString::new(serde_json::to_string(&terms).unwrap_or_default()) {
+        Ok(cstr) => cstr.into_raw(),
+        Err(_) => ptr::null_mut(),
     }
 }
 
-/// Classify a contact given name and organization
-/// Returns JSON string with category
-/// Caller must free with flow_free_string
+// ============ Whisper Model Registry ============
+
+/// Read a model id argument
+fn model_id_arg<'a>(model_id: *const c_char) -> Option<&'a str> {
+    if model_id.is_null() {
+        return None;
+    }
+    unsafe { CStr::from_ptr(model_id) }
+        .to_str()
+        .ok()
+        .map(str::trim)
+        .filter(|id| !id.is_empty())
+}
+
+/// Install a Whisper checkpoint in the background: "owner/name" or "owner/name@revision" downloads
+/// from Hugging Face, an absolute path imports a local directory. Reinstalls if already present
+/// Poll flow_get_model_install_progress_json for progress, then select it with flow_use_whisper_model
+/// Returns the model id (caller must free with flow_free_string), or null if the source is invalid
+/// or another install is running
 #[unsafe(no_mangle)]
-pub extern "C" fn flow_classify_contact(
+pub extern "C" fn flow_install_whisper_model(
     handle: *mut FlowHandle,
-    name: *const c_char,
-    organization: *const c_char,
+    source: *const c_char,
 ) -> *mut c_char {
     let handle = unsafe { &*handle };
-    clear_last_error(handle);
 
-    let name_str = unsafe {
-        if name.is_null() {
-            set_last_error(handle, "Name cannot be null");
-            return ptr::null_mut();
-        }
-        match CStr::from_ptr(name).to_str() {
+    let source = if source.is_null() {
+        ""
+    } else {
+        match unsafe { CStr::from_ptr(source) }.to_str() {
             Ok(s) => s,
             Err(_) => {
-                set_last_error(handle, "Invalid UTF-8 in name");
+                set_last_error(handle, "Invalid model source");
                 return ptr::null_mut();
             }
         }
     };
 
-    let org_str = unsafe {
-        if organization.is_null() {
-            String::new()
-        } else {
-            match CStr::from_ptr(organization).to_str() {
-                Ok(s) => s.to_string(),
-                Err(_) => String::new(),
-            }
+    let spec = match ModelSpec::parse(source) {
+        Ok(spec) => spec,
+        Err(e) => {
+            set_last_error(handle, e.to_string());
+            return ptr::null_mut();
         }
     };
 
-    let input = ContactInput {
-        name: name_str.to_string(),
-        organization: org_str,
+    let models_dir = match whisper_models::get_models_dir() {
+        Ok(dir) => dir,
+        Err(e) => {
+            set_last_error(handle, format!("Failed to get models directory: {e}"));
+            return ptr::null_mut();
+        }
     };
 
-    let category = handle.contact_classifier.classify(&input);
+    if !handle.model_download.try_begin(&spec.id) {
+        set_last_error(handle, "Another model install is in progress");
+        return ptr::null_mut();
+    }
 
-    let result = serde_json::json!({
-        "name": name_str,
-        "category": category,
+    let model_id = spec.id.clone();
+    let progress = Arc::clone(&handle.model_download);
+    let loading_flag = Arc::clone(&handle.is_model_loading);
+    loading_flag.store(true, Ordering::SeqCst);
+
+    handle.runtime.spawn(async move {
+        if let Err(e) = whisper_models::install(&spec, &models_dir, &progress).await {
+            error!("Failed to install Whisper model {}: {}", spec.id, e);
+        }
+        loading_flag.store(false, Ordering::SeqCst);
     });
 
-    match CString::new(result.to_string()) {
+    clear_last_error(handle);
+    match CString::new(model_id) {
+        Ok(cstr) => cstr.into_raw(),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+/// Get progress of the current or last model install as JSON
+/// {"model_id", "state" (idle/downloading/verifying/installed/failed), "downloaded_bytes", "total_bytes", "error"}
+/// Caller must free with flow_free_string
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_get_model_install_progress_json(handle: *mut FlowHandle) -> *mut c_char {
+    let handle = unsafe { &*handle };
+
+    let json = serde_json::to_string(&handle.model_download.snapshot()).unwrap_or_default();
+    match CString::new(json) {
+        Ok(cstr) => cstr.into_raw(),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+/// Get installed Whisper models as JSON, with their source, files, checksums and size on disk
+/// Caller must free with flow_free_string; returns null on error
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_get_installed_whisper_models_json(handle: *mut FlowHandle) -> *mut c_char {
+    let handle = unsafe { &*handle };
+
+    let models = match whisper_models::get_models_dir()
+        .and_then(|dir| whisper_models::list_installed(&dir))
+    {
+        Ok(models) => models,
+        Err(e) => {
+            set_last_error(handle, format!("Failed to list models: {e}"));
+            return ptr::null_mut();
+        }
+    };
+
+    clear_last_error(handle);
+    match CString::new(serde_json::to_string(&models).unwrap_or_default()) {
         Ok(cstr) => cstr.into_raw(),
-        Err(_) => {
-            set_last_error(handle, "Failed to serialize result");
-            ptr::null_mut()
-        }
+        Err(_) => ptr::null_mut(),
     }
 }
 
-/// Classify multiple contacts from JSON array
-/// Input format: [{"name": "...", "organization": "..."}]
-/// Output format: {"ContactName": "category", ...}
-/// Caller must free with flow_free_string
+/// Delete an installed Whisper model and any partial download of it
+/// Returns true if the model was removed
 #[unsafe(no_mangle)]
-pub extern "C" fn flow_classify_contacts_batch(
+pub extern "C" fn flow_delete_whisper_model(
     handle: *mut FlowHandle,
-    contacts_json: *const c_char,
-) -> *mut c_char {
+    model_id: *const c_char,
+) -> bool {
     let handle = unsafe { &*handle };
-    clear_last_error(handle);
 
-    let json_str = unsafe {
-        if contacts_json.is_null() {
-            set_last_error(handle, "JSON cannot be null");
-            return ptr::null_mut();
-        }
-        match CStr::from_ptr(contacts_json).to_str() {
-            Ok(s) => s,
-            Err(_) => {
-                set_last_error(handle, "Invalid UTF-8 in JSON");
-                return ptr::null_mut();
-            }
-        }
+    let Some(model_id) = model_id_arg(model_id) else {
+        set_last_error(handle, "Invalid model id");
+        return false;
     };
 
-    let inputs: Vec<ContactInput> = match serde_json::from_str(json_str) {
-        Ok(i) => i,
+    let progress = handle.model_download.snapshot();
+    if handle.model_download.is_active() && progress.model_id.as_deref() == Some(model_id) {
+        set_last_error(handle, "Model is being installed");
+        return false;
+    }
+
+    match whisper_models::get_models_dir()
+        .and_then(|dir| whisper_models::delete_model(&dir, model_id))
+    {
+        Ok(removed) => {
+            clear_last_error(handle);
+            removed
+        }
         Err(e) => {
-            set_last_error(handle, format!("Invalid JSON: {}", e));
-            return ptr::null_mut();
+            let message = format!("Failed to delete model: {e}");
+            error!("{message}");
+            set_last_error(handle, message);
+            false
         }
+    }
+}
+
+/// Re-hash an installed Whisper model's files against the SHA-256 recorded at install
+/// Returns true if every file matches
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_verify_whisper_model(
+    handle: *mut FlowHandle,
+    model_id: *const c_char,
+) -> bool {
+    let handle = unsafe { &*handle };
+
+    let Some(model_id) = model_id_arg(model_id) else {
+        set_last_error(handle, "Invalid model id");
+        return false;
     };
 
-    let result_json = handle.contact_classifier.classify_batch_json(&inputs);
+    let result = whisper_models::get_models_dir().and_then(|dir| {
+        handle
+            .runtime
+            .block_on(whisper_models::verify_model(&dir, model_id))
+    });
 
-    match CString::new(result_json) {
-        Ok(cstr) => cstr.into_raw(),
-        Err(_) => {
-            set_last_error(handle, "Failed to create result string");
-            ptr::null_mut()
+    match result {
+        Ok(()) => {
+            clear_last_error(handle);
+            true
+        }
+        Err(e) => {
+            set_last_error(handle, e.to_string());
+            false
         }
     }
 }
 
-/// Record interaction with a contact (updates frequency)
+/// Switch to local transcription with an installed model (custom or built-in)
+/// Returns true on success
 #[unsafe(no_mangle)]
-pub extern "C" fn flow_record_contact_interaction(handle: *mut FlowHandle, name: *const c_char) {
-    let handle = unsafe { &*handle };
-    clear_last_error(handle);
+pub extern "C" fn flow_use_whisper_model(handle: *mut FlowHandle, model_id: *const c_char) -> bool {
+    let handle = unsafe { &mut *handle };
 
-    let name_str = unsafe {
-        if name.is_null() {
-            return;
+    let Some(model_id) = model_id_arg(model_id) else {
+        set_last_error(handle, "Invalid model id");
+        return false;
+    };
+
+    let models_dir = match whisper_models::get_models_dir() {
+        Ok(dir) => dir,
+        Err(e) => {
+            set_last_error(handle, format!("Failed to get models directory: {e}"));
+            return false;
         }
-        match CStr::from_ptr(name).to_str() {
-            Ok(s) => s,
-            Err(_) => return,
+    };
+
+    let spec = match whisper_models::read_manifest(&models_dir, model_id)
+        .and_then(|manifest| manifest.map(|m| m.spec()).transpose())
+    {
+        Ok(Some(spec)) => spec,
+        Ok(None) => {
+            set_last_error(handle, format!("Model {model_id} is not installed"));
+            return false;
+        }
+        Err(e) => {
+            set_last_error(handle, e.to_string());
+            return false;
         }
     };
 
-    handle.contact_classifier.record_interaction(name_str);
+    let saved = handle
+        .storage
+        .set_setting(SETTING_USE_LOCAL_TRANSCRIPTION, "true")
+        .and_then(|_| {
+            handle
+                .storage
+                .set_setting(SETTING_LOCAL_WHISPER_MODEL, model_id)
+        });
+    if let Err(e) = saved {
+        let message = format!("Failed to save Whisper model: {e}");
+        error!("{message}");
+        set_last_error(handle, message);
+        return false;
+    }
+
+    let provider = Arc::new(local_whisper_provider(handle, spec, models_dir));
+
+    // Warm the model up in the background
+    let provider_clone = Arc::clone(&provider);
+    handle.runtime.spawn(async move {
+        if let Err(e) = provider_clone.load_model().await {
+            error!("Failed to load Whisper model: {}", e);
+        }
+    });
+
+    handle.transcription = provider;
+    debug!(
+        "Enabled local Whisper transcription with model {}",
+        model_id
+    );
+    clear_last_error(handle);
+    true
 }
 
-/// Get frequent contacts as JSON array
-/// Returns: [{"name": "...", "category": "...", "frequency": N}, ...]
-/// Caller must free with flow_free_string
+// ============ Offline Mode ============
+
+/// Turn offline mode on or off (persisted)
+/// While on, no network request is made: transcription uses local Whisper with the selected
+/// model (which must already be installed) and text is formatted on-device
+/// Returns true on success
 #[unsafe(no_mangle)]
-pub extern "C" fn flow_get_frequent_contacts(handle: *mut FlowHandle, limit: u32) -> *mut c_char {
+pub extern "C" fn flow_set_offline_mode(handle: *mut FlowHandle, enabled: bool) -> bool {
     let handle = unsafe { &*handle };
-    clear_last_error(handle);
-
-    let contacts = handle
-        .contact_classifier
-        .get_frequent_contacts(limit as usize);
 
-    let result: Vec<serde_json::Value> = contacts
-        .iter()
-        .map(|c| {
-            serde_json::json!({
-                "name": c.name,
-                "category": c.category,
-                "frequency": c.frequency,
-                "organization": c.organization,
-            })
-        })
-        .collect();
+    if let Err(e) = handle
+        .storage
+        .set_setting(SETTING_OFFLINE_MODE, if enabled { "true" } else { "false" })
+    {
+        let message = format!("Failed to save offline mode: {e}");
+        error!("{message}");
+        set_last_error(handle, message);
+        return false;
+    }
 
-    match CString::new(serde_json::to_string(&result).unwrap_or_default()) {
-        Ok(cstr) => cstr.into_raw(),
-        Err(_) => {
-            set_last_error(handle, "Failed to serialize contacts");
-            ptr::null_mut()
+    crate::offline::set_offline(enabled);
+    if !enabled {
+        // Release the offline Whisper model
+        *handle.offline_transcription.lock() = None;
+        // Queued transcriptions can go out again
+        if let Some(worker) = handle.queue_worker.lock().as_ref() {
+            worker.wake();
         }
     }
+
+    debug!(
+        "Offline mode {}",
+        if enabled { "enabled" } else { "disabled" }
+    );
+    clear_last_error(handle);
+    true
 }
 
-/// Get suggested writing mode for a contact category
-/// Returns: 0=Formal, 1=Casual, 2=VeryCasual, 3=Excited
+/// Check whether offline mode is on
 #[unsafe(no_mangle)]
-pub extern "C" fn flow_get_writing_mode_for_category(
-    handle: *mut FlowHandle,
-    category: u32,
-) -> u32 {
+pub extern "C" fn flow_is_offline_mode(_handle: *mut FlowHandle) -> bool {
+    crate::offline::is_offline()
+}
+
+/// List the models served by the configured OpenAI-compatible endpoint
+/// Returns a JSON array of model ids (caller must free with flow_free_string), or null on failure
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_list_openai_models(handle: *mut FlowHandle) -> *mut c_char {
     let handle = unsafe { &*handle };
-    clear_last_error(handle);
 
-    use crate::types::ContactCategory;
+    let api_key = non_empty_setting(&handle.storage, SETTING_OPENAI_API_KEY);
+    let provider = openai_completion_provider(&handle.storage, api_key);
 
-    let contact_category = match category {
-        0 => ContactCategory::Professional,
-        1 => ContactCategory::CloseFamily,
-        2 => ContactCategory::CasualPeer,
-        3 => ContactCategory::Partner,
-        4 => ContactCategory::FormalNeutral,
-        _ => ContactCategory::FormalNeutral,
+    let models = match handle.runtime.block_on(provider.list_models()) {
+        Ok(models) => models,
+        Err(e) => {
+            let message = format!("Failed to list models: {e}");
+            error!("{message}");
+            set_last_error(handle, message);
+            return ptr::null_mut();
+        }
     };
 
-    let writing_mode = contact_category.suggested_writing_mode();
-
-    match writing_mode {
-        WritingMode::Formal => 0,
-        WritingMode::Casual => 1,
-        WritingMode::VeryCasual => 2,
-        WritingMode::Excited => 3,
+    clear_last_error(handle);
+    match CString::new(serde_json::to_string(&models).unwrap_or_default()) {
+        Ok(cstr) => cstr.into_raw(),
+        Err(_) => ptr::null_mut(),
     }
 }
 
-// ============ Cloud Transcription Provider ============
-
-/// Set cloud transcription provider (saves preference)
-/// provider: 0 = OpenAI, 1 = Auto (default)
+/// Set the models used by the OpenAI providers
+/// Each model is checked against the endpoint's /models list before it is saved
+/// transcription_model / completion_model: null to leave unchanged, empty to restore the default
 /// Returns true on success
 #[unsafe(no_mangle)]
-pub extern "C" fn flow_set_cloud_transcription_provider(
+pub extern "C" fn flow_set_openai_models(
     handle: *mut FlowHandle,
-    provider: u8,
+    transcription_model: *const c_char,
+    completion_model: *const c_char,
 ) -> bool {
     let handle = unsafe { &mut *handle };
 
-    let provider_name = match provider {
-        0 => "openai",
-        1 => "auto",
-        _ => {
-            set_last_error(
-                handle,
-                "Invalid cloud transcription provider (0=OpenAI, 1=Auto)",
-            );
-            return false;
+    let read = |ptr: *const c_char| -> std::result::Result<Option<String>, ()> {
+        if ptr.is_null() {
+            return Ok(None);
         }
+        unsafe { CStr::from_ptr(ptr) }
+            .to_str()
+            .map(|s| Some(s.trim().to_string()))
+            .map_err(|_| ())
     };
 
-    // Save the provider preference
-    if let Err(e) = handle
-        .storage
-        .set_setting(SETTING_CLOUD_TRANSCRIPTION_PROVIDER, provider_name)
-    {
-        let message = format!("Failed to save cloud transcription provider: {e}");
+    let (Ok(transcription_model), Ok(completion_model)) =
+        (read(transcription_model), read(completion_model))
+    else {
+        set_last_error(handle, "Invalid model name");
+        return false;
+    };
+
+    let api_key = non_empty_setting(&handle.storage, SETTING_OPENAI_API_KEY);
+
+    let validation = handle.runtime.block_on(async {
+        if let Some(model) = transcription_model.as_deref().filter(|m| !m.is_empty()) {
+            openai_transcription_provider(&handle.storage, api_key.clone())
+                .with_model(model)
+                .validate_model()
+                .await?;
+        }
+        if let Some(model) = completion_model.as_deref().filter(|m| !m.is_empty()) {
+            openai_completion_provider(&handle.storage, api_key.clone())
+                .with_model(model)
+                .validate_model()
+                .await?;
+        }
+        Ok::<_, crate::error::Error>(())
+    });
+
+    if let Err(e) = validation {
+        let message = format!("Model validation failed: {e}");
         error!("{message}");
         set_last_error(handle, message);
         return false;
     }
 
+    for (setting, model) in [
+        (SETTING_OPENAI_TRANSCRIPTION_MODEL, &transcription_model),
+        (SETTING_OPENAI_COMPLETION_MODEL, &completion_model),
+    ] {
+        if let Some(model) = model
+            && let Err(e) = handle.storage.set_setting(setting, model)
+        {
+            let message = format!("Failed to save OpenAI model: {e}");
+            error!("{message}");
+            set_last_error(handle, message);
+            return false;
+        }
+    }
+
+    refresh_openai_providers(handle);
+
     clear_last_error(handle);
     true
 }
 
-/// Get the current cloud transcription provider
-/// Returns: 0 = OpenAI, 1 = Auto (default)
-#[unsafe(no_mangle)]
-pub extern "C" fn flow_get_cloud_transcription_provider(handle: *mut FlowHandle) -> u8 {
-    let handle = unsafe { &*handle };
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::TranscriptionResponse;
+    use crate::providers::mock_server::{MockResponse, MockServer};
+    use async_trait::async_trait;
 
-    match handle
-        .storage
-        .get_setting(SETTING_CLOUD_TRANSCRIPTION_PROVIDER)
-    {
-        Ok(Some(name)) => match name.as_str() {
-            "openai" => 0,
-            "auto" => 1,
-            _ => 1, // default to Auto
-        },
-        _ => 1, // default to Auto
+    /// Stands in for local Whisper, hearing the same words in every recording
+    struct OnDeviceTranscription(&'static str);
+
+    #[async_trait]
+    impl TranscriptionProvider for OnDeviceTranscription {
+        fn name(&self) -> &'static str {
+            "On-device"
+        }
+
+        async fn transcribe(
+            &self,
+            request: TranscriptionRequest,
+        ) -> crate::error::Result<TranscriptionResponse> {
+            Ok(TranscriptionResponse {
+                text: self.0.to_string(),
+                confidence: None,
+                language: None,
+                duration_ms: request.audio.len() as u64 * 500 / request.sample_rate as u64,
+                segments: None,
+                completed_text: None,
+                provider: None,
+            })
+        }
+
+        fn is_configured(&self) -> bool {
+            true
+        }
+
+        fn is_local(&self) -> bool {
+            true
+        }
+    }
+
+    fn chat_response(content: &str) -> MockResponse {
+        MockResponse::json(
+            serde_json::json!({
+                "model": "gpt-test",
+                "choices": [{ "message": { "role": "assistant", "content": content } }],
+            })
+            .to_string(),
+        )
+    }
+
+    /// 500ms of silence, 1s of a tone standing in for speech, 500ms of silence
+    fn speech() -> Vec<u8> {
+        let samples: Vec<f32> = (0..32000)
+            .map(|i| match i {
+                8000..24000 => (i as f32 * 220.0 * std::f32::consts::TAU / 16000.0).sin() * 0.3,
+                _ => 0.0,
+            })
+            .collect();
+        crate::audio::samples_to_pcm(&samples)
+    }
+
+    #[test]
+    fn test_local_transcription_is_formatted_for_its_app() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = CString::new(dir.path().join("flow.db").to_string_lossy().as_ref()).unwrap();
+        let handle = flow_init(db.as_ptr());
+        assert!(!handle.is_null());
+
+        // Formatted once, then an empty completion, then the provider fails
+        let server = unsafe { &*handle }
+            .runtime
+            .block_on(MockServer::start_sequence(vec![
+                chat_response("be right back! See you at noon!"),
+                chat_response("  "),
+                MockResponse::error(500, "down"),
+            ]));
+        let url = CString::new(server.base_url.as_str()).unwrap();
+        let key = CString::new("test-key").unwrap();
+        assert!(flow_set_openai_base_url(handle, url.as_ptr()));
+        assert!(flow_set_completion_provider(handle, 0, key.as_ptr()));
+        let (brb, expansion) = (c"brb", c"be right back");
+        assert!(flow_add_shortcut(handle, brb.as_ptr(), expansion.as_ptr()));
+        assert!(flow_set_app_mode(handle, c"Notes".as_ptr(), 3));
+
+        let flow = unsafe { &*handle };
+        let provider = OnDeviceTranscription("brb, see you at noon");
+        let audio = speech();
+        let transcribe = || {
+            let request = recording_request(flow, &audio, 16000, Some("Notes".to_string()));
+            run_transcription(flow, &provider, true, &request).unwrap()
+        };
+
+        let history = transcribe();
+        assert_eq!(history.raw_text, "brb, see you at noon");
+        assert_eq!(history.text, "be right back! See you at noon!");
+        assert_eq!(history.completion_provider.as_deref(), Some("OpenAI GPT"));
+
+        // The app's mode, the app and the expanded shortcut all reach the prompt
+        let requests = server.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].path, "/chat/completions");
+        let body = requests[0].json();
+        let system = body["messages"][0]["content"].as_str().unwrap();
+        assert!(
+            system.contains(WritingMode::Excited.prompt_modifier()),
+            "{system}"
+        );
+        assert!(system.contains("typing in Notes"), "{system}");
+        let instruction = preservation_instruction(&flow.shortcuts.process("brb").1).unwrap();
+        assert!(system.ends_with(&instruction), "{system}");
+        let user = body["messages"][1]["content"].as_str().unwrap();
+        assert!(user.contains("be right back, see you at noon"), "{user}");
+
+        // Without formatting, the corrected transcription is kept
+        for _ in 0..2 {
+            let history = transcribe();
+            assert_eq!(history.text, "be right back, see you at noon");
+            assert_eq!(history.completion_provider, None);
+        }
+        assert_eq!(server.requests().len(), 3);
+
+        flow_destroy(handle);
     }
 }
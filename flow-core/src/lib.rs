@@ -4,18 +4,23 @@
 //! self-learning typo correction, voice shortcuts, and writing mode customization.
 
 pub mod apps;
+pub mod archive;
 pub mod audio;
 pub mod contacts;
 pub mod error;
 pub mod ffi;
 pub mod learning;
+pub mod live;
 pub mod macos_messages;
 pub mod metrics;
 pub mod modes;
+pub mod offline;
 pub mod providers;
+pub mod queue;
 pub mod shortcuts;
 pub mod storage;
 pub mod types;
+pub mod vocabulary;
 pub mod voice_commands;
 pub mod whisper_models;
 
@@ -24,7 +29,7 @@ pub use types::*;
 
 /// Re-export the main engine components for convenience
 pub use apps::{AppRegistry, AppTracker};
-pub use audio::AudioCapture;
+pub use audio::{AudioCapture, AudioSource, FileSource};
 pub use contacts::ContactClassifier;
 pub use learning::LearningEngine;
 pub use macos_messages::MessagesDetector;
@@ -0,0 +1,558 @@
+//! Transcription while the user is still talking
+//!
+//! During a recording the audio source streams frames to a live session. The
+//! session transcribes each stretch of speech once a pause closes it, and keeps
+//! a speculative transcript of the speech since, which the host can show as a
+//! partial result. Speculative transcripts re-send the same audio every update,
+//! so they are only worth it with a local model. When the recording stops only the tail after the last
+//! closed chunk is left to transcribe.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use parking_lot::Mutex;
+use serde::Serialize;
+use tracing::{debug, warn};
+
+use crate::audio::{
+    AudioFrame, AudioProcessing, FrameSink, SPEECH_SAMPLE_RATE, VadConfig, VoiceActivityDetector,
+    resample,
+};
+use crate::error::{Error, Result};
+use crate::providers::TranscriptionResponse;
+
+/// Transcribes a chunk of 16kHz mono audio
+pub type ChunkTranscriber = Box<dyn FnMut(&[f32]) -> Result<TranscriptionResponse> + Send>;
+
+/// How often the session looks for finished frames while none arrive
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// When a live session transcribes
+#[derive(Debug, Clone)]
+pub struct LiveConfig {
+    /// New audio heard before pauses are looked for and the speculative transcript refreshed
+    pub update_interval_ms: u32,
+    /// Transcribe the speech after the last closed chunk at every update, so the partial
+    /// result follows along; off, only closed chunks are ever transcribed
+    pub speculative: bool,
+    /// Chunks shorter than this wait for a later pause, so each has some context
+    pub min_chunk_ms: u32,
+    /// Pause detection; a chunk is closed by `min_silence_ms` without speech, and
+    /// one reaching `max_chunk_ms` is cut at its quietest pause
+    pub vad: VadConfig,
+    /// Clean-up applied to the audio before it is transcribed, as for the final recording
+    pub processing: AudioProcessing,
+}
+
+impl Default for LiveConfig {
+    fn default() -> Self {
+        Self {
+            update_interval_ms: 1000,
+            speculative: true,
+            min_chunk_ms: 3000,
+            vad: VadConfig::default(),
+            processing: AudioProcessing::default(),
+        }
+    }
+}
+
+/// What has been transcribed of the recording so far
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct PartialTranscript {
+    /// Best guess at the whole recording so far
+    pub text: String,
+    /// Leading part of `text` from closed chunks, which won't change
+    pub stable_text: String,
+    /// Audio heard so far
+    pub audio_ms: u64,
+    /// Incremented whenever `text` changes
+    pub revision: u64,
+}
+
+/// What a live session transcribed before the recording stopped
+#[derive(Debug, Clone, Default)]
+pub struct LiveTranscript {
+    /// Closed chunks merged, with timings from the start of the recording
+    pub committed: Option<TranscriptionResponse>,
+    /// Where the audio nobody transcribed yet starts
+    pub tail_start_ms: u64,
+}
+
+/// Chunking and transcription state, fed frames by the session thread
+struct LiveState {
+    config: LiveConfig,
+    transcribe: ChunkTranscriber,
+    vad: VoiceActivityDetector,
+    /// Audio after the last closed chunk, at `sample_rate`
+    tail: Vec<f32>,
+    sample_rate: u32,
+    tail_start_ms: f64,
+    /// Samples of `tail` heard since the speculative transcript was refreshed
+    unprocessed: usize,
+    committed: Option<TranscriptionResponse>,
+    partial: Arc<Mutex<PartialTranscript>>,
+    /// Set when a chunk could not be transcribed; the final pass does the rest
+    failed: bool,
+}
+
+impl LiveState {
+    fn new(
+        config: LiveConfig,
+        transcribe: ChunkTranscriber,
+        partial: Arc<Mutex<PartialTranscript>>,
+    ) -> Self {
+        Self {
+            vad: VoiceActivityDetector::new(config.vad.clone()),
+            config,
+            transcribe,
+            tail: Vec::new(),
+            sample_rate: SPEECH_SAMPLE_RATE,
+            tail_start_ms: 0.0,
+            unprocessed: 0,
+            committed: None,
+            partial,
+            failed: false,
+        }
+    }
+
+    fn push(&mut self, frame: AudioFrame) {
+        if frame.sample_rate == 0 {
+            return;
+        }
+        // The device changed mid-recording
+        if frame.sample_rate != self.sample_rate {
+            if !self.tail.is_empty() {
+                self.tail = resample(&self.tail, self.sample_rate, frame.sample_rate);
+            }
+            self.unprocessed =
+                self.unprocessed * frame.sample_rate as usize / self.sample_rate.max(1) as usize;
+            self.sample_rate = frame.sample_rate;
+        }
+        self.tail.extend_from_slice(&frame.samples);
+        self.unprocessed += frame.samples.len();
+        self.partial.lock().audio_ms = self.heard_ms();
+    }
+
+    fn heard_ms(&self) -> u64 {
+        (self.tail_start_ms + self.tail.len() as f64 * 1000.0 / self.sample_rate as f64) as u64
+    }
+
+    fn due(&self) -> bool {
+        !self.failed
+            && self.unprocessed as u64 * 1000
+                >= self.config.update_interval_ms as u64 * self.sample_rate as u64
+    }
+
+    /// Close the chunks that a pause has ended and refresh the speculative transcript
+    fn update(&mut self) {
+        self.unprocessed = 0;
+        let mut window = if self.sample_rate == SPEECH_SAMPLE_RATE {
+            self.tail.clone()
+        } else {
+            resample(&self.tail, self.sample_rate, SPEECH_SAMPLE_RATE)
+        };
+        self.config
+            .processing
+            .apply(&mut window, SPEECH_SAMPLE_RATE);
+
+        let segments = match self.vad.detect(&window, SPEECH_SAMPLE_RATE) {
+            Ok(segments) => segments,
+            Err(e) => {
+                warn!(
+                    "Live transcription stopped, voice activity detection failed: {}",
+                    e
+                );
+                self.failed = true;
+                return;
+            }
+        };
+
+        let cut = self.chunk_end(&window, &segments);
+        let closed: Vec<_> = segments.iter().filter(|s| s.start < cut).collect();
+        if let (Some(first), Some(last)) = (closed.first(), closed.last()) {
+            let speech = first.start..last.end.min(cut);
+            let offset_ms = self.tail_start_ms as u64 + ms(speech.start);
+            match (self.transcribe)(&window[speech]) {
+                Ok(response) => {
+                    let response = response.offset_by(offset_ms);
+                    debug!("Live transcription closed a chunk at {}ms", offset_ms);
+                    match self.committed.as_mut() {
+                        Some(committed) => committed.append(response),
+                        None => self.committed = Some(response),
+                    }
+                }
+                Err(e) => {
+                    warn!("Live transcription stopped: {}", e);
+                    self.failed = true;
+                    return;
+                }
+            }
+        }
+        if cut > 0 {
+            let tail_cut =
+                (cut as u64 * self.sample_rate as u64 / SPEECH_SAMPLE_RATE as u64) as usize;
+            let tail_cut = tail_cut.min(self.tail.len());
+            self.tail.drain(..tail_cut);
+            self.tail_start_ms += tail_cut as f64 * 1000.0 / self.sample_rate as f64;
+        }
+
+        // Whatever is still being said, transcribed as it stands
+        let pending = match (segments.iter().find(|s| s.end > cut), segments.last()) {
+            _ if !self.config.speculative => String::new(),
+            (Some(first), Some(last)) => {
+                match (self.transcribe)(&window[first.start.max(cut)..last.end]) {
+                    Ok(response) => response.text.trim().to_string(),
+                    Err(e) => {
+                        warn!("Live transcription stopped: {}", e);
+                        self.failed = true;
+                        return;
+                    }
+                }
+            }
+            _ => String::new(),
+        };
+        self.publish(pending);
+    }
+
+    /// End of the leading audio that pauses have closed into a chunk (0 for none)
+    fn chunk_end(&self, window: &[f32], segments: &[std::ops::Range<usize>]) -> usize {
+        let samples = |ms: u32| (SPEECH_SAMPLE_RATE as u64 * ms as u64 / 1000) as usize;
+        let min_chunk = samples(self.config.min_chunk_ms);
+
+        // Speech that has run past the chunk limit is cut at its quietest pause
+        if window.len() > samples(self.config.vad.max_chunk_ms) {
+            let chunks = self
+                .vad
+                .split_at_pauses(window, SPEECH_SAMPLE_RATE, segments);
+            if chunks.len() > 1 {
+                return chunks[chunks.len() - 2].end;
+            }
+        }
+
+        // Otherwise after the last segment that a long enough pause follows
+        let closed_by_pause = |index: usize| {
+            let next_start = segments
+                .get(index + 1)
+                .map_or(window.len(), |next| next.start);
+            next_start.saturating_sub(segments[index].end)
+                >= samples(self.config.vad.min_silence_ms)
+                || index + 1 < segments.len()
+        };
+        (0..segments.len())
+            .rev()
+            .find(|&index| closed_by_pause(index))
+            .map(|index| match segments.get(index + 1) {
+                Some(next) => (segments[index].end + next.start) / 2,
+                None => segments[index].end,
+            })
+            .filter(|&end| end >= min_chunk)
+            .unwrap_or(0)
+    }
+
+    fn publish(&self, pending: String) {
+        let stable_text = self
+            .committed
+            .as_ref()
+            .map_or(String::new(), |committed| committed.text.trim().to_string());
+        let text = match (stable_text.is_empty(), pending.is_empty()) {
+            (_, true) => stable_text.clone(),
+            (true, false) => pending,
+            (false, false) => format!("{stable_text} {pending}"),
+        };
+
+        let mut partial = self.partial.lock();
+        if partial.text != text {
+            partial.revision += 1;
+        }
+        partial.text = text;
+        partial.stable_text = stable_text;
+        partial.audio_ms = self.heard_ms();
+    }
+
+    fn finish(self) -> LiveTranscript {
+        LiveTranscript {
+            committed: self.committed,
+            tail_start_ms: self.tail_start_ms as u64,
+        }
+    }
+}
+
+/// Milliseconds of 16kHz samples
+fn ms(samples: usize) -> u64 {
+    samples as u64 * 1000 / SPEECH_SAMPLE_RATE as u64
+}
+
+/// A recording being transcribed on its own thread while it is captured
+///
+/// Give `sink()` to the audio source before the recording starts, and call
+/// `finish` once it has stopped. Dropping the session abandons it.
+pub struct LiveSession {
+    sink: FrameSink,
+    partial: Arc<Mutex<PartialTranscript>>,
+    finishing: Arc<AtomicBool>,
+    thread: Option<JoinHandle<LiveTranscript>>,
+}
+
+impl LiveSession {
+    pub fn spawn(config: LiveConfig, transcribe: ChunkTranscriber) -> Result<Self> {
+        let (sink, frames) = mpsc::channel();
+        let partial = Arc::new(Mutex::new(PartialTranscript::default()));
+        let finishing = Arc::new(AtomicBool::new(false));
+
+        let state = LiveState::new(config, transcribe, Arc::clone(&partial));
+        let thread_finishing = Arc::clone(&finishing);
+        let thread = std::thread::Builder::new()
+            .name("flow-live-transcription".to_string())
+            .spawn(move || run(state, frames, &thread_finishing))
+            .map_err(|e| {
+                Error::Transcription(format!("Failed to start live transcription: {e}"))
+            })?;
+
+        Ok(Self {
+            sink,
+            partial,
+            finishing,
+            thread: Some(thread),
+        })
+    }
+
+    /// Where the audio source sends the recording
+    pub fn sink(&self) -> FrameSink {
+        self.sink.clone()
+    }
+
+    pub fn partial(&self) -> PartialTranscript {
+        self.partial.lock().clone()
+    }
+
+    /// Take what was transcribed, once the audio has all been sent
+    ///
+    /// Waits for a chunk being transcribed; the frames still queued are kept
+    /// for the final pass rather than transcribed here.
+    pub fn finish(mut self) -> LiveTranscript {
+        self.finishing.store(true, Ordering::SeqCst);
+        self.thread
+            .take()
+            .and_then(|thread| thread.join().ok())
+            .unwrap_or_default()
+    }
+}
+
+impl Drop for LiveSession {
+    fn drop(&mut self) {
+        // Stops after the chunk in progress; nobody waits for it
+        self.finishing.store(true, Ordering::SeqCst);
+    }
+}
+
+fn run(
+    mut state: LiveState,
+    frames: Receiver<AudioFrame>,
+    finishing: &AtomicBool,
+) -> LiveTranscript {
+    loop {
+        match frames.recv_timeout(POLL_INTERVAL) {
+            Ok(frame) => state.push(frame),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+        if finishing.load(Ordering::SeqCst) {
+            break;
+        }
+        // Catch up with everything heard before transcribing again
+        while let Ok(frame) = frames.try_recv() {
+            state.push(frame);
+        }
+        if state.due() {
+            state.update();
+        }
+    }
+    // Audio that arrived meanwhile belongs to the tail
+    while let Ok(frame) = frames.try_recv() {
+        state.push(frame);
+    }
+    state.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    /// Tone bursts standing in for words: (milliseconds, speaking)
+    fn script(rate: u32, parts: &[(u32, bool)]) -> Vec<f32> {
+        let mut samples = Vec::new();
+        for &(ms, speaking) in parts {
+            for _ in 0..rate as u64 * ms as u64 / 1000 {
+                let t = samples.len() as f32 / rate as f32;
+                let voice = (t * 220.0 * std::f32::consts::TAU).sin() * 0.3;
+                samples.push(if speaking { voice } else { 0.0 });
+            }
+        }
+        samples
+    }
+
+    /// Names each chunk by its length, recording what it was asked to transcribe
+    fn transcriber(calls: Arc<Mutex<Vec<u64>>>) -> ChunkTranscriber {
+        Box::new(move |samples: &[f32]| {
+            let duration_ms = ms(samples.len());
+            calls.lock().push(duration_ms);
+            Ok(TranscriptionResponse {
+                text: format!("<{}>", duration_ms / 500 * 500),
+                confidence: None,
+                language: None,
+                duration_ms,
+                segments: None,
+                completed_text: None,
+                provider: None,
+            })
+        })
+    }
+
+    fn config() -> LiveConfig {
+        LiveConfig {
+            min_chunk_ms: 1000,
+            processing: AudioProcessing::none(),
+            ..Default::default()
+        }
+    }
+
+    fn feed(state: &mut LiveState, samples: &[f32], rate: u32) {
+        for chunk in samples.chunks(rate as usize / 100) {
+            state.push(AudioFrame {
+                samples: chunk.to_vec(),
+                sample_rate: rate,
+            });
+            if state.due() {
+                state.update();
+            }
+        }
+    }
+
+    #[test]
+    fn test_closes_chunks_at_pauses() {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let partial = Arc::new(Mutex::new(PartialTranscript::default()));
+        let mut state = LiveState::new(config(), transcriber(calls.clone()), partial.clone());
+
+        // Two phrases and the start of a third, at a device rate of 48kHz
+        let samples = script(
+            48000,
+            &[
+                (500, false),
+                (2000, true),
+                (1000, false),
+                (1500, true),
+                (1000, false),
+                (600, true),
+            ],
+        );
+        feed(&mut state, &samples, 48000);
+
+        let partial = partial.lock().clone();
+        assert_eq!(partial.audio_ms, 6600);
+        assert_eq!(partial.stable_text, "<2000> <1500>");
+        assert!(partial.text.starts_with("<2000> <1500>"), "{partial:?}");
+        assert!(partial.revision > 0);
+
+        let transcript = state.finish();
+        let committed = transcript.committed.unwrap();
+        assert_eq!(committed.text, "<2000> <1500>");
+        // The tail starts in the pause before the third phrase
+        assert!(
+            (5000..=5800).contains(&transcript.tail_start_ms),
+            "{}",
+            transcript.tail_start_ms
+        );
+    }
+
+    #[test]
+    fn test_short_or_silent_recordings_leave_everything_to_the_end() {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let partial = Arc::new(Mutex::new(PartialTranscript::default()));
+        let mut state = LiveState::new(config(), transcriber(calls.clone()), partial.clone());
+        feed(&mut state, &script(16000, &[(3000, false)]), 16000);
+        assert!(calls.lock().is_empty());
+        assert_eq!(partial.lock().text, "");
+
+        // A word shorter than the minimum chunk is only ever speculative
+        let mut state = LiveState::new(
+            LiveConfig {
+                min_chunk_ms: 5000,
+                ..config()
+            },
+            transcriber(calls.clone()),
+            partial.clone(),
+        );
+        feed(
+            &mut state,
+            &script(16000, &[(1000, true), (2000, false)]),
+            16000,
+        );
+        assert_eq!(partial.lock().stable_text, "");
+        assert!(!partial.lock().text.is_empty());
+        let transcript = state.finish();
+        assert!(transcript.committed.is_none());
+        assert_eq!(transcript.tail_start_ms, 0);
+    }
+
+    #[test]
+    fn test_without_speculation_only_closed_chunks_are_sent() {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let partial = Arc::new(Mutex::new(PartialTranscript::default()));
+        let config = LiveConfig {
+            speculative: false,
+            ..config()
+        };
+        let mut state = LiveState::new(config, transcriber(calls.clone()), partial.clone());
+        feed(
+            &mut state,
+            &script(
+                16000,
+                &[(500, false), (2000, true), (1000, false), (1500, true)],
+            ),
+            16000,
+        );
+
+        // The first phrase once, and nothing of the one still being said
+        assert_eq!(calls.lock().len(), 1);
+        let partial = partial.lock().clone();
+        assert_eq!(partial.stable_text, "<2000>");
+        assert_eq!(partial.text, "<2000>");
+    }
+
+    #[test]
+    fn test_session_streams_on_its_own_thread() {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        // Frequent updates, so one lands after the pause however the frames are batched
+        let config = LiveConfig {
+            update_interval_ms: 100,
+            ..config()
+        };
+        let session = LiveSession::spawn(config, transcriber(calls.clone())).unwrap();
+        let sink = session.sink();
+        let samples = script(16000, &[(1500, true), (1000, false), (500, true)]);
+        for chunk in samples.chunks(160) {
+            sink.send(AudioFrame {
+                samples: chunk.to_vec(),
+                sample_rate: 16000,
+            })
+            .unwrap();
+        }
+
+        let start = Instant::now();
+        while session.partial().stable_text.is_empty() {
+            assert!(
+                start.elapsed() < Duration::from_secs(5),
+                "nothing transcribed"
+            );
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        let transcript = session.finish();
+        assert_eq!(transcript.committed.unwrap().text, "<1500>");
+        assert!(transcript.tail_start_ms >= 1500);
+    }
+}
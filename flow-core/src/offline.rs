@@ -0,0 +1,31 @@
+//! Global offline mode
+//!
+//! Air-gapped installs switch this on to guarantee nothing leaves the machine.
+//! Every network call checks `ensure_online` before building a request, so with
+//! offline mode on it fails with `Error::Offline` without opening a socket.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::error::{Error, Result};
+
+static OFFLINE: AtomicBool = AtomicBool::new(false);
+
+/// Turn offline mode on or off for the whole process
+pub fn set_offline(enabled: bool) {
+    OFFLINE.store(enabled, Ordering::SeqCst);
+}
+
+/// Whether offline mode is on
+pub fn is_offline() -> bool {
+    OFFLINE.load(Ordering::SeqCst)
+}
+
+/// Fail with `Error::Offline` when offline mode is on
+/// `service` names what needed the network, for the error message
+pub fn ensure_online(service: &str) -> Result<()> {
+    if is_offline() {
+        Err(Error::Offline(service.to_string()))
+    } else {
+        Ok(())
+    }
+}
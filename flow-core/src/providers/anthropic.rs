@@ -0,0 +1,458 @@
+//! Anthropic provider implementation for Claude completion via the Messages API
+
+use async_trait::async_trait;
+use futures::StreamExt;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, error};
+
+use crate::error::{Error, Result};
+use crate::types::WritingMode;
+
+use super::completion::TokenUsage;
+use super::streaming::{AnthropicStreamEvent, sse_events};
+use super::{
+    CompletionChunk, CompletionProvider, CompletionRequest, CompletionResponse, CompletionStream,
+    StreamingCompletionProvider,
+};
+
+const ANTHROPIC_API_BASE: &str = "https://api.anthropic.com/v1";
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+/// The Messages API requires max_tokens, so use this when the request doesn't set one
+const DEFAULT_MAX_TOKENS: u32 = 1024;
+
+/// Anthropic Claude completion provider
+pub struct AnthropicCompletionProvider {
+    client: Client,
+    api_key: Option<String>,
+    model: String,
+    base_url: String,
+}
+
+impl AnthropicCompletionProvider {
+    /// Create a new provider (API key loaded from environment if not provided)
+    pub fn new(api_key: Option<String>) -> Self {
+        let key = api_key.or_else(|| std::env::var("ANTHROPIC_API_KEY").ok());
+
+        Self {
+            client: Client::new(),
+            api_key: key,
+            model: "claude-3-5-haiku-latest".to_string(),
+            base_url: ANTHROPIC_API_BASE.to_string(),
+        }
+    }
+
+    /// Set the model to use
+    pub fn with_model(mut self, model: impl Into<String>) -> Self {
+        self.model = model.into();
+        self
+    }
+
+    /// Override the API base URL (e.g. for a proxy)
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    fn api_key(&self) -> Result<&str> {
+        self.api_key
+            .as_deref()
+            .ok_or_else(|| Error::ProviderNotConfigured("Anthropic API key not set".to_string()))
+    }
+
+    fn build_system_prompt(&self, mode: WritingMode, app_context: Option<&str>) -> String {
+        let mut prompt = String::from(
+            "You are a text formatter. The user will provide raw transcribed text wrapped in <TRANSCRIPTION> tags. \
+             Reformat ONLY the text inside according to the style below. Output the reformatted text exactly as it would \
+             be typed. Do NOT generate new content, do NOT add commentary or responses, do NOT say anything.\n\n",
+        );
+
+        prompt.push_str("Formatting style: ");
+        prompt.push_str(mode.prompt_modifier());
+
+        if let Some(context) = app_context {
+            prompt.push_str("\n\nContext: User is typing in ");
+            prompt.push_str(context);
+            prompt.push_str(". Adjust formatting for this context.");
+        }
+
+        prompt
+    }
+
+    fn build_request(&self, request: CompletionRequest, stream: bool) -> MessagesRequest {
+        let mut system_prompt = request.system_prompt.unwrap_or_else(|| {
+            self.build_system_prompt(request.mode, request.app_context.as_deref())
+        });
+
+        // Add shortcut preservation instruction if present
+        if let Some(preservation) = request.shortcut_preservation {
+            system_prompt.push_str(&preservation);
+        }
+
+        MessagesRequest {
+            model: self.model.clone(),
+            system: system_prompt,
+            messages: vec![Message {
+                role: "user".to_string(),
+                content: format!("<TRANSCRIPTION>\n{}\n</TRANSCRIPTION>", request.text),
+            }],
+            max_tokens: request.max_tokens.unwrap_or(DEFAULT_MAX_TOKENS),
+            temperature: 0.3, // low temperature for consistent formatting
+            stream,
+        }
+    }
+
+    async fn send(&self, body: &MessagesRequest) -> Result<reqwest::Response> {
+        crate::offline::ensure_online("Anthropic")?;
+        let api_key = self.api_key()?;
+
+        let response = self
+            .client
+            .post(format!("{}/messages", self.base_url))
+            .header("x-api-key", api_key)
+            .header("anthropic-version", ANTHROPIC_VERSION)
+            .header("Content-Type", "application/json")
+            .json(body)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response.text().await.unwrap_or_default();
+            error!("Anthropic API error: {} - {}", status, error_text);
+            return Err(Error::CompletionApi {
+                status,
+                message: format!("Anthropic API error: {} - {}", status, error_text),
+            });
+        }
+
+        Ok(response)
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct MessagesRequest {
+    model: String,
+    system: String,
+    messages: Vec<Message>,
+    max_tokens: u32,
+    temperature: f32,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    stream: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct Message {
+    role: String,
+    content: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct MessagesResponse {
+    content: Vec<ContentBlock>,
+    usage: Option<MessagesUsage>,
+    model: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ContentBlock {
+    #[serde(rename = "type")]
+    block_type: String,
+    #[serde(default)]
+    text: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct MessagesUsage {
+    input_tokens: u32,
+    output_tokens: u32,
+}
+
+fn token_usage(input_tokens: u32, output_tokens: u32) -> TokenUsage {
+    TokenUsage {
+        prompt_tokens: input_tokens,
+        completion_tokens: output_tokens,
+        total_tokens: input_tokens + output_tokens,
+    }
+}
+
+#[async_trait]
+impl CompletionProvider for AnthropicCompletionProvider {
+    fn name(&self) -> &'static str {
+        "Anthropic"
+    }
+
+    async fn complete(&self, request: CompletionRequest) -> Result<CompletionResponse> {
+        let body = self.build_request(request, false);
+
+        debug!("Sending completion request to Anthropic");
+
+        let response = self.send(&body).await?;
+        let messages_response: MessagesResponse = response.json().await?;
+
+        let text = messages_response
+            .content
+            .into_iter()
+            .filter(|block| block.block_type == "text")
+            .map(|block| block.text)
+            .collect::<String>();
+
+        if text.is_empty() {
+            return Err(Error::Completion("No completion returned".to_string()));
+        }
+
+        Ok(CompletionResponse {
+            text,
+            usage: messages_response
+                .usage
+                .map(|u| token_usage(u.input_tokens, u.output_tokens)),
+            model: Some(messages_response.model),
+            provider: None,
+        })
+    }
+
+    fn is_configured(&self) -> bool {
+        self.api_key.is_some()
+    }
+
+    fn as_streaming(&self) -> Option<&dyn StreamingCompletionProvider> {
+        Some(self)
+    }
+}
+
+#[async_trait]
+impl StreamingCompletionProvider for AnthropicCompletionProvider {
+    fn name(&self) -> &'static str {
+        "Anthropic"
+    }
+
+    async fn complete_stream(&self, request: CompletionRequest) -> Result<CompletionStream> {
+        let body = self.build_request(request, true);
+
+        debug!("Sending streaming completion request to Anthropic");
+
+        let response = self.send(&body).await?;
+
+        // input tokens arrive in message_start, output tokens in message_delta
+        let stream = sse_events(response)
+            .scan((0u32, 0u32), |(input_tokens, output_tokens), event| {
+                let item = match event.and_then(|e| {
+                    serde_json::from_str::<AnthropicStreamEvent>(&e.data).map_err(Into::into)
+                }) {
+                    Ok(AnthropicStreamEvent::MessageStart { message }) => {
+                        *input_tokens = message.usage.input_tokens;
+                        *output_tokens = message.usage.output_tokens;
+                        None
+                    }
+                    Ok(AnthropicStreamEvent::ContentBlockDelta { delta, .. })
+                        if delta.delta_type == "text_delta" =>
+                    {
+                        Some(Ok(CompletionChunk {
+                            text: delta.text,
+                            is_final: false,
+                            usage: None,
+                        }))
+                    }
+                    Ok(AnthropicStreamEvent::MessageDelta { usage, .. }) => {
+                        *output_tokens = usage.output_tokens;
+                        None
+                    }
+                    Ok(AnthropicStreamEvent::MessageStop) => Some(Ok(CompletionChunk {
+                        text: String::new(),
+                        is_final: true,
+                        usage: Some(token_usage(*input_tokens, *output_tokens)),
+                    })),
+                    Ok(AnthropicStreamEvent::Error { error }) => {
+                        Some(Err(Error::Completion(format!(
+                            "Anthropic stream error: {} - {}",
+                            error.error_type, error.message
+                        ))))
+                    }
+                    Ok(_) => None,
+                    Err(e) => Some(Err(e)),
+                };
+                futures::future::ready(Some(item))
+            })
+            .filter_map(futures::future::ready);
+
+        Ok(Box::pin(stream))
+    }
+
+    fn is_configured(&self) -> bool {
+        self.api_key.is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::collect_stream;
+    use crate::providers::mock_server::{MockResponse, MockServer};
+
+    fn provider(server: &MockServer) -> AnthropicCompletionProvider {
+        AnthropicCompletionProvider::new(Some("sk-ant-test".to_string()))
+            .with_base_url(&server.base_url)
+    }
+
+    #[test]
+    fn test_system_prompt_building() {
+        let provider = AnthropicCompletionProvider::new(None);
+
+        let prompt = provider.build_system_prompt(WritingMode::Formal, None);
+        assert!(prompt.contains("professional"));
+        assert!(prompt.contains("<TRANSCRIPTION>"));
+
+        let prompt = provider.build_system_prompt(WritingMode::VeryCasual, Some("Slack"));
+        assert!(prompt.contains("texting style"));
+        assert!(prompt.contains("Slack"));
+    }
+
+    #[tokio::test]
+    async fn test_complete() {
+        let server = MockServer::start(MockResponse::json(
+            r#"{
+                "id": "msg_1",
+                "type": "message",
+                "role": "assistant",
+                "model": "claude-3-5-haiku-latest",
+                "content": [{"type": "text", "text": "Hello, world."}],
+                "stop_reason": "end_turn",
+                "usage": {"input_tokens": 42, "output_tokens": 5}
+            }"#,
+        ))
+        .await;
+
+        let response = provider(&server)
+            .complete(
+                CompletionRequest::new("hello world".to_string(), WritingMode::Casual)
+                    .with_shortcut_preservation("\nKEEP THIS"),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.text, "Hello, world.");
+        let usage = response.usage.unwrap();
+        assert_eq!(usage.prompt_tokens, 42);
+        assert_eq!(usage.completion_tokens, 5);
+        assert_eq!(usage.total_tokens, 47);
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].method, "POST");
+        assert_eq!(requests[0].path, "/messages");
+        assert_eq!(requests[0].header("x-api-key"), Some("sk-ant-test"));
+        assert_eq!(
+            requests[0].header("anthropic-version"),
+            Some(ANTHROPIC_VERSION)
+        );
+        let body = requests[0].json();
+        assert_eq!(body["max_tokens"], DEFAULT_MAX_TOKENS);
+        assert!(body.get("stream").is_none());
+        assert!(body["system"].as_str().unwrap().ends_with("KEEP THIS"));
+        assert!(
+            body["messages"][0]["content"]
+                .as_str()
+                .unwrap()
+                .contains("hello world")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_complete_stream() {
+        let server = MockServer::start(MockResponse::sse(&[
+            r#"event: message_start
+data: {"type":"message_start","message":{"id":"msg_1","model":"claude-3-5-haiku-latest","usage":{"input_tokens":30,"output_tokens":1}}}"#,
+            r#"event: content_block_start
+data: {"type":"content_block_start","index":0,"content_block":{"type":"text","text":""}}"#,
+            "event: ping\ndata: {\"type\":\"ping\"}",
+            r#"event: content_block_delta
+data: {"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"Hello"}}"#,
+            r#"event: content_block_delta
+data: {"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":", world."}}"#,
+            r#"event: content_block_stop
+data: {"type":"content_block_stop","index":0}"#,
+            r#"event: message_delta
+data: {"type":"message_delta","delta":{"stop_reason":"end_turn"},"usage":{"output_tokens":6}}"#,
+            r#"event: message_stop
+data: {"type":"message_stop"}"#,
+        ]))
+        .await;
+
+        let provider = provider(&server);
+        let stream = provider
+            .complete_stream(CompletionRequest::new(
+                "hello world".to_string(),
+                WritingMode::Casual,
+            ))
+            .await
+            .unwrap();
+
+        let response = collect_stream(stream).await.unwrap();
+        assert_eq!(response.text, "Hello, world.");
+        let usage = response.usage.unwrap();
+        assert_eq!(usage.prompt_tokens, 30);
+        assert_eq!(usage.completion_tokens, 6);
+        assert_eq!(usage.total_tokens, 36);
+
+        let body = server.requests()[0].json();
+        assert_eq!(body["stream"], true);
+    }
+
+    #[tokio::test]
+    async fn test_stream_error_event() {
+        let server = MockServer::start(MockResponse::sse(&[r#"event: error
+data: {"type":"error","error":{"type":"overloaded_error","message":"Overloaded"}}"#]))
+        .await;
+
+        let stream = provider(&server)
+            .complete_stream(CompletionRequest::new(
+                "hello".to_string(),
+                WritingMode::Casual,
+            ))
+            .await
+            .unwrap();
+
+        let err = collect_stream(stream).await.unwrap_err();
+        assert!(err.to_string().contains("overloaded_error"));
+    }
+
+    #[tokio::test]
+    async fn test_http_error() {
+        let server = MockServer::start(MockResponse::error(
+            401,
+            r#"{"type":"error","error":{"type":"authentication_error","message":"invalid x-api-key"}}"#,
+        ))
+        .await;
+
+        let err = provider(&server)
+            .complete(CompletionRequest::new(
+                "hello".to_string(),
+                WritingMode::Casual,
+            ))
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            Error::CompletionApi { status, .. } if status == reqwest::StatusCode::UNAUTHORIZED
+        ));
+        assert!(err.to_string().contains("401"));
+    }
+
+    #[tokio::test]
+    async fn test_not_configured() {
+        let provider = AnthropicCompletionProvider {
+            api_key: None,
+            ..AnthropicCompletionProvider::new(None)
+        };
+        assert!(!CompletionProvider::is_configured(&provider));
+
+        let err = provider
+            .complete(CompletionRequest::new(
+                "hello".to_string(),
+                WritingMode::Casual,
+            ))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::ProviderNotConfigured(_)));
+    }
+}
@@ -10,16 +10,22 @@ use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use tracing::{debug, error};
 
+use crate::audio::{AudioCodec, encode_for_upload};
 use crate::error::{Error, Result};
 
-use super::{TranscriptionProvider, TranscriptionRequest, TranscriptionResponse};
+use super::{
+    ShortcutExpansion, TranscriptionProvider, TranscriptionRequest, TranscriptionResponse,
+};
 
 const BASE10_PROXY_URL: &str = "https://base10-proxy.test-j.workers.dev";
-const BASE10_VALIDATE_URL: &str = "https://base10-proxy.test-j.workers.dev/validate-corrections";
+/// Lossless and about half the size of WAV; Ogg Opus is smaller still, but nothing yet
+/// checks that Base10 Whisper decodes it
+const UPLOAD_CODEC: AudioCodec = AudioCodec::Flac;
 
 /// Base10 transcription provider (with integrated completion)
 pub struct Base10TranscriptionProvider {
     client: Client,
+    base_url: String,
 }
 
 /// A correction pair to validate
@@ -54,44 +60,62 @@ struct ValidateCorrectionsResponse {
 pub async fn validate_corrections(
     corrections: Vec<CorrectionPair>,
 ) -> Result<Vec<CorrectionValidation>> {
-    if corrections.is_empty() {
-        return Ok(vec![]);
-    }
-
-    let client = Client::new();
-    let request = ValidateCorrectionsRequest { corrections };
-
-    debug!(
-        "Validating {} corrections via worker",
-        request.corrections.len()
-    );
-
-    let response = client
-        .post(BASE10_VALIDATE_URL)
-        .json(&request)
-        .send()
-        .await?;
-
-    if !response.status().is_success() {
-        let status = response.status();
-        let error_text = response.text().await.unwrap_or_default();
-        error!("Validation worker error: {} - {}", status, error_text);
-        return Err(Error::Transcription(format!(
-            "Validation error: {} - {}",
-            status, error_text
-        )));
-    }
-
-    let validation_response: ValidateCorrectionsResponse = response.json().await?;
-    Ok(validation_response.results)
+    Base10TranscriptionProvider::new(None)
+        .validate_corrections(corrections)
+        .await
 }
 
 impl Base10TranscriptionProvider {
     pub fn new(_api_key: Option<String>) -> Self {
         Self {
             client: Client::new(),
+            base_url: BASE10_PROXY_URL.to_string(),
         }
     }
+
+    /// Override the worker base URL
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Validate corrections using AI via the worker
+    pub async fn validate_corrections(
+        &self,
+        corrections: Vec<CorrectionPair>,
+    ) -> Result<Vec<CorrectionValidation>> {
+        if corrections.is_empty() {
+            return Ok(vec![]);
+        }
+
+        crate::offline::ensure_online("Correction validation")?;
+        let request = ValidateCorrectionsRequest { corrections };
+
+        debug!(
+            "Validating {} corrections via worker",
+            request.corrections.len()
+        );
+
+        let response = self
+            .client
+            .post(format!("{}/validate-corrections", self.base_url))
+            .json(&request)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response.text().await.unwrap_or_default();
+            error!("Validation worker error: {} - {}", status, error_text);
+            return Err(Error::TranscriptionApi {
+                status,
+                message: format!("Validation error: {} - {}", status, error_text),
+            });
+        }
+
+        let validation_response: ValidateCorrectionsResponse = response.json().await?;
+        Ok(validation_response.results)
+    }
 }
 
 #[derive(Debug, Serialize)]
@@ -109,11 +133,15 @@ struct WhisperInput {
 #[derive(Debug, Serialize)]
 struct AudioInput {
     audio_b64: String,
+    /// Format of the encoded audio, forwarded by the worker
+    codec: AudioCodec,
 }
 
 #[derive(Debug, Serialize)]
 struct WhisperParams {
     audio_language: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    prompt: Option<String>,
 }
 
 #[derive(Debug, Serialize)]
@@ -122,6 +150,9 @@ struct WorkerCompletionParams {
     #[serde(skip_serializing_if = "Option::is_none")]
     app_context: Option<String>,
     shortcuts_triggered: Vec<String>,
+    /// Expanded by the worker before formatting
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    shortcuts: Vec<ShortcutExpansion>,
     #[serde(skip_serializing_if = "Option::is_none")]
     voice_instruction: Option<String>,
 }
@@ -140,9 +171,14 @@ impl TranscriptionProvider for Base10TranscriptionProvider {
         "Auto (Cloud)"
     }
 
+    fn completes_text(&self) -> bool {
+        true
+    }
+
     async fn transcribe(&self, request: TranscriptionRequest) -> Result<TranscriptionResponse> {
-        let wav_data = pcm_to_wav(&request.audio, request.sample_rate, 1);
-        let audio_base64 = STANDARD.encode(&wav_data);
+        crate::offline::ensure_online("Auto (Cloud) transcription")?;
+        let (audio, codec) = encode_for_upload(&request.audio, request.sample_rate, UPLOAD_CODEC);
+        let audio_base64 = STANDARD.encode(&audio);
         let language = request.language.as_deref().unwrap_or("auto").to_string();
 
         // Completion params are required
@@ -154,15 +190,18 @@ impl TranscriptionProvider for Base10TranscriptionProvider {
             whisper_input: WhisperInput {
                 audio: AudioInput {
                     audio_b64: audio_base64,
+                    codec,
                 },
                 whisper_params: WhisperParams {
                     audio_language: language,
+                    prompt: request.prompt,
                 },
             },
             completion: WorkerCompletionParams {
                 mode: completion.mode,
                 app_context: completion.app_context,
                 shortcuts_triggered: completion.shortcuts_triggered,
+                shortcuts: completion.shortcuts,
                 voice_instruction: completion.voice_instruction,
             },
         };
@@ -171,7 +210,7 @@ impl TranscriptionProvider for Base10TranscriptionProvider {
 
         let response = self
             .client
-            .post(BASE10_PROXY_URL)
+            .post(&self.base_url)
             .json(&worker_request)
             .send()
             .await?;
@@ -180,10 +219,10 @@ impl TranscriptionProvider for Base10TranscriptionProvider {
             let status = response.status();
             let error_text = response.text().await.unwrap_or_default();
             error!("Worker error: {} - {}", status, error_text);
-            return Err(Error::Transcription(format!(
-                "Worker error: {} - {}",
-                status, error_text
-            )));
+            return Err(Error::TranscriptionApi {
+                status,
+                message: format!("Worker error: {} - {}", status, error_text),
+            });
         }
 
         let worker_response: WorkerResponse = response.json().await?;
@@ -198,6 +237,7 @@ impl TranscriptionProvider for Base10TranscriptionProvider {
             duration_ms,
             segments: None,
             completed_text: Some(worker_response.text),
+            provider: None,
         })
     }
 
@@ -206,36 +246,10 @@ impl TranscriptionProvider for Base10TranscriptionProvider {
     }
 }
 
-fn pcm_to_wav(pcm: &[u8], sample_rate: u32, channels: u16) -> Vec<u8> {
-    let bits_per_sample: u16 = 16;
-    let byte_rate = sample_rate * u32::from(channels) * u32::from(bits_per_sample) / 8;
-    let block_align = channels * bits_per_sample / 8;
-    let data_size = pcm.len() as u32;
-    let file_size = 36 + data_size;
-
-    let mut wav = Vec::with_capacity(44 + pcm.len());
-
-    wav.extend_from_slice(b"RIFF");
-    wav.extend_from_slice(&file_size.to_le_bytes());
-    wav.extend_from_slice(b"WAVE");
-    wav.extend_from_slice(b"fmt ");
-    wav.extend_from_slice(&16u32.to_le_bytes());
-    wav.extend_from_slice(&1u16.to_le_bytes());
-    wav.extend_from_slice(&channels.to_le_bytes());
-    wav.extend_from_slice(&sample_rate.to_le_bytes());
-    wav.extend_from_slice(&byte_rate.to_le_bytes());
-    wav.extend_from_slice(&block_align.to_le_bytes());
-    wav.extend_from_slice(&bits_per_sample.to_le_bytes());
-    wav.extend_from_slice(b"data");
-    wav.extend_from_slice(&data_size.to_le_bytes());
-    wav.extend_from_slice(pcm);
-
-    wav
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::audio::pcm_to_wav;
 
     #[test]
     fn test_pcm_to_wav() {
@@ -249,6 +263,27 @@ mod tests {
         assert_eq!(wav.len(), 44 + 32000);
     }
 
+    #[test]
+    fn test_whisper_params_prompt() {
+        let with_prompt = WhisperParams {
+            audio_language: "en".to_string(),
+            prompt: Some("Flowwispr, Base10.".to_string()),
+        };
+        assert_eq!(
+            serde_json::to_value(&with_prompt).unwrap(),
+            serde_json::json!({"audio_language": "en", "prompt": "Flowwispr, Base10."})
+        );
+
+        let without_prompt = WhisperParams {
+            audio_language: "auto".to_string(),
+            prompt: None,
+        };
+        assert_eq!(
+            serde_json::to_value(&without_prompt).unwrap(),
+            serde_json::json!({"audio_language": "auto"})
+        );
+    }
+
     #[test]
     fn test_provider_always_configured() {
         let provider = Base10TranscriptionProvider::new(None);
@@ -6,6 +6,8 @@ use serde::{Deserialize, Serialize};
 use crate::error::Result;
 use crate::modes::WritingMode;
 
+use super::StreamingCompletionProvider;
+
 /// Request for text completion/formatting
 #[derive(Debug, Clone)]
 pub struct CompletionRequest {
@@ -65,6 +67,9 @@ pub struct CompletionResponse {
     pub usage: Option<TokenUsage>,
     /// Model used for completion
     pub model: Option<String>,
+    /// Provider that served the request, when it differs from the configured one
+    #[serde(default)]
+    pub provider: Option<String>,
 }
 
 /// Token usage statistics
@@ -86,4 +91,9 @@ pub trait CompletionProvider: Send + Sync {
 
     /// Check if the provider is configured and ready
     fn is_configured(&self) -> bool;
+
+    /// Streaming interface for this provider, if it supports one
+    fn as_streaming(&self) -> Option<&dyn StreamingCompletionProvider> {
+        None
+    }
 }
@@ -0,0 +1,540 @@
+//! Fallback chains that try transcription and completion providers in order
+//!
+//! A chain moves on to the next provider only when the failover policy accepts the error,
+//! so misconfiguration and bad requests surface immediately instead of being masked.
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use tracing::warn;
+
+use crate::error::{Error, Result};
+
+use super::{
+    CompletionChunk, CompletionProvider, CompletionRequest, CompletionResponse, CompletionStream,
+    StreamingCompletionProvider, TranscriptionProvider, TranscriptionRequest,
+    TranscriptionResponse,
+};
+
+/// Decides which errors move a fallback chain on to the next provider
+#[derive(Clone)]
+pub struct FailoverPolicy {
+    predicate: Arc<dyn Fn(&Error) -> bool + Send + Sync>,
+}
+
+impl FailoverPolicy {
+    /// Create a policy from a predicate returning true for errors that should fail over
+    pub fn new(predicate: impl Fn(&Error) -> bool + Send + Sync + 'static) -> Self {
+        Self {
+            predicate: Arc::new(predicate),
+        }
+    }
+
+    /// Check whether an error should trigger failover
+    pub fn should_failover(&self, error: &Error) -> bool {
+        (self.predicate)(error)
+    }
+}
+
+impl Default for FailoverPolicy {
+    /// Fail over on network errors and 5xx responses only
+    fn default() -> Self {
+        Self::new(is_transient_error)
+    }
+}
+
+/// Network failures and server-side (5xx) API errors
+pub fn is_transient_error(error: &Error) -> bool {
+    match error {
+        Error::Network(e) => e.status().is_none_or(|status| status.is_server_error()),
+        Error::TranscriptionApi { status, .. } | Error::CompletionApi { status, .. } => {
+            status.is_server_error()
+        }
+        _ => false,
+    }
+}
+
+/// Transcription provider that tries each configured provider in order
+pub struct FallbackTranscriptionProvider {
+    providers: Vec<Arc<dyn TranscriptionProvider>>,
+    policy: FailoverPolicy,
+}
+
+impl FallbackTranscriptionProvider {
+    /// Create a chain from providers in priority order
+    pub fn new(providers: Vec<Arc<dyn TranscriptionProvider>>) -> Self {
+        Self {
+            providers,
+            policy: FailoverPolicy::default(),
+        }
+    }
+
+    /// Set the policy deciding which errors trigger failover
+    pub fn with_policy(mut self, policy: FailoverPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+}
+
+#[async_trait]
+impl TranscriptionProvider for FallbackTranscriptionProvider {
+    fn name(&self) -> &'static str {
+        "Fallback"
+    }
+
+    async fn transcribe(&self, request: TranscriptionRequest) -> Result<TranscriptionResponse> {
+        let mut last_error = None;
+
+        for provider in self.providers.iter().filter(|p| p.is_configured()) {
+            match provider.transcribe(request.clone()).await {
+                Ok(mut response) => {
+                    if last_error.is_some() {
+                        warn!(
+                            "Transcription served by fallback provider {}",
+                            provider.name()
+                        );
+                    }
+                    response
+                        .provider
+                        .get_or_insert_with(|| provider.name().to_string());
+                    return Ok(response);
+                }
+                Err(e) if self.policy.should_failover(&e) => {
+                    warn!("{} failed, trying next provider: {}", provider.name(), e);
+                    last_error = Some(e);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        Err(last_error.unwrap_or_else(|| {
+            Error::ProviderNotConfigured("No transcription provider configured".to_string())
+        }))
+    }
+
+    fn is_configured(&self) -> bool {
+        self.providers.iter().any(|p| p.is_configured())
+    }
+
+    /// Whether the provider that will be tried first formats the text
+    /// A response served after failover may still come back without a completion
+    fn completes_text(&self) -> bool {
+        self.providers
+            .iter()
+            .find(|p| p.is_configured())
+            .is_some_and(|p| p.completes_text())
+    }
+}
+
+/// Completion provider that tries each configured provider in order
+pub struct FallbackCompletionProvider {
+    providers: Vec<Arc<dyn CompletionProvider>>,
+    policy: FailoverPolicy,
+}
+
+impl FallbackCompletionProvider {
+    /// Create a chain from providers in priority order
+    pub fn new(providers: Vec<Arc<dyn CompletionProvider>>) -> Self {
+        Self {
+            providers,
+            policy: FailoverPolicy::default(),
+        }
+    }
+
+    /// Set the policy deciding which errors trigger failover
+    pub fn with_policy(mut self, policy: FailoverPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+}
+
+#[async_trait]
+impl CompletionProvider for FallbackCompletionProvider {
+    /// Named after the primary provider, which serves requests unless it fails
+    fn name(&self) -> &'static str {
+        self.providers.first().map_or("Fallback", |p| p.name())
+    }
+
+    async fn complete(&self, request: CompletionRequest) -> Result<CompletionResponse> {
+        let mut last_error = None;
+
+        for provider in self.providers.iter().filter(|p| p.is_configured()) {
+            match provider.complete(request.clone()).await {
+                Ok(mut response) => {
+                    if last_error.is_some() {
+                        warn!("Completion served by fallback provider {}", provider.name());
+                    }
+                    response
+                        .provider
+                        .get_or_insert_with(|| provider.name().to_string());
+                    return Ok(response);
+                }
+                Err(e) if self.policy.should_failover(&e) => {
+                    warn!("{} failed, trying next provider: {}", provider.name(), e);
+                    last_error = Some(e);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        Err(last_error.unwrap_or_else(|| {
+            Error::ProviderNotConfigured("No completion provider configured".to_string())
+        }))
+    }
+
+    fn is_configured(&self) -> bool {
+        self.providers.iter().any(|p| p.is_configured())
+    }
+
+    fn as_streaming(&self) -> Option<&dyn StreamingCompletionProvider> {
+        Some(self)
+    }
+}
+
+#[async_trait]
+impl StreamingCompletionProvider for FallbackCompletionProvider {
+    fn name(&self) -> &'static str {
+        CompletionProvider::name(self)
+    }
+
+    /// Failover only covers opening the stream; errors mid-stream are returned as-is
+    async fn complete_stream(&self, request: CompletionRequest) -> Result<CompletionStream> {
+        let mut last_error = None;
+
+        for provider in self.providers.iter().filter(|p| p.is_configured()) {
+            let result = match provider.as_streaming() {
+                Some(streaming) => streaming.complete_stream(request.clone()).await,
+                // deliver non-streaming providers as a single final chunk
+                None => provider.complete(request.clone()).await.map(|response| {
+                    let chunk = CompletionChunk {
+                        text: response.text,
+                        is_final: true,
+                        usage: response.usage,
+                    };
+                    Box::pin(futures::stream::once(async { Ok(chunk) })) as CompletionStream
+                }),
+            };
+
+            match result {
+                Ok(stream) => return Ok(stream),
+                Err(e) if self.policy.should_failover(&e) => {
+                    warn!("{} failed, trying next provider: {}", provider.name(), e);
+                    last_error = Some(e);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        Err(last_error.unwrap_or_else(|| {
+            Error::ProviderNotConfigured("No completion provider configured".to_string())
+        }))
+    }
+
+    fn is_configured(&self) -> bool {
+        self.providers.iter().any(|p| p.is_configured())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::collect_stream;
+    use crate::types::WritingMode;
+    use parking_lot::Mutex;
+    use reqwest::StatusCode;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Provider returning a scripted result and counting calls
+    struct Scripted {
+        name: &'static str,
+        configured: bool,
+        error: Mutex<Option<Error>>,
+        calls: AtomicUsize,
+    }
+
+    impl Scripted {
+        fn ok(name: &'static str) -> Arc<Self> {
+            Arc::new(Self {
+                name,
+                configured: true,
+                error: Mutex::new(None),
+                calls: AtomicUsize::new(0),
+            })
+        }
+
+        fn failing(name: &'static str, error: Error) -> Arc<Self> {
+            Arc::new(Self {
+                name,
+                configured: true,
+                error: Mutex::new(Some(error)),
+                calls: AtomicUsize::new(0),
+            })
+        }
+
+        fn unconfigured(name: &'static str) -> Arc<Self> {
+            Arc::new(Self {
+                name,
+                configured: false,
+                error: Mutex::new(None),
+                calls: AtomicUsize::new(0),
+            })
+        }
+
+        fn calls(&self) -> usize {
+            self.calls.load(Ordering::SeqCst)
+        }
+
+        fn result(&self) -> Result<String> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            match self.error.lock().take() {
+                Some(e) => Err(e),
+                None => Ok(format!("from {}", self.name)),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl TranscriptionProvider for Scripted {
+        fn name(&self) -> &'static str {
+            self.name
+        }
+
+        async fn transcribe(
+            &self,
+            _request: TranscriptionRequest,
+        ) -> Result<TranscriptionResponse> {
+            Ok(TranscriptionResponse {
+                text: self.result()?,
+                confidence: None,
+                language: None,
+                duration_ms: 0,
+                segments: None,
+                completed_text: None,
+                provider: None,
+            })
+        }
+
+        fn is_configured(&self) -> bool {
+            self.configured
+        }
+    }
+
+    #[async_trait]
+    impl CompletionProvider for Scripted {
+        fn name(&self) -> &'static str {
+            self.name
+        }
+
+        async fn complete(&self, _request: CompletionRequest) -> Result<CompletionResponse> {
+            Ok(CompletionResponse {
+                text: self.result()?,
+                usage: None,
+                model: None,
+                provider: None,
+            })
+        }
+
+        fn is_configured(&self) -> bool {
+            self.configured
+        }
+    }
+
+    fn server_error() -> Error {
+        api_error(503)
+    }
+
+    fn api_error(status: u16) -> Error {
+        Error::TranscriptionApi {
+            status: StatusCode::from_u16(status).unwrap(),
+            message: format!("Worker error: {status} - upstream"),
+        }
+    }
+
+    fn transcription_request() -> TranscriptionRequest {
+        TranscriptionRequest::new(vec![0; 3200], 16000)
+    }
+
+    fn completion_request() -> CompletionRequest {
+        CompletionRequest::new("hello".to_string(), WritingMode::Casual)
+    }
+
+    #[test]
+    fn test_default_policy() {
+        let policy = FailoverPolicy::default();
+
+        assert!(policy.should_failover(&server_error()));
+        assert!(policy.should_failover(&Error::CompletionApi {
+            status: StatusCode::BAD_GATEWAY,
+            message: "OpenRouter API error (502 Bad Gateway): upstream".to_string(),
+        }));
+        assert!(!policy.should_failover(&Error::CompletionApi {
+            status: StatusCode::UNAUTHORIZED,
+            message: "OpenAI API error: 401 Unauthorized - invalid key".to_string(),
+        }));
+        // Only the status counts, not numbers in the message
+        assert!(!policy.should_failover(&Error::Completion(
+            "Parse error: 500 tokens truncated".to_string()
+        )));
+        assert!(!policy.should_failover(&Error::Completion("No completion returned".to_string())));
+        assert!(!policy.should_failover(&Error::ProviderNotConfigured(
+            "OpenAI API key not set".to_string()
+        )));
+        assert!(!policy.should_failover(&Error::Audio("no input".to_string())));
+    }
+
+    #[tokio::test]
+    async fn test_transcription_fails_over_on_server_error() {
+        let primary = Scripted::failing("Primary", server_error());
+        let secondary = Scripted::ok("Secondary");
+        let chain = FallbackTranscriptionProvider::new(vec![primary.clone(), secondary.clone()]);
+
+        let response = chain.transcribe(transcription_request()).await.unwrap();
+        assert_eq!(response.text, "from Secondary");
+        assert_eq!(response.provider.as_deref(), Some("Secondary"));
+        assert_eq!(primary.calls(), 1);
+        assert_eq!(secondary.calls(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_transcription_records_primary_provider() {
+        let primary = Scripted::ok("Primary");
+        let secondary = Scripted::ok("Secondary");
+        let chain = FallbackTranscriptionProvider::new(vec![primary, secondary.clone()]);
+
+        let response = chain.transcribe(transcription_request()).await.unwrap();
+        assert_eq!(response.provider.as_deref(), Some("Primary"));
+        assert_eq!(secondary.calls(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_no_failover_on_non_transient_error() {
+        let primary = Scripted::failing(
+            "Primary",
+            Error::ProviderNotConfigured("API key not set".to_string()),
+        );
+        let secondary = Scripted::ok("Secondary");
+        let chain = FallbackTranscriptionProvider::new(vec![primary, secondary.clone()]);
+
+        let err = chain.transcribe(transcription_request()).await.unwrap_err();
+        assert!(matches!(err, Error::ProviderNotConfigured(_)));
+        assert_eq!(secondary.calls(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_skips_unconfigured_providers() {
+        let primary = Scripted::unconfigured("Primary");
+        let secondary = Scripted::ok("Secondary");
+        let chain = FallbackTranscriptionProvider::new(vec![primary.clone(), secondary]);
+
+        let response = chain.transcribe(transcription_request()).await.unwrap();
+        assert_eq!(response.provider.as_deref(), Some("Secondary"));
+        assert_eq!(primary.calls(), 0);
+
+        let empty = FallbackTranscriptionProvider::new(vec![Scripted::unconfigured("Only")]);
+        assert!(!empty.is_configured());
+        let err = empty.transcribe(transcription_request()).await.unwrap_err();
+        assert!(matches!(err, Error::ProviderNotConfigured(_)));
+    }
+
+    #[tokio::test]
+    async fn test_returns_last_error_when_all_fail() {
+        let chain = FallbackTranscriptionProvider::new(vec![
+            Scripted::failing("Primary", server_error()),
+            Scripted::failing("Secondary", api_error(500)),
+        ]);
+
+        let err = chain.transcribe(transcription_request()).await.unwrap_err();
+        assert!(matches!(
+            err,
+            Error::TranscriptionApi { status, .. } if status == StatusCode::INTERNAL_SERVER_ERROR
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_custom_policy() {
+        let primary = Scripted::failing("Primary", Error::Audio("bad audio".to_string()));
+        let secondary = Scripted::ok("Secondary");
+        let chain = FallbackTranscriptionProvider::new(vec![primary, secondary])
+            .with_policy(FailoverPolicy::new(|_| true));
+
+        let response = chain.transcribe(transcription_request()).await.unwrap();
+        assert_eq!(response.provider.as_deref(), Some("Secondary"));
+    }
+
+    /// Transcription provider that formats the text itself, like the worker
+    struct Completing {
+        configured: bool,
+    }
+
+    #[async_trait]
+    impl TranscriptionProvider for Completing {
+        fn name(&self) -> &'static str {
+            "Worker"
+        }
+
+        async fn transcribe(
+            &self,
+            _request: TranscriptionRequest,
+        ) -> Result<TranscriptionResponse> {
+            Err(server_error())
+        }
+
+        fn is_configured(&self) -> bool {
+            self.configured
+        }
+
+        fn completes_text(&self) -> bool {
+            true
+        }
+    }
+
+    #[test]
+    fn test_completes_text_follows_first_configured_provider() {
+        let chain = FallbackTranscriptionProvider::new(vec![
+            Arc::new(Completing { configured: true }),
+            Scripted::ok("Whisper"),
+        ]);
+        assert!(chain.completes_text());
+
+        let chain = FallbackTranscriptionProvider::new(vec![
+            Arc::new(Completing { configured: false }),
+            Scripted::ok("Whisper"),
+        ]);
+        assert!(!chain.completes_text());
+    }
+
+    #[tokio::test]
+    async fn test_completion_fails_over() {
+        let primary = Scripted::failing(
+            "Primary",
+            Error::CompletionApi {
+                status: StatusCode::from_u16(529).unwrap(),
+                message: "Anthropic API error: 529 - overloaded".to_string(),
+            },
+        );
+        let secondary = Scripted::ok("Secondary");
+        let chain = FallbackCompletionProvider::new(vec![primary, secondary]);
+        assert_eq!(CompletionProvider::name(&chain), "Primary");
+
+        let response = chain.complete(completion_request()).await.unwrap();
+        assert_eq!(response.text, "from Secondary");
+        assert_eq!(response.provider.as_deref(), Some("Secondary"));
+    }
+
+    #[tokio::test]
+    async fn test_completion_stream_wraps_non_streaming_provider() {
+        let chain = FallbackCompletionProvider::new(vec![
+            Scripted::failing("Primary", server_error()),
+            Scripted::ok("Secondary"),
+        ]);
+
+        let stream = chain
+            .as_streaming()
+            .unwrap()
+            .complete_stream(completion_request())
+            .await
+            .unwrap();
+        let response = collect_stream(stream).await.unwrap();
+        assert_eq!(response.text, "from Secondary");
+    }
+}
@@ -3,27 +3,33 @@
 use async_trait::async_trait;
 use base64::Engine;
 use base64::engine::general_purpose::STANDARD;
+use futures::StreamExt;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use tracing::{debug, error};
 
+use crate::audio::{AudioCodec, encode_for_upload};
 use crate::error::{Error, Result};
 use crate::types::WritingMode;
 
 use super::completion::TokenUsage;
+use super::streaming::{GeminiStreamChunk, sse_events};
 use super::{
-    CompletionProvider, CompletionRequest, CompletionResponse, TranscriptionProvider,
-    TranscriptionRequest, TranscriptionResponse,
+    CompletionChunk, CompletionProvider, CompletionRequest, CompletionResponse, CompletionStream,
+    StreamingCompletionProvider, TranscriptionProvider, TranscriptionRequest,
+    TranscriptionResponse,
 };
 
 const GEMINI_API_BASE: &str = "https://generativelanguage.googleapis.com/v1beta";
-const GEMINI_OPENAI_COMPAT_BASE: &str = "https://generativelanguage.googleapis.com/v1beta/openai";
+/// Gemini documents FLAC among its audio formats, but only Vorbis for Ogg
+const UPLOAD_CODEC: AudioCodec = AudioCodec::Flac;
 
 /// Gemini transcription provider (using native API with audio input)
 pub struct GeminiTranscriptionProvider {
     client: Client,
     api_key: Option<String>,
     model: String,
+    base_url: String,
 }
 
 impl GeminiTranscriptionProvider {
@@ -35,6 +41,7 @@ impl GeminiTranscriptionProvider {
             client: Client::new(),
             api_key: key,
             model: "gemini-3-flash-preview".to_string(),
+            base_url: GEMINI_API_BASE.to_string(),
         }
     }
 
@@ -44,6 +51,12 @@ impl GeminiTranscriptionProvider {
         self
     }
 
+    /// Override the API base URL
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into().trim_end_matches('/').to_string();
+        self
+    }
+
     fn api_key(&self) -> Result<&str> {
         self.api_key
             .as_deref()
@@ -53,6 +66,8 @@ impl GeminiTranscriptionProvider {
 
 #[derive(Debug, Serialize)]
 struct GeminiGenerateContentRequest {
+    #[serde(rename = "systemInstruction", skip_serializing_if = "Option::is_none")]
+    system_instruction: Option<GeminiContent>,
     contents: Vec<GeminiContent>,
     #[serde(skip_serializing_if = "Option::is_none")]
     generation_config: Option<GeminiGenerationConfig>,
@@ -86,6 +101,8 @@ struct GeminiInlineData {
 struct GeminiGenerationConfig {
     #[serde(skip_serializing_if = "Option::is_none")]
     temperature: Option<f32>,
+    #[serde(rename = "maxOutputTokens", skip_serializing_if = "Option::is_none")]
+    max_output_tokens: Option<u32>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -129,33 +146,50 @@ impl TranscriptionProvider for GeminiTranscriptionProvider {
     }
 
     async fn transcribe(&self, request: TranscriptionRequest) -> Result<TranscriptionResponse> {
+        crate::offline::ensure_online("Gemini")?;
         let api_key = self.api_key()?;
 
-        // Convert PCM to WAV format for the API
-        let wav_data = pcm_to_wav(&request.audio, request.sample_rate, 1);
-        let audio_base64 = STANDARD.encode(&wav_data);
+        // Compress the recording for upload
+        let (audio, codec) = encode_for_upload(&request.audio, request.sample_rate, UPLOAD_CODEC);
+        let audio_base64 = STANDARD.encode(&audio);
 
         // Build the request with audio input
         let mut parts = vec![GeminiPart::InlineData {
             inline_data: GeminiInlineData {
-                mime_type: "audio/wav".to_string(),
+                mime_type: codec.mime_type().to_string(),
                 data: audio_base64,
             },
         }];
 
-        // Add prompt if provided
-        let prompt_text = if let Some(prompt) = &request.prompt {
-            prompt.clone()
+        let mut prompt_text = if request.translate {
+            "Translate this audio into English. Output only the English translation, nothing else."
+                .to_string()
+        } else if let Some(language) = &request.language {
+            format!(
+                "Transcribe this audio accurately. The speaker is using language code \"{}\". \
+                 Output only the transcribed text, nothing else.",
+                language
+            )
         } else {
             "Transcribe this audio accurately. Output only the transcribed text, nothing else."
                 .to_string()
         };
+
+        // Prompt carries vocabulary the speaker is likely to use
+        if let Some(prompt) = &request.prompt {
+            prompt_text.push_str(
+                "\n\nThe audio may contain these names and terms; spell them exactly as written: ",
+            );
+            prompt_text.push_str(prompt);
+        }
         parts.insert(0, GeminiPart::Text { text: prompt_text });
 
         let generate_request = GeminiGenerateContentRequest {
+            system_instruction: None,
             contents: vec![GeminiContent { parts }],
             generation_config: Some(GeminiGenerationConfig {
                 temperature: Some(0.0), // Low temperature for accurate transcription
+                max_output_tokens: None,
             }),
         };
 
@@ -163,7 +197,7 @@ impl TranscriptionProvider for GeminiTranscriptionProvider {
 
         let url = format!(
             "{}/models/{}:generateContent?key={}",
-            GEMINI_API_BASE, self.model, api_key
+            self.base_url, self.model, api_key
         );
         let response = self
             .client
@@ -177,10 +211,10 @@ impl TranscriptionProvider for GeminiTranscriptionProvider {
             let status = response.status();
             let error_text = response.text().await.unwrap_or_default();
             error!("Gemini API error: {} - {}", status, error_text);
-            return Err(Error::Transcription(format!(
-                "Gemini API error: {} - {}",
-                status, error_text
-            )));
+            return Err(Error::TranscriptionApi {
+                status,
+                message: format!("Gemini API error: {} - {}", status, error_text),
+            });
         }
 
         let gemini_response: GeminiGenerateContentResponse = response.json().await?;
@@ -208,6 +242,7 @@ impl TranscriptionProvider for GeminiTranscriptionProvider {
             duration_ms,
             segments: None,
             completed_text: None,
+            provider: None,
         })
     }
 
@@ -216,11 +251,12 @@ impl TranscriptionProvider for GeminiTranscriptionProvider {
     }
 }
 
-/// Gemini completion provider (using OpenAI-compatible endpoint)
+/// Gemini completion provider (OpenAI-compatible endpoint, native API for streaming)
 pub struct GeminiCompletionProvider {
     client: Client,
     api_key: Option<String>,
     model: String,
+    base_url: String,
 }
 
 impl GeminiCompletionProvider {
@@ -232,6 +268,7 @@ impl GeminiCompletionProvider {
             client: Client::new(),
             api_key: key,
             model: "gemini-3-flash-preview".to_string(),
+            base_url: GEMINI_API_BASE.to_string(),
         }
     }
 
@@ -241,12 +278,32 @@ impl GeminiCompletionProvider {
         self
     }
 
+    /// Override the API base URL
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into().trim_end_matches('/').to_string();
+        self
+    }
+
     fn api_key(&self) -> Result<&str> {
         self.api_key
             .as_deref()
             .ok_or_else(|| Error::ProviderNotConfigured("Gemini API key not set".to_string()))
     }
 
+    /// Resolve the system prompt for a request, including shortcut preservation
+    fn system_prompt(&self, request: &mut CompletionRequest) -> String {
+        let mut system_prompt = request.system_prompt.take().unwrap_or_else(|| {
+            self.build_system_prompt(request.mode, request.app_context.as_deref())
+        });
+
+        // Add shortcut preservation instruction if present
+        if let Some(preservation) = request.shortcut_preservation.take() {
+            system_prompt.push_str(&preservation);
+        }
+
+        system_prompt
+    }
+
     fn build_system_prompt(&self, mode: WritingMode, app_context: Option<&str>) -> String {
         let mut prompt = String::from(
             "You are a text formatter. The user will provide raw transcribed text wrapped in <TRANSCRIPTION> tags. \
@@ -312,17 +369,10 @@ impl CompletionProvider for GeminiCompletionProvider {
         "Gemini"
     }
 
-    async fn complete(&self, request: CompletionRequest) -> Result<CompletionResponse> {
+    async fn complete(&self, mut request: CompletionRequest) -> Result<CompletionResponse> {
+        crate::offline::ensure_online("Gemini")?;
         let api_key = self.api_key()?;
-
-        let mut system_prompt = request.system_prompt.unwrap_or_else(|| {
-            self.build_system_prompt(request.mode, request.app_context.as_deref())
-        });
-
-        // Add shortcut preservation instruction if present
-        if let Some(preservation) = request.shortcut_preservation {
-            system_prompt.push_str(&preservation);
-        }
+        let system_prompt = self.system_prompt(&mut request);
 
         let chat_request = ChatRequest {
             model: self.model.clone(),
@@ -344,7 +394,7 @@ impl CompletionProvider for GeminiCompletionProvider {
 
         let response = self
             .client
-            .post(format!("{}/chat/completions", GEMINI_OPENAI_COMPAT_BASE))
+            .post(format!("{}/openai/chat/completions", self.base_url))
             .header("Authorization", format!("Bearer {}", api_key))
             .header("Content-Type", "application/json")
             .json(&chat_request)
@@ -355,10 +405,10 @@ impl CompletionProvider for GeminiCompletionProvider {
             let status = response.status();
             let error_text = response.text().await.unwrap_or_default();
             error!("Gemini API error: {} - {}", status, error_text);
-            return Err(Error::Completion(format!(
-                "Gemini API error: {} - {}",
-                status, error_text
-            )));
+            return Err(Error::CompletionApi {
+                status,
+                message: format!("Gemini API error: {} - {}", status, error_text),
+            });
         }
 
         let chat_response: ChatResponse = response.json().await?;
@@ -378,50 +428,139 @@ impl CompletionProvider for GeminiCompletionProvider {
                 total_tokens: u.total_tokens,
             }),
             model: Some(chat_response.model),
+            provider: None,
         })
     }
 
     fn is_configured(&self) -> bool {
         self.api_key.is_some()
     }
+
+    fn as_streaming(&self) -> Option<&dyn StreamingCompletionProvider> {
+        Some(self)
+    }
 }
 
-/// Convert raw PCM data to WAV format
-fn pcm_to_wav(pcm: &[u8], sample_rate: u32, channels: u16) -> Vec<u8> {
-    let bits_per_sample: u16 = 16;
-    let byte_rate = sample_rate * u32::from(channels) * u32::from(bits_per_sample) / 8;
-    let block_align = channels * bits_per_sample / 8;
-    let data_size = pcm.len() as u32;
-    let file_size = 36 + data_size;
-
-    let mut wav = Vec::with_capacity(44 + pcm.len());
-
-    // RIFF header
-    wav.extend_from_slice(b"RIFF");
-    wav.extend_from_slice(&file_size.to_le_bytes());
-    wav.extend_from_slice(b"WAVE");
-
-    // fmt chunk
-    wav.extend_from_slice(b"fmt ");
-    wav.extend_from_slice(&16u32.to_le_bytes()); // chunk size
-    wav.extend_from_slice(&1u16.to_le_bytes()); // PCM format
-    wav.extend_from_slice(&channels.to_le_bytes());
-    wav.extend_from_slice(&sample_rate.to_le_bytes());
-    wav.extend_from_slice(&byte_rate.to_le_bytes());
-    wav.extend_from_slice(&block_align.to_le_bytes());
-    wav.extend_from_slice(&bits_per_sample.to_le_bytes());
-
-    // data chunk
-    wav.extend_from_slice(b"data");
-    wav.extend_from_slice(&data_size.to_le_bytes());
-    wav.extend_from_slice(pcm);
-
-    wav
+#[async_trait]
+impl StreamingCompletionProvider for GeminiCompletionProvider {
+    fn name(&self) -> &'static str {
+        "Gemini"
+    }
+
+    async fn complete_stream(&self, mut request: CompletionRequest) -> Result<CompletionStream> {
+        crate::offline::ensure_online("Gemini")?;
+        let api_key = self.api_key()?;
+        let system_prompt = self.system_prompt(&mut request);
+
+        // the OpenAI-compatible endpoint doesn't stream reliably, so use the native API
+        let generate_request = GeminiGenerateContentRequest {
+            system_instruction: Some(GeminiContent {
+                parts: vec![GeminiPart::Text {
+                    text: system_prompt,
+                }],
+            }),
+            contents: vec![GeminiContent {
+                parts: vec![GeminiPart::Text {
+                    text: format!("<TRANSCRIPTION>\n{}\n</TRANSCRIPTION>", request.text),
+                }],
+            }],
+            generation_config: Some(GeminiGenerationConfig {
+                temperature: Some(0.3),
+                max_output_tokens: request.max_tokens,
+            }),
+        };
+
+        debug!("Sending streaming completion request to Gemini");
+
+        let url = format!(
+            "{}/models/{}:streamGenerateContent?alt=sse&key={}",
+            self.base_url, self.model, api_key
+        );
+        let response = self
+            .client
+            .post(&url)
+            .header("Content-Type", "application/json")
+            .json(&generate_request)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response.text().await.unwrap_or_default();
+            error!("Gemini API error: {} - {}", status, error_text);
+            return Err(Error::CompletionApi {
+                status,
+                message: format!("Gemini API error: {} - {}", status, error_text),
+            });
+        }
+
+        let stream = sse_events(response).flat_map(|event| {
+            let chunks = match event.and_then(|e| Ok(serde_json::from_str(&e.data)?)) {
+                Ok(chunk) => gemini_chunks(chunk),
+                Err(e) => vec![Err(e)],
+            };
+            futures::stream::iter(chunks)
+        });
+
+        Ok(Box::pin(stream))
+    }
+
+    fn is_configured(&self) -> bool {
+        self.api_key.is_some()
+    }
+}
+
+/// Map one `streamGenerateContent` response into completion chunks
+///
+/// Gemini has no end-of-stream sentinel; the candidate's `finishReason` marks the last
+/// response, which also carries the final usage metadata.
+fn gemini_chunks(chunk: GeminiStreamChunk) -> Vec<Result<CompletionChunk>> {
+    if let Some(error) = chunk.error {
+        return vec![Err(Error::Completion(format!(
+            "Gemini stream error: {} - {}",
+            error.status, error.message
+        )))];
+    }
+
+    let mut chunks = Vec::new();
+    let mut finished = false;
+
+    for candidate in chunk.candidates.into_iter().take(1) {
+        finished = candidate.finish_reason.is_some();
+        let text: String = candidate
+            .content
+            .map(|c| c.parts.into_iter().filter_map(|p| p.text).collect())
+            .unwrap_or_default();
+        if !text.is_empty() {
+            chunks.push(Ok(CompletionChunk {
+                text,
+                is_final: false,
+                usage: None,
+            }));
+        }
+    }
+
+    if finished {
+        chunks.push(Ok(CompletionChunk {
+            text: String::new(),
+            is_final: true,
+            usage: chunk.usage_metadata.map(|u| TokenUsage {
+                prompt_tokens: u.prompt_token_count,
+                completion_tokens: u.candidates_token_count,
+                total_tokens: u.total_token_count,
+            }),
+        }));
+    }
+
+    chunks
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::audio::pcm_to_wav;
+    use crate::providers::collect_stream;
+    use crate::providers::mock_server::{MockResponse, MockServer};
 
     #[test]
     fn test_pcm_to_wav() {
@@ -455,6 +594,74 @@ mod tests {
         assert!(prompt.contains("exactly as it would be typed"));
     }
 
+    #[tokio::test]
+    async fn test_complete_stream() {
+        let server = MockServer::start(MockResponse::sse(&[
+            r#"data: {"candidates":[{"content":{"parts":[{"text":"Hello"}],"role":"model"}}],"modelVersion":"gemini-3-flash-preview"}"#,
+            r#"data: {"candidates":[{"content":{"parts":[{"text":", world."}],"role":"model"},"finishReason":"STOP"}],"usageMetadata":{"promptTokenCount":15,"candidatesTokenCount":3,"totalTokenCount":18}}"#,
+        ]))
+        .await;
+
+        let provider = GeminiCompletionProvider::new(Some("gem-test".to_string()))
+            .with_model("gemini-test")
+            .with_base_url(&server.base_url);
+        let stream = provider
+            .complete_stream(
+                CompletionRequest::new("hello world".to_string(), WritingMode::Casual)
+                    .with_shortcut_preservation("\nKEEP THIS"),
+            )
+            .await
+            .unwrap();
+
+        let response = collect_stream(stream).await.unwrap();
+        assert_eq!(response.text, "Hello, world.");
+        let usage = response.usage.unwrap();
+        assert_eq!(usage.prompt_tokens, 15);
+        assert_eq!(usage.completion_tokens, 3);
+        assert_eq!(usage.total_tokens, 18);
+
+        let requests = server.requests();
+        assert_eq!(
+            requests[0].path,
+            "/models/gemini-test:streamGenerateContent?alt=sse&key=gem-test"
+        );
+        let body = requests[0].json();
+        assert!(
+            body["systemInstruction"]["parts"][0]["text"]
+                .as_str()
+                .unwrap()
+                .ends_with("KEEP THIS")
+        );
+        assert!(
+            body["contents"][0]["parts"][0]["text"]
+                .as_str()
+                .unwrap()
+                .contains("hello world")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_complete_stream_error() {
+        let server = MockServer::start(MockResponse::sse(&[
+            r#"data: {"error":{"code":503,"message":"The model is overloaded.","status":"UNAVAILABLE"}}"#,
+        ]))
+        .await;
+
+        let provider = GeminiCompletionProvider::new(Some("gem-test".to_string()))
+            .with_base_url(&server.base_url);
+        let stream = provider
+            .complete_stream(CompletionRequest::new(
+                "hello".to_string(),
+                WritingMode::Casual,
+            ))
+            .await
+            .unwrap();
+
+        let err = collect_stream(stream).await.unwrap_err();
+        assert!(matches!(err, Error::Completion(_)));
+        assert!(err.to_string().contains("UNAVAILABLE"));
+    }
+
     #[test]
     fn test_provider_not_configured() {
         let provider = GeminiTranscriptionProvider::new(None);
@@ -0,0 +1,219 @@
+//! Rule-based formatting that runs entirely on-device
+//!
+//! Stands in for an LLM in offline mode: drops filler words, tidies capitalization
+//! and end punctuation, and applies a light version of each writing mode's style.
+
+use async_trait::async_trait;
+
+use crate::error::Result;
+use crate::modes::WritingMode;
+
+use super::{CompletionProvider, CompletionRequest, CompletionResponse};
+
+/// Hesitations removed from dictated text
+const FILLER_WORDS: &[&str] = &["um", "umm", "uh", "uhh", "uhm", "erm", "hmm"];
+
+/// Informal contractions spelled out in formal mode
+const FORMAL_REPLACEMENTS: &[(&str, &str)] = &[
+    ("gonna", "going to"),
+    ("wanna", "want to"),
+    ("gotta", "have to"),
+    ("kinda", "kind of"),
+    ("sorta", "sort of"),
+    ("dunno", "don't know"),
+];
+
+/// Format dictated text for a writing mode without a language model
+pub fn format_text(text: &str, mode: WritingMode) -> String {
+    let mut words: Vec<String> = Vec::new();
+
+    for word in text.split_whitespace() {
+        let (prefix, core, suffix) = split_punctuation(word);
+        let lower = core.to_lowercase();
+
+        if FILLER_WORDS.contains(&lower.as_str()) {
+            // Keep a sentence end that was attached to the filler ("done, um.")
+            if let Some(end) = suffix.chars().find(|c| matches!(c, '.' | '!' | '?'))
+                && let Some(previous) = words.last_mut()
+            {
+                let trimmed = previous.trim_end_matches([',', ';']).len();
+                previous.truncate(trimmed);
+                if !ends_sentence(previous) {
+                    previous.push(end);
+                }
+            }
+            continue;
+        }
+
+        let replacement = match mode {
+            WritingMode::Formal => FORMAL_REPLACEMENTS
+                .iter()
+                .find(|(informal, _)| *informal == lower)
+                .map(|(_, formal)| *formal),
+            _ => None,
+        };
+        let core = match replacement {
+            Some(formal) if core.starts_with(char::is_uppercase) => capitalize(formal),
+            Some(formal) => formal.to_string(),
+            None if lower == "i" || lower.starts_with("i'") => capitalize(core),
+            None => core.to_string(),
+        };
+        words.push(format!("{prefix}{core}{suffix}"));
+    }
+
+    let mut sentence_start = true;
+    for word in &mut words {
+        if sentence_start {
+            *word = match mode {
+                WritingMode::VeryCasual if !is_acronym(word) => lowercase_first(word),
+                WritingMode::VeryCasual => word.clone(),
+                _ => capitalize(word),
+            };
+        }
+        sentence_start = ends_sentence(word);
+    }
+
+    let mut output = words.join(" ");
+    let ends_with_word = output.ends_with(|c: char| c.is_alphanumeric());
+    match mode {
+        WritingMode::Formal | WritingMode::Casual if ends_with_word => output.push('.'),
+        WritingMode::VeryCasual if output.ends_with('.') && !output.ends_with("..") => {
+            output.pop();
+        }
+        WritingMode::Excited if output.ends_with('.') && !output.ends_with("..") => {
+            output.pop();
+            output.push('!');
+        }
+        WritingMode::Excited if ends_with_word => output.push('!'),
+        _ => {}
+    }
+    output
+}
+
+/// Split leading and trailing punctuation from a word
+fn split_punctuation(word: &str) -> (&str, &str, &str) {
+    let start = word
+        .find(|c: char| c.is_alphanumeric())
+        .unwrap_or(word.len());
+    let end = word
+        .rfind(|c: char| c.is_alphanumeric())
+        .map(|i| i + word[i..].chars().next().map_or(1, char::len_utf8))
+        .unwrap_or(start)
+        .max(start);
+    (&word[..start], &word[start..end], &word[end..])
+}
+
+fn ends_sentence(word: &str) -> bool {
+    word.trim_end_matches(['"', '\'', ')'])
+        .ends_with(['.', '!', '?'])
+}
+
+fn is_acronym(word: &str) -> bool {
+    let letters: Vec<char> = word.chars().filter(|c| c.is_alphabetic()).collect();
+    letters.len() > 1 && letters.iter().all(|c| c.is_uppercase())
+}
+
+fn capitalize(word: &str) -> String {
+    match word.find(char::is_alphabetic) {
+        Some(i) => {
+            let mut chars = word[i..].chars();
+            let first = chars.next().unwrap();
+            format!("{}{}{}", &word[..i], first.to_uppercase(), chars.as_str())
+        }
+        None => word.to_string(),
+    }
+}
+
+fn lowercase_first(word: &str) -> String {
+    match word.find(char::is_alphabetic) {
+        Some(i) => {
+            let mut chars = word[i..].chars();
+            let first = chars.next().unwrap();
+            format!("{}{}{}", &word[..i], first.to_lowercase(), chars.as_str())
+        }
+        None => word.to_string(),
+    }
+}
+
+/// Completion provider backed by `format_text`; never touches the network
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LocalFormattingProvider;
+
+impl LocalFormattingProvider {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+#[async_trait]
+impl CompletionProvider for LocalFormattingProvider {
+    fn name(&self) -> &'static str {
+        "Local Formatting"
+    }
+
+    async fn complete(&self, request: CompletionRequest) -> Result<CompletionResponse> {
+        Ok(CompletionResponse {
+            text: format_text(&request.text, request.mode),
+            usage: None,
+            model: None,
+            provider: None,
+        })
+    }
+
+    fn is_configured(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_removes_fillers_and_capitalizes() {
+        assert_eq!(
+            format_text("um so i think, uh, we ship it", WritingMode::Casual),
+            "So I think, we ship it."
+        );
+        assert_eq!(
+            format_text("that's done, um. next is i'm testing", WritingMode::Casual),
+            "That's done. Next is I'm testing."
+        );
+        assert_eq!(format_text("  ", WritingMode::Formal), "");
+    }
+
+    #[test]
+    fn test_mode_styles() {
+        let text = "Gonna be late. See you at 5";
+        assert_eq!(
+            format_text(text, WritingMode::Formal),
+            "Going to be late. See you at 5."
+        );
+        assert_eq!(
+            format_text(text, WritingMode::VeryCasual),
+            "gonna be late. see you at 5"
+        );
+        assert_eq!(
+            format_text("We won the game.", WritingMode::Excited),
+            "We won the game!"
+        );
+        assert_eq!(
+            format_text("NASA called. ok", WritingMode::VeryCasual),
+            "NASA called. ok"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_provider_formats_request() {
+        let provider = LocalFormattingProvider::new();
+        let response = provider
+            .complete(CompletionRequest::new(
+                "uh hello there".to_string(),
+                WritingMode::Casual,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.text, "Hello there.");
+        assert!(provider.is_configured());
+    }
+}
@@ -8,23 +8,182 @@
 //! - Best: Distilled large-v3 (~750MB) - best quality available
 
 use crate::error::{Error, Result};
+use crate::whisper_models::{self, DownloadProgress, ModelFiles, ModelSpec};
 use async_trait::async_trait;
-use candle_core::{Device, IndexOp, Tensor};
+use candle_core::{Device, Tensor};
 use candle_nn::VarBuilder;
 use candle_transformers::models::whisper::{self as m, Config, audio};
 use candle_transformers::quantized_var_builder;
-use hf_hub::{Repo, RepoType, api::sync::Api};
 use parking_lot::Mutex;
+use rand::SeedableRng;
+use rand::rngs::StdRng;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokenizers::Tokenizer;
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 
-use super::{TranscriptionProvider, TranscriptionRequest, TranscriptionResponse};
+use super::whisper_decode::{
+    DecodingOptions, SearchParams, TimedSpan, decode_with_fallback, split_timestamped,
+};
+use super::whisper_decoder::CachedDecoder;
+use super::{
+    TranscriptionProvider, TranscriptionRequest, TranscriptionResponse, TranscriptionSegment,
+    WordTiming,
+};
 
 // Include the mel filter bytes (80 mel bins for Whisper)
 const MEL_FILTER_BYTES: &[u8] = include_bytes!("../../melfilters.bytes");
 
+/// Load the bundled 80-bin mel filterbank
+pub(crate) fn mel_filters() -> Vec<f32> {
+    let mut mel_filters = vec![0f32; MEL_FILTER_BYTES.len() / 4];
+    <byteorder::LittleEndian as byteorder::ByteOrder>::read_f32_into(
+        MEL_FILTER_BYTES,
+        &mut mel_filters,
+    );
+    mel_filters
+}
+
+/// Languages supported by multilingual Whisper checkpoints (ISO 639-1 code, English name)
+const LANGUAGES: &[(&str, &str)] = &[
+    ("en", "english"),
+    ("zh", "chinese"),
+    ("de", "german"),
+    ("es", "spanish"),
+    ("ru", "russian"),
+    ("ko", "korean"),
+    ("fr", "french"),
+    ("ja", "japanese"),
+    ("pt", "portuguese"),
+    ("tr", "turkish"),
+    ("pl", "polish"),
+    ("ca", "catalan"),
+    ("nl", "dutch"),
+    ("ar", "arabic"),
+    ("sv", "swedish"),
+    ("it", "italian"),
+    ("id", "indonesian"),
+    ("hi", "hindi"),
+    ("fi", "finnish"),
+    ("vi", "vietnamese"),
+    ("he", "hebrew"),
+    ("uk", "ukrainian"),
+    ("el", "greek"),
+    ("ms", "malay"),
+    ("cs", "czech"),
+    ("ro", "romanian"),
+    ("da", "danish"),
+    ("hu", "hungarian"),
+    ("ta", "tamil"),
+    ("no", "norwegian"),
+    ("th", "thai"),
+    ("ur", "urdu"),
+    ("hr", "croatian"),
+    ("bg", "bulgarian"),
+    ("lt", "lithuanian"),
+    ("la", "latin"),
+    ("mi", "maori"),
+    ("ml", "malayalam"),
+    ("cy", "welsh"),
+    ("sk", "slovak"),
+    ("te", "telugu"),
+    ("fa", "persian"),
+    ("lv", "latvian"),
+    ("bn", "bengali"),
+    ("sr", "serbian"),
+    ("az", "azerbaijani"),
+    ("sl", "slovenian"),
+    ("kn", "kannada"),
+    ("et", "estonian"),
+    ("mk", "macedonian"),
+    ("br", "breton"),
+    ("eu", "basque"),
+    ("is", "icelandic"),
+    ("hy", "armenian"),
+    ("ne", "nepali"),
+    ("mn", "mongolian"),
+    ("bs", "bosnian"),
+    ("kk", "kazakh"),
+    ("sq", "albanian"),
+    ("sw", "swahili"),
+    ("gl", "galician"),
+    ("mr", "marathi"),
+    ("pa", "punjabi"),
+    ("si", "sinhala"),
+    ("km", "khmer"),
+    ("sn", "shona"),
+    ("yo", "yoruba"),
+    ("so", "somali"),
+    ("af", "afrikaans"),
+    ("oc", "occitan"),
+    ("ka", "georgian"),
+    ("be", "belarusian"),
+    ("tg", "tajik"),
+    ("sd", "sindhi"),
+    ("gu", "gujarati"),
+    ("am", "amharic"),
+    ("yi", "yiddish"),
+    ("lo", "lao"),
+    ("uz", "uzbek"),
+    ("fo", "faroese"),
+    ("ht", "haitian creole"),
+    ("ps", "pashto"),
+    ("tk", "turkmen"),
+    ("nn", "nynorsk"),
+    ("mt", "maltese"),
+    ("sa", "sanskrit"),
+    ("lb", "luxembourgish"),
+    ("my", "myanmar"),
+    ("bo", "tibetan"),
+    ("tl", "tagalog"),
+    ("mg", "malagasy"),
+    ("as", "assamese"),
+    ("tt", "tatar"),
+    ("haw", "hawaiian"),
+    ("ln", "lingala"),
+    ("ha", "hausa"),
+    ("ba", "bashkir"),
+    ("jw", "javanese"),
+    ("su", "sundanese"),
+    ("yue", "cantonese"),
+];
+
+/// Multilingual checkpoints have 51865+ tokens; English-only ones stop at 51864
+const MULTILINGUAL_VOCAB_SIZE: usize = 51865;
+
+/// Resolve a language hint (code or English name, case-insensitive) to a Whisper language code
+/// Returns None for "auto" or unsupported languages
+pub fn whisper_language_code(hint: &str) -> Option<&'static str> {
+    let hint = hint.trim().to_lowercase();
+    LANGUAGES
+        .iter()
+        .find(|(code, name)| *code == hint || *name == hint)
+        .map(|(code, _)| *code)
+}
+
+/// Pick the most likely language from first-step decoder logits
+/// candidates: (language code, token id) pairs present in the tokenizer
+/// Returns the language code and its probability among the candidates
+fn pick_language(
+    logits: &[f32],
+    candidates: &[(&'static str, u32)],
+) -> Option<(&'static str, f32)> {
+    let scores: Vec<(&'static str, f32)> = candidates
+        .iter()
+        .filter_map(|(code, id)| logits.get(*id as usize).map(|logit| (*code, *logit)))
+        .collect();
+    let max = scores
+        .iter()
+        .map(|(_, logit)| *logit)
+        .fold(f32::NEG_INFINITY, f32::max);
+    let total: f32 = scores.iter().map(|(_, logit)| (logit - max).exp()).sum();
+
+    scores
+        .into_iter()
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(code, logit)| (code, (logit - max).exp() / total))
+}
+
 /// Whisper model variants with clear speed/quality tradeoffs
 ///
 /// Models ordered by speed (fastest first):
@@ -95,6 +254,35 @@ impl WhisperModel {
         }
     }
 
+    /// Registry spec for this model
+    /// Files that older versions downloaded into the flat models directory are adopted
+    pub fn spec(&self, models_dir: &Path) -> ModelSpec {
+        let (repo, revision) = self.model_id();
+        let spec = ModelSpec::hugging_face(repo, revision).with_id(self.as_str());
+
+        if self.is_quantized() {
+            let (config, tokenizer, weights) = (
+                "config-tiny-en.json",
+                "tokenizer-tiny-en.json",
+                "model-tiny-en-q80.gguf",
+            );
+            return spec
+                .with_files(config, tokenizer, weights)
+                .with_legacy_files(ModelFiles {
+                    config: models_dir.join(config),
+                    tokenizer: models_dir.join(tokenizer),
+                    weights: models_dir.join(weights),
+                });
+        }
+
+        let name = repo.split('/').next_back().unwrap_or(repo);
+        spec.with_legacy_files(ModelFiles {
+            config: models_dir.join(format!("{}-config.json", name)),
+            tokenizer: models_dir.join(format!("{}-tokenizer.json", name)),
+            weights: models_dir.join(format!("{}-model.safetensors", name)),
+        })
+    }
+
     /// Approximate download size in MB
     pub fn size_mb(&self) -> usize {
         match self {
@@ -140,27 +328,40 @@ impl WhisperModel {
 }
 
 /// Model can be either quantized or full-precision
-enum Model {
-    Normal(m::model::Whisper),
-    Quantized(m::quantized_model::Whisper),
+/// Audio encoder from either checkpoint format
+/// The text decoder is shared (see `CachedDecoder`)
+enum Encoder {
+    Normal(m::model::AudioEncoder),
+    Quantized(m::quantized_model::AudioEncoder),
+}
+
+impl Encoder {
+    fn forward(&mut self, mel: &Tensor) -> candle_core::Result<Tensor> {
+        match self {
+            Encoder::Normal(encoder) => encoder.forward(mel, true),
+            Encoder::Quantized(encoder) => encoder.forward(mel, true),
+        }
+    }
 }
 
 /// Whisper engine state
 struct WhisperEngine {
-    model: Model,
+    encoder: Encoder,
+    decoder: CachedDecoder,
     tokenizer: Tokenizer,
     config: Config,
     device: Device,
     mel_filters: Vec<f32>,
+    /// Sampling source for temperature fallback (fixed seed for reproducible output)
+    rng: StdRng,
 }
 
 impl WhisperEngine {
-    async fn new(model_size: WhisperModel, models_dir: &Path) -> Result<Self> {
-        std::fs::create_dir_all(models_dir).map_err(|e| {
-            Error::Transcription(format!("Failed to create models directory: {}", e))
-        })?;
-
-        info!("Initializing Whisper {:?} model", model_size);
+    fn new(files: &ModelFiles) -> Result<Self> {
+        info!(
+            "Initializing Whisper model from {}",
+            files.weights.display()
+        );
 
         // Setup device - try Metal (Apple Silicon GPU) first, fallback to CPU
         let device = if cfg!(target_os = "macos") {
@@ -179,203 +380,134 @@ impl WhisperEngine {
         };
 
         // Load model based on type (quantized or normal)
-        let (model, config, tokenizer) = if model_size.is_quantized() {
-            Self::load_quantized_model(model_size, models_dir, &device).await?
+        let (encoder, decoder, config, tokenizer) = if files.is_quantized() {
+            Self::load_quantized_model(files, &device)?
         } else {
-            Self::load_normal_model(model_size, models_dir, &device).await?
+            Self::load_normal_model(files, &device)?
         };
 
-        // Load mel filters
-        let mut mel_filters = vec![0f32; MEL_FILTER_BYTES.len() / 4];
-        <byteorder::LittleEndian as byteorder::ByteOrder>::read_f32_into(
-            MEL_FILTER_BYTES,
-            &mut mel_filters,
-        );
+        let mel_filters = mel_filters();
 
         info!("Whisper model loaded successfully");
 
         Ok(Self {
-            model,
+            encoder,
+            decoder,
             tokenizer,
             config,
             device,
             mel_filters,
+            rng: StdRng::seed_from_u64(299792458),
         })
     }
 
-    async fn load_normal_model(
-        model_size: WhisperModel,
-        models_dir: &Path,
+    fn load_normal_model(
+        files: &ModelFiles,
         device: &Device,
-    ) -> Result<(Model, Config, Tokenizer)> {
-        let (config_path, tokenizer_path, weights_path) =
-            Self::ensure_normal_model_files(model_size, models_dir).await?;
-
+    ) -> Result<(Encoder, CachedDecoder, Config, Tokenizer)> {
         // Load config
         let config: Config = serde_json::from_str(
-            &std::fs::read_to_string(&config_path)
+            &std::fs::read_to_string(&files.config)
                 .map_err(|e| Error::Transcription(format!("Failed to read config: {}", e)))?,
         )
         .map_err(|e| Error::Transcription(format!("Failed to parse config: {}", e)))?;
 
         // Load tokenizer
-        let tokenizer = Tokenizer::from_file(tokenizer_path)
+        let tokenizer = Tokenizer::from_file(&files.tokenizer)
             .map_err(|e| Error::Transcription(format!("Failed to load tokenizer: {}", e)))?;
 
         // Load model weights
         info!("Loading model weights...");
         let vb = unsafe {
-            VarBuilder::from_mmaped_safetensors(&[weights_path], m::DTYPE, device)
+            VarBuilder::from_mmaped_safetensors(&[&files.weights], m::DTYPE, device)
                 .map_err(|e| Error::Transcription(format!("Failed to load weights: {}", e)))?
         };
-        let model = m::model::Whisper::load(&vb, config.clone())
-            .map_err(|e| Error::Transcription(format!("Failed to load model: {}", e)))?;
-
-        Ok((Model::Normal(model), config, tokenizer))
+        // Only Candle's encoder is kept; decoding uses the KV-cached decoder
+        let encoder = m::model::Whisper::load(&vb, config.clone())
+            .map_err(|e| Error::Transcription(format!("Failed to load model: {}", e)))?
+            .encoder;
+        let decoder = CachedDecoder::load(vb.pp("model.decoder"), &config)
+            .map_err(|e| Error::Transcription(format!("Failed to load decoder: {}", e)))?;
+
+        Ok((Encoder::Normal(encoder), decoder, config, tokenizer))
     }
 
-    async fn load_quantized_model(
-        model_size: WhisperModel,
-        models_dir: &Path,
+    fn load_quantized_model(
+        files: &ModelFiles,
         device: &Device,
-    ) -> Result<(Model, Config, Tokenizer)> {
-        let (config_path, tokenizer_path, weights_path) =
-            Self::ensure_quantized_model_files(model_size, models_dir).await?;
-
+    ) -> Result<(Encoder, CachedDecoder, Config, Tokenizer)> {
         // Load config
         let config: Config = serde_json::from_str(
-            &std::fs::read_to_string(&config_path)
+            &std::fs::read_to_string(&files.config)
                 .map_err(|e| Error::Transcription(format!("Failed to read config: {}", e)))?,
         )
         .map_err(|e| Error::Transcription(format!("Failed to parse config: {}", e)))?;
 
         // Load tokenizer
-        let tokenizer = Tokenizer::from_file(tokenizer_path)
+        let tokenizer = Tokenizer::from_file(&files.tokenizer)
             .map_err(|e| Error::Transcription(format!("Failed to load tokenizer: {}", e)))?;
 
         // Load quantized model weights (GGUF format)
         info!("Loading quantized model weights...");
-        let vb = quantized_var_builder::VarBuilder::from_gguf(&weights_path, device)
+        let vb = quantized_var_builder::VarBuilder::from_gguf(&files.weights, device)
             .map_err(|e| Error::Transcription(format!("Failed to load GGUF weights: {}", e)))?;
-        let model = m::quantized_model::Whisper::load(&vb, config.clone())
-            .map_err(|e| Error::Transcription(format!("Failed to load quantized model: {}", e)))?;
-
-        Ok((Model::Quantized(model), config, tokenizer))
+        let encoder = m::quantized_model::Whisper::load(&vb, config.clone())
+            .map_err(|e| Error::Transcription(format!("Failed to load quantized model: {}", e)))?
+            .encoder;
+        let decoder =
+            CachedDecoder::load_quantized(vb.pp("model.decoder"), &config).map_err(|e| {
+                Error::Transcription(format!("Failed to load quantized decoder: {}", e))
+            })?;
+
+        Ok((Encoder::Quantized(encoder), decoder, config, tokenizer))
     }
 
-    async fn ensure_normal_model_files(
-        model_size: WhisperModel,
-        models_dir: &Path,
-    ) -> Result<(PathBuf, PathBuf, PathBuf)> {
-        let (model_id, revision) = model_size.model_id();
-        let model_name = model_id.split('/').next_back().unwrap();
-
-        let config_path = models_dir.join(format!("{}-config.json", model_name));
-        let tokenizer_path = models_dir.join(format!("{}-tokenizer.json", model_name));
-        let weights_path = models_dir.join(format!("{}-model.safetensors", model_name));
-
-        // Check if all files exist
-        if config_path.exists() && tokenizer_path.exists() && weights_path.exists() {
-            info!("Model files already cached");
-            return Ok((config_path, tokenizer_path, weights_path));
-        }
-
-        // Download from HuggingFace
-        info!(
-            "Downloading {} model files ({}MB)...",
-            model_id,
-            model_size.size_mb()
-        );
-
-        let api = Api::new()
-            .map_err(|e| Error::Transcription(format!("Failed to init HuggingFace API: {}", e)))?;
-        let repo = api.repo(Repo::with_revision(
-            model_id.to_string(),
-            RepoType::Model,
-            revision.to_string(),
-        ));
-
-        // Download config
-        info!("Downloading config.json");
-        let config_file = repo
-            .get("config.json")
-            .map_err(|e| Error::Transcription(format!("Failed to download config: {}", e)))?;
-        std::fs::copy(&config_file, &config_path)
-            .map_err(|e| Error::Transcription(format!("Failed to save config: {}", e)))?;
-
-        // Download tokenizer
-        info!("Downloading tokenizer.json");
-        let tokenizer_file = repo
-            .get("tokenizer.json")
-            .map_err(|e| Error::Transcription(format!("Failed to download tokenizer: {}", e)))?;
-        std::fs::copy(&tokenizer_file, &tokenizer_path)
-            .map_err(|e| Error::Transcription(format!("Failed to save tokenizer: {}", e)))?;
-
-        // Download model weights
-        info!("Downloading model weights (this may take a while)");
-        let weights_file = repo
-            .get("model.safetensors")
-            .map_err(|e| Error::Transcription(format!("Failed to download weights: {}", e)))?;
-        std::fs::copy(&weights_file, &weights_path)
-            .map_err(|e| Error::Transcription(format!("Failed to save weights: {}", e)))?;
-
-        info!("Model downloaded successfully");
-
-        Ok((config_path, tokenizer_path, weights_path))
-    }
-
-    async fn ensure_quantized_model_files(
-        _model_size: WhisperModel,
-        models_dir: &Path,
-    ) -> Result<(PathBuf, PathBuf, PathBuf)> {
-        // Quantized models use different file naming from lmz/candle-whisper
-        let config_path = models_dir.join("config-tiny-en.json");
-        let tokenizer_path = models_dir.join("tokenizer-tiny-en.json");
-        let weights_path = models_dir.join("model-tiny-en-q80.gguf");
-
-        // Check if all files exist
-        if config_path.exists() && tokenizer_path.exists() && weights_path.exists() {
-            info!("Quantized model files already cached");
-            return Ok((config_path, tokenizer_path, weights_path));
-        }
-
-        // Download from lmz/candle-whisper
-        info!("Downloading quantized model files (~15MB)...");
-
-        let api = Api::new()
-            .map_err(|e| Error::Transcription(format!("Failed to init HuggingFace API: {}", e)))?;
-        let repo = api.repo(Repo::new("lmz/candle-whisper".to_string(), RepoType::Model));
-
-        // Download config
-        info!("Downloading config-tiny-en.json");
-        let config_file = repo
-            .get("config-tiny-en.json")
-            .map_err(|e| Error::Transcription(format!("Failed to download config: {}", e)))?;
-        std::fs::copy(&config_file, &config_path)
-            .map_err(|e| Error::Transcription(format!("Failed to save config: {}", e)))?;
-
-        // Download tokenizer
-        info!("Downloading tokenizer-tiny-en.json");
-        let tokenizer_file = repo
-            .get("tokenizer-tiny-en.json")
-            .map_err(|e| Error::Transcription(format!("Failed to download tokenizer: {}", e)))?;
-        std::fs::copy(&tokenizer_file, &tokenizer_path)
-            .map_err(|e| Error::Transcription(format!("Failed to save tokenizer: {}", e)))?;
-
-        // Download quantized weights (GGUF format)
-        info!("Downloading quantized model weights");
-        let weights_file = repo
-            .get("model-tiny-en-q80.gguf")
-            .map_err(|e| Error::Transcription(format!("Failed to download GGUF weights: {}", e)))?;
-        std::fs::copy(&weights_file, &weights_path)
-            .map_err(|e| Error::Transcription(format!("Failed to save weights: {}", e)))?;
-
-        info!("Quantized model downloaded successfully");
+    /// Multilingual checkpoints accept language and task tokens
+    fn is_multilingual(&self) -> bool {
+        self.config.vocab_size >= MULTILINGUAL_VOCAB_SIZE
+    }
 
-        Ok((config_path, tokenizer_path, weights_path))
+    /// Detect the spoken language from the first 30s window
+    /// Runs a single decoder step on `<|startoftranscript|>` and compares language token logits
+    fn detect_language(&mut self, mel: &Tensor) -> Result<&'static str> {
+        let sot_token = self.token_id(m::SOT_TOKEN)?;
+        let candidates: Vec<(&'static str, u32)> = LANGUAGES
+            .iter()
+            .filter_map(|(code, _)| {
+                self.tokenizer
+                    .token_to_id(&format!("<|{}|>", code))
+                    .map(|id| (*code, id))
+            })
+            .collect();
+
+        let logits = (|| -> candle_core::Result<Vec<f32>> {
+            let (_, _, content_frames) = mel.dims3()?;
+            let mel_segment = mel.narrow(2, 0, usize::min(content_frames, m::N_FRAMES))?;
+            let audio_features = self.encoder.forward(&mel_segment)?;
+            self.decoder.start_window(&audio_features, 1)?;
+            self.decoder.logits(&[sot_token])
+        })()
+        .map_err(|e| Error::Transcription(format!("Language detection failed: {}", e)))?;
+
+        let (language, probability) = pick_language(&logits, &candidates)
+            .ok_or_else(|| Error::Transcription("No language tokens in tokenizer".to_string()))?;
+        debug!("Detected language: {} (p = {:.2})", language, probability);
+        Ok(language)
     }
 
-    fn transcribe_pcm(&mut self, pcm_data: &[f32]) -> Result<String> {
+    /// Transcribe 16kHz mono audio
+    /// language: explicit language code, or None to auto-detect (multilingual models only)
+    /// translate: translate to English instead of transcribing (multilingual models only)
+    /// initial_prompt: text the transcript is conditioned on, e.g. custom vocabulary
+    /// Windows judged to be silence (no-speech) produce no text
+    fn transcribe_pcm(
+        &mut self,
+        pcm_data: &[f32],
+        language: Option<&'static str>,
+        translate: bool,
+        initial_prompt: Option<&str>,
+        options: &DecodingOptions,
+    ) -> Result<LocalTranscript> {
         debug!("Transcribing {} samples", pcm_data.len());
 
         // Convert to mel spectrogram
@@ -392,211 +524,241 @@ impl WhisperEngine {
         )
         .map_err(|e| Error::Transcription(format!("Failed to create mel tensor: {}", e)))?;
 
+        // English-only models have no language or translate tokens
+        let multilingual = self.is_multilingual();
+        let language = if multilingual {
+            match language {
+                Some(language) => language,
+                None => self.detect_language(&mel)?,
+            }
+        } else {
+            if language.is_some_and(|l| l != "en") || translate {
+                warn!("English-only Whisper model, ignoring language hint and translate task");
+            }
+            "en"
+        };
+
         // Get token IDs upfront to avoid borrow issues
         let sot_token = self.token_id(m::SOT_TOKEN)?;
-        let transcribe_token = self.token_id(m::TRANSCRIBE_TOKEN)?;
+        let task_token = if multilingual && translate {
+            self.token_id(m::TRANSLATE_TOKEN)?
+        } else {
+            self.token_id(m::TRANSCRIBE_TOKEN)?
+        };
         let eot_token = self.token_id(m::EOT_TOKEN)?;
         let no_timestamps_token = self.token_id(m::NO_TIMESTAMPS_TOKEN)?;
 
-        // Decode audio based on model type
-        let segments = match &mut self.model {
-            Model::Normal(model) => Self::decode_audio_normal(
-                model,
-                &mel,
-                &self.tokenizer,
-                &self.config,
-                &self.device,
-                sot_token,
-                transcribe_token,
-                eot_token,
-                no_timestamps_token,
-            )?,
-            Model::Quantized(model) => Self::decode_audio_quantized(
-                model,
-                &mel,
-                &self.tokenizer,
-                &self.config,
-                &self.device,
-                sot_token,
-                transcribe_token,
-                eot_token,
-                no_timestamps_token,
-            )?,
+        // Timestamp tokens follow <|notimestamps|>
+        let timestamp_begin = no_timestamps_token + 1;
+
+        // <|startoftranscript|> [<|lang|>] <|transcribe|>/<|translate|> [<|notimestamps|>]
+        let mut task_tokens = vec![sot_token];
+        if multilingual {
+            task_tokens.push(self.token_id(&format!("<|{}|>", language))?);
+        }
+        task_tokens.push(task_token);
+        if !options.timestamps {
+            task_tokens.push(no_timestamps_token);
+        }
+
+        // Preceded by <|startofprev|> and the initial prompt, if any
+        let context = match initial_prompt {
+            Some(prompt) => self.context_tokens(prompt, eot_token)?,
+            None => Vec::new(),
+        };
+        let n_ctx = self.config.max_target_positions;
+        let (prompt_tokens, sot_index) = build_prompt(&context, &task_tokens, n_ctx);
+
+        // Never generate the config's suppressed tokens or special tokens other than
+        // <|endoftext|>; timestamps only when decoding with them
+        let mut suppress = self.config.suppress_tokens.clone();
+        suppress.extend(eot_token + 1..timestamp_begin);
+        if !options.timestamps {
+            suppress.extend(timestamp_begin..self.config.vocab_size as u32);
+        }
+        let no_speech_token = m::NO_SPEECH_TOKENS
+            .iter()
+            .find_map(|token| self.tokenizer.token_to_id(token));
+
+        let params = SearchParams {
+            prompt: &prompt_tokens,
+            sot_index,
+            eot_token,
+            no_speech_token,
+            suppress: &suppress,
+            max_tokens: usize::min(n_ctx / 2, n_ctx.saturating_sub(prompt_tokens.len())),
+            timestamp_begin: options.timestamps.then_some(timestamp_begin),
         };
 
-        // Join segments
-        let text = segments.join(" ");
-        Ok(text.trim().to_string())
+        // The mel is zero-padded past the audio; windows may extend into the padding
+        let content_frames = pcm_data.len() / m::HOP_LENGTH;
+        let (segments, confidence) = self.decode_audio(&mel, content_frames, &params, options)?;
+
+        let text = segments
+            .iter()
+            .map(|segment| segment.text.as_str())
+            .collect::<Vec<_>>()
+            .join(" ");
+        Ok(LocalTranscript {
+            text,
+            language,
+            confidence,
+            segments,
+        })
     }
 
-    #[allow(clippy::too_many_arguments)]
-    fn decode_audio_normal(
-        model: &mut m::model::Whisper,
+    /// Decode the mel spectrogram window by window
+    /// With timestamps, each window starts where the last complete segment ended;
+    /// otherwise windows are consecutive 30s chunks
+    /// Returns the segments and the overall confidence
+    fn decode_audio(
+        &mut self,
         mel: &Tensor,
-        tokenizer: &Tokenizer,
-        config: &Config,
-        device: &Device,
-        sot_token: u32,
-        transcribe_token: u32,
-        eot_token: u32,
-        no_timestamps_token: u32,
-    ) -> Result<Vec<String>> {
-        let (_, _, content_frames) = mel
+        content_frames: usize,
+        params: &SearchParams,
+        options: &DecodingOptions,
+    ) -> Result<(Vec<TranscriptionSegment>, Option<f32>)> {
+        let (_, _, total_frames) = mel
             .dims3()
             .map_err(|e| Error::Transcription(format!("Invalid mel dimensions: {}", e)))?;
+        let tokenizer = &self.tokenizer;
+        let eot_token = params.eot_token;
+        // Text tokens only: timestamps are not always flagged as special
+        let detokenize = |tokens: &[u32]| {
+            let text_tokens: Vec<u32> = tokens.iter().copied().filter(|&t| t < eot_token).collect();
+            tokenizer
+                .decode(&text_tokens, true)
+                .map_err(|e| Error::Transcription(format!("Failed to decode tokens: {}", e)))
+        };
+        let frame_secs = m::HOP_LENGTH as f64 / m::SAMPLE_RATE as f64;
+        // Two prefixes per beam: the current step and the one being extended
+        let cache_capacity = 2 * options.beam_size.max(1) + 1;
         let mut segments = Vec::new();
+        let mut sum_logprob = 0.0;
+        let mut token_count = 0;
         let mut seek = 0;
 
         while seek < content_frames {
-            let segment_size = usize::min(content_frames - seek, m::N_FRAMES);
+            let segment_size = usize::min(total_frames - seek, m::N_FRAMES);
             let mel_segment = mel
                 .narrow(2, seek, segment_size)
                 .map_err(|e| Error::Transcription(format!("Failed to narrow mel: {}", e)))?;
 
-            let audio_features = model
+            let audio_features = self
                 .encoder
-                .forward(&mel_segment, true)
+                .forward(&mel_segment)
                 .map_err(|e| Error::Transcription(format!("Encoder failed: {}", e)))?;
-
-            let mut tokens = vec![sot_token, transcribe_token, no_timestamps_token];
-            let max_tokens = config.max_target_positions / 2;
-
-            for i in 0..max_tokens {
-                let tokens_t = Tensor::new(tokens.as_slice(), device)
-                    .map_err(|e| Error::Transcription(format!("Failed to create tokens: {}", e)))?
-                    .unsqueeze(0)
-                    .map_err(|e| Error::Transcription(format!("Failed to unsqueeze: {}", e)))?;
-
-                let decoder_output = model
-                    .decoder
-                    .forward(&tokens_t, &audio_features, i == 0)
-                    .map_err(|e| Error::Transcription(format!("Decoder failed: {}", e)))?;
-
-                let (_, seq_len, _) = decoder_output.dims3().map_err(|e| {
-                    Error::Transcription(format!("Invalid decoder output dims: {}", e))
-                })?;
-
-                let tail = decoder_output
-                    .i((..1, seq_len - 1..))
-                    .map_err(|e| Error::Transcription(format!("Failed to index tail: {}", e)))?;
-
-                let logits = model
-                    .decoder
-                    .final_linear(&tail)
-                    .map_err(|e| Error::Transcription(format!("Failed final linear: {}", e)))?
-                    .i(0)
-                    .map_err(|e| Error::Transcription(format!("Failed to index: {}", e)))?
-                    .i(0)
-                    .map_err(|e| Error::Transcription(format!("Failed to index: {}", e)))?;
-
-                let next_token = logits
-                    .argmax(0)
-                    .map_err(|e| Error::Transcription(format!("Failed argmax: {}", e)))?
-                    .to_scalar::<u32>()
-                    .map_err(|e| Error::Transcription(format!("Failed to_scalar: {}", e)))?;
-
-                if next_token == eot_token {
-                    break;
-                }
-                tokens.push(next_token);
+            self.decoder
+                .start_window(&audio_features, cache_capacity)
+                .map_err(|e| Error::Transcription(format!("Decoder failed: {}", e)))?;
+
+            let decoder = &mut self.decoder;
+            let mut logits = |tokens: &[u32]| {
+                decoder
+                    .logits(tokens)
+                    .map_err(|e| Error::Transcription(format!("Decoder failed: {}", e)))
+            };
+
+            let window =
+                decode_with_fallback(&mut logits, &detokenize, params, options, &mut self.rng)?;
+
+            if window.is_silence(options) {
+                debug!(
+                    "Skipping silent window (no-speech p = {:.2}, avg logprob = {:.2})",
+                    window.no_speech_prob, window.avg_logprob
+                );
+                seek += segment_size;
+                continue;
             }
 
-            let text = tokenizer
-                .decode(&tokens[3..], true)
-                .map_err(|e| Error::Transcription(format!("Failed to decode tokens: {}", e)))?;
+            let (spans, advance) = match params.timestamp_begin {
+                Some(timestamp_begin) => split_timestamped(&window, timestamp_begin, segment_size),
+                None => (
+                    vec![TimedSpan {
+                        start_secs: 0.0,
+                        end_secs: segment_size as f64 * frame_secs,
+                        tokens: window.tokens,
+                        logprobs: window.logprobs,
+                    }],
+                    segment_size,
+                ),
+            };
+
+            let window_start = seek as f64 * frame_secs;
+            let window_end = usize::min(seek + segment_size, content_frames) as f64 * frame_secs;
+            for span in spans {
+                let text = detokenize(&span.tokens)?;
+                let text = text.trim();
+                if text.is_empty() {
+                    continue;
+                }
+                sum_logprob += span.logprobs.iter().sum::<f64>();
+                token_count += span.logprobs.len();
+
+                // Words are runs of tokens, each starting with a space
+                let pieces = span
+                    .tokens
+                    .iter()
+                    .map(|&token| detokenize(&[token]))
+                    .collect::<Result<Vec<_>>>()?;
+                let mut words = Vec::new();
+                for range in word_boundaries(&pieces) {
+                    let word = detokenize(&span.tokens[range.clone()])?;
+                    let word = word.trim();
+                    if word.is_empty() {
+                        continue;
+                    }
+                    let probability = span.logprobs[range.clone()]
+                        .iter()
+                        .map(|logprob| logprob.exp())
+                        .sum::<f64>()
+                        / range.len() as f64;
+                    words.push((word.to_string(), probability as f32));
+                }
 
-            if !text.trim().is_empty() {
-                segments.push(text.trim().to_string());
+                let start_ms = ((window_start + span.start_secs).min(window_end) * 1000.0) as u64;
+                let end_ms = ((window_start + span.end_secs).min(window_end) * 1000.0) as u64;
+                let end_ms = end_ms.max(start_ms);
+                segments.push(TranscriptionSegment {
+                    text: text.to_string(),
+                    start_ms,
+                    end_ms,
+                    confidence: mean_probability(span.logprobs.iter().sum(), span.logprobs.len()),
+                    words: estimate_word_timings(words, start_ms, end_ms),
+                });
             }
-            seek += segment_size;
+            seek += advance;
         }
 
-        Ok(segments)
+        Ok((segments, mean_probability(sum_logprob, token_count)))
     }
 
-    #[allow(clippy::too_many_arguments)]
-    fn decode_audio_quantized(
-        model: &mut m::quantized_model::Whisper,
-        mel: &Tensor,
-        tokenizer: &Tokenizer,
-        config: &Config,
-        device: &Device,
-        sot_token: u32,
-        transcribe_token: u32,
-        eot_token: u32,
-        no_timestamps_token: u32,
-    ) -> Result<Vec<String>> {
-        let (_, _, content_frames) = mel
-            .dims3()
-            .map_err(|e| Error::Transcription(format!("Invalid mel dimensions: {}", e)))?;
-        let mut segments = Vec::new();
-        let mut seek = 0;
-
-        while seek < content_frames {
-            let segment_size = usize::min(content_frames - seek, m::N_FRAMES);
-            let mel_segment = mel
-                .narrow(2, seek, segment_size)
-                .map_err(|e| Error::Transcription(format!("Failed to narrow mel: {}", e)))?;
-
-            let audio_features = model
-                .encoder
-                .forward(&mel_segment, true)
-                .map_err(|e| Error::Transcription(format!("Encoder failed: {}", e)))?;
-
-            let mut tokens = vec![sot_token, transcribe_token, no_timestamps_token];
-            let max_tokens = config.max_target_positions / 2;
-
-            for i in 0..max_tokens {
-                let tokens_t = Tensor::new(tokens.as_slice(), device)
-                    .map_err(|e| Error::Transcription(format!("Failed to create tokens: {}", e)))?
-                    .unsqueeze(0)
-                    .map_err(|e| Error::Transcription(format!("Failed to unsqueeze: {}", e)))?;
-
-                let decoder_output = model
-                    .decoder
-                    .forward(&tokens_t, &audio_features, i == 0)
-                    .map_err(|e| Error::Transcription(format!("Decoder failed: {}", e)))?;
-
-                let (_, seq_len, _) = decoder_output.dims3().map_err(|e| {
-                    Error::Transcription(format!("Invalid decoder output dims: {}", e))
-                })?;
-
-                let tail = decoder_output
-                    .i((..1, seq_len - 1..))
-                    .map_err(|e| Error::Transcription(format!("Failed to index tail: {}", e)))?;
-
-                let logits = model
-                    .decoder
-                    .final_linear(&tail)
-                    .map_err(|e| Error::Transcription(format!("Failed final linear: {}", e)))?
-                    .i(0)
-                    .map_err(|e| Error::Transcription(format!("Failed to index: {}", e)))?
-                    .i(0)
-                    .map_err(|e| Error::Transcription(format!("Failed to index: {}", e)))?;
-
-                let next_token = logits
-                    .argmax(0)
-                    .map_err(|e| Error::Transcription(format!("Failed argmax: {}", e)))?
-                    .to_scalar::<u32>()
-                    .map_err(|e| Error::Transcription(format!("Failed to_scalar: {}", e)))?;
-
-                if next_token == eot_token {
-                    break;
-                }
-                tokens.push(next_token);
-            }
-
-            let text = tokenizer
-                .decode(&tokens[3..], true)
-                .map_err(|e| Error::Transcription(format!("Failed to decode tokens: {}", e)))?;
-
-            if !text.trim().is_empty() {
-                segments.push(text.trim().to_string());
-            }
-            seek += segment_size;
+    /// `<|startofprev|>` followed by the text tokens of the initial prompt
+    /// Empty when the prompt has no text or the tokenizer lacks the token
+    fn context_tokens(&self, prompt: &str, eot_token: u32) -> Result<Vec<u32>> {
+        let Some(sot_prev) = self.tokenizer.token_to_id(SOT_PREV_TOKEN) else {
+            warn!(
+                "Tokenizer has no {}, ignoring initial prompt",
+                SOT_PREV_TOKEN
+            );
+            return Ok(Vec::new());
+        };
+        let encoding = self
+            .tokenizer
+            .encode(format!(" {}", prompt.trim()), false)
+            .map_err(|e| Error::Transcription(format!("Failed to encode prompt: {}", e)))?;
+
+        // Special tokens (ids from <|endoftext|> up) must not leak in from user text
+        let text_tokens: Vec<u32> = encoding
+            .get_ids()
+            .iter()
+            .copied()
+            .filter(|&id| id < eot_token)
+            .collect();
+        if text_tokens.is_empty() {
+            return Ok(Vec::new());
         }
-
-        Ok(segments)
+        Ok(std::iter::once(sot_prev).chain(text_tokens).collect())
     }
 
     fn token_id(&self, token: &str) -> Result<u32> {
@@ -606,26 +768,130 @@ impl WhisperEngine {
     }
 }
 
+/// Text, language, confidence and timed segments of one transcription
+struct LocalTranscript {
+    text: String,
+    language: &'static str,
+    confidence: Option<f32>,
+    segments: Vec<TranscriptionSegment>,
+}
+
+/// Geometric mean token probability, None without tokens
+fn mean_probability(sum_logprob: f64, token_count: usize) -> Option<f32> {
+    (token_count > 0).then(|| (sum_logprob / token_count as f64).exp() as f32)
+}
+
+/// Token index ranges of the words in a run of decoded token pieces
+/// A piece starting with whitespace begins a new word (Whisper's BPE marks word starts
+/// with a leading space); other pieces, punctuation included, extend the current word
+fn word_boundaries(pieces: &[String]) -> Vec<std::ops::Range<usize>> {
+    let mut ranges: Vec<std::ops::Range<usize>> = Vec::new();
+    for (index, piece) in pieces.iter().enumerate() {
+        let starts_word = piece.starts_with(char::is_whitespace);
+        match ranges.last_mut() {
+            Some(range) if !starts_word => range.end = index + 1,
+            _ => ranges.push(index..index + 1),
+        }
+    }
+    ranges
+}
+
+/// Spread a segment's duration over its words in proportion to their length
+/// Whisper timestamps are per segment; word times are an estimate within it
+/// words: (text, probability) pairs
+fn estimate_word_timings(words: Vec<(String, f32)>, start_ms: u64, end_ms: u64) -> Vec<WordTiming> {
+    let total_chars: usize = words.iter().map(|(w, _)| w.chars().count()).sum();
+    if total_chars == 0 {
+        return Vec::new();
+    }
+
+    let duration = end_ms.saturating_sub(start_ms) as f64;
+    let mut chars_before = 0;
+    words
+        .into_iter()
+        .map(|(word, probability)| {
+            let chars = word.chars().count();
+            let start = start_ms + (duration * chars_before as f64 / total_chars as f64) as u64;
+            chars_before += chars;
+            let end = start_ms + (duration * chars_before as f64 / total_chars as f64) as u64;
+            WordTiming {
+                text: word,
+                start_ms: start,
+                end_ms: end,
+                confidence: Some(probability),
+            }
+        })
+        .collect()
+}
+
+/// Prompt the decoder with previous-context tokens
+const SOT_PREV_TOKEN: &str = "<|startofprev|>";
+
+/// Prepend previous context (`<|startofprev|>` + text tokens) to the task tokens
+/// Like Whisper, keeps at most the last `n_ctx / 2 - 1` context tokens so half the
+/// decoder context stays free for the transcript
+/// Returns the prompt and the index of `<|startoftranscript|>`
+fn build_prompt(context: &[u32], task_tokens: &[u32], n_ctx: usize) -> (Vec<u32>, usize) {
+    let Some((&sot_prev, text)) = context.split_first() else {
+        return (task_tokens.to_vec(), 0);
+    };
+    let keep = usize::min(text.len(), (n_ctx / 2).saturating_sub(1));
+    let mut prompt = Vec::with_capacity(1 + keep + task_tokens.len());
+    prompt.push(sot_prev);
+    prompt.extend_from_slice(&text[text.len() - keep..]);
+    let sot_index = prompt.len();
+    prompt.extend_from_slice(task_tokens);
+    (prompt, sot_index)
+}
+
 /// Local Whisper transcription provider with Metal + Accelerate acceleration
 pub struct LocalWhisperTranscriptionProvider {
     engine: Arc<Mutex<Option<WhisperEngine>>>,
-    model_size: WhisperModel,
+    spec: ModelSpec,
     models_dir: PathBuf,
+    decoding: DecodingOptions,
+    progress: Arc<DownloadProgress>,
 }
 
 impl LocalWhisperTranscriptionProvider {
     /// Create a new provider with a model size
     pub fn new(model_size: WhisperModel, models_dir: PathBuf) -> Self {
+        Self::from_spec(model_size.spec(&models_dir), models_dir)
+    }
+
+    /// Create a provider for any registry model (custom repo or imported directory)
+    pub fn from_spec(spec: ModelSpec, models_dir: PathBuf) -> Self {
         Self {
             engine: Arc::new(Mutex::new(None)),
-            model_size,
+            spec,
             models_dir,
+            decoding: DecodingOptions::default(),
+            progress: Arc::new(DownloadProgress::new()),
         }
     }
 
-    /// Load the model (call once before first use)
+    /// Set beam size, temperature fallback and no-speech thresholds
+    pub fn with_decoding_options(mut self, decoding: DecodingOptions) -> Self {
+        self.decoding = decoding;
+        self
+    }
+
+    /// Report model download progress here
+    pub fn with_progress(mut self, progress: Arc<DownloadProgress>) -> Self {
+        self.progress = progress;
+        self
+    }
+
+    /// Registry id of the model this provider loads
+    pub fn model_id(&self) -> &str {
+        &self.spec.id
+    }
+
+    /// Load the model (call once before first use), installing it if needed
     pub async fn load_model(&self) -> Result<()> {
-        let engine = WhisperEngine::new(self.model_size, &self.models_dir).await?;
+        let files =
+            whisper_models::ensure_installed(&self.spec, &self.models_dir, &self.progress).await?;
+        let engine = WhisperEngine::new(&files)?;
         *self.engine.lock() = Some(engine);
         Ok(())
     }
@@ -634,42 +900,6 @@ impl LocalWhisperTranscriptionProvider {
     pub fn is_model_loaded(&self) -> bool {
         self.engine.lock().is_some()
     }
-
-    /// Resample audio using linear interpolation
-    fn resample_audio(samples: &[f32], from_rate: u32, to_rate: u32) -> Vec<f32> {
-        if from_rate == to_rate {
-            return samples.to_vec();
-        }
-
-        let ratio = to_rate as f32 / from_rate as f32;
-        let output_len = (samples.len() as f32 * ratio) as usize;
-        let mut output = Vec::with_capacity(output_len);
-
-        for i in 0..output_len {
-            let src_pos = i as f32 / ratio;
-            let src_idx = src_pos as usize;
-            let frac = src_pos - src_idx as f32;
-
-            if src_idx + 1 < samples.len() {
-                let sample = samples[src_idx] * (1.0 - frac) + samples[src_idx + 1] * frac;
-                output.push(sample);
-            } else if src_idx < samples.len() {
-                output.push(samples[src_idx]);
-            }
-        }
-
-        output
-    }
-
-    /// Convert PCM bytes (16-bit little-endian) to f32 normalized audio
-    fn pcm_bytes_to_f32(audio_bytes: &[u8]) -> Vec<f32> {
-        let mut samples = Vec::with_capacity(audio_bytes.len() / 2);
-        for chunk in audio_bytes.chunks_exact(2) {
-            let sample = i16::from_le_bytes([chunk[0], chunk[1]]);
-            samples.push(sample as f32 / 32768.0);
-        }
-        samples
-    }
 }
 
 #[async_trait]
@@ -684,12 +914,20 @@ impl TranscriptionProvider for LocalWhisperTranscriptionProvider {
             self.load_model().await?;
         }
 
+        let language =
+            match request.language.as_deref().map(str::trim) {
+                None | Some("") | Some("auto") => None,
+                Some(hint) => Some(whisper_language_code(hint).ok_or_else(|| {
+                    Error::Transcription(format!("Unsupported language: {}", hint))
+                })?),
+            };
+
         // Convert audio bytes to f32 format expected by whisper (mono at 16kHz)
-        let mut audio_data = Self::pcm_bytes_to_f32(&request.audio);
+        let mut audio_data = crate::audio::pcm_to_samples(&request.audio);
 
         // Resample to 16kHz if needed
         if request.sample_rate != 16000 {
-            audio_data = Self::resample_audio(&audio_data, request.sample_rate, 16000);
+            audio_data = crate::audio::resample(&audio_data, request.sample_rate, 16000);
         }
 
         // Transcribe
@@ -698,21 +936,274 @@ impl TranscriptionProvider for LocalWhisperTranscriptionProvider {
             .as_mut()
             .ok_or_else(|| Error::Transcription("Whisper engine not initialized".to_string()))?;
 
-        let text = engine.transcribe_pcm(&audio_data)?;
-
-        debug!("Local Whisper transcription: {}", text);
+        let transcript = engine.transcribe_pcm(
+            &audio_data,
+            language,
+            request.translate,
+            request.prompt.as_deref(),
+            &self.decoding,
+        )?;
+
+        debug!(
+            "Local Whisper transcription ({}, {} segments): {}",
+            transcript.language,
+            transcript.segments.len(),
+            transcript.text
+        );
 
         Ok(TranscriptionResponse {
-            text,
-            confidence: None,
-            language: Some("en".to_string()),
+            text: transcript.text,
+            confidence: transcript.confidence,
+            language: Some(transcript.language.to_string()),
             duration_ms: request.audio.len() as u64 * 1000 / request.sample_rate as u64,
-            segments: None,
+            segments: Some(transcript.segments),
             completed_text: None,
+            provider: None,
         })
     }
 
     fn is_configured(&self) -> bool {
         self.models_dir.exists()
     }
+
+    fn is_local(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::whisper_decoder::tests::{random_whisper, tiny_config};
+    use super::*;
+    use std::collections::HashMap;
+    use std::time::Instant;
+    use tokenizers::models::wordlevel::WordLevel;
+    use tokenizers::pre_tokenizers::whitespace::WhitespaceSplit;
+
+    const FIXTURE_WAV: &str = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/synthetic_speech_16k.wav"
+    );
+
+    /// Word-level tokenizer with the English-only special token ids
+    fn test_tokenizer(vocab_size: usize) -> Tokenizer {
+        let specials: HashMap<u32, &str> = [
+            (50256, m::EOT_TOKEN),
+            (50257, m::SOT_TOKEN),
+            (50357, m::TRANSLATE_TOKEN),
+            (50358, m::TRANSCRIBE_TOKEN),
+            (50360, SOT_PREV_TOKEN),
+            (50361, m::NO_SPEECH_TOKENS[0]),
+            (50362, m::NO_TIMESTAMPS_TOKEN),
+        ]
+        .into_iter()
+        .collect();
+        let vocab = (0..vocab_size as u32)
+            .map(|id| match specials.get(&id) {
+                Some(token) => (token.to_string(), id),
+                None => (format!("t{}", id), id),
+            })
+            .collect();
+        let model = WordLevel::builder()
+            .vocab(vocab)
+            .unk_token(m::EOT_TOKEN.to_string())
+            .build()
+            .unwrap();
+        let mut tokenizer = Tokenizer::new(model);
+        tokenizer.with_pre_tokenizer(Some(WhitespaceSplit));
+        tokenizer
+    }
+
+    /// Engine with a tiny randomly initialised model (no download needed)
+    fn test_engine() -> WhisperEngine {
+        let config = tiny_config();
+        let (whisper, decoder) = random_whisper(&config);
+        WhisperEngine {
+            encoder: Encoder::Normal(whisper.encoder),
+            decoder,
+            tokenizer: test_tokenizer(config.vocab_size),
+            config,
+            device: Device::Cpu,
+            mel_filters: mel_filters(),
+            rng: StdRng::seed_from_u64(0),
+        }
+    }
+
+    fn load_fixture() -> Vec<f32> {
+        let reader = hound::WavReader::open(FIXTURE_WAV).unwrap();
+        assert_eq!(reader.spec().sample_rate, 16000);
+        assert_eq!(reader.spec().channels, 1);
+        reader
+            .into_samples::<i16>()
+            .map(|s| s.unwrap() as f32 / 32768.0)
+            .collect()
+    }
+
+    /// Real-time factor (processing time / audio duration) of the local pipeline on the
+    /// bundled fixture, with the checkpoint directory in FLOW_BENCH_WHISPER_MODEL (laid
+    /// out as for an imported model). Fails when either beam size is slower than real time.
+    /// Run with: FLOW_BENCH_WHISPER_MODEL=<dir> cargo test -r bench_local_whisper_rtf -- --ignored
+    #[test]
+    #[ignore = "benchmark, needs FLOW_BENCH_WHISPER_MODEL"]
+    fn bench_local_whisper_rtf() {
+        let dir = std::env::var_os("FLOW_BENCH_WHISPER_MODEL")
+            .map(PathBuf::from)
+            .expect("FLOW_BENCH_WHISPER_MODEL must name a Whisper checkpoint directory");
+        let spec = ModelSpec::local(&dir).unwrap();
+        let mut engine = WhisperEngine::new(&ModelFiles {
+            config: dir.join(&spec.config_file),
+            tokenizer: dir.join(&spec.tokenizer_file),
+            weights: dir.join(&spec.weights_file),
+        })
+        .unwrap();
+
+        let pcm = load_fixture();
+        let audio_secs = pcm.len() as f64 / 16000.0;
+        let rtf: Vec<(usize, f64)> = [1, 5]
+            .into_iter()
+            .map(|beam_size| {
+                let options = DecodingOptions::default().with_beam_size(beam_size);
+                let start = Instant::now();
+                engine
+                    .transcribe_pcm(&pcm, None, false, None, &options)
+                    .unwrap();
+                (beam_size, start.elapsed().as_secs_f64() / audio_secs)
+            })
+            .collect();
+        assert!(
+            rtf.iter().all(|&(_, rtf)| rtf < 1.0),
+            "slower than real time (beam size, RTF): {:?}",
+            rtf
+        );
+    }
+
+    #[test]
+    fn test_build_prompt_with_context() {
+        let task = [50257, 50358, 50362];
+        assert_eq!(build_prompt(&[], &task, 448), (task.to_vec(), 0));
+
+        // Context is truncated from the front, keeping the most recent tokens
+        let context: Vec<u32> = std::iter::once(50360).chain(1..=10).collect();
+        let (prompt, sot_index) = build_prompt(&context, &task, 10);
+        assert_eq!(prompt, vec![50360, 7, 8, 9, 10, 50257, 50358, 50362]);
+        assert_eq!(prompt[sot_index], 50257);
+    }
+
+    #[test]
+    fn test_transcribe_with_initial_prompt() {
+        let mut engine = test_engine();
+        let options = DecodingOptions {
+            temperatures: vec![0.0],
+            ..DecodingOptions::default()
+        };
+        let pcm = vec![0.0f32; 16000];
+
+        let context = engine.context_tokens("t5 t6 <|endoftext|>", 50256).unwrap();
+        assert_eq!(context, vec![50360, 5, 6]);
+        assert!(engine.context_tokens("  ", 50256).unwrap().is_empty());
+
+        // The vocabulary context must fit alongside the transcript
+        let prompt = (0..300)
+            .map(|i| format!("t{}", i))
+            .collect::<Vec<_>>()
+            .join(" ");
+        engine
+            .transcribe_pcm(&pcm, None, false, Some(&prompt), &options)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_estimate_word_timings() {
+        let words = estimate_word_timings(
+            vec![
+                ("hi".to_string(), 0.9),
+                ("there".to_string(), 0.8),
+                ("world".to_string(), 0.3),
+            ],
+            1000,
+            2200,
+        );
+        let timings: Vec<(&str, u64, u64)> = words
+            .iter()
+            .map(|w| (w.text.as_str(), w.start_ms, w.end_ms))
+            .collect();
+        assert_eq!(
+            timings,
+            vec![
+                ("hi", 1000, 1200),
+                ("there", 1200, 1700),
+                ("world", 1700, 2200)
+            ]
+        );
+        assert_eq!(words[2].confidence, Some(0.3));
+        assert!(estimate_word_timings(Vec::new(), 0, 100).is_empty());
+    }
+
+    #[test]
+    fn test_word_boundaries() {
+        let pieces: Vec<String> = [" Hello", ",", " wor", "ld", "!", " ok"]
+            .iter()
+            .map(|p| p.to_string())
+            .collect();
+        assert_eq!(word_boundaries(&pieces), vec![0..2, 2..5, 5..6]);
+        // A leading piece without a space still starts a word
+        assert_eq!(word_boundaries(&pieces[3..]), vec![0..2, 2..3]);
+        assert!(word_boundaries(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_transcribe_segments_are_ordered() {
+        let mut engine = test_engine();
+        let options = DecodingOptions {
+            temperatures: vec![0.0],
+            no_speech_threshold: 1.0,
+            ..DecodingOptions::default()
+        };
+        let pcm = &load_fixture()[..3 * 16000];
+        let audio_ms = pcm.len() as u64 * 1000 / 16000;
+
+        let transcript = engine
+            .transcribe_pcm(pcm, None, false, None, &options)
+            .unwrap();
+        for segment in &transcript.segments {
+            assert!(segment.start_ms <= segment.end_ms);
+            assert!(segment.end_ms <= audio_ms);
+            assert!(segment.confidence.is_some_and(|c| (0.0..=1.0).contains(&c)));
+            for word in &segment.words {
+                assert!(word.confidence.is_some_and(|c| (0.0..=1.0).contains(&c)));
+            }
+        }
+        if !transcript.segments.is_empty() {
+            assert!(transcript.confidence.is_some());
+        }
+        for pair in transcript.segments.windows(2) {
+            assert!(pair[0].start_ms <= pair[1].start_ms);
+        }
+    }
+
+    #[test]
+    fn test_whisper_language_code() {
+        assert_eq!(whisper_language_code("de"), Some("de"));
+        assert_eq!(whisper_language_code("German"), Some("de"));
+        assert_eq!(whisper_language_code(" haitian creole "), Some("ht"));
+        assert_eq!(whisper_language_code("yue"), Some("yue"));
+        assert_eq!(whisper_language_code("auto"), None);
+        assert_eq!(whisper_language_code("klingon"), None);
+    }
+
+    #[test]
+    fn test_pick_language() {
+        let mut logits = vec![0.0f32; 100];
+        logits[10] = 2.0; // en
+        logits[11] = 5.0; // de
+        logits[12] = 1.0; // fr
+        logits[50] = 9.0; // a non-language token must not win
+        let candidates = [("en", 10), ("de", 11), ("fr", 12), ("ja", 500)];
+
+        let (language, probability) = pick_language(&logits, &candidates).unwrap();
+        assert_eq!(language, "de");
+        assert!(probability > 0.9 && probability < 1.0);
+
+        assert_eq!(pick_language(&logits, &[]), None);
+    }
 }
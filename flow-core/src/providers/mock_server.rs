@@ -0,0 +1,175 @@
+//! Minimal HTTP server for exercising providers against canned responses in tests
+
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+/// A request captured by the mock server
+#[derive(Debug, Clone)]
+pub struct CapturedRequest {
+    pub method: String,
+    pub path: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl CapturedRequest {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn json(&self) -> serde_json::Value {
+        serde_json::from_slice(&self.body).expect("request body is not JSON")
+    }
+}
+
+/// Canned response served for every request
+#[derive(Debug, Clone)]
+pub struct MockResponse {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: String,
+}
+
+impl MockResponse {
+    pub fn json(body: impl Into<String>) -> Self {
+        Self {
+            status: 200,
+            content_type: "application/json",
+            body: body.into(),
+        }
+    }
+
+    pub fn sse(events: &[&str]) -> Self {
+        let body = events
+            .iter()
+            .map(|event| format!("{event}\n\n"))
+            .collect::<String>();
+        Self {
+            status: 200,
+            content_type: "text/event-stream",
+            body,
+        }
+    }
+
+    pub fn error(status: u16, body: impl Into<String>) -> Self {
+        Self {
+            status,
+            content_type: "application/json",
+            body: body.into(),
+        }
+    }
+}
+
+/// Running mock server; requests are recorded in arrival order
+pub struct MockServer {
+    pub base_url: String,
+    requests: Arc<Mutex<Vec<CapturedRequest>>>,
+}
+
+impl MockServer {
+    /// Start a server that answers every request with `response`
+    pub async fn start(response: MockResponse) -> Self {
+        Self::start_sequence(vec![response]).await
+    }
+
+    /// Start a server that answers requests with `responses` in order,
+    /// repeating the last one once the list is exhausted
+    pub async fn start_sequence(responses: Vec<MockResponse>) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let captured = Arc::clone(&requests);
+
+        tokio::spawn(async move {
+            let mut served = 0usize;
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let response = responses[served.min(responses.len() - 1)].clone();
+                served += 1;
+                let captured = Arc::clone(&captured);
+                tokio::spawn(async move {
+                    if let Some(request) = read_request(&mut socket).await {
+                        captured.lock().push(request);
+                    }
+                    let head = format!(
+                        "HTTP/1.1 {} Mock\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                        response.status,
+                        response.content_type,
+                        response.body.len()
+                    );
+                    let _ = socket.write_all(head.as_bytes()).await;
+                    // split the body so clients must reassemble partial lines
+                    let body = response.body.as_bytes();
+                    let mid = body.len() / 2;
+                    let _ = socket.write_all(&body[..mid]).await;
+                    let _ = socket.flush().await;
+                    let _ = socket.write_all(&body[mid..]).await;
+                    let _ = socket.shutdown().await;
+                });
+            }
+        });
+
+        Self {
+            base_url: format!("http://{addr}"),
+            requests,
+        }
+    }
+
+    /// All requests received so far
+    pub fn requests(&self) -> Vec<CapturedRequest> {
+        self.requests.lock().clone()
+    }
+}
+
+async fn read_request(socket: &mut tokio::net::TcpStream) -> Option<CapturedRequest> {
+    let mut data = Vec::new();
+    let mut chunk = [0u8; 4096];
+
+    let header_end = loop {
+        let n = socket.read(&mut chunk).await.ok()?;
+        if n == 0 {
+            return None;
+        }
+        data.extend_from_slice(&chunk[..n]);
+        if let Some(pos) = data.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos + 4;
+        }
+    };
+
+    let head = String::from_utf8_lossy(&data[..header_end]).to_string();
+    let mut lines = head.split("\r\n");
+    let mut request_line = lines.next()?.split_whitespace();
+    let method = request_line.next()?.to_string();
+    let path = request_line.next()?.to_string();
+    let headers: Vec<(String, String)> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(k, v)| (k.trim().to_string(), v.trim().to_string()))
+        .collect();
+
+    let content_length = headers
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, v)| v.parse::<usize>().ok())
+        .unwrap_or(0);
+
+    let mut body = data[header_end..].to_vec();
+    while body.len() < content_length {
+        let n = socket.read(&mut chunk).await.ok()?;
+        if n == 0 {
+            break;
+        }
+        body.extend_from_slice(&chunk[..n]);
+    }
+
+    Some(CapturedRequest {
+        method,
+        path,
+        headers,
+        body,
+    })
+}
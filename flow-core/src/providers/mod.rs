@@ -1,27 +1,42 @@
 //! Provider abstraction layer for transcription and completion services
 //!
 //! Supports pluggable providers for cloud (OpenAI, ElevenLabs, Anthropic, Base10) and local services.
+mod anthropic;
 mod base10;
 mod completion;
+mod fallback;
 mod gemini;
+mod local_formatting;
 mod local_whisper;
+#[cfg(test)]
+pub(crate) mod mock_server;
 mod openai;
 mod openrouter;
 mod streaming;
 mod transcription;
+mod whisper_decode;
+mod whisper_decoder;
 
+pub use anthropic::AnthropicCompletionProvider;
 pub use base10::{
     Base10TranscriptionProvider, CorrectionPair, CorrectionValidation, validate_corrections,
 };
 pub use completion::{CompletionProvider, CompletionRequest, CompletionResponse, TokenUsage};
+pub use fallback::{
+    FailoverPolicy, FallbackCompletionProvider, FallbackTranscriptionProvider, is_transient_error,
+};
 pub use gemini::{GeminiCompletionProvider, GeminiTranscriptionProvider};
-pub use local_whisper::{LocalWhisperTranscriptionProvider, WhisperModel};
+pub use local_formatting::{LocalFormattingProvider, format_text};
+pub(crate) use local_whisper::mel_filters;
+pub use local_whisper::{LocalWhisperTranscriptionProvider, WhisperModel, whisper_language_code};
 pub use openai::{OpenAICompletionProvider, OpenAITranscriptionProvider};
 pub use openrouter::OpenRouterCompletionProvider;
 pub use streaming::{
     CompletionChunk, CompletionStream, StreamingCompletionProvider, collect_stream,
 };
 pub use transcription::{
-    CompletionParams as TranscriptionCompletionParams, TranscriptionProvider, TranscriptionRequest,
-    TranscriptionResponse,
+    CompletionParams as TranscriptionCompletionParams, ConfidenceSpan, LOW_CONFIDENCE_THRESHOLD,
+    ShortcutExpansion, TranscriptionProvider, TranscriptionRequest, TranscriptionResponse,
+    TranscriptionSegment, WordTiming, low_confidence_spans,
 };
+pub use whisper_decode::DecodingOptions;
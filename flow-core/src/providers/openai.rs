@@ -1,26 +1,89 @@
 //! OpenAI provider implementations for Whisper transcription and GPT completion
+//!
+//! Both providers also work against self-hosted OpenAI-compatible servers (whisper.cpp server,
+//! faster-whisper-server, Ollama, llama.cpp server, vLLM, LocalAI) via `with_base_url`.
 
 use async_trait::async_trait;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use tracing::{debug, error};
 
+use crate::audio::{AudioCodec, encode_for_upload};
 use crate::error::{Error, Result};
 use crate::types::WritingMode;
 
 use super::completion::TokenUsage;
+use super::streaming::openai_completion_stream;
 use super::{
-    CompletionProvider, CompletionRequest, CompletionResponse, TranscriptionProvider,
-    TranscriptionRequest, TranscriptionResponse,
+    CompletionProvider, CompletionRequest, CompletionResponse, CompletionStream,
+    StreamingCompletionProvider, TranscriptionProvider, TranscriptionRequest,
+    TranscriptionResponse,
 };
 
 const OPENAI_API_BASE: &str = "https://api.openai.com/v1";
+/// FLAC rather than Opus: OpenAI-compatible servers behind a custom base URL
+/// don't all decode Ogg
+const UPLOAD_CODEC: AudioCodec = AudioCodec::Flac;
+
+#[derive(Debug, Deserialize)]
+struct ModelsResponse {
+    data: Vec<ModelEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ModelEntry {
+    id: String,
+}
+
+/// List model ids served by an OpenAI-compatible `/models` endpoint
+async fn fetch_models(client: &Client, base_url: &str, api_key: &str) -> Result<Vec<String>> {
+    crate::offline::ensure_online("OpenAI-compatible endpoint")?;
+    let response = client
+        .get(format!("{}/models", base_url))
+        .header("Authorization", format!("Bearer {}", api_key))
+        .send()
+        .await?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let error_text = response.text().await.unwrap_or_default();
+        error!("OpenAI models API error: {} - {}", status, error_text);
+        return Err(Error::Config(format!(
+            "OpenAI models API error: {} - {}",
+            status, error_text
+        )));
+    }
+
+    let models: ModelsResponse = response.json().await?;
+    Ok(models.data.into_iter().map(|m| m.id).collect())
+}
+
+/// Check that a model is served by an OpenAI-compatible endpoint
+async fn ensure_model_available(
+    client: &Client,
+    base_url: &str,
+    api_key: &str,
+    model: &str,
+) -> Result<()> {
+    let models = fetch_models(client, base_url, api_key).await?;
+    if models.iter().any(|m| m == model) {
+        Ok(())
+    } else {
+        Err(Error::Config(format!(
+            "Model '{}' is not available at {} (available: {})",
+            model,
+            base_url,
+            models.join(", ")
+        )))
+    }
+}
 
 /// OpenAI Whisper transcription provider
 pub struct OpenAITranscriptionProvider {
     client: Client,
     api_key: Option<String>,
     model: String,
+    base_url: String,
 }
 
 impl OpenAITranscriptionProvider {
@@ -32,6 +95,7 @@ impl OpenAITranscriptionProvider {
             client: Client::new(),
             api_key: key,
             model: "whisper-1".to_string(),
+            base_url: OPENAI_API_BASE.to_string(),
         }
     }
 
@@ -41,6 +105,22 @@ impl OpenAITranscriptionProvider {
         self
     }
 
+    /// Override the API base URL (e.g. "http://localhost:8080/v1" for a self-hosted server)
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    /// List the models served by the configured endpoint
+    pub async fn list_models(&self) -> Result<Vec<String>> {
+        fetch_models(&self.client, &self.base_url, self.api_key()?).await
+    }
+
+    /// Check that the configured model is served by the endpoint
+    pub async fn validate_model(&self) -> Result<()> {
+        ensure_model_available(&self.client, &self.base_url, self.api_key()?, &self.model).await
+    }
+
     fn api_key(&self) -> Result<&str> {
         self.api_key
             .as_deref()
@@ -64,15 +144,16 @@ impl TranscriptionProvider for OpenAITranscriptionProvider {
     }
 
     async fn transcribe(&self, request: TranscriptionRequest) -> Result<TranscriptionResponse> {
+        crate::offline::ensure_online("OpenAI Whisper")?;
         let api_key = self.api_key()?;
 
-        // convert PCM to WAV format for the API
-        let wav_data = pcm_to_wav(&request.audio, request.sample_rate, 1);
+        // compress the recording for upload
+        let (audio, codec) = encode_for_upload(&request.audio, request.sample_rate, UPLOAD_CODEC);
 
         // build multipart form
-        let file_part = reqwest::multipart::Part::bytes(wav_data)
-            .file_name("audio.wav")
-            .mime_str("audio/wav")
+        let file_part = reqwest::multipart::Part::bytes(audio)
+            .file_name(codec.file_name())
+            .mime_str(codec.mime_type())
             .map_err(|e| Error::Transcription(format!("Failed to create form part: {e}")))?;
 
         let mut form = reqwest::multipart::Form::new()
@@ -80,9 +161,15 @@ impl TranscriptionProvider for OpenAITranscriptionProvider {
             .text("model", self.model.clone())
             .text("response_format", "json");
 
-        if let Some(lang) = &request.language {
-            form = form.text("language", lang.clone());
-        }
+        // the translations endpoint always outputs English and takes no language hint
+        let endpoint = if request.translate {
+            "translations"
+        } else {
+            if let Some(lang) = &request.language {
+                form = form.text("language", lang.clone());
+            }
+            "transcriptions"
+        };
 
         if let Some(prompt) = &request.prompt {
             form = form.text("prompt", prompt.clone());
@@ -92,7 +179,7 @@ impl TranscriptionProvider for OpenAITranscriptionProvider {
 
         let response = self
             .client
-            .post(format!("{}/audio/transcriptions", OPENAI_API_BASE))
+            .post(format!("{}/audio/{}", self.base_url, endpoint))
             .header("Authorization", format!("Bearer {}", api_key))
             .multipart(form)
             .send()
@@ -102,10 +189,10 @@ impl TranscriptionProvider for OpenAITranscriptionProvider {
             let status = response.status();
             let error_text = response.text().await.unwrap_or_default();
             error!("Whisper API error: {} - {}", status, error_text);
-            return Err(Error::Transcription(format!(
-                "Whisper API error: {} - {}",
-                status, error_text
-            )));
+            return Err(Error::TranscriptionApi {
+                status,
+                message: format!("Whisper API error: {} - {}", status, error_text),
+            });
         }
 
         let whisper_response: WhisperResponse = response.json().await?;
@@ -123,10 +210,11 @@ impl TranscriptionProvider for OpenAITranscriptionProvider {
         Ok(TranscriptionResponse {
             text: whisper_response.text,
             confidence: None, // Whisper doesn't provide confidence
-            language: whisper_response.language,
+            language: whisper_response.language.or(request.language),
             duration_ms,
             segments: None,
             completed_text: None,
+            provider: None,
         })
     }
 
@@ -140,6 +228,7 @@ pub struct OpenAICompletionProvider {
     client: Client,
     api_key: Option<String>,
     model: String,
+    base_url: String,
 }
 
 impl OpenAICompletionProvider {
@@ -151,6 +240,7 @@ impl OpenAICompletionProvider {
             client: Client::new(),
             api_key: key,
             model: "gpt-4o-mini".to_string(),
+            base_url: OPENAI_API_BASE.to_string(),
         }
     }
 
@@ -160,6 +250,22 @@ impl OpenAICompletionProvider {
         self
     }
 
+    /// Override the API base URL (e.g. "http://localhost:11434/v1" for Ollama)
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    /// List the models served by the configured endpoint
+    pub async fn list_models(&self) -> Result<Vec<String>> {
+        fetch_models(&self.client, &self.base_url, self.api_key()?).await
+    }
+
+    /// Check that the configured model is served by the endpoint
+    pub async fn validate_model(&self) -> Result<()> {
+        ensure_model_available(&self.client, &self.base_url, self.api_key()?, &self.model).await
+    }
+
     fn api_key(&self) -> Result<&str> {
         self.api_key
             .as_deref()
@@ -184,6 +290,63 @@ impl OpenAICompletionProvider {
 
         prompt
     }
+
+    fn build_chat_request(&self, request: CompletionRequest, stream: bool) -> ChatRequest {
+        let mut system_prompt = request.system_prompt.unwrap_or_else(|| {
+            self.build_system_prompt(request.mode, request.app_context.as_deref())
+        });
+
+        // Add shortcut preservation instruction if present
+        if let Some(preservation) = request.shortcut_preservation {
+            system_prompt.push_str(&preservation);
+        }
+
+        ChatRequest {
+            model: self.model.clone(),
+            messages: vec![
+                ChatMessage {
+                    role: "system".to_string(),
+                    content: system_prompt,
+                },
+                ChatMessage {
+                    role: "user".to_string(),
+                    content: format!("<TRANSCRIPTION>\n{}\n</TRANSCRIPTION>", request.text),
+                },
+            ],
+            max_tokens: request.max_tokens,
+            temperature: 0.3, // low temperature for consistent formatting
+            stream,
+            stream_options: stream.then_some(StreamOptions {
+                include_usage: true,
+            }),
+        }
+    }
+
+    async fn send(&self, chat_request: &ChatRequest) -> Result<reqwest::Response> {
+        crate::offline::ensure_online("OpenAI GPT")?;
+        let api_key = self.api_key()?;
+
+        let response = self
+            .client
+            .post(format!("{}/chat/completions", self.base_url))
+            .header("Authorization", format!("Bearer {}", api_key))
+            .header("Content-Type", "application/json")
+            .json(chat_request)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response.text().await.unwrap_or_default();
+            error!("OpenAI API error: {} - {}", status, error_text);
+            return Err(Error::CompletionApi {
+                status,
+                message: format!("OpenAI API error: {} - {}", status, error_text),
+            });
+        }
+
+        Ok(response)
+    }
 }
 
 #[derive(Debug, Serialize)]
@@ -193,6 +356,15 @@ struct ChatRequest {
     #[serde(skip_serializing_if = "Option::is_none")]
     max_tokens: Option<u32>,
     temperature: f32,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stream_options: Option<StreamOptions>,
+}
+
+#[derive(Debug, Serialize)]
+struct StreamOptions {
+    include_usage: bool,
 }
 
 #[derive(Debug, Serialize)]
@@ -232,54 +404,11 @@ impl CompletionProvider for OpenAICompletionProvider {
     }
 
     async fn complete(&self, request: CompletionRequest) -> Result<CompletionResponse> {
-        let api_key = self.api_key()?;
-
-        let mut system_prompt = request.system_prompt.unwrap_or_else(|| {
-            self.build_system_prompt(request.mode, request.app_context.as_deref())
-        });
-
-        // Add shortcut preservation instruction if present
-        if let Some(preservation) = request.shortcut_preservation {
-            system_prompt.push_str(&preservation);
-        }
-
-        let chat_request = ChatRequest {
-            model: self.model.clone(),
-            messages: vec![
-                ChatMessage {
-                    role: "system".to_string(),
-                    content: system_prompt,
-                },
-                ChatMessage {
-                    role: "user".to_string(),
-                    content: format!("<TRANSCRIPTION>\n{}\n</TRANSCRIPTION>", request.text),
-                },
-            ],
-            max_tokens: request.max_tokens,
-            temperature: 0.3, // low temperature for consistent formatting
-        };
+        let chat_request = self.build_chat_request(request, false);
 
         debug!("Sending completion request to OpenAI");
 
-        let response = self
-            .client
-            .post(format!("{}/chat/completions", OPENAI_API_BASE))
-            .header("Authorization", format!("Bearer {}", api_key))
-            .header("Content-Type", "application/json")
-            .json(&chat_request)
-            .send()
-            .await?;
-
-        if !response.status().is_success() {
-            let status = response.status();
-            let error_text = response.text().await.unwrap_or_default();
-            error!("OpenAI API error: {} - {}", status, error_text);
-            return Err(Error::Completion(format!(
-                "OpenAI API error: {} - {}",
-                status, error_text
-            )));
-        }
-
+        let response = self.send(&chat_request).await?;
         let chat_response: ChatResponse = response.json().await?;
 
         let text = chat_response
@@ -297,50 +426,45 @@ impl CompletionProvider for OpenAICompletionProvider {
                 total_tokens: u.total_tokens,
             }),
             model: Some(chat_response.model),
+            provider: None,
         })
     }
 
     fn is_configured(&self) -> bool {
         self.api_key.is_some()
     }
+
+    fn as_streaming(&self) -> Option<&dyn StreamingCompletionProvider> {
+        Some(self)
+    }
 }
 
-/// Convert raw PCM data to WAV format
-fn pcm_to_wav(pcm: &[u8], sample_rate: u32, channels: u16) -> Vec<u8> {
-    let bits_per_sample: u16 = 16;
-    let byte_rate = sample_rate * u32::from(channels) * u32::from(bits_per_sample) / 8;
-    let block_align = channels * bits_per_sample / 8;
-    let data_size = pcm.len() as u32;
-    let file_size = 36 + data_size;
-
-    let mut wav = Vec::with_capacity(44 + pcm.len());
-
-    // RIFF header
-    wav.extend_from_slice(b"RIFF");
-    wav.extend_from_slice(&file_size.to_le_bytes());
-    wav.extend_from_slice(b"WAVE");
-
-    // fmt chunk
-    wav.extend_from_slice(b"fmt ");
-    wav.extend_from_slice(&16u32.to_le_bytes()); // chunk size
-    wav.extend_from_slice(&1u16.to_le_bytes()); // PCM format
-    wav.extend_from_slice(&channels.to_le_bytes());
-    wav.extend_from_slice(&sample_rate.to_le_bytes());
-    wav.extend_from_slice(&byte_rate.to_le_bytes());
-    wav.extend_from_slice(&block_align.to_le_bytes());
-    wav.extend_from_slice(&bits_per_sample.to_le_bytes());
-
-    // data chunk
-    wav.extend_from_slice(b"data");
-    wav.extend_from_slice(&data_size.to_le_bytes());
-    wav.extend_from_slice(pcm);
-
-    wav
+#[async_trait]
+impl StreamingCompletionProvider for OpenAICompletionProvider {
+    fn name(&self) -> &'static str {
+        "OpenAI GPT"
+    }
+
+    async fn complete_stream(&self, request: CompletionRequest) -> Result<CompletionStream> {
+        let chat_request = self.build_chat_request(request, true);
+
+        debug!("Sending streaming completion request to OpenAI");
+
+        let response = self.send(&chat_request).await?;
+        Ok(openai_completion_stream(response))
+    }
+
+    fn is_configured(&self) -> bool {
+        self.api_key.is_some()
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::audio::pcm_to_wav;
+    use crate::providers::mock_server::{MockResponse, MockServer};
+    use futures::StreamExt;
 
     #[test]
     fn test_pcm_to_wav() {
@@ -374,6 +498,141 @@ mod tests {
         assert!(prompt.contains("exactly as it would be typed"));
     }
 
+    #[tokio::test]
+    async fn test_complete_stream() {
+        let server = MockServer::start(MockResponse::sse(&[
+            r#"data: {"id":"c1","object":"chat.completion.chunk","choices":[{"index":0,"delta":{"role":"assistant","content":""},"finish_reason":null}]}"#,
+            r#"data: {"id":"c1","object":"chat.completion.chunk","choices":[{"index":0,"delta":{"content":"Hello"},"finish_reason":null}]}"#,
+            r#"data: {"id":"c1","object":"chat.completion.chunk","choices":[{"index":0,"delta":{"content":", world."},"finish_reason":"stop"}]}"#,
+            r#"data: {"id":"c1","object":"chat.completion.chunk","choices":[],"usage":{"prompt_tokens":20,"completion_tokens":4,"total_tokens":24}}"#,
+            "data: [DONE]",
+        ]))
+        .await;
+
+        let provider = OpenAICompletionProvider::new(Some("sk-test".to_string()))
+            .with_base_url(&server.base_url);
+        let stream = provider
+            .complete_stream(CompletionRequest::new(
+                "hello world".to_string(),
+                WritingMode::Casual,
+            ))
+            .await
+            .unwrap();
+
+        let chunks: Vec<_> = stream.collect().await;
+        let texts: Vec<_> = chunks
+            .iter()
+            .map(|c| c.as_ref().unwrap().text.as_str())
+            .collect();
+        assert_eq!(texts, vec!["Hello", ", world.", ""]);
+
+        let last = chunks.last().unwrap().as_ref().unwrap();
+        assert!(last.is_final);
+        assert_eq!(last.usage.as_ref().unwrap().total_tokens, 24);
+
+        let requests = server.requests();
+        assert_eq!(requests[0].path, "/chat/completions");
+        assert_eq!(requests[0].header("authorization"), Some("Bearer sk-test"));
+        let body = requests[0].json();
+        assert_eq!(body["stream"], true);
+        assert_eq!(body["stream_options"]["include_usage"], true);
+    }
+
+    #[tokio::test]
+    async fn test_complete_stream_http_error() {
+        let server = MockServer::start(MockResponse::error(429, "rate limited")).await;
+
+        let provider = OpenAICompletionProvider::new(Some("sk-test".to_string()))
+            .with_base_url(&server.base_url);
+        let err = provider
+            .complete_stream(CompletionRequest::new(
+                "hello".to_string(),
+                WritingMode::Casual,
+            ))
+            .await
+            .err()
+            .unwrap();
+        assert!(matches!(
+            err,
+            Error::CompletionApi { status, .. } if status == reqwest::StatusCode::TOO_MANY_REQUESTS
+        ));
+        assert!(err.to_string().contains("429"));
+    }
+
+    #[tokio::test]
+    async fn test_transcribe_custom_base_url() {
+        let server = MockServer::start(MockResponse::json(
+            r#"{"text": "hello from whisper.cpp", "language": "en", "duration": 1.5}"#,
+        ))
+        .await;
+
+        let provider = OpenAITranscriptionProvider::new(Some("local".to_string()))
+            .with_base_url(format!("{}/", server.base_url))
+            .with_model("ggml-base.en");
+        let response = provider
+            .transcribe(TranscriptionRequest::new(vec![0u8; 32000], 16000))
+            .await
+            .unwrap();
+
+        assert_eq!(response.text, "hello from whisper.cpp");
+        assert_eq!(response.duration_ms, 1500);
+
+        let requests = server.requests();
+        assert_eq!(requests[0].path, "/audio/transcriptions");
+        let body = String::from_utf8_lossy(&requests[0].body);
+        assert!(body.contains("ggml-base.en"));
+    }
+
+    #[tokio::test]
+    async fn test_transcribe_translation_endpoint() {
+        let server = MockServer::start(MockResponse::json(r#"{"text": "good morning"}"#)).await;
+
+        let provider = OpenAITranscriptionProvider::new(Some("test-key".to_string()))
+            .with_base_url(server.base_url.clone());
+        let request = TranscriptionRequest::new(vec![0u8; 32000], 16000)
+            .with_language("de")
+            .with_translation();
+        let response = provider.transcribe(request).await.unwrap();
+
+        assert_eq!(response.text, "good morning");
+        let requests = server.requests();
+        assert_eq!(requests[0].path, "/audio/translations");
+        let body = String::from_utf8_lossy(&requests[0].body);
+        assert!(!body.contains("name=\"language\""));
+    }
+
+    #[tokio::test]
+    async fn test_validate_model_against_server() {
+        let server = MockServer::start(MockResponse::json(
+            r#"{"object": "list", "data": [
+                {"id": "llama3.2:3b", "object": "model", "owned_by": "library"},
+                {"id": "qwen2.5:7b", "object": "model", "owned_by": "library"}
+            ]}"#,
+        ))
+        .await;
+
+        let provider = OpenAICompletionProvider::new(Some("ollama".to_string()))
+            .with_base_url(&server.base_url)
+            .with_model("llama3.2:3b");
+        assert_eq!(
+            provider.list_models().await.unwrap(),
+            vec!["llama3.2:3b", "qwen2.5:7b"]
+        );
+        provider.validate_model().await.unwrap();
+
+        let err = provider
+            .with_model("gpt-4o-mini")
+            .validate_model()
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::Config(_)));
+        assert!(err.to_string().contains("llama3.2:3b"));
+
+        let requests = server.requests();
+        assert_eq!(requests[0].method, "GET");
+        assert_eq!(requests[0].path, "/models");
+    }
+
     #[test]
     fn test_provider_not_configured() {
         let provider = OpenAITranscriptionProvider::new(None);
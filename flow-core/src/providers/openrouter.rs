@@ -9,7 +9,11 @@ use crate::error::{Error, Result};
 use crate::types::WritingMode;
 
 use super::completion::TokenUsage;
-use super::{CompletionProvider, CompletionRequest, CompletionResponse};
+use super::streaming::openai_completion_stream;
+use super::{
+    CompletionProvider, CompletionRequest, CompletionResponse, CompletionStream,
+    StreamingCompletionProvider,
+};
 
 const OPENROUTER_API_BASE: &str = "https://openrouter.ai/api/v1";
 
@@ -18,6 +22,7 @@ pub struct OpenRouterCompletionProvider {
     client: Client,
     api_key: Option<String>,
     models: Vec<String>,
+    base_url: String,
 }
 
 impl OpenRouterCompletionProvider {
@@ -32,6 +37,7 @@ impl OpenRouterCompletionProvider {
                 "meta-llama/llama-4-maverick:nitro".to_string(),
                 "openai/gpt-oss-120b:nitro".to_string(),
             ],
+            base_url: OPENROUTER_API_BASE.to_string(),
         }
     }
 
@@ -47,6 +53,12 @@ impl OpenRouterCompletionProvider {
         self
     }
 
+    /// Override the API base URL
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into().trim_end_matches('/').to_string();
+        self
+    }
+
     fn api_key(&self) -> Result<&str> {
         self.api_key
             .as_deref()
@@ -71,6 +83,73 @@ impl OpenRouterCompletionProvider {
 
         prompt
     }
+
+    fn build_chat_request(&self, request: CompletionRequest, stream: bool) -> ChatRequest {
+        let mut system_prompt = request.system_prompt.unwrap_or_else(|| {
+            self.build_system_prompt(request.mode, request.app_context.as_deref())
+        });
+
+        // Add shortcut preservation instruction if present
+        if let Some(preservation) = request.shortcut_preservation {
+            system_prompt.push_str(&preservation);
+        }
+
+        ChatRequest {
+            models: self.models.clone(),
+            messages: vec![
+                ChatMessage {
+                    role: "system".to_string(),
+                    content: system_prompt,
+                },
+                ChatMessage {
+                    role: "user".to_string(),
+                    content: format!("<TRANSCRIPTION>\n{}\n</TRANSCRIPTION>", request.text),
+                },
+            ],
+            max_tokens: Some(1000),
+            temperature: 0.3,
+            provider: Some(ProviderConfig {
+                allow_fallbacks: Some(true),
+                sort: Some(SortConfig {
+                    by: "throughput".to_string(),
+                    partition: "none".to_string(),
+                }),
+            }),
+            stream,
+            stream_options: stream.then_some(StreamOptions {
+                include_usage: true,
+            }),
+        }
+    }
+
+    async fn send(&self, chat_request: &ChatRequest) -> Result<reqwest::Response> {
+        crate::offline::ensure_online("OpenRouter")?;
+        let api_key = self.api_key()?;
+
+        let response = self
+            .client
+            .post(format!("{}/chat/completions", self.base_url))
+            .header("Authorization", format!("Bearer {}", api_key))
+            .header("Content-Type", "application/json")
+            .json(chat_request)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response
+                .text()
+                .await
+                .unwrap_or_else(|_| String::from("Unknown error"));
+            error!("OpenRouter API error ({}): {}", status, error_text);
+            return Err(Error::CompletionApi {
+                status,
+                message: format!("OpenRouter API error ({}): {}", status, error_text),
+            });
+        }
+
+        Ok(response)
+    }
 }
 
 #[derive(Debug, Serialize)]
@@ -82,6 +161,15 @@ struct ChatRequest {
     temperature: f32,
     #[serde(skip_serializing_if = "Option::is_none")]
     provider: Option<ProviderConfig>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stream_options: Option<StreamOptions>,
+}
+
+#[derive(Debug, Serialize)]
+struct StreamOptions {
+    include_usage: bool,
 }
 
 #[derive(Debug, Serialize)]
@@ -135,66 +223,14 @@ impl CompletionProvider for OpenRouterCompletionProvider {
     }
 
     async fn complete(&self, request: CompletionRequest) -> Result<CompletionResponse> {
-        let api_key = self.api_key()?;
-
-        let mut system_prompt = request.system_prompt.unwrap_or_else(|| {
-            self.build_system_prompt(request.mode, request.app_context.as_deref())
-        });
-
-        // Add shortcut preservation instruction if present
-        if let Some(preservation) = request.shortcut_preservation {
-            system_prompt.push_str(&preservation);
-        }
-
-        let chat_request = ChatRequest {
-            models: self.models.clone(),
-            messages: vec![
-                ChatMessage {
-                    role: "system".to_string(),
-                    content: system_prompt,
-                },
-                ChatMessage {
-                    role: "user".to_string(),
-                    content: format!("<TRANSCRIPTION>\n{}\n</TRANSCRIPTION>", request.text),
-                },
-            ],
-            max_tokens: Some(1000),
-            temperature: 0.3,
-            provider: Some(ProviderConfig {
-                allow_fallbacks: Some(true),
-                sort: Some(SortConfig {
-                    by: "throughput".to_string(),
-                    partition: "none".to_string(),
-                }),
-            }),
-        };
+        let chat_request = self.build_chat_request(request, false);
 
         debug!(
             "Sending completion request to OpenRouter with models: {:?}",
             self.models
         );
 
-        let response = self
-            .client
-            .post(format!("{}/chat/completions", OPENROUTER_API_BASE))
-            .header("Authorization", format!("Bearer {}", api_key))
-            .header("Content-Type", "application/json")
-            .json(&chat_request)
-            .send()
-            .await?;
-
-        if !response.status().is_success() {
-            let status = response.status();
-            let error_text = response
-                .text()
-                .await
-                .unwrap_or_else(|_| String::from("Unknown error"));
-            error!("OpenRouter API error ({}): {}", status, error_text);
-            return Err(Error::Completion(format!(
-                "OpenRouter API error ({}): {}",
-                status, error_text
-            )));
-        }
+        let response = self.send(&chat_request).await?;
 
         let chat_response: ChatResponse = response.json().await?;
 
@@ -216,10 +252,76 @@ impl CompletionProvider for OpenRouterCompletionProvider {
             text,
             usage,
             model: Some(chat_response.model),
+            provider: None,
         })
     }
 
     fn is_configured(&self) -> bool {
         self.api_key.is_some()
     }
+
+    fn as_streaming(&self) -> Option<&dyn StreamingCompletionProvider> {
+        Some(self)
+    }
+}
+
+#[async_trait]
+impl StreamingCompletionProvider for OpenRouterCompletionProvider {
+    fn name(&self) -> &'static str {
+        "OpenRouter"
+    }
+
+    async fn complete_stream(&self, request: CompletionRequest) -> Result<CompletionStream> {
+        let chat_request = self.build_chat_request(request, true);
+
+        debug!(
+            "Sending streaming completion request to OpenRouter with models: {:?}",
+            self.models
+        );
+
+        let response = self.send(&chat_request).await?;
+        Ok(openai_completion_stream(response))
+    }
+
+    fn is_configured(&self) -> bool {
+        self.api_key.is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::collect_stream;
+    use crate::providers::mock_server::{MockResponse, MockServer};
+
+    #[tokio::test]
+    async fn test_complete_stream() {
+        // OpenRouter interleaves SSE comments while the upstream model is warming up
+        let server = MockServer::start(MockResponse::sse(&[
+            ": OPENROUTER PROCESSING",
+            r#"data: {"id":"gen-1","object":"chat.completion.chunk","choices":[{"index":0,"delta":{"content":"Hi "},"finish_reason":null}]}"#,
+            r#"data: {"id":"gen-1","object":"chat.completion.chunk","choices":[{"index":0,"delta":{"content":"there"},"finish_reason":"stop"}],"usage":{"prompt_tokens":12,"completion_tokens":2,"total_tokens":14}}"#,
+            "data: [DONE]",
+        ]))
+        .await;
+
+        let provider = OpenRouterCompletionProvider::new(Some("or-test".to_string()))
+            .with_model("test/model")
+            .with_base_url(&server.base_url);
+        let stream = provider
+            .complete_stream(CompletionRequest::new(
+                "hi there".to_string(),
+                WritingMode::Casual,
+            ))
+            .await
+            .unwrap();
+
+        let response = collect_stream(stream).await.unwrap();
+        assert_eq!(response.text, "Hi there");
+        assert_eq!(response.usage.unwrap().total_tokens, 14);
+
+        let body = server.requests()[0].json();
+        assert_eq!(body["stream"], true);
+        assert_eq!(body["models"][0], "test/model");
+    }
 }
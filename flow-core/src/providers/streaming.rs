@@ -2,10 +2,11 @@
 //!
 //! Provides Server-Sent Events (SSE) parsing and streaming completion traits.
 
+use std::collections::VecDeque;
 use std::pin::Pin;
 
 use async_trait::async_trait;
-use futures::Stream;
+use futures::{Stream, StreamExt};
 use serde::Deserialize;
 
 use crate::error::Result;
@@ -48,7 +49,6 @@ pub struct SseEvent {
 }
 
 /// Parse SSE data from a line
-#[allow(dead_code)]
 pub fn parse_sse_line(line: &str) -> Option<SseEvent> {
     let line = line.trim();
 
@@ -69,6 +69,119 @@ pub fn parse_sse_line(line: &str) -> Option<SseEvent> {
     }
 }
 
+/// Turn an HTTP response body into a stream of SSE events
+///
+/// Lines are buffered across network chunks so events split mid-line are
+/// reassembled. An `event:` line is attached to the `data:` line that follows it.
+pub fn sse_events(response: reqwest::Response) -> impl Stream<Item = Result<SseEvent>> + Send {
+    struct State<S> {
+        bytes: S,
+        buffer: Vec<u8>,
+        pending: VecDeque<SseEvent>,
+        event: Option<String>,
+        done: bool,
+    }
+
+    impl<S> State<S> {
+        fn push_line(&mut self, line: &[u8]) {
+            let line = String::from_utf8_lossy(line);
+            if let Some(parsed) = parse_sse_line(&line) {
+                if parsed.event.is_some() {
+                    self.event = parsed.event;
+                } else {
+                    self.pending.push_back(SseEvent {
+                        event: self.event.take(),
+                        data: parsed.data,
+                    });
+                }
+            }
+        }
+    }
+
+    let state = State {
+        bytes: response.bytes_stream().boxed(),
+        buffer: Vec::new(),
+        pending: VecDeque::new(),
+        event: None,
+        done: false,
+    };
+
+    futures::stream::unfold(state, |mut state| async move {
+        loop {
+            if let Some(event) = state.pending.pop_front() {
+                return Some((Ok(event), state));
+            }
+            if state.done {
+                return None;
+            }
+
+            match state.bytes.next().await {
+                Some(Ok(chunk)) => {
+                    state.buffer.extend_from_slice(&chunk);
+                    while let Some(pos) = state.buffer.iter().position(|&b| b == b'\n') {
+                        let line: Vec<u8> = state.buffer.drain(..=pos).collect();
+                        state.push_line(&line);
+                    }
+                }
+                Some(Err(e)) => {
+                    state.done = true;
+                    return Some((Err(e.into()), state));
+                }
+                None => {
+                    state.done = true;
+                    let rest = std::mem::take(&mut state.buffer);
+                    state.push_line(&rest);
+                }
+            }
+        }
+    })
+}
+
+/// Map an OpenAI-compatible chat completions SSE response into completion chunks
+///
+/// Used by every provider that speaks the `/chat/completions` streaming format.
+/// Usage arrives on the last data chunk (with `stream_options.include_usage`) and is
+/// attached to the final chunk emitted for the `[DONE]` sentinel.
+pub fn openai_completion_stream(response: reqwest::Response) -> CompletionStream {
+    let stream = sse_events(response)
+        .scan(None, |usage: &mut Option<TokenUsage>, event| {
+            let item = match event {
+                Ok(event) if event.data == "[DONE]" => Some(Ok(CompletionChunk {
+                    text: String::new(),
+                    is_final: true,
+                    usage: usage.take(),
+                })),
+                Ok(event) => match serde_json::from_str::<OpenAIStreamChunk>(&event.data) {
+                    Ok(chunk) => {
+                        if let Some(u) = chunk.usage {
+                            *usage = Some(TokenUsage {
+                                prompt_tokens: u.prompt_tokens,
+                                completion_tokens: u.completion_tokens,
+                                total_tokens: u.total_tokens,
+                            });
+                        }
+                        let text: String = chunk
+                            .choices
+                            .into_iter()
+                            .filter_map(|c| c.delta.content)
+                            .collect();
+                        (!text.is_empty()).then_some(Ok(CompletionChunk {
+                            text,
+                            is_final: false,
+                            usage: None,
+                        }))
+                    }
+                    Err(e) => Some(Err(e.into())),
+                },
+                Err(e) => Some(Err(e)),
+            };
+            futures::future::ready(Some(item))
+        })
+        .filter_map(futures::future::ready);
+
+    Box::pin(stream)
+}
+
 /// OpenAI streaming response chunk
 #[allow(dead_code)]
 #[derive(Debug, Deserialize)]
@@ -184,10 +297,60 @@ pub struct AnthropicError {
     pub message: String,
 }
 
+/// Gemini `streamGenerateContent` response chunk
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiStreamChunk {
+    #[serde(default)]
+    pub candidates: Vec<GeminiStreamCandidate>,
+    #[serde(default)]
+    pub usage_metadata: Option<GeminiUsageMetadata>,
+    #[serde(default)]
+    pub error: Option<GeminiStreamError>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GeminiStreamError {
+    #[serde(default)]
+    pub message: String,
+    #[serde(default)]
+    pub status: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiStreamCandidate {
+    #[serde(default)]
+    pub content: Option<GeminiStreamContent>,
+    #[serde(default)]
+    pub finish_reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GeminiStreamContent {
+    #[serde(default)]
+    pub parts: Vec<GeminiStreamPart>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GeminiStreamPart {
+    #[serde(default)]
+    pub text: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiUsageMetadata {
+    #[serde(default)]
+    pub prompt_token_count: u32,
+    #[serde(default)]
+    pub candidates_token_count: u32,
+    #[serde(default)]
+    pub total_token_count: u32,
+}
+
 /// Collect a stream into a complete response
 pub async fn collect_stream(stream: CompletionStream) -> Result<CompletionResponse> {
-    use futures::StreamExt;
-
     let mut text = String::new();
     let mut usage = None;
     let model = None;
@@ -201,7 +364,12 @@ pub async fn collect_stream(stream: CompletionStream) -> Result<CompletionRespon
         }
     }
 
-    Ok(CompletionResponse { text, usage, model })
+    Ok(CompletionResponse {
+        text,
+        usage,
+        model,
+        provider: None,
+    })
 }
 
 #[cfg(test)]
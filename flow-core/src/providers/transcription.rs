@@ -15,7 +15,9 @@ pub struct TranscriptionRequest {
     pub sample_rate: u32,
     /// Optional language hint (ISO 639-1 code, e.g., "en")
     pub language: Option<String>,
-    /// Optional prompt to guide transcription
+    /// Translate speech to English instead of transcribing in the spoken language
+    pub translate: bool,
+    /// Optional prompt to guide transcription (e.g. custom vocabulary, previous text)
     pub prompt: Option<String>,
     /// Optional completion parameters for combined transcription+completion
     pub completion: Option<CompletionParams>,
@@ -30,17 +32,29 @@ pub struct CompletionParams {
     pub app_context: Option<String>,
     /// Shortcut replacement texts that must be preserved exactly
     pub shortcuts_triggered: Vec<String>,
+    /// Shortcuts the worker expands in its transcription before formatting
+    /// Only the expansions it makes are given to the formatting model
+    pub shortcuts: Vec<ShortcutExpansion>,
     /// Voice instruction (e.g., "reject him politely", "translate to Spanish")
     /// When present, worker uses instruction mode instead of normal formatting
     pub voice_instruction: Option<String>,
 }
 
+/// A voice shortcut for the worker to expand when its trigger is in the transcription
+#[derive(Debug, Clone, Serialize)]
+pub struct ShortcutExpansion {
+    pub trigger: String,
+    pub replacement: String,
+    pub case_sensitive: bool,
+}
+
 impl TranscriptionRequest {
     pub fn new(audio: AudioData, sample_rate: u32) -> Self {
         Self {
             audio,
             sample_rate,
             language: None,
+            translate: false,
             prompt: None,
             completion: None,
         }
@@ -51,6 +65,11 @@ impl TranscriptionRequest {
         self
     }
 
+    pub fn with_translation(mut self) -> Self {
+        self.translate = true;
+        self
+    }
+
     pub fn with_prompt(mut self, prompt: impl Into<String>) -> Self {
         self.prompt = Some(prompt.into());
         self
@@ -73,20 +92,154 @@ pub struct TranscriptionResponse {
     pub language: Option<String>,
     /// Duration of audio in milliseconds
     pub duration_ms: u64,
-    /// Individual word segments if available
+    /// Timed segments if available
     pub segments: Option<Vec<TranscriptionSegment>>,
     /// Completed/formatted text if worker performed completion
     #[serde(default)]
     pub completed_text: Option<String>,
+    /// Provider that served the request, when it differs from the configured one
+    #[serde(default)]
+    pub provider: Option<String>,
+}
+
+impl TranscriptionResponse {
+    /// Shift segment and word timings by `offset_ms`, for audio cut from a longer recording
+    pub fn offset_by(mut self, offset_ms: u64) -> Self {
+        for segment in self.segments.iter_mut().flatten() {
+            segment.start_ms += offset_ms;
+            segment.end_ms += offset_ms;
+            for word in &mut segment.words {
+                word.start_ms += offset_ms;
+                word.end_ms += offset_ms;
+            }
+        }
+        self
+    }
+
+    /// Append the response for the next chunk of the same recording
+    /// Confidence is averaged by duration; worker completions are not merged
+    pub fn append(&mut self, next: TranscriptionResponse) {
+        let next_text = next.text.trim();
+        if !next_text.is_empty() {
+            if !self.text.trim().is_empty() {
+                self.text.push(' ');
+            }
+            self.text.push_str(next_text);
+        }
+
+        self.confidence = match (self.confidence, next.confidence) {
+            (Some(a), Some(b)) => {
+                let total = (self.duration_ms + next.duration_ms).max(1) as f32;
+                Some((a * self.duration_ms as f32 + b * next.duration_ms as f32) / total)
+            }
+            (a, b) => a.or(b),
+        };
+        self.duration_ms += next.duration_ms;
+        self.language = self.language.take().or(next.language);
+        self.provider = self.provider.take().or(next.provider);
+        self.completed_text = None;
+
+        if let Some(segments) = next.segments {
+            self.segments.get_or_insert_with(Vec::new).extend(segments);
+        }
+    }
 }
 
 /// A segment of transcribed text with timing
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct TranscriptionSegment {
     pub text: String,
     pub start_ms: u64,
     pub end_ms: u64,
     pub confidence: Option<f32>,
+    /// Per-word timings within the segment, if available
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub words: Vec<WordTiming>,
+}
+
+/// Timing of a single word
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WordTiming {
+    pub text: String,
+    pub start_ms: u64,
+    pub end_ms: u64,
+    /// Probability (0.0 - 1.0) the word was recognised correctly, if available
+    #[serde(default)]
+    pub confidence: Option<f32>,
+}
+
+/// Word confidence below which a word is flagged as possibly wrong
+pub const LOW_CONFIDENCE_THRESHOLD: f32 = 0.5;
+
+/// A run of consecutive low-confidence words
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConfidenceSpan {
+    /// The words of the span as they appear in the text
+    pub text: String,
+    /// Start offset in the transcript, in characters
+    pub start: usize,
+    /// End offset (exclusive) in the transcript, in characters
+    pub end: usize,
+    pub start_ms: u64,
+    pub end_ms: u64,
+    /// Lowest word confidence in the span
+    pub confidence: f32,
+}
+
+/// Find the low-confidence word spans of a transcript
+/// Words are located in `text` in order; words that cannot be found are skipped
+pub fn low_confidence_spans(
+    text: &str,
+    segments: &[TranscriptionSegment],
+    threshold: f32,
+) -> Vec<ConfidenceSpan> {
+    let mut spans: Vec<ConfidenceSpan> = Vec::new();
+    let mut cursor = 0;
+    // Whether the previous located word was low-confidence (spans only merge adjacent words)
+    let mut extending = false;
+
+    for word in segments.iter().flat_map(|segment| &segment.words) {
+        let Some(found) = text[cursor..].find(word.text.as_str()) else {
+            continue;
+        };
+        let byte_start = cursor + found;
+        let byte_end = byte_start + word.text.len();
+        cursor = byte_end;
+
+        let confidence = match word.confidence {
+            Some(confidence) if confidence < threshold => confidence,
+            _ => {
+                extending = false;
+                continue;
+            }
+        };
+
+        let start = text[..byte_start].chars().count();
+        let end = start + word.text.chars().count();
+        match spans.last_mut() {
+            Some(span) if extending => {
+                let span_byte_start = text
+                    .char_indices()
+                    .nth(span.start)
+                    .map_or(0, |(index, _)| index);
+                span.text = text[span_byte_start..byte_end].to_string();
+                span.end = end;
+                span.end_ms = word.end_ms;
+                span.confidence = span.confidence.min(confidence);
+            }
+            _ => spans.push(ConfidenceSpan {
+                text: word.text.clone(),
+                start,
+                end,
+                start_ms: word.start_ms,
+                end_ms: word.end_ms,
+                confidence,
+            }),
+        }
+        extending = true;
+    }
+
+    spans
 }
 
 /// Trait for transcription providers
@@ -100,4 +253,97 @@ pub trait TranscriptionProvider: Send + Sync {
 
     /// Check if the provider is configured and ready
     fn is_configured(&self) -> bool;
+
+    /// Whether the provider formats the text itself (a worker completion), so a
+    /// recording has to be sent whole rather than transcribed in pieces
+    fn completes_text(&self) -> bool {
+        false
+    }
+
+    /// Whether the provider runs on this machine, so it works in offline mode
+    fn is_local(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn word(text: &str, start_ms: u64, confidence: f32) -> WordTiming {
+        WordTiming {
+            text: text.to_string(),
+            start_ms,
+            end_ms: start_ms + 100,
+            confidence: Some(confidence),
+        }
+    }
+
+    #[test]
+    fn test_low_confidence_spans() {
+        let text = "Ask Zoë about the Q3 numbers, then email Bob.";
+        let segment = TranscriptionSegment {
+            text: text.to_string(),
+            start_ms: 0,
+            end_ms: 900,
+            confidence: Some(0.7),
+            words: vec![
+                word("Ask", 0, 0.95),
+                word("Zoë", 100, 0.3),
+                word("about", 200, 0.9),
+                word("the", 300, 0.9),
+                word("Q3", 400, 0.2),
+                word("numbers,", 500, 0.4),
+                word("then", 600, 0.9),
+                word("email", 700, 0.9),
+                word("Bob.", 800, 0.45),
+            ],
+        };
+
+        let spans = low_confidence_spans(text, &[segment], LOW_CONFIDENCE_THRESHOLD);
+        let summary: Vec<(&str, usize, usize)> = spans
+            .iter()
+            .map(|s| (s.text.as_str(), s.start, s.end))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![("Zoë", 4, 7), ("Q3 numbers,", 18, 29), ("Bob.", 41, 45)]
+        );
+        assert_eq!(spans[1].confidence, 0.2);
+        assert_eq!((spans[1].start_ms, spans[1].end_ms), (400, 600));
+        assert!(low_confidence_spans(text, &[], LOW_CONFIDENCE_THRESHOLD).is_empty());
+    }
+
+    #[test]
+    fn test_append_chunk_responses() {
+        let response =
+            |text: &str, duration_ms: u64, confidence: Option<f32>| TranscriptionResponse {
+                text: text.to_string(),
+                confidence,
+                language: None,
+                duration_ms,
+                segments: Some(vec![TranscriptionSegment {
+                    text: text.to_string(),
+                    start_ms: 0,
+                    end_ms: duration_ms,
+                    confidence,
+                    words: vec![word(text, 0, 0.9)],
+                }]),
+                completed_text: None,
+                provider: None,
+            };
+
+        let mut merged = response("Hello", 1000, Some(0.9)).offset_by(500);
+        merged.append(response("", 500, None).offset_by(1500));
+        merged.append(response("world", 3000, Some(0.5)).offset_by(2000));
+
+        assert_eq!(merged.text, "Hello world");
+        assert_eq!(merged.duration_ms, 4500);
+        assert!((merged.confidence.unwrap() - 2850.0 / 4500.0).abs() < 1e-6);
+        let segments = merged.segments.unwrap();
+        assert_eq!(segments.len(), 3);
+        assert_eq!((segments[0].start_ms, segments[0].end_ms), (500, 1500));
+        assert_eq!((segments[2].start_ms, segments[2].end_ms), (2000, 5000));
+        assert_eq!(segments[2].words[0].start_ms, 2000);
+    }
 }
@@ -0,0 +1,715 @@
+//! Token search for the local Whisper decoder
+//!
+//! Model-agnostic beam search, temperature sampling and the standard Whisper
+//! fallback loop. The model is only seen through a function that returns the
+//! next-token logits for a token prefix.
+
+use std::io::Write;
+
+use candle_transformers::models::whisper as m;
+use flate2::Compression;
+use flate2::write::ZlibEncoder;
+use rand::Rng;
+
+use crate::error::Result;
+
+/// Decoding parameters for the local Whisper decoder
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodingOptions {
+    /// Beams kept at temperature 0 (1 = greedy)
+    pub beam_size: usize,
+    /// Temperatures tried in order until the output passes the quality checks
+    pub temperatures: Vec<f64>,
+    /// Retry when the zlib compression ratio of the text is above this (repetition loops)
+    pub compression_ratio_threshold: f64,
+    /// Retry when the average token log probability is below this
+    pub logprob_threshold: f64,
+    /// Drop a window as silence when the no-speech probability is above this
+    /// and the average log probability is below `logprob_threshold`
+    pub no_speech_threshold: f64,
+    /// Predict timestamp tokens: yields timed segments and lets long audio
+    /// advance to the last complete segment instead of fixed 30s cuts
+    pub timestamps: bool,
+}
+
+impl Default for DecodingOptions {
+    fn default() -> Self {
+        Self {
+            beam_size: 1,
+            temperatures: m::TEMPERATURES.to_vec(),
+            compression_ratio_threshold: m::COMPRESSION_RATIO_THRESHOLD,
+            logprob_threshold: m::LOGPROB_THRESHOLD,
+            no_speech_threshold: m::NO_SPEECH_THRESHOLD,
+            timestamps: true,
+        }
+    }
+}
+
+impl DecodingOptions {
+    /// Set the beam size used at temperature 0 (clamped to at least 1)
+    pub fn with_beam_size(mut self, beam_size: usize) -> Self {
+        self.beam_size = beam_size.max(1);
+        self
+    }
+
+    /// Enable or disable timestamp-token decoding
+    pub fn with_timestamps(mut self, timestamps: bool) -> Self {
+        self.timestamps = timestamps;
+        self
+    }
+}
+
+/// Seconds per timestamp token (`<|0.00|>`, `<|0.02|>`, ...)
+pub(crate) const SECONDS_PER_TIMESTAMP: f64 = 0.02;
+
+/// Mel frames per timestamp token (the encoder halves the 10ms frame rate)
+pub(crate) const FRAMES_PER_TIMESTAMP: usize = 2;
+
+/// Latest timestamp allowed as the first token of a window (1 s), as in Whisper
+const MAX_INITIAL_TIMESTAMP: u32 = 50;
+
+/// Tokens chosen for one 30s window
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct DecodedWindow {
+    /// Generated tokens, without the prompt and the end-of-text token
+    pub tokens: Vec<u32>,
+    /// Log probability of each generated token
+    pub logprobs: Vec<f64>,
+    /// Decoded text
+    pub text: String,
+    /// Sum of token log probabilities divided by (token count + 1)
+    pub avg_logprob: f64,
+    /// Probability of the no-speech token at the start-of-transcript position
+    pub no_speech_prob: f64,
+    /// Temperature of the accepted attempt
+    pub temperature: f64,
+}
+
+impl DecodedWindow {
+    /// Whisper's silence rule: confident no-speech and a low-confidence transcript
+    pub fn is_silence(&self, options: &DecodingOptions) -> bool {
+        self.no_speech_prob > options.no_speech_threshold
+            && self.avg_logprob < options.logprob_threshold
+    }
+}
+
+/// Fixed per-window inputs for the search
+pub(crate) struct SearchParams<'a> {
+    /// Prompt tokens: `[<|startofprev|> context...] <|startoftranscript|> [<|lang|>] <|task|> <|notimestamps|>`
+    pub prompt: &'a [u32],
+    /// Position of `<|startoftranscript|>` in the prompt
+    pub sot_index: usize,
+    pub eot_token: u32,
+    pub no_speech_token: Option<u32>,
+    /// Tokens that are never generated (config suppress list, special tokens)
+    pub suppress: &'a [u32],
+    pub max_tokens: usize,
+    /// First timestamp token, when decoding with timestamps
+    pub timestamp_begin: Option<u32>,
+}
+
+/// Decode one window, retrying at higher temperatures while the output looks degenerate
+///
+/// `logits` returns the next-token logits for a token prefix; `detokenize` turns
+/// generated tokens into text for the compression ratio check.
+pub(crate) fn decode_with_fallback(
+    logits: &mut impl FnMut(&[u32]) -> Result<Vec<f32>>,
+    detokenize: &impl Fn(&[u32]) -> Result<String>,
+    params: &SearchParams,
+    options: &DecodingOptions,
+    rng: &mut impl Rng,
+) -> Result<DecodedWindow> {
+    // The logits at the start-of-transcript position carry the no-speech probability
+    let no_speech_prob = match params.no_speech_token {
+        Some(token) => {
+            let first = logits(&params.prompt[..=params.sot_index])?;
+            softmax(&first)
+                .get(token as usize)
+                .copied()
+                .unwrap_or_default()
+        }
+        None => 0.0,
+    };
+
+    let temperatures: &[f64] = if options.temperatures.is_empty() {
+        &[0.0]
+    } else {
+        &options.temperatures
+    };
+
+    let mut result = None;
+    for &temperature in temperatures {
+        let (tokens, logprobs) = if temperature > 0.0 {
+            sample(logits, params, temperature, rng)?
+        } else {
+            beam_search(logits, params, options.beam_size.max(1))?
+        };
+        let text = detokenize(&tokens)?;
+        let avg_logprob = logprobs.iter().sum::<f64>() / (tokens.len() + 1) as f64;
+        let ratio = compression_ratio(&text);
+
+        let window = DecodedWindow {
+            tokens,
+            logprobs,
+            text,
+            avg_logprob,
+            no_speech_prob,
+            temperature,
+        };
+
+        // Silence does not improve with temperature, keep the first attempt
+        let needs_fallback =
+            ratio > options.compression_ratio_threshold || avg_logprob < options.logprob_threshold;
+        if !needs_fallback || window.is_silence(options) {
+            return Ok(window);
+        }
+
+        tracing::debug!(
+            "Whisper fallback at t={:.1}: compression ratio {:.2}, avg logprob {:.2}",
+            temperature,
+            ratio,
+            avg_logprob
+        );
+        result = Some(window);
+    }
+
+    // Every temperature failed the checks; keep the last attempt
+    Ok(result.expect("at least one temperature is always tried"))
+}
+
+/// A partial hypothesis: prompt plus generated tokens, per-token log probabilities, score
+type Beam = (Vec<u32>, Vec<f64>, f64);
+
+/// Beam search at temperature 0 (greedy when `beam_size` is 1)
+/// Returns the generated tokens and their log probabilities
+fn beam_search(
+    logits: &mut impl FnMut(&[u32]) -> Result<Vec<f32>>,
+    params: &SearchParams,
+    beam_size: usize,
+) -> Result<(Vec<u32>, Vec<f64>)> {
+    let prompt_len = params.prompt.len();
+    let mut beams: Vec<Beam> = vec![(params.prompt.to_vec(), Vec::new(), 0.0)];
+    let mut finished: Vec<Beam> = Vec::new();
+
+    for _ in 0..params.max_tokens {
+        let mut candidates: Vec<(usize, u32, f64, f64)> = Vec::new();
+        for (index, (tokens, _, score)) in beams.iter().enumerate() {
+            let mut step = logits(tokens)?;
+            filter_logits(&mut step, &tokens[prompt_len..], params);
+            let logprobs = log_softmax(&step);
+            // One spare candidate per beam so an ending beam can be replaced
+            for (token, logprob) in top_k(&logprobs, beam_size + 1) {
+                candidates.push((index, token, logprob, score + logprob));
+            }
+        }
+        candidates.sort_by(|a, b| b.3.total_cmp(&a.3));
+
+        let mut next = Vec::with_capacity(beam_size);
+        for (index, token, logprob, score) in candidates {
+            let (tokens, logprobs, _) = &beams[index];
+            if token == params.eot_token {
+                finished.push((tokens[prompt_len..].to_vec(), logprobs.clone(), score));
+            } else {
+                let mut tokens = tokens.clone();
+                tokens.push(token);
+                let mut logprobs = logprobs.clone();
+                logprobs.push(logprob);
+                next.push((tokens, logprobs, score));
+            }
+            if next.len() == beam_size {
+                break;
+            }
+        }
+        beams = next;
+
+        if finished.len() >= beam_size || beams.is_empty() {
+            break;
+        }
+    }
+
+    // Hit the token limit: consider the unfinished beams too
+    if finished.len() < beam_size {
+        finished.extend(
+            beams
+                .into_iter()
+                .map(|(tokens, logprobs, score)| (tokens[prompt_len..].to_vec(), logprobs, score)),
+        );
+    }
+
+    // Length-normalised score so long hypotheses are not penalised for every extra token
+    Ok(finished
+        .into_iter()
+        .max_by(|a, b| {
+            let a_score = a.2 / a.0.len().max(1) as f64;
+            let b_score = b.2 / b.0.len().max(1) as f64;
+            a_score.total_cmp(&b_score)
+        })
+        .map(|(tokens, logprobs, _)| (tokens, logprobs))
+        .unwrap_or_default())
+}
+
+/// Sample one hypothesis at the given temperature
+/// Returns the generated tokens and their log probabilities (at temperature 1)
+fn sample(
+    logits: &mut impl FnMut(&[u32]) -> Result<Vec<f32>>,
+    params: &SearchParams,
+    temperature: f64,
+    rng: &mut impl Rng,
+) -> Result<(Vec<u32>, Vec<f64>)> {
+    let prompt_len = params.prompt.len();
+    let mut tokens = params.prompt.to_vec();
+    let mut token_logprobs = Vec::new();
+
+    for _ in 0..params.max_tokens {
+        let mut step = logits(&tokens)?;
+        filter_logits(&mut step, &tokens[prompt_len..], params);
+        let logprobs = log_softmax(&step);
+
+        let scaled: Vec<f32> = step.iter().map(|l| l / temperature as f32).collect();
+        let probs = softmax(&scaled);
+        let mut target = rng.random::<f64>();
+        let mut token = probs.len().saturating_sub(1);
+        for (index, p) in probs.iter().enumerate() {
+            target -= p;
+            if target <= 0.0 {
+                token = index;
+                break;
+            }
+        }
+
+        let token = token as u32;
+        if token == params.eot_token {
+            break;
+        }
+        token_logprobs.push(logprobs[token as usize]);
+        tokens.push(token);
+    }
+
+    Ok((tokens.split_off(prompt_len), token_logprobs))
+}
+
+/// Mask tokens that may not follow `generated`: the suppress list and, when
+/// decoding with timestamps, Whisper's timestamp rules
+fn filter_logits(logits: &mut [f32], generated: &[u32], params: &SearchParams) {
+    suppress(logits, params.suppress);
+    if let Some(timestamp_begin) = params.timestamp_begin {
+        apply_timestamp_rules(logits, generated, timestamp_begin, params.eot_token);
+    }
+}
+
+/// Timestamps come in pairs around each segment's text, never decrease, and the
+/// first token is an early timestamp
+fn apply_timestamp_rules(logits: &mut [f32], generated: &[u32], timestamp_begin: u32, eot: u32) {
+    let begin = (timestamp_begin as usize).min(logits.len());
+    let eot = (eot as usize).min(begin);
+    let is_timestamp = |token: &u32| *token >= timestamp_begin;
+
+    let last_was_timestamp = generated.last().is_some_and(is_timestamp);
+    let penultimate_was_timestamp =
+        generated.len() < 2 || is_timestamp(&generated[generated.len() - 2]);
+    if last_was_timestamp {
+        if penultimate_was_timestamp {
+            // A segment was just closed and reopened: text must follow
+            logits[begin..].fill(f32::NEG_INFINITY);
+        } else {
+            // Text was followed by a timestamp: close the pair or end
+            logits[..eot].fill(f32::NEG_INFINITY);
+        }
+    }
+
+    if let Some(&last) = generated.iter().rev().find(|t| is_timestamp(t)) {
+        // Segments have non-zero length, except where one ends and the next starts
+        let min_next = if last_was_timestamp && !penultimate_was_timestamp {
+            last
+        } else {
+            last + 1
+        };
+        let end = (min_next as usize).min(logits.len());
+        logits[begin..end.max(begin)].fill(f32::NEG_INFINITY);
+    }
+
+    if generated.is_empty() {
+        logits[..begin].fill(f32::NEG_INFINITY);
+        let last_allowed = begin + MAX_INITIAL_TIMESTAMP as usize;
+        if last_allowed + 1 < logits.len() {
+            logits[last_allowed + 1..].fill(f32::NEG_INFINITY);
+        }
+    }
+
+    // Prefer a timestamp when all timestamps together outweigh any single text token
+    let logprobs = log_softmax(logits);
+    let timestamp_logprob = log_sum_exp(&logprobs[begin..]);
+    let max_text_logprob = logprobs[..begin]
+        .iter()
+        .copied()
+        .fold(f64::NEG_INFINITY, f64::max);
+    if timestamp_logprob > max_text_logprob {
+        logits[..begin].fill(f32::NEG_INFINITY);
+    }
+}
+
+/// Tokens between two timestamps, with times relative to the window start
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct TimedSpan {
+    pub start_secs: f64,
+    pub end_secs: f64,
+    /// Text tokens (timestamps removed)
+    pub tokens: Vec<u32>,
+    pub logprobs: Vec<f64>,
+}
+
+/// Split a window decoded with timestamps into timed spans
+/// Returns the spans and the mel frames to advance: up to the last complete
+/// segment, or the whole window when the text ends without a closing pair
+pub(crate) fn split_timestamped(
+    window: &DecodedWindow,
+    timestamp_begin: u32,
+    window_frames: usize,
+) -> (Vec<TimedSpan>, usize) {
+    let tokens = &window.tokens;
+    let is_timestamp = |index: usize| tokens[index] >= timestamp_begin;
+    let time = |token: u32| (token - timestamp_begin) as f64 * SECONDS_PER_TIMESTAMP;
+    let window_secs = (window_frames / FRAMES_PER_TIMESTAMP) as f64 * SECONDS_PER_TIMESTAMP;
+
+    let span = |range: std::ops::Range<usize>, start_secs: f64, end_secs: f64| {
+        let (tokens, logprobs) = range
+            .filter(|&i| !is_timestamp(i))
+            .map(|i| {
+                (
+                    tokens[i],
+                    window.logprobs.get(i).copied().unwrap_or_default(),
+                )
+            })
+            .unzip();
+        TimedSpan {
+            start_secs,
+            end_secs,
+            tokens,
+            logprobs,
+        }
+    };
+
+    let single_timestamp_ending =
+        tokens.len() >= 2 && is_timestamp(tokens.len() - 1) && !is_timestamp(tokens.len() - 2);
+    let mut boundaries: Vec<usize> = (1..tokens.len())
+        .filter(|&i| is_timestamp(i) && is_timestamp(i - 1))
+        .collect();
+
+    if boundaries.is_empty() {
+        // Zero or one segment: it ends at its closing timestamp, or the window end
+        let end_secs = match tokens.last() {
+            Some(&last) if single_timestamp_ending && last != timestamp_begin => time(last),
+            _ => window_secs,
+        };
+        let start_secs = match tokens.first() {
+            Some(&first) if first >= timestamp_begin => time(first).min(end_secs),
+            _ => 0.0,
+        };
+        return (
+            vec![span(0..tokens.len(), start_secs, end_secs)],
+            window_frames,
+        );
+    }
+
+    if single_timestamp_ending {
+        boundaries.push(tokens.len());
+    }
+
+    let mut spans = Vec::with_capacity(boundaries.len());
+    let mut start = 0;
+    for &end in &boundaries {
+        let start_secs = if is_timestamp(start) {
+            time(tokens[start])
+        } else {
+            0.0
+        };
+        spans.push(span(start..end, start_secs, time(tokens[end - 1])));
+        start = end;
+    }
+
+    let advance = if single_timestamp_ending {
+        window_frames
+    } else {
+        // Resume at the start of the unfinished segment
+        let last = (tokens[start - 1] - timestamp_begin) as usize * FRAMES_PER_TIMESTAMP;
+        if last == 0 {
+            window_frames
+        } else {
+            last.min(window_frames)
+        }
+    };
+    (spans, advance)
+}
+
+/// Ratio of UTF-8 bytes to zlib-compressed bytes; repetitive text compresses well
+pub(crate) fn compression_ratio(text: &str) -> f64 {
+    if text.is_empty() {
+        return 0.0;
+    }
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    let compressed = encoder
+        .write_all(text.as_bytes())
+        .and_then(|_| encoder.finish());
+    match compressed {
+        Ok(compressed) if !compressed.is_empty() => text.len() as f64 / compressed.len() as f64,
+        _ => 0.0,
+    }
+}
+
+fn suppress(logits: &mut [f32], tokens: &[u32]) {
+    for &token in tokens {
+        if let Some(logit) = logits.get_mut(token as usize) {
+            *logit = f32::NEG_INFINITY;
+        }
+    }
+}
+
+fn softmax(logits: &[f32]) -> Vec<f64> {
+    let max = logits.iter().copied().fold(f32::NEG_INFINITY, f32::max) as f64;
+    let exps: Vec<f64> = logits.iter().map(|&l| (l as f64 - max).exp()).collect();
+    let total: f64 = exps.iter().sum();
+    exps.into_iter().map(|e| e / total).collect()
+}
+
+fn log_sum_exp(values: &[f64]) -> f64 {
+    let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    if max == f64::NEG_INFINITY {
+        return max;
+    }
+    max + values.iter().map(|v| (v - max).exp()).sum::<f64>().ln()
+}
+
+fn log_softmax(logits: &[f32]) -> Vec<f64> {
+    let max = logits.iter().copied().fold(f32::NEG_INFINITY, f32::max) as f64;
+    let log_total = logits
+        .iter()
+        .map(|&l| (l as f64 - max).exp())
+        .sum::<f64>()
+        .ln();
+    logits.iter().map(|&l| l as f64 - max - log_total).collect()
+}
+
+fn top_k(logprobs: &[f64], k: usize) -> Vec<(u32, f64)> {
+    let mut indexed: Vec<(u32, f64)> = logprobs
+        .iter()
+        .enumerate()
+        .filter(|(_, lp)| lp.is_finite())
+        .map(|(i, &lp)| (i as u32, lp))
+        .collect();
+    // Partial selection: a full sort of the ~52k vocabulary dominates small-model decoding
+    if k < indexed.len() {
+        indexed.select_nth_unstable_by(k, |a, b| b.1.total_cmp(&a.1));
+        indexed.truncate(k);
+    }
+    indexed.sort_by(|a, b| b.1.total_cmp(&a.1));
+    indexed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    const EOT: u32 = 0;
+    const NO_SPEECH: u32 = 1;
+    const SOT: u32 = 2;
+    const VOCAB: usize = 8;
+
+    fn logits_from(probs: &[(u32, f32)]) -> Vec<f32> {
+        let mut logits = vec![-20.0; VOCAB];
+        for &(token, p) in probs {
+            logits[token as usize] = p.ln();
+        }
+        logits
+    }
+
+    fn detokenize(tokens: &[u32]) -> Result<String> {
+        Ok(tokens.iter().map(|t| format!("w{} ", t)).collect())
+    }
+
+    fn params(prompt: &[u32]) -> SearchParams<'_> {
+        SearchParams {
+            prompt,
+            sot_index: 0,
+            eot_token: EOT,
+            no_speech_token: Some(NO_SPEECH),
+            suppress: &[],
+            max_tokens: 10,
+            timestamp_begin: None,
+        }
+    }
+
+    #[test]
+    fn test_beam_search_beats_greedy() {
+        // Greedy takes 3 (0.6) and is then unsure; 4 (0.4) leads to a confident 5
+        let mut logits = |tokens: &[u32]| -> Result<Vec<f32>> {
+            Ok(match &tokens[1..] {
+                [] => logits_from(&[(3, 0.6), (4, 0.4)]),
+                [3] => logits_from(&[(6, 0.3), (7, 0.3), (5, 0.2), (EOT, 0.2)]),
+                [4] => logits_from(&[(5, 0.99)]),
+                _ => logits_from(&[(EOT, 0.99)]),
+            })
+        };
+        let prompt = [SOT];
+
+        let (greedy, _) = beam_search(&mut logits, &params(&prompt), 1).unwrap();
+        assert_eq!(greedy[0], 3);
+
+        let (beam, logprobs) = beam_search(&mut logits, &params(&prompt), 3).unwrap();
+        assert_eq!(beam, vec![4, 5]);
+        assert_eq!(logprobs.len(), 2);
+        assert!((logprobs[0] - 0.4f64.ln()).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_suppressed_tokens_never_generated() {
+        let mut logits = |tokens: &[u32]| -> Result<Vec<f32>> {
+            Ok(match &tokens[1..] {
+                [] => logits_from(&[(7, 0.9), (3, 0.1)]),
+                _ => logits_from(&[(EOT, 0.99)]),
+            })
+        };
+        let prompt = [SOT];
+        let params = SearchParams {
+            suppress: &[7],
+            ..params(&prompt)
+        };
+
+        let (tokens, _) = beam_search(&mut logits, &params, 2).unwrap();
+        assert_eq!(tokens, vec![3]);
+    }
+
+    #[test]
+    fn test_fallback_on_repetition_loop() {
+        // At temperature 0 the model loops on token 3 until the token limit
+        let mut logits =
+            |_: &[u32]| -> Result<Vec<f32>> { Ok(logits_from(&[(3, 0.6), (EOT, 0.4)])) };
+        let prompt = [SOT];
+        let params = SearchParams {
+            max_tokens: 200,
+            ..params(&prompt)
+        };
+        let options = DecodingOptions::default();
+        let mut rng = StdRng::seed_from_u64(7);
+
+        let window =
+            decode_with_fallback(&mut logits, &detokenize, &params, &options, &mut rng).unwrap();
+        assert!(window.temperature > 0.0);
+        assert!(compression_ratio(&window.text) <= options.compression_ratio_threshold);
+    }
+
+    #[test]
+    fn test_no_speech_window_is_silence() {
+        let mut logits = |tokens: &[u32]| -> Result<Vec<f32>> {
+            Ok(match tokens.len() {
+                // Start-of-transcript position: mostly no-speech
+                1 => logits_from(&[(NO_SPEECH, 0.9), (3, 0.1)]),
+                // Low-confidence hallucination
+                2 | 3 => logits_from(&[(3, 0.2), (4, 0.2), (5, 0.2), (6, 0.2), (7, 0.2)]),
+                _ => logits_from(&[(EOT, 0.99)]),
+            })
+        };
+        let prompt = [SOT, 5];
+        let options = DecodingOptions::default();
+        let mut rng = StdRng::seed_from_u64(7);
+
+        let window = decode_with_fallback(
+            &mut logits,
+            &detokenize,
+            &params(&prompt),
+            &options,
+            &mut rng,
+        )
+        .unwrap();
+        assert!(window.no_speech_prob > 0.8);
+        assert!(window.is_silence(&options));
+        assert_eq!(window.temperature, 0.0);
+    }
+
+    #[test]
+    fn test_timestamp_rules() {
+        // Text 0..4, <|endoftext|> 4, a special token 5, timestamps from 6
+        const TEXT_EOT: u32 = 4;
+        const TS: u32 = 6;
+        let rules = |generated: &[u32]| {
+            let mut logits = vec![0.0f32; 10];
+            logits[1] = 5.0;
+            logits[TEXT_EOT as usize] = 5.0;
+            apply_timestamp_rules(&mut logits, generated, TS, TEXT_EOT);
+            logits
+        };
+        let masked = |logits: &[f32], index: usize| logits[index] == f32::NEG_INFINITY;
+
+        // The first token is a timestamp
+        let first = rules(&[]);
+        assert!((0..6).all(|i| masked(&first, i)));
+        assert!((6..10).all(|i| !masked(&first, i)));
+
+        // Timestamps never go backwards
+        let open = rules(&[TS, 1]);
+        assert!(masked(&open, 6));
+        assert!(!masked(&open, 7) && !masked(&open, 1));
+
+        // After text and a timestamp: close with an equal or later timestamp, or end
+        let closing = rules(&[TS, 1, TS + 2]);
+        assert!((0..4).all(|i| masked(&closing, i)));
+        assert!(!masked(&closing, TEXT_EOT as usize));
+        assert!(masked(&closing, 7) && !masked(&closing, 8));
+
+        // After a timestamp pair: text must follow
+        let reopened = rules(&[TS, 1, TS + 2, TS + 2]);
+        assert!((6..10).all(|i| masked(&reopened, i)));
+        assert!(!masked(&reopened, 1));
+    }
+
+    #[test]
+    fn test_split_timestamped() {
+        const TS: u32 = 100;
+        let window = |tokens: Vec<u32>| DecodedWindow {
+            logprobs: tokens.iter().map(|&t| -(t as f64) / 100.0).collect(),
+            tokens,
+            text: String::new(),
+            avg_logprob: 0.0,
+            no_speech_prob: 0.0,
+            temperature: 0.0,
+        };
+
+        // Two complete segments: consume the whole window
+        let (spans, advance) = split_timestamped(
+            &window(vec![TS, 1, 2, TS + 50, TS + 50, 3, TS + 100]),
+            TS,
+            3000,
+        );
+        assert_eq!(advance, 3000);
+        assert_eq!(spans.len(), 2);
+        assert_eq!(spans[0].tokens, vec![1, 2]);
+        assert_eq!(spans[0].logprobs, vec![-0.01, -0.02]);
+        assert!((spans[0].end_secs - 1.0).abs() < 1e-9);
+        assert_eq!(spans[1].tokens, vec![3]);
+        assert!((spans[1].start_secs - 1.0).abs() < 1e-9);
+        assert!((spans[1].end_secs - 2.0).abs() < 1e-9);
+
+        // The last segment is unfinished: resume at its start (1 s = 100 frames)
+        let (spans, advance) =
+            split_timestamped(&window(vec![TS, 1, TS + 50, TS + 50, 3]), TS, 3000);
+        assert_eq!(spans.len(), 1);
+        assert_eq!(spans[0].tokens, vec![1]);
+        assert_eq!(advance, 100);
+
+        // No closing timestamp: one span covering the window
+        let (spans, advance) = split_timestamped(&window(vec![TS + 10, 1, 2]), TS, 1500);
+        assert_eq!(advance, 1500);
+        assert_eq!(spans.len(), 1);
+        assert!((spans[0].start_secs - 0.2).abs() < 1e-9);
+        assert!((spans[0].end_secs - 15.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_compression_ratio() {
+        assert_eq!(compression_ratio(""), 0.0);
+        assert!(compression_ratio("The quick brown fox jumps over the lazy dog.") < 1.5);
+        let looping = "Thank you for watching. ".repeat(20);
+        assert!(compression_ratio(&looping) > m::COMPRESSION_RATIO_THRESHOLD);
+    }
+}
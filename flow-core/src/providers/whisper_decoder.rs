@@ -0,0 +1,450 @@
+//! Incremental Whisper text decoder
+//!
+//! Candle's Whisper decoder only caches cross-attention keys/values, so every
+//! step re-runs self-attention over the whole token sequence. This decoder keeps
+//! a self-attention key/value cache per decoded prefix and only feeds the new
+//! tokens. Weights load from either safetensors or GGUF checkpoints.
+
+use std::collections::VecDeque;
+
+use candle_core::{Device, IndexOp, Module, Result, Tensor};
+use candle_nn::LayerNorm;
+use candle_transformers::models::whisper::Config;
+use candle_transformers::{quantized_nn, quantized_var_builder};
+
+/// Linear layer from either checkpoint format
+type Layer = Box<dyn Module + Send>;
+
+/// Per-block (key, value) tensors, each shaped (1, seq_len, n_state)
+type KvCache = Vec<(Tensor, Tensor)>;
+
+const LAYER_NORM_EPS: f64 = 1e-5;
+
+/// Loads weights from a float (safetensors) or quantized (GGUF) var builder
+trait WeightSource: Sized {
+    fn pp(&self, name: &str) -> Self;
+    fn linear(&self, in_dim: usize, out_dim: usize, bias: bool) -> Result<Layer>;
+    fn layer_norm(&self, size: usize) -> Result<LayerNorm>;
+    fn tensor(&self, shape: (usize, usize), name: &str) -> Result<Tensor>;
+}
+
+impl WeightSource for candle_nn::VarBuilder<'_> {
+    fn pp(&self, name: &str) -> Self {
+        candle_nn::VarBuilder::pp(self, name)
+    }
+
+    fn linear(&self, in_dim: usize, out_dim: usize, bias: bool) -> Result<Layer> {
+        Ok(Box::new(candle_nn::linear_b(
+            in_dim,
+            out_dim,
+            bias,
+            self.clone(),
+        )?))
+    }
+
+    fn layer_norm(&self, size: usize) -> Result<LayerNorm> {
+        candle_nn::layer_norm(size, LAYER_NORM_EPS, self.clone())
+    }
+
+    fn tensor(&self, shape: (usize, usize), name: &str) -> Result<Tensor> {
+        self.get(shape, name)
+    }
+}
+
+impl WeightSource for quantized_var_builder::VarBuilder {
+    fn pp(&self, name: &str) -> Self {
+        quantized_var_builder::VarBuilder::pp(self, name)
+    }
+
+    fn linear(&self, in_dim: usize, out_dim: usize, bias: bool) -> Result<Layer> {
+        Ok(Box::new(quantized_nn::linear_b(
+            in_dim,
+            out_dim,
+            bias,
+            self.clone(),
+        )?))
+    }
+
+    fn layer_norm(&self, size: usize) -> Result<LayerNorm> {
+        quantized_nn::layer_norm(size, LAYER_NORM_EPS, self.clone())
+    }
+
+    fn tensor(&self, shape: (usize, usize), name: &str) -> Result<Tensor> {
+        self.get(shape, name)?.dequantize(self.device())
+    }
+}
+
+struct Attention {
+    query: Layer,
+    key: Layer,
+    value: Layer,
+    out: Layer,
+    n_head: usize,
+}
+
+impl Attention {
+    fn load(n_state: usize, n_head: usize, vb: &impl WeightSource) -> Result<Self> {
+        Ok(Self {
+            query: vb.pp("q_proj").linear(n_state, n_state, true)?,
+            key: vb.pp("k_proj").linear(n_state, n_state, false)?,
+            value: vb.pp("v_proj").linear(n_state, n_state, true)?,
+            out: vb.pp("out_proj").linear(n_state, n_state, true)?,
+            n_head,
+        })
+    }
+
+    fn key_value(&self, x: &Tensor) -> Result<(Tensor, Tensor)> {
+        Ok((self.key.forward(x)?, self.value.forward(x)?))
+    }
+
+    /// Attend from `x` to the given keys/values
+    /// mask: (new tokens, total tokens) additive mask, only needed for multi-token inputs
+    fn forward(&self, x: &Tensor, k: &Tensor, v: &Tensor, mask: Option<&Tensor>) -> Result<Tensor> {
+        let q = self.query.forward(x)?;
+        let (_, _, n_state) = q.dims3()?;
+        let scale = ((n_state / self.n_head) as f64).powf(-0.25);
+        let q = (self.split_heads(&q)? * scale)?;
+        let k = (self.split_heads(k)?.transpose(2, 3)? * scale)?;
+        let v = self.split_heads(v)?.contiguous()?;
+
+        let mut qk = q.matmul(&k)?;
+        if let Some(mask) = mask {
+            qk = qk.broadcast_add(mask)?;
+        }
+        let w = candle_nn::ops::softmax_last_dim(&qk)?;
+        let wv = w.matmul(&v)?.transpose(1, 2)?.flatten_from(2)?;
+        self.out.forward(&wv)
+    }
+
+    fn split_heads(&self, x: &Tensor) -> Result<Tensor> {
+        let (n_batch, n_ctx, n_state) = x.dims3()?;
+        x.reshape((n_batch, n_ctx, self.n_head, n_state / self.n_head))?
+            .transpose(1, 2)?
+            .contiguous()
+    }
+}
+
+struct Block {
+    attn: Attention,
+    attn_ln: LayerNorm,
+    cross_attn: Attention,
+    cross_attn_ln: LayerNorm,
+    mlp_linear1: Layer,
+    mlp_linear2: Layer,
+    mlp_ln: LayerNorm,
+}
+
+impl Block {
+    fn load(n_state: usize, n_head: usize, vb: &impl WeightSource) -> Result<Self> {
+        let n_mlp = n_state * 4;
+        Ok(Self {
+            attn: Attention::load(n_state, n_head, &vb.pp("self_attn"))?,
+            attn_ln: vb.pp("self_attn_layer_norm").layer_norm(n_state)?,
+            cross_attn: Attention::load(n_state, n_head, &vb.pp("encoder_attn"))?,
+            cross_attn_ln: vb.pp("encoder_attn_layer_norm").layer_norm(n_state)?,
+            mlp_linear1: vb.pp("fc1").linear(n_state, n_mlp, true)?,
+            mlp_linear2: vb.pp("fc2").linear(n_mlp, n_state, true)?,
+            mlp_ln: vb.pp("final_layer_norm").layer_norm(n_state)?,
+        })
+    }
+}
+
+/// Self-attention cache for one decoded token prefix
+struct CachedPrefix {
+    tokens: Vec<u32>,
+    kv: KvCache,
+    logits: Vec<f32>,
+}
+
+/// Whisper text decoder that only runs new tokens through the network
+pub(crate) struct CachedDecoder {
+    token_embedding: Tensor,
+    positional_embedding: Tensor,
+    blocks: Vec<Block>,
+    ln: LayerNorm,
+    /// Cross-attention keys/values for the current audio window
+    cross_kv: KvCache,
+    /// Recently decoded prefixes; beam search extends several of them per step
+    prefixes: VecDeque<CachedPrefix>,
+    capacity: usize,
+}
+
+impl CachedDecoder {
+    /// Load from a safetensors checkpoint (`model.decoder` prefix)
+    pub fn load(vb: candle_nn::VarBuilder, config: &Config) -> Result<Self> {
+        Self::load_from(&vb, config)
+    }
+
+    /// Load from a GGUF checkpoint (`model.decoder` prefix)
+    pub fn load_quantized(vb: quantized_var_builder::VarBuilder, config: &Config) -> Result<Self> {
+        Self::load_from(&vb, config)
+    }
+
+    fn load_from(vb: &impl WeightSource, config: &Config) -> Result<Self> {
+        let n_state = config.d_model;
+        let blocks = (0..config.decoder_layers)
+            .map(|i| {
+                Block::load(
+                    n_state,
+                    config.decoder_attention_heads,
+                    &vb.pp(&format!("layers.{i}")),
+                )
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            token_embedding: vb.tensor((config.vocab_size, n_state), "embed_tokens.weight")?,
+            positional_embedding: vb.tensor(
+                (config.max_target_positions, n_state),
+                "embed_positions.weight",
+            )?,
+            blocks,
+            ln: vb.pp("layer_norm").layer_norm(n_state)?,
+            cross_kv: Vec::new(),
+            prefixes: VecDeque::new(),
+            capacity: 1,
+        })
+    }
+
+    /// Start decoding a new audio window
+    /// Computes cross-attention keys/values once and drops all cached prefixes
+    /// capacity: number of prefixes to keep (at least 2 per beam)
+    pub fn start_window(&mut self, audio_features: &Tensor, capacity: usize) -> Result<()> {
+        self.cross_kv = self
+            .blocks
+            .iter()
+            .map(|block| block.cross_attn.key_value(audio_features))
+            .collect::<Result<_>>()?;
+        self.prefixes.clear();
+        self.capacity = capacity.max(1);
+        Ok(())
+    }
+
+    /// Next-token logits after `tokens`
+    /// Reuses the longest cached prefix and only runs the remaining tokens
+    pub fn logits(&mut self, tokens: &[u32]) -> Result<Vec<f32>> {
+        if tokens.is_empty() {
+            candle_core::bail!("Cannot decode an empty token sequence");
+        }
+        if self.cross_kv.is_empty() {
+            candle_core::bail!("start_window must be called before decoding");
+        }
+
+        let cached = self
+            .prefixes
+            .iter()
+            .enumerate()
+            .filter(|(_, prefix)| tokens.starts_with(&prefix.tokens))
+            .max_by_key(|(_, prefix)| prefix.tokens.len())
+            .map(|(index, _)| index);
+
+        if let Some(index) = cached
+            && self.prefixes[index].tokens.len() == tokens.len()
+        {
+            return Ok(self.prefixes[index].logits.clone());
+        }
+
+        let (offset, past) = match cached {
+            Some(index) => {
+                let prefix = &self.prefixes[index];
+                (prefix.tokens.len(), Some(&prefix.kv))
+            }
+            None => (0, None),
+        };
+        let (kv, logits) = self.forward(&tokens[offset..], offset, past)?;
+
+        if self.prefixes.len() >= self.capacity {
+            self.prefixes.pop_front();
+        }
+        self.prefixes.push_back(CachedPrefix {
+            tokens: tokens.to_vec(),
+            kv,
+            logits: logits.clone(),
+        });
+        Ok(logits)
+    }
+
+    /// Run `tokens` (starting at position `offset`) on top of the `past` cache
+    fn forward(
+        &self,
+        tokens: &[u32],
+        offset: usize,
+        past: Option<&KvCache>,
+    ) -> Result<(KvCache, Vec<f32>)> {
+        let device = self.token_embedding.device();
+        let n_new = tokens.len();
+
+        let ids = Tensor::new(tokens, device)?;
+        let positions = self.positional_embedding.narrow(0, offset, n_new)?;
+        let mut x = (self.token_embedding.index_select(&ids, 0)? + positions)?.unsqueeze(0)?;
+
+        // New tokens may attend to the whole past and to earlier new tokens only
+        let mask = if n_new > 1 {
+            Some(causal_mask(n_new, offset, device)?)
+        } else {
+            None
+        };
+
+        let mut kv = Vec::with_capacity(self.blocks.len());
+        for (i, block) in self.blocks.iter().enumerate() {
+            let h = block.attn_ln.forward(&x)?;
+            let (k_new, v_new) = block.attn.key_value(&h)?;
+            let (k, v) = match past {
+                Some(past) => (
+                    Tensor::cat(&[&past[i].0, &k_new], 1)?,
+                    Tensor::cat(&[&past[i].1, &v_new], 1)?,
+                ),
+                None => (k_new, v_new),
+            };
+            x = (&x + block.attn.forward(&h, &k, &v, mask.as_ref())?)?;
+            kv.push((k, v));
+
+            let (cross_k, cross_v) = &self.cross_kv[i];
+            let h = block.cross_attn_ln.forward(&x)?;
+            x = (&x + block.cross_attn.forward(&h, cross_k, cross_v, None)?)?;
+
+            let h = block.mlp_ln.forward(&x)?;
+            let mlp = block
+                .mlp_linear2
+                .forward(&block.mlp_linear1.forward(&h)?.gelu()?)?;
+            x = (x + mlp)?;
+        }
+
+        let last = self.ln.forward(&x)?.i((0, n_new - 1..))?;
+        let logits = last.matmul(&self.token_embedding.t()?)?.squeeze(0)?;
+        Ok((kv, logits.to_vec1()?))
+    }
+}
+
+/// Additive mask for `n_new` tokens following `offset` cached tokens
+fn causal_mask(n_new: usize, offset: usize, device: &Device) -> Result<Tensor> {
+    let total = offset + n_new;
+    let mask: Vec<f32> = (0..n_new)
+        .flat_map(|i| {
+            (0..total).map(move |j| {
+                if j > offset + i {
+                    f32::NEG_INFINITY
+                } else {
+                    0.0
+                }
+            })
+        })
+        .collect();
+    Tensor::from_vec(mask, (n_new, total), device)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use candle_core::DType;
+    use candle_nn::VarMap;
+    use candle_transformers::models::whisper as m;
+
+    /// Tiny English-only config so randomly initialised models run fast
+    pub(crate) fn tiny_config() -> Config {
+        Config {
+            num_mel_bins: 80,
+            max_source_positions: 1500,
+            d_model: 64,
+            encoder_attention_heads: 2,
+            encoder_layers: 2,
+            vocab_size: 51864,
+            max_target_positions: 64,
+            decoder_attention_heads: 2,
+            decoder_layers: 2,
+            suppress_tokens: Vec::new(),
+        }
+    }
+
+    /// Random weights shared by the Candle reference model and the cached decoder
+    pub(crate) fn random_whisper(config: &Config) -> (m::model::Whisper, CachedDecoder) {
+        let varmap = VarMap::new();
+        let vb = candle_nn::VarBuilder::from_varmap(&varmap, DType::F32, &Device::Cpu);
+        let whisper = m::model::Whisper::load(&vb, config.clone()).unwrap();
+        for var in varmap.all_vars() {
+            let random = Tensor::randn(0f32, 0.2, var.shape(), &Device::Cpu).unwrap();
+            var.set(&random).unwrap();
+        }
+        let decoder = CachedDecoder::load(vb.pp("model.decoder"), config).unwrap();
+        (whisper, decoder)
+    }
+
+    fn max_abs_diff(a: &[f32], b: &[f32]) -> f32 {
+        a.iter()
+            .zip(b)
+            .map(|(x, y)| (x - y).abs())
+            .fold(0.0, f32::max)
+    }
+
+    fn reference_logits(
+        whisper: &mut m::model::Whisper,
+        tokens: &[u32],
+        audio_features: &Tensor,
+    ) -> Vec<f32> {
+        let tokens_t = Tensor::new(tokens, &Device::Cpu)
+            .unwrap()
+            .unsqueeze(0)
+            .unwrap();
+        let output = whisper
+            .decoder
+            .forward(&tokens_t, audio_features, true)
+            .unwrap();
+        let tail = output.i((..1, tokens.len() - 1..)).unwrap();
+        whisper
+            .decoder
+            .final_linear(&tail)
+            .unwrap()
+            .i(0)
+            .unwrap()
+            .i(0)
+            .unwrap()
+            .to_vec1()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_cached_logits_match_full_decoder() {
+        let config = tiny_config();
+        let (mut whisper, mut decoder) = random_whisper(&config);
+        // A short window keeps the encoder cheap in debug builds
+        let mel = Tensor::randn(0f32, 1.0, (1, 80, 300), &Device::Cpu).unwrap();
+        let audio_features = whisper.encoder.forward(&mel, true).unwrap();
+        decoder.start_window(&audio_features, 4).unwrap();
+
+        let prompt = [50257, 50358, 50362];
+        // Multi-token prompt, then single-token steps on two diverging branches
+        let sequences: [&[u32]; 5] = [
+            &prompt,
+            &[50257, 50358, 50362, 11],
+            &[50257, 50358, 50362, 11, 12],
+            &[50257, 50358, 50362, 11, 13],
+            &[50257, 50358, 50362, 11, 13, 14],
+        ];
+        for tokens in sequences {
+            let cached = decoder.logits(tokens).unwrap();
+            let reference = reference_logits(&mut whisper, tokens, &audio_features);
+            assert_eq!(cached.len(), config.vocab_size);
+            assert!(
+                max_abs_diff(&cached, &reference) < 1e-3,
+                "logits diverge for {:?}",
+                tokens
+            );
+        }
+
+        // Exact prefix hits are served from the cache
+        let again = decoder.logits(&prompt).unwrap();
+        let reference = reference_logits(&mut whisper, &prompt, &audio_features);
+        assert!(max_abs_diff(&again, &reference) < 1e-3);
+    }
+
+    #[test]
+    fn test_causal_mask() {
+        let mask = causal_mask(2, 3, &Device::Cpu).unwrap();
+        let rows: Vec<Vec<f32>> = mask.to_vec2().unwrap();
+        assert_eq!(rows[0][..4], [0.0; 4]);
+        assert_eq!(rows[0][4], f32::NEG_INFINITY);
+        assert_eq!(rows[1], vec![0.0; 5]);
+        assert_eq!(mask.dims(), &[2, 5]);
+        assert!(rows.iter().all(|r| r.len() == 5));
+    }
+}
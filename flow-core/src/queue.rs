@@ -0,0 +1,136 @@
+//! Background retries for transcriptions that failed without network
+//!
+//! A cloud transcription that fails with a network error keeps its recording
+//! in the `transcription_queue` table. A worker thread retries each job with
+//! exponential backoff until it goes through, sleeping while nothing is due.
+
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use crate::error::{Error, Result};
+
+/// Retries before a queued transcription is given up
+pub const MAX_QUEUE_ATTEMPTS: u32 = 20;
+
+/// Delay before the first retry, doubled for each one after
+const BASE_RETRY_DELAY: Duration = Duration::from_secs(15);
+
+/// Longest wait between retries
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30 * 60);
+
+/// How long to wait before retry number `attempt` (1 for the first)
+pub fn retry_delay(attempt: u32) -> Duration {
+    let doublings = attempt.saturating_sub(1).min(16);
+    BASE_RETRY_DELAY
+        .saturating_mul(1 << doublings)
+        .min(MAX_RETRY_DELAY)
+}
+
+enum WorkerControl {
+    /// Jobs were queued or made due; look at the queue now
+    Wake,
+    Stop,
+}
+
+/// Thread that works through the queue whenever a job is due or it is woken
+///
+/// Stops when dropped, after finishing the pass it is in.
+pub struct QueueWorker {
+    control: Sender<WorkerControl>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl QueueWorker {
+    /// Start the worker; `run_due` processes the jobs that are due and returns how long
+    /// until the next one is (None when the queue is empty)
+    pub fn spawn<F>(mut run_due: F) -> Result<Self>
+    where
+        F: FnMut() -> Option<Duration> + Send + 'static,
+    {
+        let (control, commands) = mpsc::channel();
+        let thread = std::thread::Builder::new()
+            .name("flow-transcription-queue".to_string())
+            .spawn(move || {
+                // Jobs left over from an earlier session
+                let mut wait = run_due();
+                loop {
+                    let command = match wait {
+                        Some(wait) => commands.recv_timeout(wait),
+                        None => commands.recv().map_err(|_| RecvTimeoutError::Disconnected),
+                    };
+                    match command {
+                        Ok(WorkerControl::Wake) | Err(RecvTimeoutError::Timeout) => {
+                            wait = run_due();
+                        }
+                        Ok(WorkerControl::Stop) | Err(RecvTimeoutError::Disconnected) => break,
+                    }
+                }
+            })
+            .map_err(|e| Error::Config(format!("Failed to start transcription queue: {e}")))?;
+
+        Ok(Self {
+            control,
+            thread: Some(thread),
+        })
+    }
+
+    /// Look at the queue now instead of waiting for the next job to fall due
+    pub fn wake(&self) {
+        let _ = self.control.send(WorkerControl::Wake);
+    }
+}
+
+impl Drop for QueueWorker {
+    fn drop(&mut self) {
+        let _ = self.control.send(WorkerControl::Stop);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Instant;
+
+    #[test]
+    fn test_retry_delay_backs_off() {
+        assert_eq!(retry_delay(1), Duration::from_secs(15));
+        assert_eq!(retry_delay(2), Duration::from_secs(30));
+        assert_eq!(retry_delay(4), Duration::from_secs(120));
+        assert_eq!(retry_delay(8), MAX_RETRY_DELAY);
+        assert_eq!(retry_delay(MAX_QUEUE_ATTEMPTS), MAX_RETRY_DELAY);
+    }
+
+    fn wait_for(passes: &AtomicUsize, count: usize) {
+        let start = Instant::now();
+        while passes.load(Ordering::SeqCst) < count {
+            assert!(start.elapsed() < Duration::from_secs(5), "worker never ran");
+            std::thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    #[test]
+    fn test_worker_runs_when_due_or_woken() {
+        // Due again shortly after the first two passes, then idle
+        let passes = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&passes);
+        let worker = QueueWorker::spawn(move || {
+            let pass = counter.fetch_add(1, Ordering::SeqCst) + 1;
+            (pass < 3).then(|| Duration::from_millis(10))
+        })
+        .unwrap();
+        wait_for(&passes, 3);
+        std::thread::sleep(Duration::from_millis(50));
+        assert_eq!(passes.load(Ordering::SeqCst), 3);
+
+        worker.wake();
+        wait_for(&passes, 4);
+        drop(worker);
+        assert_eq!(passes.load(Ordering::SeqCst), 4);
+    }
+}
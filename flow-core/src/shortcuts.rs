@@ -3,6 +3,8 @@
 //! Allows users to define trigger phrases that expand to replacement text.
 //! Example: "my linkedin" -> "jsn.cam/li"
 
+use std::ops::Range;
+
 use aho_corasick::{AhoCorasick, AhoCorasickBuilder, MatchKind};
 use parking_lot::RwLock;
 use tracing::debug;
@@ -193,6 +195,162 @@ pub struct TriggeredShortcut {
     pub position: usize,
 }
 
+/// Instruction for a completion provider to leave expanded shortcuts exactly as written,
+/// appended to its system prompt (None when nothing was expanded)
+pub fn preservation_instruction(triggered: &[TriggeredShortcut]) -> Option<String> {
+    let mut replacements: Vec<&str> = Vec::new();
+    for shortcut in triggered {
+        if !replacements.contains(&shortcut.replacement.as_str()) {
+            replacements.push(&shortcut.replacement);
+        }
+    }
+    if replacements.is_empty() {
+        return None;
+    }
+
+    let mut instruction = String::from(
+        "\n\nThe text contains these expanded shortcuts. Keep each one exactly as written, \
+         word for word, without rephrasing, translating or changing its punctuation:",
+    );
+    for replacement in replacements {
+        instruction.push_str("\n- ");
+        instruction.push_str(replacement);
+    }
+    Some(instruction)
+}
+
+/// Make sure every expansion in `triggered` appears word for word in `text`, the output of
+/// a language model given the dictation `source` the shortcuts were found in
+///
+/// An expansion the model only respaced, recased or repunctuated is put back over that span.
+/// One it reworded or dropped is inserted again near where it was said in `source`, leaving
+/// the rest of the formatting alone
+pub fn restore_expansions(text: &str, source: &str, triggered: &[TriggeredShortcut]) -> String {
+    let mut text = text.to_string();
+    for shortcut in triggered {
+        if text.contains(&shortcut.replacement) {
+            continue;
+        }
+
+        if let Some(span) = normalized_span(&text, &shortcut.replacement) {
+            debug!(
+                "Restoring expansion of '{}' over '{}'",
+                shortcut.trigger,
+                &text[span.clone()]
+            );
+            text.replace_range(span, &shortcut.replacement);
+            continue;
+        }
+
+        debug!(
+            "Expansion of '{}' missing from formatted text, inserting it again",
+            shortcut.trigger
+        );
+        let at = insertion_point(&text, source, shortcut.position);
+        let before = text[..at].chars().next_back();
+        let after = text[at..].chars().next();
+        let mut insert = String::with_capacity(shortcut.replacement.len() + 2);
+        if before.is_some_and(|c| !c.is_whitespace()) {
+            insert.push(' ');
+        }
+        insert.push_str(&shortcut.replacement);
+        if after.is_some_and(|c| !c.is_whitespace()) {
+            insert.push(' ');
+        }
+        text.insert_str(at, &insert);
+    }
+    text
+}
+
+/// The run of words in `text` equal to `target` once spacing, case and punctuation are
+/// ignored
+///
+/// Punctuation closing the run is left out unless `target` ends with it too.
+fn normalized_span(text: &str, target: &str) -> Option<Range<usize>> {
+    let target_key = comparison_key(target);
+    if target_key.is_empty() {
+        return None;
+    }
+
+    let words = word_spans(text);
+    let target_words = target.split_whitespace().count().max(1);
+    // Respacing may split a word ("jason@example. com") or merge two
+    for len in target_words.saturating_sub(1).max(1)..=target_words + 2 {
+        for window in words.windows(len) {
+            let mut span = window[0].start..window[len - 1].end;
+            while let Some(last) = text[span.clone()].chars().last() {
+                if !matches!(last, '.' | ',' | ';' | ':' | '!' | '?') || target.ends_with(last) {
+                    break;
+                }
+                span.end -= last.len_utf8();
+            }
+
+            if comparison_key(&text[span.clone()]) == target_key {
+                return Some(span);
+            }
+        }
+    }
+    None
+}
+
+/// Where to put an expansion said at byte `position` of `source` back into `text`
+///
+/// That is just after the word said before it when the model kept that word (the occurrence
+/// nearest the same relative place), otherwise the word boundary nearest that place.
+fn insertion_point(text: &str, source: &str, position: usize) -> usize {
+    let Some(said_before) = source.get(..position) else {
+        return text.len();
+    };
+    let target = (text.len() as f64 * position as f64 / source.len().max(1) as f64) as usize;
+    let words = word_spans(text);
+
+    let Some(previous) = said_before.split_whitespace().next_back() else {
+        return 0;
+    };
+    let previous = comparison_key(previous);
+    words
+        .iter()
+        .filter(|word| comparison_key(&text[(*word).clone()]) == previous)
+        .map(|word| word.end)
+        .min_by_key(|end| end.abs_diff(target))
+        .or_else(|| {
+            words
+                .iter()
+                .map(|word| word.start)
+                .chain(std::iter::once(text.len()))
+                .min_by_key(|start| start.abs_diff(target))
+        })
+        .unwrap_or(text.len())
+}
+
+/// Byte ranges of the whitespace-separated words of `text`
+fn word_spans(text: &str) -> Vec<Range<usize>> {
+    let mut spans = Vec::new();
+    let mut start = None;
+    for (i, c) in text.char_indices() {
+        match (c.is_whitespace(), start) {
+            (true, Some(s)) => {
+                spans.push(s..i);
+                start = None;
+            }
+            (false, None) => start = Some(i),
+            _ => {}
+        }
+    }
+    if let Some(s) = start {
+        spans.push(s..text.len());
+    }
+    spans
+}
+
+/// Lowercase letters and digits only, so spacing and punctuation changes don't count
+fn comparison_key(text: &str) -> String {
+    text.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -233,6 +391,63 @@ mod tests {
         assert_eq!(triggered.len(), 1);
     }
 
+    #[test]
+    fn test_preservation_instruction() {
+        let engine = ShortcutsEngine::new();
+        engine.add_shortcut(Shortcut::new(
+            "my email".to_string(),
+            "jason@example.com".to_string(),
+        ));
+
+        let (_, triggered) = engine.process("hello world");
+        assert_eq!(preservation_instruction(&triggered), None);
+
+        // Each expansion is listed once
+        let (_, triggered) = engine.process("my email, again my email");
+        let instruction = preservation_instruction(&triggered).unwrap();
+        assert!(instruction.starts_with("\n\n"));
+        assert_eq!(instruction.matches("\n- jason@example.com").count(), 1);
+    }
+
+    #[test]
+    fn test_restore_expansions() {
+        let engine = ShortcutsEngine::new();
+        engine.add_shortcut(Shortcut::new(
+            "my address".to_string(),
+            "123 Main Street, Springfield".to_string(),
+        ));
+        engine.add_shortcut(Shortcut::new(
+            "my email".to_string(),
+            "jason@example.com".to_string(),
+        ));
+        let source = "ship it to my address and email my email";
+        let (_, triggered) = engine.process(source);
+
+        // Kept verbatim: nothing to do
+        let kept = "Ship it to 123 Main Street, Springfield and email jason@example.com.";
+        assert_eq!(restore_expansions(kept, source, &triggered), kept);
+
+        // Recased and respaced expansions are put back, keeping the sentence's punctuation
+        let respaced = "Ship it to 123 main street Springfield and email Jason@example. com.";
+        assert_eq!(restore_expansions(respaced, source, &triggered), kept);
+
+        // A reworded expansion is inserted again rather than overwriting nearby words
+        let source = "email my email";
+        let (_, triggered) = engine.process(source);
+        assert_eq!(
+            restore_expansions("Email Jason at example dot com!", source, &triggered),
+            "Email jason@example.com Jason at example dot com!"
+        );
+
+        // So is a dropped one, near where it was said
+        let source = "my email is what to use";
+        let (_, triggered) = engine.process(source);
+        assert_eq!(
+            restore_expansions("Use this.", source, &triggered),
+            "jason@example.com Use this."
+        );
+    }
+
     #[test]
     fn test_no_shortcuts() {
         let engine = ShortcutsEngine::new();
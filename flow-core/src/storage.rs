@@ -10,8 +10,8 @@ use uuid::Uuid;
 use crate::error::Result;
 use crate::types::{
     AnalyticsEvent, AppCategory, AppContext, Contact, ContactCategory, Correction,
-    CorrectionSource, EventType, Shortcut, Transcription, TranscriptionHistoryEntry,
-    TranscriptionStatus, WritingMode,
+    CorrectionSource, EventType, QueuedTranscription, Shortcut, Transcription,
+    TranscriptionHistoryEntry, TranscriptionId, TranscriptionStatus, WritingMode,
 };
 
 /// Storage backend using SQLite
@@ -24,11 +24,57 @@ pub const SETTING_GEMINI_API_KEY: &str = "gemini_api_key";
 pub const SETTING_ANTHROPIC_API_KEY: &str = "anthropic_api_key";
 pub const SETTING_OPENROUTER_API_KEY: &str = "openrouter_api_key";
 pub const SETTING_BASE10_API_KEY: &str = "base10_api_key";
+/// Completion provider: "openai" (default) | "gemini" | "openrouter" | "anthropic"
 pub const SETTING_COMPLETION_PROVIDER: &str = "completion_provider";
 pub const SETTING_USE_LOCAL_TRANSCRIPTION: &str = "use_local_transcription";
 pub const SETTING_LOCAL_WHISPER_MODEL: &str = "local_whisper_model";
 /// Cloud transcription provider: "auto" (default) | "openai"
 pub const SETTING_CLOUD_TRANSCRIPTION_PROVIDER: &str = "cloud_transcription_provider";
+/// Base URL for OpenAI-compatible servers, e.g. "http://localhost:11434/v1" (empty = api.openai.com)
+pub const SETTING_OPENAI_BASE_URL: &str = "openai_base_url";
+/// Model names for the OpenAI providers (empty = provider default)
+pub const SETTING_OPENAI_TRANSCRIPTION_MODEL: &str = "openai_transcription_model";
+pub const SETTING_OPENAI_COMPLETION_MODEL: &str = "openai_completion_model";
+/// Spoken language hint for transcription: "auto" (default) or an ISO 639-1 code
+pub const SETTING_TRANSCRIPTION_LANGUAGE: &str = "transcription_language";
+/// Translate speech to English instead of transcribing: "true" | "false" (default)
+pub const SETTING_TRANSLATE_TO_ENGLISH: &str = "translate_to_english";
+/// Beam size for local Whisper decoding: "1" (default, greedy) to "8"
+pub const SETTING_WHISPER_BEAM_SIZE: &str = "whisper_beam_size";
+/// Never use the network; transcribe and format locally: "true" | "false" (default)
+pub const SETTING_OFFLINE_MODE: &str = "offline_mode";
+/// Trim silence and skip recordings without speech: "true" (default) | "false"
+pub const SETTING_VAD_ENABLED: &str = "vad_enabled";
+/// Safetensors model that confirms voice activity, if any
+pub const SETTING_VAD_MODEL_PATH: &str = "vad_model_path";
+/// Input device id or name to record from (system default when unset)
+pub const SETTING_INPUT_DEVICE: &str = "input_device";
+/// Milliseconds of audio kept from before each recording starts (0 or unset: off)
+pub const SETTING_PRE_ROLL_MS: &str = "pre_roll_ms";
+/// Seconds a microphone kept open for pre-roll may sit idle before it is closed
+pub const SETTING_PRE_ROLL_IDLE_TIMEOUT_SECS: &str = "pre_roll_idle_timeout_secs";
+/// Whether recordings are high-pass filtered ("true"/"false", on when unset)
+pub const SETTING_HIGH_PASS_FILTER: &str = "high_pass_filter";
+/// Whether background noise is suppressed in recordings ("true"/"false", off when unset)
+pub const SETTING_NOISE_SUPPRESSION: &str = "noise_suppression";
+/// Whether recordings are levelled and peak-limited ("true"/"false", on when unset)
+pub const SETTING_AUTOMATIC_GAIN_CONTROL: &str = "automatic_gain_control";
+/// Whether recordings end by themselves after trailing silence ("true"/"false", off when unset)
+pub const SETTING_HANDS_FREE: &str = "hands_free";
+/// Silence after speech that ends a hands-free recording, in milliseconds
+pub const SETTING_HANDS_FREE_TRAILING_SILENCE_MS: &str = "hands_free_trailing_silence_ms";
+/// Shortest hands-free recording that silence may end, in milliseconds
+pub const SETTING_HANDS_FREE_MIN_DURATION_MS: &str = "hands_free_min_duration_ms";
+/// Longest hands-free recording, in milliseconds
+pub const SETTING_HANDS_FREE_MAX_DURATION_MS: &str = "hands_free_max_duration_ms";
+/// Whether recordings are transcribed while they are captured ("true"/"false", off when unset)
+pub const SETTING_LIVE_TRANSCRIPTION: &str = "live_transcription";
+/// Whether recordings are kept on disk with their history entries ("true"/"false", off when unset)
+pub const SETTING_AUDIO_ARCHIVE: &str = "audio_archive";
+/// Size of the audio archive in megabytes before the oldest recordings go (0: unlimited)
+pub const SETTING_AUDIO_ARCHIVE_MAX_MB: &str = "audio_archive_max_mb";
+/// Days archived recordings are kept (0: unlimited)
+pub const SETTING_AUDIO_ARCHIVE_MAX_AGE_DAYS: &str = "audio_archive_max_age_days";
 
 impl Storage {
     /// Open or create a database at the given path
@@ -147,6 +193,23 @@ impl Storage {
                 updated_at TEXT NOT NULL
             );
 
+            CREATE TABLE IF NOT EXISTS vocabulary (
+                term TEXT PRIMARY KEY COLLATE NOCASE,
+                created_at TEXT NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS transcription_queue (
+                id TEXT PRIMARY KEY,
+                audio BLOB NOT NULL,
+                sample_rate INTEGER NOT NULL,
+                writing_mode TEXT NOT NULL,
+                app_name TEXT,
+                attempts INTEGER NOT NULL DEFAULT 0,
+                next_attempt_at INTEGER NOT NULL,
+                last_error TEXT,
+                created_at TEXT NOT NULL
+            );
+
             CREATE INDEX IF NOT EXISTS idx_transcriptions_created ON transcriptions(created_at);
             CREATE INDEX IF NOT EXISTS idx_shortcuts_trigger ON shortcuts(trigger);
             CREATE INDEX IF NOT EXISTS idx_corrections_original ON corrections(original);
@@ -156,6 +219,7 @@ impl Storage {
             CREATE INDEX IF NOT EXISTS idx_style_samples_app ON style_samples(app_name);
             CREATE INDEX IF NOT EXISTS idx_contacts_name ON contacts(name);
             CREATE INDEX IF NOT EXISTS idx_contacts_frequency ON contacts(frequency DESC);
+            CREATE INDEX IF NOT EXISTS idx_transcription_queue_due ON transcription_queue(next_attempt_at);
             "#,
         )?;
 
@@ -165,6 +229,30 @@ impl Storage {
             [],
         );
 
+        // Migration: Add provider column to transcription_history if it doesn't exist
+        let _ = conn.execute(
+            "ALTER TABLE transcription_history ADD COLUMN provider TEXT",
+            [],
+        );
+
+        // Migration: Add segments column (JSON timings) to transcription_history if it doesn't exist
+        let _ = conn.execute(
+            "ALTER TABLE transcription_history ADD COLUMN segments TEXT",
+            [],
+        );
+
+        // Migration: Add confidence column to transcription_history if it doesn't exist
+        let _ = conn.execute(
+            "ALTER TABLE transcription_history ADD COLUMN confidence REAL",
+            [],
+        );
+
+        // Migration: Add completion_provider column to transcription_history if it doesn't exist
+        let _ = conn.execute(
+            "ALTER TABLE transcription_history ADD COLUMN completion_provider TEXT",
+            [],
+        );
+
         // Seed default corrections (only if table is empty)
         let count: i64 = conn.query_row(
             "SELECT COUNT(*) FROM corrections",
@@ -328,8 +416,9 @@ impl Storage {
         conn.execute(
             r#"
             INSERT INTO transcription_history (id, status, text, raw_text, error, duration_ms,
-                                               app_name, bundle_id, window_title, app_category, created_at)
-            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)
+                                               app_name, bundle_id, window_title, app_category, created_at,
+                                               provider, segments, confidence, completion_provider)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)
             "#,
             params![
                 entry.id.to_string(),
@@ -352,6 +441,11 @@ impl Storage {
                     .as_ref()
                     .map(|c| format!("{:?}", c.category)),
                 entry.created_at.to_rfc3339(),
+                entry.provider,
+                (!entry.segments.is_empty())
+                    .then(|| serde_json::to_string(&entry.segments).unwrap_or_default()),
+                entry.confidence,
+                entry.completion_provider,
             ],
         )?;
         debug!("Saved transcription history {}", entry.id);
@@ -361,61 +455,191 @@ impl Storage {
     /// Get recent transcription history entries
     pub fn get_recent_history(&self, limit: usize) -> Result<Vec<TranscriptionHistoryEntry>> {
         let conn = self.conn.lock();
-        let mut stmt = conn.prepare(
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {HISTORY_COLUMNS} FROM transcription_history ORDER BY created_at DESC LIMIT ?1"
+        ))?;
+
+        let entries = stmt
+            .query_map([limit as i64], history_entry_from_row)?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        Ok(entries)
+    }
+
+    /// Get one transcription history entry by id
+    pub fn get_history_entry(&self, id: &Uuid) -> Result<Option<TranscriptionHistoryEntry>> {
+        let conn = self.conn.lock();
+        let entry = conn
+            .query_row(
+                &format!("SELECT {HISTORY_COLUMNS} FROM transcription_history WHERE id = ?1"),
+                params![id.to_string()],
+                history_entry_from_row,
+            )
+            .optional()?;
+        Ok(entry)
+    }
+
+    /// Replace the outcome of a history entry (after transcribing its audio again)
+    /// Keeps the entry's app and creation time; returns false if the entry doesn't exist
+    pub fn update_history_entry(&self, entry: &TranscriptionHistoryEntry) -> Result<bool> {
+        let conn = self.conn.lock();
+        let rows = conn.execute(
             r#"
-            SELECT id, status, text, raw_text, error, duration_ms,
-                   app_name, bundle_id, window_title, app_category, created_at
-            FROM transcription_history
-            ORDER BY created_at DESC
-            LIMIT ?1
+            UPDATE transcription_history
+            SET status = ?2, text = ?3, raw_text = ?4, error = ?5, duration_ms = ?6,
+                provider = ?7, segments = ?8, confidence = ?9, completion_provider = ?10
+            WHERE id = ?1
             "#,
+            params![
+                entry.id.to_string(),
+                match entry.status {
+                    TranscriptionStatus::Success => "success",
+                    TranscriptionStatus::Failed => "failed",
+                },
+                entry.text,
+                entry.raw_text,
+                entry.error,
+                entry.duration_ms as i64,
+                entry.provider,
+                (!entry.segments.is_empty())
+                    .then(|| serde_json::to_string(&entry.segments).unwrap_or_default()),
+                entry.confidence,
+                entry.completion_provider,
+            ],
         )?;
+        debug!("Updated transcription history {}", entry.id);
+        Ok(rows > 0)
+    }
 
-        let entries = stmt
-            .query_map([limit as i64], |row| {
-                let id: String = row.get(0)?;
-                let status_str: String = row.get(1)?;
-                let app_name: Option<String> = row.get(6)?;
-                let bundle_id: Option<String> = row.get(7)?;
-                let window_title: Option<String> = row.get(8)?;
-                let app_category_str: Option<String> = row.get(9)?;
-                let created_at_str: String = row.get(10)?;
+    // ========== Transcription queue methods ==========
 
-                let app_context = app_name.map(|name| {
-                    let category = app_category_str
-                        .as_ref()
-                        .and_then(|s| parse_app_category(s))
-                        .unwrap_or(AppCategory::Unknown);
-                    AppContext {
-                        app_name: name,
-                        bundle_id,
-                        window_title,
-                        category,
-                    }
-                });
+    /// Queue a transcription to retry, replacing any job for the same history entry
+    pub fn enqueue_transcription(&self, job: &QueuedTranscription) -> Result<()> {
+        let conn = self.conn.lock();
+        conn.execute(
+            r#"
+            INSERT OR REPLACE INTO transcription_queue (id, audio, sample_rate, writing_mode, app_name,
+                                                        attempts, next_attempt_at, last_error, created_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+            "#,
+            params![
+                job.id.to_string(),
+                job.audio,
+                job.sample_rate,
+                format!("{:?}", job.mode),
+                job.app_name,
+                job.attempts,
+                job.next_attempt_at.timestamp_millis(),
+                job.last_error,
+                job.created_at.to_rfc3339(),
+            ],
+        )?;
+        debug!("Queued transcription {}", job.id);
+        Ok(())
+    }
 
-                let status = match status_str.as_str() {
-                    "success" => TranscriptionStatus::Success,
-                    "failed" => TranscriptionStatus::Failed,
-                    _ => TranscriptionStatus::Failed,
-                };
+    /// Queued transcriptions due by `now`, oldest first
+    pub fn get_due_transcriptions(&self, now: DateTime<Utc>) -> Result<Vec<QueuedTranscription>> {
+        let conn = self.conn.lock();
+        let mut stmt = conn.prepare(
+            r#"
+            SELECT id, audio, sample_rate, writing_mode, app_name, attempts, next_attempt_at,
+                   last_error, created_at
+            FROM transcription_queue
+            WHERE next_attempt_at <= ?1
+            ORDER BY created_at ASC
+            "#,
+        )?;
 
-                Ok(TranscriptionHistoryEntry {
+        let jobs = stmt
+            .query_map([now.timestamp_millis()], |row| {
+                let id: String = row.get(0)?;
+                let mode: String = row.get(3)?;
+                let next_attempt_at: i64 = row.get(6)?;
+                let created_at: String = row.get(8)?;
+                Ok(QueuedTranscription {
                     id: Uuid::parse_str(&id).unwrap_or_else(|_| Uuid::new_v4()),
-                    status,
-                    text: row.get(2)?,
-                    raw_text: row.get(3)?,
-                    error: row.get(4)?,
-                    duration_ms: row.get::<_, i64>(5)? as u64,
-                    app_context,
-                    created_at: DateTime::parse_from_rfc3339(&created_at_str)
+                    audio: row.get(1)?,
+                    sample_rate: row.get(2)?,
+                    mode: parse_writing_mode(&mode).unwrap_or_default(),
+                    app_name: row.get(4)?,
+                    attempts: row.get(5)?,
+                    next_attempt_at: DateTime::from_timestamp_millis(next_attempt_at)
+                        .unwrap_or_else(Utc::now),
+                    last_error: row.get(7)?,
+                    created_at: DateTime::parse_from_rfc3339(&created_at)
                         .map(|dt| dt.with_timezone(&Utc))
                         .unwrap_or_else(|_| Utc::now()),
                 })
             })?
             .collect::<std::result::Result<Vec<_>, _>>()?;
 
-        Ok(entries)
+        Ok(jobs)
+    }
+
+    /// When the next queued transcription is due, if any are queued
+    pub fn next_queued_transcription_at(&self) -> Result<Option<DateTime<Utc>>> {
+        let conn = self.conn.lock();
+        let next: Option<i64> = conn.query_row(
+            "SELECT MIN(next_attempt_at) FROM transcription_queue",
+            [],
+            |row| row.get(0),
+        )?;
+        Ok(next.and_then(DateTime::from_timestamp_millis))
+    }
+
+    /// Record a failed attempt and when to try again
+    pub fn reschedule_transcription(
+        &self,
+        id: &TranscriptionId,
+        attempts: u32,
+        next_attempt_at: DateTime<Utc>,
+        error: &str,
+    ) -> Result<()> {
+        let conn = self.conn.lock();
+        conn.execute(
+            r#"
+            UPDATE transcription_queue
+            SET attempts = ?2, next_attempt_at = ?3, last_error = ?4
+            WHERE id = ?1
+            "#,
+            params![
+                id.to_string(),
+                attempts,
+                next_attempt_at.timestamp_millis(),
+                error
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Make every queued transcription due now (e.g. when the network comes back)
+    pub fn retry_queued_transcriptions_now(&self) -> Result<usize> {
+        let conn = self.conn.lock();
+        let rows = conn.execute(
+            "UPDATE transcription_queue SET next_attempt_at = ?1",
+            params![Utc::now().timestamp_millis()],
+        )?;
+        Ok(rows)
+    }
+
+    /// Remove a queued transcription; returns false if it wasn't queued
+    pub fn delete_queued_transcription(&self, id: &TranscriptionId) -> Result<bool> {
+        let conn = self.conn.lock();
+        let rows = conn.execute(
+            "DELETE FROM transcription_queue WHERE id = ?1",
+            params![id.to_string()],
+        )?;
+        Ok(rows > 0)
+    }
+
+    /// Number of transcriptions waiting to be retried
+    pub fn queued_transcription_count(&self) -> Result<usize> {
+        let conn = self.conn.lock();
+        let count: i64 = conn.query_row("SELECT COUNT(*) FROM transcription_queue", [], |row| {
+            row.get(0)
+        })?;
+        Ok(count as usize)
     }
 
     // ========== Shortcut methods ==========
@@ -805,6 +1029,36 @@ impl Storage {
         Ok(result.and_then(|s| parse_writing_mode(&s)))
     }
 
+    // ========== Vocabulary methods ==========
+
+    /// Add a custom vocabulary term (case-insensitive, duplicates ignored)
+    pub fn add_vocabulary_term(&self, term: &str) -> Result<()> {
+        let conn = self.conn.lock();
+        conn.execute(
+            "INSERT OR IGNORE INTO vocabulary (term, created_at) VALUES (?1, ?2)",
+            params![term, Utc::now().to_rfc3339()],
+        )?;
+        debug!("Added vocabulary term: {}", term);
+        Ok(())
+    }
+
+    /// Remove a custom vocabulary term
+    pub fn remove_vocabulary_term(&self, term: &str) -> Result<bool> {
+        let conn = self.conn.lock();
+        let removed = conn.execute("DELETE FROM vocabulary WHERE term = ?1", params![term])?;
+        Ok(removed > 0)
+    }
+
+    /// Get all custom vocabulary terms, oldest first
+    pub fn get_vocabulary_terms(&self) -> Result<Vec<String>> {
+        let conn = self.conn.lock();
+        let mut stmt = conn.prepare("SELECT term FROM vocabulary ORDER BY created_at, rowid")?;
+        let terms = stmt
+            .query_map([], |row| row.get(0))?
+            .collect::<std::result::Result<Vec<String>, _>>()?;
+        Ok(terms)
+    }
+
     // ========== Style sample methods ==========
 
     /// Save a style sample for learning user's writing style in an app
@@ -890,6 +1144,59 @@ impl Storage {
     }
 }
 
+/// Columns read by `history_entry_from_row`, in order
+const HISTORY_COLUMNS: &str = "id, status, text, raw_text, error, duration_ms, app_name, bundle_id, \
+     window_title, app_category, created_at, provider, segments, confidence, completion_provider";
+
+fn history_entry_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<TranscriptionHistoryEntry> {
+    let id: String = row.get(0)?;
+    let status_str: String = row.get(1)?;
+    let app_name: Option<String> = row.get(6)?;
+    let bundle_id: Option<String> = row.get(7)?;
+    let window_title: Option<String> = row.get(8)?;
+    let app_category_str: Option<String> = row.get(9)?;
+    let created_at_str: String = row.get(10)?;
+    let segments_json: Option<String> = row.get(12)?;
+
+    let app_context = app_name.map(|name| {
+        let category = app_category_str
+            .as_ref()
+            .and_then(|s| parse_app_category(s))
+            .unwrap_or(AppCategory::Unknown);
+        AppContext {
+            app_name: name,
+            bundle_id,
+            window_title,
+            category,
+        }
+    });
+
+    let status = match status_str.as_str() {
+        "success" => TranscriptionStatus::Success,
+        "failed" => TranscriptionStatus::Failed,
+        _ => TranscriptionStatus::Failed,
+    };
+
+    Ok(TranscriptionHistoryEntry {
+        id: Uuid::parse_str(&id).unwrap_or_else(|_| Uuid::new_v4()),
+        status,
+        text: row.get(2)?,
+        raw_text: row.get(3)?,
+        error: row.get(4)?,
+        duration_ms: row.get::<_, i64>(5)? as u64,
+        app_context,
+        provider: row.get(11)?,
+        completion_provider: row.get(14)?,
+        confidence: row.get(13)?,
+        segments: segments_json
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default(),
+        created_at: DateTime::parse_from_rfc3339(&created_at_str)
+            .map(|dt| dt.with_timezone(&Utc))
+            .unwrap_or_else(|_| Utc::now()),
+    })
+}
+
 fn parse_app_category(s: &str) -> Option<AppCategory> {
     match s {
         "Email" => Some(AppCategory::Email),
@@ -1108,6 +1415,7 @@ impl Storage {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::providers::TranscriptionSegment;
 
     #[test]
     fn test_storage_crud() {
@@ -1148,6 +1456,130 @@ mod tests {
         assert_eq!(recent[0].raw_text, "hello world");
     }
 
+    #[test]
+    fn test_history_provider_roundtrip() {
+        let storage = Storage::in_memory().unwrap();
+
+        let mut served = TranscriptionHistoryEntry::success(
+            "hello world".to_string(),
+            "Hello world.".to_string(),
+            1500,
+        );
+        served.provider = Some("OpenAI Whisper".to_string());
+        served.completion_provider = Some("Anthropic".to_string());
+        served.confidence = Some(0.82);
+        served.segments = vec![TranscriptionSegment {
+            text: "hello world".to_string(),
+            start_ms: 120,
+            end_ms: 1380,
+            confidence: Some(0.9),
+            words: vec![],
+        }];
+        storage.save_history_entry(&served).unwrap();
+
+        let failed = TranscriptionHistoryEntry::failure("Worker error".to_string(), 800);
+        storage.save_history_entry(&failed).unwrap();
+
+        let history = storage.get_recent_history(10).unwrap();
+        assert_eq!(history.len(), 2);
+        let served = history.iter().find(|e| e.id == served.id).unwrap();
+        assert_eq!(served.provider.as_deref(), Some("OpenAI Whisper"));
+        assert_eq!(served.completion_provider.as_deref(), Some("Anthropic"));
+        assert_eq!(served.confidence, Some(0.82));
+        assert_eq!(served.segments.len(), 1);
+        assert_eq!(served.segments[0].end_ms, 1380);
+        let failed = history.iter().find(|e| e.id == failed.id).unwrap();
+        assert_eq!(failed.provider, None);
+        assert_eq!(failed.completion_provider, None);
+        assert!(failed.segments.is_empty());
+    }
+
+    #[test]
+    fn test_history_entry_update() {
+        let storage = Storage::in_memory().unwrap();
+        let mut entry = TranscriptionHistoryEntry::failure("Worker error".to_string(), 800);
+        entry.app_context = Some(AppContext {
+            app_name: "Slack".to_string(),
+            bundle_id: None,
+            window_title: None,
+            category: AppCategory::Slack,
+        });
+        storage.save_history_entry(&entry).unwrap();
+
+        let mut retried = TranscriptionHistoryEntry::success(
+            "hi there".to_string(),
+            "Hi there.".to_string(),
+            800,
+        );
+        retried.id = entry.id;
+        retried.provider = Some("Local Whisper (Metal)".to_string());
+        assert!(storage.update_history_entry(&retried).unwrap());
+
+        let stored = storage.get_history_entry(&entry.id).unwrap().unwrap();
+        assert!(matches!(stored.status, TranscriptionStatus::Success));
+        assert_eq!(stored.text, "Hi there.");
+        assert_eq!(stored.error, None);
+        assert_eq!(stored.provider.as_deref(), Some("Local Whisper (Metal)"));
+        // The original app and time are kept
+        assert_eq!(stored.app_context.unwrap().app_name, "Slack");
+        assert_eq!(stored.created_at.timestamp(), entry.created_at.timestamp());
+
+        let missing = TranscriptionHistoryEntry::failure("x".to_string(), 0);
+        assert!(!storage.update_history_entry(&missing).unwrap());
+        assert!(storage.get_history_entry(&missing.id).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_transcription_queue() {
+        let storage = Storage::in_memory().unwrap();
+        assert_eq!(storage.next_queued_transcription_at().unwrap(), None);
+
+        let job = QueuedTranscription::new(
+            Uuid::new_v4(),
+            vec![1, 2, 3, 4],
+            16000,
+            WritingMode::Formal,
+            Some("Mail".to_string()),
+        );
+        storage.enqueue_transcription(&job).unwrap();
+        assert_eq!(storage.queued_transcription_count().unwrap(), 1);
+
+        let due = storage.get_due_transcriptions(Utc::now()).unwrap();
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].audio, job.audio);
+        assert_eq!(due[0].mode, WritingMode::Formal);
+        assert_eq!(due[0].app_name.as_deref(), Some("Mail"));
+
+        // Pushed back, it isn't due until then
+        let later = Utc::now() + chrono::Duration::minutes(5);
+        storage
+            .reschedule_transcription(&job.id, 1, later, "connection refused")
+            .unwrap();
+        assert!(
+            storage
+                .get_due_transcriptions(Utc::now())
+                .unwrap()
+                .is_empty()
+        );
+        assert_eq!(
+            storage
+                .next_queued_transcription_at()
+                .unwrap()
+                .map(|at| at.timestamp_millis()),
+            Some(later.timestamp_millis())
+        );
+        let due = storage.get_due_transcriptions(later).unwrap();
+        assert_eq!(due[0].attempts, 1);
+        assert_eq!(due[0].last_error.as_deref(), Some("connection refused"));
+
+        assert_eq!(storage.retry_queued_transcriptions_now().unwrap(), 1);
+        assert_eq!(storage.get_due_transcriptions(Utc::now()).unwrap().len(), 1);
+
+        assert!(storage.delete_queued_transcription(&job.id).unwrap());
+        assert!(!storage.delete_queued_transcription(&job.id).unwrap());
+        assert_eq!(storage.queued_transcription_count().unwrap(), 0);
+    }
+
     #[test]
     fn test_app_modes() {
         let storage = Storage::in_memory().unwrap();
@@ -1172,6 +1604,23 @@ mod tests {
         assert_eq!(value, Some("test-key".to_string()));
     }
 
+    #[test]
+    fn test_vocabulary_terms() {
+        let storage = Storage::in_memory().unwrap();
+
+        storage.add_vocabulary_term("Flowwispr").unwrap();
+        storage.add_vocabulary_term("Base10").unwrap();
+        storage.add_vocabulary_term("flowwispr").unwrap();
+        assert_eq!(
+            storage.get_vocabulary_terms().unwrap(),
+            vec!["Flowwispr", "Base10"]
+        );
+
+        assert!(storage.remove_vocabulary_term("FLOWWISPR").unwrap());
+        assert!(!storage.remove_vocabulary_term("missing").unwrap());
+        assert_eq!(storage.get_vocabulary_terms().unwrap(), vec!["Base10"]);
+    }
+
     #[test]
     fn test_correction_deletion() {
         let storage = Storage::in_memory().unwrap();
@@ -4,6 +4,8 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use crate::providers::TranscriptionSegment;
+
 /// Unique identifier for transcriptions
 pub type TranscriptionId = Uuid;
 
@@ -111,6 +113,17 @@ pub struct TranscriptionHistoryEntry {
     pub error: Option<String>,
     pub duration_ms: u64,
     pub app_context: Option<AppContext>,
+    /// Provider that served the transcription (records failover)
+    pub provider: Option<String>,
+    /// Provider that formatted the text, when one did (records failover)
+    #[serde(default)]
+    pub completion_provider: Option<String>,
+    /// Overall transcription confidence (0.0 - 1.0), when the provider reported one
+    #[serde(default)]
+    pub confidence: Option<f32>,
+    /// Timed segments, when the provider returned them
+    #[serde(default)]
+    pub segments: Vec<TranscriptionSegment>,
     pub created_at: DateTime<Utc>,
 }
 
@@ -124,6 +137,10 @@ impl TranscriptionHistoryEntry {
             error: None,
             duration_ms,
             app_context: None,
+            provider: None,
+            completion_provider: None,
+            confidence: None,
+            segments: Vec::new(),
             created_at: Utc::now(),
         }
     }
@@ -137,11 +154,58 @@ impl TranscriptionHistoryEntry {
             error: Some(error),
             duration_ms,
             app_context: None,
+            provider: None,
+            completion_provider: None,
+            confidence: None,
+            segments: Vec::new(),
             created_at: Utc::now(),
         }
     }
 }
 
+/// A cloud transcription that failed for lack of network, waiting to be retried
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueuedTranscription {
+    /// History entry resolved by the result
+    pub id: TranscriptionId,
+    /// Mono 16-bit PCM
+    pub audio: AudioData,
+    pub sample_rate: u32,
+    /// Writing mode chosen when the recording was made
+    pub mode: WritingMode,
+    /// App the recording was dictated into
+    pub app_name: Option<String>,
+    /// Retries made so far
+    pub attempts: u32,
+    pub next_attempt_at: DateTime<Utc>,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl QueuedTranscription {
+    /// Queue a recording for its (failed) history entry, due right away
+    pub fn new(
+        id: TranscriptionId,
+        audio: AudioData,
+        sample_rate: u32,
+        mode: WritingMode,
+        app_name: Option<String>,
+    ) -> Self {
+        let now = Utc::now();
+        Self {
+            id,
+            audio,
+            sample_rate,
+            mode,
+            app_name,
+            attempts: 0,
+            next_attempt_at: now,
+            last_error: None,
+            created_at: now,
+        }
+    }
+}
+
 impl Transcription {
     pub fn new(
         raw_text: String,
@@ -0,0 +1,162 @@
+//! Custom vocabulary for transcription prompts
+//!
+//! Whisper-style models spell words the way they appear in the prompt, so names
+//! and jargon the user cares about (product names, teammates, shortcut triggers)
+//! are collected into a short initial prompt that biases recognition toward them.
+
+use std::collections::HashSet;
+
+use crate::contacts::ContactClassifier;
+use crate::error::Result;
+use crate::learning::LearningEngine;
+use crate::shortcuts::ShortcutsEngine;
+use crate::storage::Storage;
+
+/// Upper bound on prompt length (Whisper keeps at most ~224 prompt tokens)
+pub const MAX_PROMPT_CHARS: usize = 600;
+
+/// Number of frequent contacts included in the prompt
+const MAX_CONTACTS: usize = 20;
+
+/// Terms feeding the vocabulary prompt, highest priority first
+#[derive(Debug, Clone, Default)]
+pub struct VocabularySources {
+    /// Terms the user added explicitly
+    pub user_terms: Vec<String>,
+    /// Trigger phrases of enabled shortcuts
+    pub shortcut_triggers: Vec<String>,
+    /// Corrected spellings from high-confidence learned corrections
+    pub corrections: Vec<String>,
+    /// Names of frequently contacted people
+    pub contacts: Vec<String>,
+}
+
+impl VocabularySources {
+    /// Gather terms from storage and the in-memory engines
+    pub fn collect(
+        storage: &Storage,
+        shortcuts: &ShortcutsEngine,
+        learning: &LearningEngine,
+        contacts: &ContactClassifier,
+    ) -> Result<Self> {
+        let mut corrections = learning.get_all_corrections();
+        corrections.sort_by(|a, b| b.2.total_cmp(&a.2).then_with(|| a.1.cmp(&b.1)));
+
+        Ok(Self {
+            user_terms: storage.get_vocabulary_terms()?,
+            shortcut_triggers: shortcuts.get_all().into_iter().map(|s| s.trigger).collect(),
+            corrections: corrections
+                .into_iter()
+                .map(|(_, corrected, _)| corrected)
+                .collect(),
+            contacts: contacts
+                .get_frequent_contacts(MAX_CONTACTS)
+                .into_iter()
+                .filter(|c| c.frequency > 0)
+                .map(|c| c.name)
+                .collect(),
+        })
+    }
+
+    /// Build the prompt: a comma-separated list of unique terms, truncated to
+    /// `MAX_PROMPT_CHARS` with lower-priority sources dropped first
+    /// Returns None when there is no vocabulary
+    pub fn build_prompt(&self) -> Option<String> {
+        let mut seen = HashSet::new();
+        let mut prompt = String::new();
+
+        let terms = self
+            .user_terms
+            .iter()
+            .chain(&self.shortcut_triggers)
+            .chain(&self.corrections)
+            .chain(&self.contacts)
+            .map(|term| term.trim())
+            .filter(|term| term.chars().any(char::is_alphabetic));
+
+        for term in terms {
+            if !seen.insert(term.to_lowercase()) {
+                continue;
+            }
+            let separator = if prompt.is_empty() { "" } else { ", " };
+            if prompt.len() + separator.len() + term.len() + 1 > MAX_PROMPT_CHARS {
+                break;
+            }
+            prompt.push_str(separator);
+            prompt.push_str(term);
+        }
+
+        if prompt.is_empty() {
+            None
+        } else {
+            prompt.push('.');
+            Some(prompt)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Contact, ContactCategory, Shortcut};
+
+    #[test]
+    fn test_prompt_dedup_and_priority() {
+        let sources = VocabularySources {
+            user_terms: vec!["Flow".to_string(), "Base10".to_string()],
+            shortcut_triggers: vec!["my linkedin".to_string()],
+            corrections: vec!["GitHub".to_string(), "flow".to_string()],
+            contacts: vec!["Jason Cameron".to_string(), "+1 555 0100".to_string()],
+        };
+
+        assert_eq!(
+            sources.build_prompt().as_deref(),
+            Some("Flow, Base10, my linkedin, GitHub, Jason Cameron.")
+        );
+        assert_eq!(VocabularySources::default().build_prompt(), None);
+    }
+
+    #[test]
+    fn test_prompt_truncates_low_priority_terms() {
+        let sources = VocabularySources {
+            user_terms: vec!["Flow".to_string()],
+            contacts: (0..200).map(|i| format!("Contact Number{}", i)).collect(),
+            ..Default::default()
+        };
+
+        let prompt = sources.build_prompt().unwrap();
+        assert!(prompt.len() <= MAX_PROMPT_CHARS);
+        assert!(prompt.starts_with("Flow, Contact Number0"));
+        assert!(prompt.ends_with('.'));
+    }
+
+    #[test]
+    fn test_collect_sources() {
+        let storage = Storage::in_memory().unwrap();
+        storage.add_vocabulary_term("Flowwispr").unwrap();
+
+        let contacts = ContactClassifier::new();
+        let mut frequent = Contact::new(
+            "Alice Nguyen".to_string(),
+            None,
+            ContactCategory::Professional,
+        );
+        frequent.frequency = 3;
+        contacts.upsert_contact(frequent);
+        contacts.upsert_contact(Contact::new(
+            "Never Messaged".to_string(),
+            None,
+            ContactCategory::FormalNeutral,
+        ));
+
+        let shortcuts = ShortcutsEngine::new();
+        shortcuts.add_shortcut(Shortcut::new("my email".to_string(), "a@b.c".to_string()));
+        let learning = LearningEngine::new();
+
+        let sources =
+            VocabularySources::collect(&storage, &shortcuts, &learning, &contacts).unwrap();
+        assert_eq!(sources.user_terms, vec!["Flowwispr"]);
+        assert_eq!(sources.shortcut_triggers, vec!["my email"]);
+        assert_eq!(sources.contacts, vec!["Alice Nguyen"]);
+    }
+}
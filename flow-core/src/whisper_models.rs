@@ -1,7 +1,38 @@
 //! Whisper model management utilities
+//!
+//! Every installed model lives in its own directory under `get_models_dir()`,
+//! next to a `manifest.json` recording its source and the SHA-256 of each file.
+//! Models are downloaded from a Hugging Face repo (any Whisper safetensors or
+//! GGUF checkpoint) or imported from a local directory.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use reqwest::StatusCode;
+use reqwest::header::{CONTENT_LENGTH, ETAG, LOCATION, RANGE};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tracing::{debug, info, warn};
 
 use crate::error::{Error, Result};
-use std::path::PathBuf;
+
+/// Manifest written into each installed model's directory
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+/// Hugging Face hub, overridable with HF_ENDPOINT like the official clients
+const DEFAULT_HF_ENDPOINT: &str = "https://huggingface.co";
+
+/// Extension appended to files that are still downloading
+const PARTIAL_EXTENSION: &str = "part";
+
+/// Read buffer for hashing and copying
+const CHUNK_SIZE: usize = 1 << 20;
+
+/// Redirects followed when resolving hub metadata (renamed repos)
+const MAX_METADATA_REDIRECTS: usize = 5;
 
 /// Get default model directory (~/Library/Application Support/FlowWispr/models)
 pub fn get_models_dir() -> Result<PathBuf> {
@@ -16,3 +47,1190 @@ pub fn get_models_dir() -> Result<PathBuf> {
 
     Ok(models_dir)
 }
+
+/// Where a model's files come from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ModelSource {
+    /// A Hugging Face model repo at a revision (branch, tag or commit)
+    HuggingFace { repo: String, revision: String },
+    /// A directory on disk holding config.json, tokenizer.json and weights
+    Local { path: PathBuf },
+}
+
+/// Which part of a checkpoint a file holds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ModelFileRole {
+    Config,
+    Tokenizer,
+    Weights,
+}
+
+impl ModelFileRole {
+    const ALL: [ModelFileRole; 3] = [
+        ModelFileRole::Config,
+        ModelFileRole::Tokenizer,
+        ModelFileRole::Weights,
+    ];
+}
+
+/// Paths of the three files a Whisper checkpoint is loaded from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModelFiles {
+    pub config: PathBuf,
+    pub tokenizer: PathBuf,
+    pub weights: PathBuf,
+}
+
+impl ModelFiles {
+    /// GGUF weights are loaded as a quantized model
+    pub fn is_quantized(&self) -> bool {
+        is_gguf(&self.weights)
+    }
+
+    fn path(&self, role: ModelFileRole) -> &Path {
+        match role {
+            ModelFileRole::Config => &self.config,
+            ModelFileRole::Tokenizer => &self.tokenizer,
+            ModelFileRole::Weights => &self.weights,
+        }
+    }
+
+    fn exist(&self) -> bool {
+        ModelFileRole::ALL
+            .iter()
+            .all(|&role| self.path(role).is_file())
+    }
+}
+
+/// A model to install: its registry id, source and file names
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModelSpec {
+    /// Directory name under the models directory
+    pub id: String,
+    pub source: ModelSource,
+    pub config_file: String,
+    pub tokenizer_file: String,
+    pub weights_file: String,
+    /// Expected SHA-256 (lowercase hex) per file; hub LFS hashes are checked as well
+    pub expected_sha256: HashMap<ModelFileRole, String>,
+    /// Files from the pre-registry flat layout, adopted instead of downloading
+    legacy_files: Option<ModelFiles>,
+}
+
+impl ModelSpec {
+    /// Model from a Hugging Face repo with the standard transformers file names
+    pub fn hugging_face(repo: &str, revision: &str) -> Self {
+        Self::from_source(ModelSource::HuggingFace {
+            repo: repo.to_string(),
+            revision: revision.to_string(),
+        })
+        .with_id(repo.replace('/', "--"))
+    }
+
+    /// Model imported from a local directory
+    /// Expects config.json and tokenizer.json next to a single .safetensors or .gguf file
+    pub fn local(dir: &Path) -> Result<Self> {
+        let dir = dir
+            .canonicalize()
+            .map_err(|e| Error::Model(format!("Cannot open {}: {}", dir.display(), e)))?;
+        if !dir.is_dir() {
+            return Err(Error::Model(format!(
+                "{} is not a directory",
+                dir.display()
+            )));
+        }
+
+        for required in ["config.json", "tokenizer.json"] {
+            if !dir.join(required).is_file() {
+                return Err(Error::Model(format!(
+                    "{} is missing {}",
+                    dir.display(),
+                    required
+                )));
+            }
+        }
+
+        let id = dir
+            .file_name()
+            .map(|name| sanitize_id(&name.to_string_lossy()))
+            .unwrap_or_default();
+
+        let weights = find_weights(&dir)?;
+        Ok(Self::from_source(ModelSource::Local { path: dir })
+            .with_id(id)
+            .with_files("config.json", "tokenizer.json", weights))
+    }
+
+    /// Spec with the standard transformers file names and no id
+    fn from_source(source: ModelSource) -> Self {
+        Self {
+            id: String::new(),
+            source,
+            config_file: "config.json".to_string(),
+            tokenizer_file: "tokenizer.json".to_string(),
+            weights_file: "model.safetensors".to_string(),
+            expected_sha256: HashMap::new(),
+            legacy_files: None,
+        }
+    }
+
+    /// Parse a local directory path or a repo id ("owner/name", optionally "@revision")
+    pub fn parse(source: &str) -> Result<Self> {
+        let source = source.trim();
+        if source.is_empty() {
+            return Err(Error::Model("Model source is empty".to_string()));
+        }
+
+        let path = Path::new(source);
+        if path.is_absolute() || path.is_dir() {
+            return Self::local(path);
+        }
+
+        let (repo, revision) = source.split_once('@').unwrap_or((source, "main"));
+        let valid_repo = repo.split('/').count() == 2
+            && repo.split('/').all(is_valid_id)
+            && !revision.is_empty();
+        if !valid_repo {
+            return Err(Error::Model(format!(
+                "Invalid model source '{}' (expected owner/name[@revision] or a directory)",
+                source
+            )));
+        }
+
+        Ok(Self::hugging_face(repo, revision))
+    }
+
+    /// Install under a different directory name
+    pub fn with_id(mut self, id: impl Into<String>) -> Self {
+        self.id = id.into();
+        self
+    }
+
+    /// Use non-standard file names (e.g. GGUF exports in a shared repo)
+    pub fn with_files(
+        mut self,
+        config: impl Into<String>,
+        tokenizer: impl Into<String>,
+        weights: impl Into<String>,
+    ) -> Self {
+        self.config_file = config.into();
+        self.tokenizer_file = tokenizer.into();
+        self.weights_file = weights.into();
+        self
+    }
+
+    /// Require a file to hash to the given SHA-256
+    pub fn with_sha256(mut self, role: ModelFileRole, sha256: impl Into<String>) -> Self {
+        self.expected_sha256
+            .insert(role, sha256.into().to_ascii_lowercase());
+        self
+    }
+
+    /// Adopt files downloaded before the registry existed
+    pub(crate) fn with_legacy_files(mut self, files: ModelFiles) -> Self {
+        self.legacy_files = Some(files);
+        self
+    }
+
+    /// GGUF weights are loaded as a quantized model
+    pub fn is_quantized(&self) -> bool {
+        is_gguf(Path::new(&self.weights_file))
+    }
+
+    fn file_name(&self, role: ModelFileRole) -> &str {
+        match role {
+            ModelFileRole::Config => &self.config_file,
+            ModelFileRole::Tokenizer => &self.tokenizer_file,
+            ModelFileRole::Weights => &self.weights_file,
+        }
+    }
+
+    fn files_in(&self, dir: &Path) -> ModelFiles {
+        ModelFiles {
+            config: dir.join(&self.config_file),
+            tokenizer: dir.join(&self.tokenizer_file),
+            weights: dir.join(&self.weights_file),
+        }
+    }
+}
+
+/// A file recorded in a model manifest
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestFile {
+    pub role: ModelFileRole,
+    pub name: String,
+    pub size_bytes: u64,
+    pub sha256: String,
+}
+
+/// Contents of an installed model's manifest.json
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModelManifest {
+    pub id: String,
+    pub source: ModelSource,
+    pub quantized: bool,
+    pub files: Vec<ManifestFile>,
+    pub installed_at: DateTime<Utc>,
+}
+
+impl ModelManifest {
+    fn file(&self, role: ModelFileRole) -> Result<&ManifestFile> {
+        self.files
+            .iter()
+            .find(|f| f.role == role)
+            .ok_or_else(|| Error::Model(format!("Manifest for {} has no {:?} file", self.id, role)))
+    }
+
+    /// Spec that reinstalls this model from its original source
+    pub fn spec(&self) -> Result<ModelSpec> {
+        let mut spec = ModelSpec::from_source(self.source.clone())
+            .with_id(&self.id)
+            .with_files(
+                &self.file(ModelFileRole::Config)?.name,
+                &self.file(ModelFileRole::Tokenizer)?.name,
+                &self.file(ModelFileRole::Weights)?.name,
+            );
+        for file in &self.files {
+            spec = spec.with_sha256(file.role, &file.sha256);
+        }
+        Ok(spec)
+    }
+}
+
+/// An installed model with its measured size on disk
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct InstalledModel {
+    #[serde(flatten)]
+    pub manifest: ModelManifest,
+    /// Bytes used by the model's directory
+    pub size_bytes: u64,
+}
+
+/// Stage of a model install
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InstallState {
+    #[default]
+    Idle,
+    Downloading,
+    Verifying,
+    Installed,
+    Failed,
+}
+
+/// Snapshot of a model install
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct InstallProgress {
+    pub model_id: Option<String>,
+    pub state: InstallState,
+    pub downloaded_bytes: u64,
+    pub total_bytes: u64,
+    pub error: Option<String>,
+}
+
+type ProgressCallback = Box<dyn Fn(&InstallProgress) + Send + Sync>;
+
+/// Install progress shared between the installer and whoever watches it
+/// Poll with `snapshot` or observe every update through a callback
+#[derive(Default)]
+pub struct DownloadProgress {
+    inner: Mutex<InstallProgress>,
+    callback: Option<ProgressCallback>,
+}
+
+impl DownloadProgress {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Call `callback` after every update (runs on the installing task)
+    pub fn with_callback(
+        mut self,
+        callback: impl Fn(&InstallProgress) + Send + Sync + 'static,
+    ) -> Self {
+        self.callback = Some(Box::new(callback));
+        self
+    }
+
+    pub fn snapshot(&self) -> InstallProgress {
+        self.inner.lock().clone()
+    }
+
+    /// Whether an install is downloading or verifying
+    pub fn is_active(&self) -> bool {
+        matches!(
+            self.inner.lock().state,
+            InstallState::Downloading | InstallState::Verifying
+        )
+    }
+
+    /// Claim the tracker for a new install; false if one is already running
+    pub fn try_begin(&self, model_id: &str) -> bool {
+        let mut progress = self.inner.lock();
+        if matches!(
+            progress.state,
+            InstallState::Downloading | InstallState::Verifying
+        ) {
+            return false;
+        }
+        *progress = InstallProgress {
+            model_id: Some(model_id.to_string()),
+            state: InstallState::Downloading,
+            ..Default::default()
+        };
+        true
+    }
+
+    fn update(&self, apply: impl FnOnce(&mut InstallProgress)) {
+        let snapshot = {
+            let mut progress = self.inner.lock();
+            apply(&mut progress);
+            progress.clone()
+        };
+        if let Some(callback) = &self.callback {
+            callback(&snapshot);
+        }
+    }
+}
+
+/// Files of an installed model, or install it first
+pub async fn ensure_installed(
+    spec: &ModelSpec,
+    models_dir: &Path,
+    progress: &DownloadProgress,
+) -> Result<ModelFiles> {
+    if let Some(files) = installed_files(models_dir, &spec.id)? {
+        debug!("Model {} already installed", spec.id);
+        return Ok(files);
+    }
+    let manifest = install(spec, models_dir, progress).await?;
+    Ok(spec.files_in(&models_dir.join(&manifest.id)))
+}
+
+/// Whether a model is installed (or can be adopted without downloading)
+pub fn is_installed(spec: &ModelSpec, models_dir: &Path) -> bool {
+    matches!(installed_files(models_dir, &spec.id), Ok(Some(_)))
+        || spec.legacy_files.as_ref().is_some_and(ModelFiles::exist)
+}
+
+/// Install a model, replacing any previous install with the same id
+/// Interrupted downloads resume where they stopped on the next attempt
+pub async fn install(
+    spec: &ModelSpec,
+    models_dir: &Path,
+    progress: &DownloadProgress,
+) -> Result<ModelManifest> {
+    install_with_endpoint(spec, models_dir, &hub_endpoint(), progress).await
+}
+
+/// The Hugging Face hub to download from: HF_ENDPOINT when set, like the official clients
+fn hub_endpoint() -> String {
+    std::env::var("HF_ENDPOINT").unwrap_or_else(|_| DEFAULT_HF_ENDPOINT.into())
+}
+
+/// Install a model, downloading Hugging Face files from the hub at `endpoint`
+pub async fn install_with_endpoint(
+    spec: &ModelSpec,
+    models_dir: &Path,
+    endpoint: &str,
+    progress: &DownloadProgress,
+) -> Result<ModelManifest> {
+    validate_id(&spec.id)?;
+
+    progress.update(|p| {
+        *p = InstallProgress {
+            model_id: Some(spec.id.clone()),
+            state: InstallState::Downloading,
+            ..Default::default()
+        }
+    });
+
+    let result = install_files(spec, models_dir, endpoint, progress).await;
+
+    progress.update(|p| match &result {
+        Ok(_) => p.state = InstallState::Installed,
+        Err(e) => {
+            p.state = InstallState::Failed;
+            p.error = Some(e.to_string());
+        }
+    });
+    result
+}
+
+async fn install_files(
+    spec: &ModelSpec,
+    models_dir: &Path,
+    endpoint: &str,
+    progress: &DownloadProgress,
+) -> Result<ModelManifest> {
+    let staging = staging_dir(models_dir, &spec.id);
+    tokio::fs::create_dir_all(&staging).await?;
+    let staged = spec.files_in(&staging);
+
+    let mut hub_sha256 = HashMap::new();
+    match (&spec.source, &spec.legacy_files) {
+        (_, Some(legacy)) if legacy.exist() => {
+            info!("Adopting previously downloaded files for {}", spec.id);
+            for role in ModelFileRole::ALL {
+                tokio::fs::rename(legacy.path(role), staged.path(role)).await?;
+            }
+        }
+        (ModelSource::HuggingFace { repo, revision }, _) => {
+            info!("Downloading {} ({}) from Hugging Face", repo, revision);
+            hub_sha256 =
+                download_from_hub(spec, endpoint, repo, revision, &staged, progress).await?;
+        }
+        (ModelSource::Local { path }, _) => {
+            info!("Importing {} from {}", spec.id, path.display());
+            copy_from_dir(spec, path, &staged, progress).await?;
+        }
+    }
+
+    progress.update(|p| p.state = InstallState::Verifying);
+
+    let mut files = Vec::new();
+    for role in ModelFileRole::ALL {
+        let path = staged.path(role);
+        let sha256 = sha256_file(path).await?;
+        let expected = spec.expected_sha256.get(&role).or(hub_sha256.get(&role));
+        if let Some(expected) = expected
+            && *expected != sha256
+        {
+            // Drop the corrupt file so the next attempt fetches it again
+            let _ = tokio::fs::remove_file(path).await;
+            return Err(Error::ChecksumMismatch {
+                file: spec.file_name(role).to_string(),
+                expected: expected.clone(),
+                actual: sha256,
+            });
+        }
+        files.push(ManifestFile {
+            role,
+            name: spec.file_name(role).to_string(),
+            size_bytes: tokio::fs::metadata(path).await?.len(),
+            sha256,
+        });
+    }
+
+    let manifest = ModelManifest {
+        id: spec.id.clone(),
+        source: spec.source.clone(),
+        quantized: spec.is_quantized(),
+        files,
+        installed_at: Utc::now(),
+    };
+    tokio::fs::write(
+        staging.join(MANIFEST_FILE),
+        serde_json::to_vec_pretty(&manifest)?,
+    )
+    .await?;
+
+    let target = models_dir.join(&spec.id);
+    if target.exists() {
+        tokio::fs::remove_dir_all(&target).await?;
+    }
+    tokio::fs::rename(&staging, &target).await?;
+
+    info!("Installed model {}", spec.id);
+    Ok(manifest)
+}
+
+/// Read an installed model's manifest, or None when it isn't installed
+pub fn read_manifest(models_dir: &Path, id: &str) -> Result<Option<ModelManifest>> {
+    validate_id(id)?;
+    let path = models_dir.join(id).join(MANIFEST_FILE);
+    match std::fs::read(&path) {
+        Ok(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Paths of an installed model's files, or None when it isn't installed
+/// or a file is missing or truncated
+pub fn installed_files(models_dir: &Path, id: &str) -> Result<Option<ModelFiles>> {
+    let Some(manifest) = read_manifest(models_dir, id)? else {
+        return Ok(None);
+    };
+
+    let dir = models_dir.join(id);
+    for file in &manifest.files {
+        let intact =
+            std::fs::metadata(dir.join(&file.name)).is_ok_and(|meta| meta.len() == file.size_bytes);
+        if !intact {
+            warn!("Model {} is incomplete: {} is missing", id, file.name);
+            return Ok(None);
+        }
+    }
+
+    Ok(Some(ModelFiles {
+        config: dir.join(&manifest.file(ModelFileRole::Config)?.name),
+        tokenizer: dir.join(&manifest.file(ModelFileRole::Tokenizer)?.name),
+        weights: dir.join(&manifest.file(ModelFileRole::Weights)?.name),
+    }))
+}
+
+/// List installed models, sorted by id
+pub fn list_installed(models_dir: &Path) -> Result<Vec<InstalledModel>> {
+    let entries = match std::fs::read_dir(models_dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut models = Vec::new();
+    for entry in entries {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if !entry.file_type()?.is_dir() || !is_valid_id(&name) {
+            continue;
+        }
+        match read_manifest(models_dir, &name) {
+            Ok(Some(manifest)) => models.push(InstalledModel {
+                manifest,
+                size_bytes: dir_size(&entry.path())?,
+            }),
+            Ok(None) => {}
+            Err(e) => warn!("Skipping model {}: {}", name, e),
+        }
+    }
+
+    models.sort_by(|a, b| a.manifest.id.cmp(&b.manifest.id));
+    Ok(models)
+}
+
+/// Delete an installed model and any partial download of it
+/// Returns true if anything was removed
+pub fn delete_model(models_dir: &Path, id: &str) -> Result<bool> {
+    validate_id(id)?;
+
+    let mut removed = false;
+    for dir in [models_dir.join(id), staging_dir(models_dir, id)] {
+        if dir.exists() {
+            std::fs::remove_dir_all(&dir)?;
+            removed = true;
+        }
+    }
+
+    if removed {
+        info!("Deleted model {}", id);
+    }
+    Ok(removed)
+}
+
+/// Re-hash an installed model's files against its manifest
+pub async fn verify_model(models_dir: &Path, id: &str) -> Result<()> {
+    let manifest = read_manifest(models_dir, id)?
+        .ok_or_else(|| Error::Model(format!("Model {} is not installed", id)))?;
+
+    for file in &manifest.files {
+        let actual = sha256_file(&models_dir.join(id).join(&file.name)).await?;
+        if actual != file.sha256 {
+            return Err(Error::ChecksumMismatch {
+                file: file.name.clone(),
+                expected: file.sha256.clone(),
+                actual,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Size and hub hash of a remote file
+struct RemoteFile {
+    url: String,
+    size: u64,
+    sha256: Option<String>,
+}
+
+async fn download_from_hub(
+    spec: &ModelSpec,
+    endpoint: &str,
+    repo: &str,
+    revision: &str,
+    staged: &ModelFiles,
+    progress: &DownloadProgress,
+) -> Result<HashMap<ModelFileRole, String>> {
+    crate::offline::ensure_online("Hugging Face model download")?;
+    let client = hub_client(reqwest::redirect::Policy::default())?;
+    let metadata_client = hub_client(reqwest::redirect::Policy::none())?;
+
+    let mut remote_files = Vec::new();
+    for role in ModelFileRole::ALL {
+        let url = hub_file_url(endpoint, repo, revision, spec.file_name(role))?;
+        remote_files.push((role, hub_metadata(&metadata_client, url.as_str()).await?));
+    }
+
+    // Count completed and partial files so resumed installs report true progress
+    let mut downloaded = 0;
+    for (role, remote) in &remote_files {
+        let dest = staged.path(*role);
+        downloaded += match tokio::fs::metadata(dest).await {
+            Ok(meta) => meta.len(),
+            Err(_) => partial_len(dest).await.min(remote.size),
+        };
+    }
+    let total = remote_files.iter().map(|(_, remote)| remote.size).sum();
+    progress.update(|p| {
+        p.downloaded_bytes = downloaded;
+        p.total_bytes = total;
+    });
+
+    let mut hashes = HashMap::new();
+    for (role, remote) in remote_files {
+        download_file(&client, &remote, staged.path(role), progress).await?;
+        if let Some(sha256) = remote.sha256 {
+            hashes.insert(role, sha256);
+        }
+    }
+    Ok(hashes)
+}
+
+/// `{endpoint}/{repo}/resolve/{revision}/{file}`, with the revision as a single path
+/// segment so branches like `refs/pr/15` have their slashes encoded as `%2F`
+fn hub_file_url(endpoint: &str, repo: &str, revision: &str, file: &str) -> Result<reqwest::Url> {
+    let mut url = reqwest::Url::parse(endpoint)
+        .map_err(|e| Error::Model(format!("Invalid hub endpoint {}: {}", endpoint, e)))?;
+    url.path_segments_mut()
+        .map_err(|_| Error::Model(format!("Invalid hub endpoint {}", endpoint)))?
+        .pop_if_empty()
+        .extend(repo.split('/'))
+        .push("resolve")
+        .push(revision)
+        .push(file);
+    Ok(url)
+}
+
+fn hub_client(redirect: reqwest::redirect::Policy) -> Result<reqwest::Client> {
+    let mut headers = reqwest::header::HeaderMap::new();
+    if let Ok(token) = std::env::var("HF_TOKEN")
+        && let Ok(value) = format!("Bearer {}", token.trim()).parse()
+    {
+        headers.insert(reqwest::header::AUTHORIZATION, value);
+    }
+
+    Ok(reqwest::Client::builder()
+        .redirect(redirect)
+        .default_headers(headers)
+        .build()?)
+}
+
+/// Read size and SHA-256 from the hub's headers without following the CDN redirect
+/// LFS files carry X-Linked-Size and X-Linked-Etag (the file's SHA-256)
+async fn hub_metadata(client: &reqwest::Client, url: &str) -> Result<RemoteFile> {
+    let mut current = reqwest::Url::parse(url)
+        .map_err(|e| Error::Model(format!("Invalid model URL {}: {}", url, e)))?;
+
+    for _ in 0..=MAX_METADATA_REDIRECTS {
+        let response = client
+            .head(current.clone())
+            .header(reqwest::header::ACCEPT_ENCODING, "identity")
+            .send()
+            .await?;
+        let headers = response.headers();
+        let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+
+        let linked_size = header("x-linked-size").and_then(|v| v.parse().ok());
+        let status = response.status();
+
+        if status.is_redirection() && linked_size.is_none() {
+            let location = header(LOCATION.as_str())
+                .ok_or_else(|| Error::Model(format!("Redirect without location for {}", url)))?;
+            current = current
+                .join(location)
+                .map_err(|e| Error::Model(format!("Invalid redirect for {}: {}", url, e)))?;
+            continue;
+        }
+        if !status.is_success() && !status.is_redirection() {
+            return Err(Error::Model(format!(
+                "Failed to fetch {}: HTTP {}",
+                url,
+                status.as_u16()
+            )));
+        }
+
+        let size = linked_size
+            .or_else(|| header(CONTENT_LENGTH.as_str()).and_then(|v| v.parse().ok()))
+            .unwrap_or(0);
+        let sha256 = header("x-linked-etag")
+            .or_else(|| header(ETAG.as_str()))
+            .map(|etag| {
+                etag.trim_start_matches("W/")
+                    .trim_matches('"')
+                    .to_ascii_lowercase()
+            })
+            .filter(|etag| etag.len() == 64 && etag.chars().all(|c| c.is_ascii_hexdigit()));
+
+        return Ok(RemoteFile {
+            url: url.to_string(),
+            size,
+            sha256,
+        });
+    }
+
+    Err(Error::Model(format!("Too many redirects for {}", url)))
+}
+
+/// Download into `<dest>.part`, resuming with a Range request, then move into place
+async fn download_file(
+    client: &reqwest::Client,
+    remote: &RemoteFile,
+    dest: &Path,
+    progress: &DownloadProgress,
+) -> Result<()> {
+    if dest.is_file() {
+        return Ok(());
+    }
+
+    let partial = dest.with_extension(partial_extension(dest));
+    let mut offset = partial_len(dest).await;
+    if remote.size > 0 && offset > remote.size {
+        tokio::fs::remove_file(&partial).await?;
+        progress.update(|p| p.downloaded_bytes = p.downloaded_bytes.saturating_sub(offset));
+        offset = 0;
+    }
+
+    if remote.size == 0 || offset < remote.size {
+        let mut request = client.get(&remote.url);
+        if offset > 0 {
+            debug!("Resuming {} at byte {}", remote.url, offset);
+            request = request.header(RANGE, format!("bytes={}-", offset));
+        }
+        let mut response = request.send().await?.error_for_status()?;
+
+        if offset > 0 && response.status() != StatusCode::PARTIAL_CONTENT {
+            // Server ignored the range; start over
+            progress.update(|p| p.downloaded_bytes = p.downloaded_bytes.saturating_sub(offset));
+            offset = 0;
+        }
+
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(offset > 0)
+            .truncate(offset == 0)
+            .open(&partial)
+            .await?;
+        while let Some(chunk) = response.chunk().await? {
+            file.write_all(&chunk).await?;
+            offset += chunk.len() as u64;
+            progress.update(|p| p.downloaded_bytes += chunk.len() as u64);
+        }
+        file.flush().await?;
+    }
+
+    if remote.size > 0 && offset != remote.size {
+        return Err(Error::Model(format!(
+            "Incomplete download of {}: {} of {} bytes",
+            remote.url, offset, remote.size
+        )));
+    }
+
+    tokio::fs::rename(&partial, dest).await?;
+    Ok(())
+}
+
+async fn copy_from_dir(
+    spec: &ModelSpec,
+    source_dir: &Path,
+    staged: &ModelFiles,
+    progress: &DownloadProgress,
+) -> Result<()> {
+    let mut total = 0;
+    for role in ModelFileRole::ALL {
+        total += tokio::fs::metadata(source_dir.join(spec.file_name(role)))
+            .await?
+            .len();
+    }
+    progress.update(|p| p.total_bytes = total);
+
+    let mut buffer = vec![0u8; CHUNK_SIZE];
+    for role in ModelFileRole::ALL {
+        let mut reader = tokio::fs::File::open(source_dir.join(spec.file_name(role))).await?;
+        let mut writer = tokio::fs::File::create(staged.path(role)).await?;
+        loop {
+            let read = reader.read(&mut buffer).await?;
+            if read == 0 {
+                break;
+            }
+            writer.write_all(&buffer[..read]).await?;
+            progress.update(|p| p.downloaded_bytes += read as u64);
+        }
+        writer.flush().await?;
+    }
+    Ok(())
+}
+
+/// Lowercase hex SHA-256 of a file
+async fn sha256_file(path: &Path) -> Result<String> {
+    let mut file = tokio::fs::File::open(path).await?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; CHUNK_SIZE];
+    loop {
+        let read = file.read(&mut buffer).await?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect())
+}
+
+/// Pick the weights in a local model directory
+fn find_weights(dir: &Path) -> Result<String> {
+    if dir.join("model.safetensors").is_file() {
+        return Ok("model.safetensors".to_string());
+    }
+
+    let mut candidates: Vec<String> = std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .filter(|name| name.ends_with(".safetensors") || name.ends_with(".gguf"))
+        .collect();
+
+    match candidates.len() {
+        0 => Err(Error::Model(format!(
+            "{} has no .safetensors or .gguf weights",
+            dir.display()
+        ))),
+        1 => Ok(candidates.remove(0)),
+        _ => Err(Error::Model(format!(
+            "{} has several weight files ({}); sharded checkpoints are not supported",
+            dir.display(),
+            candidates.join(", ")
+        ))),
+    }
+}
+
+fn is_gguf(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "gguf")
+}
+
+fn staging_dir(models_dir: &Path, id: &str) -> PathBuf {
+    models_dir.join(format!(".{}.partial", id))
+}
+
+fn partial_extension(path: &Path) -> String {
+    match path.extension() {
+        Some(ext) => format!("{}.{}", ext.to_string_lossy(), PARTIAL_EXTENSION),
+        None => PARTIAL_EXTENSION.to_string(),
+    }
+}
+
+async fn partial_len(dest: &Path) -> u64 {
+    let partial = dest.with_extension(partial_extension(dest));
+    tokio::fs::metadata(partial)
+        .await
+        .map(|meta| meta.len())
+        .unwrap_or(0)
+}
+
+fn dir_size(path: &Path) -> Result<u64> {
+    let mut size = 0;
+    for entry in std::fs::read_dir(path)? {
+        let entry = entry?;
+        let meta = entry.metadata()?;
+        size += if meta.is_dir() {
+            dir_size(&entry.path())?
+        } else {
+            meta.len()
+        };
+    }
+    Ok(size)
+}
+
+/// Ids are directory names: ASCII letters, digits, '-', '_' and '.', not starting with '.'
+fn is_valid_id(id: &str) -> bool {
+    !id.is_empty()
+        && !id.starts_with('.')
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+fn validate_id(id: &str) -> Result<()> {
+    if is_valid_id(id) {
+        Ok(())
+    } else {
+        Err(Error::Model(format!("Invalid model id '{}'", id)))
+    }
+}
+
+fn sanitize_id(name: &str) -> String {
+    let id: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    id.trim_start_matches('.').to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::io::{AsyncBufReadExt, BufReader};
+    use tokio::net::TcpListener;
+
+    fn write_model_dir(dir: &Path, weights: &str) {
+        std::fs::create_dir_all(dir).unwrap();
+        std::fs::write(dir.join("config.json"), b"{\"vocab_size\": 51865}").unwrap();
+        std::fs::write(dir.join("tokenizer.json"), b"{}").unwrap();
+        std::fs::write(dir.join(weights), vec![7u8; 3000]).unwrap();
+    }
+
+    #[test]
+    fn test_parse_source() {
+        let spec = ModelSpec::parse("openai/whisper-large-v3-turbo").unwrap();
+        assert_eq!(spec.id, "openai--whisper-large-v3-turbo");
+        assert_eq!(
+            spec.source,
+            ModelSource::HuggingFace {
+                repo: "openai/whisper-large-v3-turbo".to_string(),
+                revision: "main".to_string()
+            }
+        );
+        assert!(!spec.is_quantized());
+
+        let spec = ModelSpec::parse("someone/whisper-de@v2").unwrap();
+        assert!(
+            matches!(spec.source, ModelSource::HuggingFace { ref revision, .. } if revision == "v2")
+        );
+
+        assert!(ModelSpec::parse("").is_err());
+        assert!(ModelSpec::parse("not-a-repo").is_err());
+        assert!(ModelSpec::parse("../escape/repo").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_import_list_verify_delete() {
+        let root = tempfile::tempdir().unwrap();
+        let source = root.path().join("whisper-de-q8");
+        write_model_dir(&source, "model-q8.gguf");
+        let models_dir = root.path().join("models");
+
+        let spec = ModelSpec::parse(source.to_str().unwrap()).unwrap();
+        assert_eq!(spec.id, "whisper-de-q8");
+        assert!(spec.is_quantized());
+
+        let updates = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&updates);
+        let progress = DownloadProgress::new().with_callback(move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+        });
+
+        let files = ensure_installed(&spec, &models_dir, &progress)
+            .await
+            .unwrap();
+        assert!(files.is_quantized());
+        assert!(files.weights.ends_with("whisper-de-q8/model-q8.gguf"));
+        assert!(updates.load(Ordering::SeqCst) > 0);
+
+        let snapshot = progress.snapshot();
+        assert_eq!(snapshot.state, InstallState::Installed);
+        assert_eq!(snapshot.downloaded_bytes, snapshot.total_bytes);
+
+        let installed = list_installed(&models_dir).unwrap();
+        assert_eq!(installed.len(), 1);
+        assert_eq!(installed[0].manifest.id, "whisper-de-q8");
+        assert!(installed[0].size_bytes > 3000);
+        assert_eq!(
+            installed[0].manifest.spec().unwrap().weights_file,
+            "model-q8.gguf"
+        );
+
+        verify_model(&models_dir, "whisper-de-q8").await.unwrap();
+        std::fs::write(&files.tokenizer, b"[]").unwrap();
+        assert!(matches!(
+            verify_model(&models_dir, "whisper-de-q8").await,
+            Err(Error::ChecksumMismatch { .. })
+        ));
+
+        assert!(delete_model(&models_dir, "whisper-de-q8").unwrap());
+        assert!(!delete_model(&models_dir, "whisper-de-q8").unwrap());
+        assert!(list_installed(&models_dir).unwrap().is_empty());
+        assert!(delete_model(&models_dir, "../models").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_expected_checksum_mismatch() {
+        let root = tempfile::tempdir().unwrap();
+        let source = root.path().join("custom");
+        write_model_dir(&source, "model.safetensors");
+        let models_dir = root.path().join("models");
+
+        let spec = ModelSpec::local(&source)
+            .unwrap()
+            .with_sha256(ModelFileRole::Weights, "00".repeat(32));
+        let progress = DownloadProgress::new();
+        let result = install(&spec, &models_dir, &progress).await;
+
+        assert!(
+            matches!(result, Err(Error::ChecksumMismatch { ref file, .. }) if file == "model.safetensors")
+        );
+        assert_eq!(progress.snapshot().state, InstallState::Failed);
+        assert!(read_manifest(&models_dir, "custom").unwrap().is_none());
+    }
+
+    /// Minimal hub serving `files` under `prefix` (`/{repo}/resolve/{revision}/`): answers
+    /// HEAD with LFS headers and GET with optional Range support
+    async fn serve_hub(
+        prefix: &'static str,
+        files: HashMap<String, Vec<u8>>,
+    ) -> (String, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let log = Arc::clone(&requests);
+
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let files = files.clone();
+                let log = Arc::clone(&log);
+                tokio::spawn(async move {
+                    let (reader, mut writer) = stream.into_split();
+                    let mut lines = BufReader::new(reader).lines();
+                    while let Ok(Some(request_line)) = lines.next_line().await {
+                        let mut range_start = None;
+                        while let Ok(Some(line)) = lines.next_line().await {
+                            if line.is_empty() {
+                                break;
+                            }
+                            if let Some(range) =
+                                line.to_ascii_lowercase().strip_prefix("range: bytes=")
+                            {
+                                range_start = range.trim_end_matches('-').parse::<usize>().ok();
+                            }
+                        }
+                        log.lock()
+                            .push(format!("{} {:?}", request_line, range_start));
+
+                        let mut parts = request_line.split(' ');
+                        let method = parts.next().unwrap_or_default().to_string();
+                        let path = parts.next().unwrap_or_default();
+                        let body = path.strip_prefix(prefix).and_then(|name| files.get(name));
+                        let Some(body) = body else {
+                            let _ = writer
+                                .write_all(b"HTTP/1.1 404 Not Found\r\ncontent-length: 0\r\n\r\n")
+                                .await;
+                            continue;
+                        };
+
+                        let sha256: String = Sha256::digest(body)
+                            .iter()
+                            .map(|b| format!("{:02x}", b))
+                            .collect();
+                        let start = range_start.unwrap_or(0);
+                        let status = if range_start.is_some() {
+                            "206 Partial Content"
+                        } else {
+                            "200 OK"
+                        };
+                        let head = format!(
+                            "HTTP/1.1 {}\r\ncontent-length: {}\r\nx-linked-size: {}\r\nx-linked-etag: \"{}\"\r\n\r\n",
+                            status,
+                            body.len() - start,
+                            body.len(),
+                            sha256
+                        );
+                        let _ = writer.write_all(head.as_bytes()).await;
+                        if method == "GET" {
+                            let _ = writer.write_all(&body[start..]).await;
+                        }
+                    }
+                });
+            }
+        });
+
+        (endpoint, requests)
+    }
+
+    #[tokio::test]
+    async fn test_hub_download_resumes_and_verifies() {
+        let weights: Vec<u8> = (0..50_000u32).map(|i| (i % 251) as u8).collect();
+        let files = HashMap::from([
+            ("config.json".to_string(), b"{}".to_vec()),
+            ("tokenizer.json".to_string(), b"{\"model\": {}}".to_vec()),
+            ("model.safetensors".to_string(), weights.clone()),
+        ]);
+        let (endpoint, requests) = serve_hub("/test/whisper-mini/resolve/main/", files).await;
+
+        let root = tempfile::tempdir().unwrap();
+        let models_dir = root.path();
+        let spec = ModelSpec::hugging_face("test/whisper-mini", "main");
+
+        // Simulate an interrupted earlier attempt
+        let staging = staging_dir(models_dir, &spec.id);
+        std::fs::create_dir_all(&staging).unwrap();
+        std::fs::write(staging.join("model.safetensors.part"), &weights[..20_000]).unwrap();
+
+        let progress = DownloadProgress::new();
+        let manifest = install_with_endpoint(&spec, models_dir, &endpoint, &progress)
+            .await
+            .unwrap();
+
+        let snapshot = progress.snapshot();
+        assert_eq!(snapshot.total_bytes, 50_000 + 2 + 13);
+        assert_eq!(snapshot.downloaded_bytes, snapshot.total_bytes);
+        assert!(
+            requests
+                .lock()
+                .iter()
+                .any(|r| r.starts_with("GET") && r.ends_with("Some(20000)"))
+        );
+
+        let installed = std::fs::read(models_dir.join(&spec.id).join("model.safetensors")).unwrap();
+        assert_eq!(installed, weights);
+        assert_eq!(manifest.files.len(), 3);
+        assert!(!staging.exists());
+
+        // Expected hash that disagrees with the hub's
+        delete_model(models_dir, &spec.id).unwrap();
+        let spec = spec.with_sha256(ModelFileRole::Config, "ab".repeat(32));
+        assert!(matches!(
+            install_with_endpoint(&spec, models_dir, &endpoint, &progress).await,
+            Err(Error::ChecksumMismatch { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_hub_revision_is_one_path_segment() {
+        let files = HashMap::from([
+            ("config.json".to_string(), b"{}".to_vec()),
+            ("tokenizer.json".to_string(), b"{\"model\": {}}".to_vec()),
+            ("model.safetensors".to_string(), vec![1u8; 100]),
+        ]);
+        let (endpoint, requests) =
+            serve_hub("/openai/whisper-tiny/resolve/refs%2Fpr%2F15/", files).await;
+
+        let root = tempfile::tempdir().unwrap();
+        let spec = ModelSpec::hugging_face("openai/whisper-tiny", "refs/pr/15");
+        install_with_endpoint(&spec, root.path(), &endpoint, &DownloadProgress::new())
+            .await
+            .unwrap();
+
+        let requests = requests.lock();
+        assert_eq!(
+            requests[0],
+            "HEAD /openai/whisper-tiny/resolve/refs%2Fpr%2F15/config.json HTTP/1.1 None"
+        );
+        assert!(
+            requests
+                .iter()
+                .any(|r| r.starts_with("GET /openai/whisper-tiny/resolve/refs%2Fpr%2F15/"))
+        );
+    }
+}
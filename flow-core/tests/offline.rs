@@ -0,0 +1,152 @@
+//! Offline mode is process-wide, so these checks run in their own test binary
+//! where no other test needs the network.
+//!
+//! Every remote endpoint is pointed at a local listener that is never served;
+//! with offline mode on it must not see a single connection.
+
+use std::io::ErrorKind;
+use std::net::TcpListener;
+use std::time::Duration;
+
+use flow::Error;
+use flow::modes::WritingMode;
+use flow::offline;
+use flow::providers::{
+    AnthropicCompletionProvider, Base10TranscriptionProvider, CompletionProvider,
+    CompletionRequest, CorrectionPair, GeminiCompletionProvider, GeminiTranscriptionProvider,
+    LocalFormattingProvider, OpenAICompletionProvider, OpenAITranscriptionProvider,
+    OpenRouterCompletionProvider, TranscriptionProvider, TranscriptionRequest,
+};
+use flow::whisper_models::{self, DownloadProgress, InstallState, ModelSpec};
+
+/// Listener standing in for every remote endpoint
+fn trap() -> (TcpListener, String) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.set_nonblocking(true).unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    (listener, url)
+}
+
+fn connection_attempted(listener: &TcpListener) -> bool {
+    match listener.accept() {
+        Ok(_) => true,
+        Err(e) if e.kind() == ErrorKind::WouldBlock => false,
+        Err(e) => panic!("accept failed: {e}"),
+    }
+}
+
+fn assert_offline<T: std::fmt::Debug>(result: flow::Result<T>) {
+    assert!(
+        matches!(result, Err(Error::Offline(_))),
+        "expected offline error, got {result:?}"
+    );
+}
+
+fn completion_request() -> CompletionRequest {
+    CompletionRequest::new("um hello there".to_string(), WritingMode::Casual)
+}
+
+#[tokio::test]
+async fn test_offline_mode_opens_no_sockets() {
+    let (listener, url) = trap();
+    let key = Some("test-key".to_string());
+    let audio = vec![0u8; 3200];
+
+    offline::set_offline(true);
+
+    let openai = OpenAICompletionProvider::new(key.clone()).with_base_url(&url);
+    assert_offline(openai.complete(completion_request()).await);
+    assert_offline(openai.list_models().await);
+    assert_offline(
+        OpenAITranscriptionProvider::new(key.clone())
+            .with_base_url(&url)
+            .transcribe(TranscriptionRequest::new(audio.clone(), 16000))
+            .await,
+    );
+    assert_offline(
+        AnthropicCompletionProvider::new(key.clone())
+            .with_base_url(&url)
+            .complete(completion_request())
+            .await,
+    );
+    assert_offline(
+        OpenRouterCompletionProvider::new(key.clone())
+            .with_base_url(&url)
+            .complete(completion_request())
+            .await,
+    );
+    let gemini = GeminiCompletionProvider::new(key.clone()).with_base_url(&url);
+    assert_offline(gemini.complete(completion_request()).await);
+    assert_offline(
+        gemini
+            .as_streaming()
+            .unwrap()
+            .complete_stream(completion_request())
+            .await
+            .map(|_| ()),
+    );
+    assert_offline(
+        GeminiTranscriptionProvider::new(key.clone())
+            .with_base_url(&url)
+            .transcribe(TranscriptionRequest::new(audio.clone(), 16000))
+            .await,
+    );
+    let worker = Base10TranscriptionProvider::new(None).with_base_url(&url);
+    assert_offline(
+        worker
+            .transcribe(TranscriptionRequest::new(audio, 16000))
+            .await,
+    );
+    assert_offline(
+        worker
+            .validate_corrections(vec![CorrectionPair {
+                original: "teh".to_string(),
+                corrected: "the".to_string(),
+            }])
+            .await,
+    );
+
+    // Model downloads fail; pre-seeded models still install
+    let root = tempfile::tempdir().unwrap();
+    let models_dir = root.path().join("models");
+    let progress = DownloadProgress::new();
+    assert_offline(
+        whisper_models::install_with_endpoint(
+            &ModelSpec::hugging_face("openai/whisper-tiny", "main"),
+            &models_dir,
+            &url,
+            &progress,
+        )
+        .await,
+    );
+    assert_eq!(progress.snapshot().state, InstallState::Failed);
+
+    let seeded = root.path().join("seeded");
+    std::fs::create_dir_all(&seeded).unwrap();
+    std::fs::write(seeded.join("config.json"), b"{}").unwrap();
+    std::fs::write(seeded.join("tokenizer.json"), b"{}").unwrap();
+    std::fs::write(seeded.join("model.safetensors"), b"weights").unwrap();
+    let spec = ModelSpec::local(&seeded).unwrap();
+    assert!(
+        whisper_models::install(&spec, &models_dir, &progress)
+            .await
+            .is_ok()
+    );
+
+    // Formatting still works on-device
+    let formatted = LocalFormattingProvider::new()
+        .complete(completion_request())
+        .await
+        .unwrap();
+    assert_eq!(formatted.text, "Hello there.");
+
+    assert!(
+        !connection_attempted(&listener),
+        "offline mode opened a socket"
+    );
+
+    // Sanity check: the trap does catch requests once offline mode is off
+    offline::set_offline(false);
+    let _ = tokio::time::timeout(Duration::from_millis(500), openai.list_models()).await;
+    assert!(connection_attempted(&listener));
+}
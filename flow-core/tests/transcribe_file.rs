@@ -0,0 +1,458 @@
+//! Runs the full dictation pipeline from audio files, with no microphone and a
+//! local stand-in for the OpenAI transcription endpoint.
+
+use std::ffi::{CStr, CString};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::os::raw::{c_char, c_void};
+use std::path::Path;
+use std::ptr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use flow::ffi::{
+    FLOW_NO_SPEECH, FlowHandle, flow_add_shortcut, flow_delete_archived_audio, flow_destroy,
+    flow_export_archived_audio, flow_free_string, flow_get_archived_audio_path,
+    flow_get_audio_archive_json, flow_get_auto_stop_json, flow_get_last_error,
+    flow_get_partial_transcription_json, flow_get_recent_transcriptions_json, flow_init,
+    flow_is_recording, flow_process_transcription_queue, flow_queued_transcription_count,
+    flow_retranscribe, flow_set_audio_archive, flow_set_cloud_transcription_provider,
+    flow_set_completion_provider, flow_set_hands_free, flow_set_input_file,
+    flow_set_live_transcription, flow_set_openai_base_url, flow_set_transcription_queue_callback,
+    flow_start_recording, flow_stop_recording, flow_transcribe, flow_transcribe_file,
+};
+
+/// Serve `{"text": ...}` to every request, counting them
+fn transcription_server(text: &'static str) -> (String, Arc<AtomicUsize>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let requests = Arc::new(AtomicUsize::new(0));
+    let count = Arc::clone(&requests);
+
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else { continue };
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut content_length = 0;
+            loop {
+                let mut line = String::new();
+                if reader.read_line(&mut line).unwrap_or(0) == 0 || line == "\r\n" {
+                    break;
+                }
+                if let Some((name, value)) = line.split_once(':')
+                    && name.eq_ignore_ascii_case("content-length")
+                {
+                    content_length = value.trim().parse().unwrap_or(0);
+                }
+            }
+            let mut body = vec![0; content_length];
+            let _ = reader.read_exact(&mut body);
+            count.fetch_add(1, Ordering::SeqCst);
+
+            let body = serde_json::json!({ "text": text }).to_string();
+            let _ = write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+        }
+    });
+
+    (url, requests)
+}
+
+/// A local URL nothing listens on
+fn unreachable_url() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    format!("http://{}", listener.local_addr().unwrap())
+}
+
+/// 16kHz mono WAV: silence, then a tone standing in for speech, then silence
+fn write_wav(path: &Path, tone_ms: u32) {
+    write_script(path, &[(500, false), (tone_ms, true), (500, false)]);
+}
+
+/// 16kHz mono WAV of tones standing in for speech: (milliseconds, speaking)
+fn write_script(path: &Path, parts: &[(u32, bool)]) {
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate: 16000,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut writer = hound::WavWriter::create(path, spec).unwrap();
+    let mut i = 0;
+    for &(ms, speaking) in parts {
+        for _ in 0..16 * ms as usize {
+            let sample = if speaking {
+                (i as f32 * 220.0 * std::f32::consts::TAU / 16000.0).sin() * 0.3
+            } else {
+                0.0
+            };
+            writer.write_sample((sample * 32767.0) as i16).unwrap();
+            i += 1;
+        }
+    }
+    writer.finalize().unwrap();
+}
+
+/// An engine with its database in a new temporary directory, transcribing with
+/// OpenAI at `url`
+fn init_flow(url: &str) -> (*mut FlowHandle, tempfile::TempDir) {
+    let dir = tempfile::tempdir().unwrap();
+    let db = c(dir.path().join("flow.db").to_string_lossy());
+
+    let handle = flow_init(db.as_ptr());
+    assert!(!handle.is_null());
+    assert!(flow_set_openai_base_url(handle, c(url).as_ptr()));
+    assert!(flow_set_completion_provider(
+        handle,
+        0,
+        c("test-key").as_ptr()
+    ));
+    assert!(flow_set_cloud_transcription_provider(handle, 0));
+    (handle, dir)
+}
+
+fn take_string(ptr: *mut c_char) -> Option<String> {
+    if ptr.is_null() {
+        return None;
+    }
+    let text = unsafe { CStr::from_ptr(ptr) }
+        .to_string_lossy()
+        .into_owned();
+    flow_free_string(ptr);
+    Some(text)
+}
+
+fn c(text: impl AsRef<str>) -> CString {
+    CString::new(text.as_ref()).unwrap()
+}
+
+#[test]
+fn test_transcribe_files_through_pipeline() {
+    let (url, requests) = transcription_server("brb, see you at noon");
+    let (handle, dir) = init_flow(&url);
+    assert!(flow_add_shortcut(
+        handle,
+        c("brb").as_ptr(),
+        c("be right back").as_ptr()
+    ));
+
+    // A dictated file goes through shortcuts like a recording
+    let speech = dir.path().join("speech.wav");
+    write_wav(&speech, 1000);
+    let path = c(speech.to_string_lossy());
+    let text = take_string(flow_transcribe_file(handle, path.as_ptr(), ptr::null()));
+    assert_eq!(text.as_deref(), Some("be right back, see you at noon"));
+    assert_eq!(requests.load(Ordering::SeqCst), 1);
+
+    // Silence never reaches the provider
+    let silence = dir.path().join("silence.wav");
+    write_wav(&silence, 0);
+    let silent_path = c(silence.to_string_lossy());
+    let text = take_string(flow_transcribe_file(
+        handle,
+        silent_path.as_ptr(),
+        ptr::null(),
+    ));
+    assert_eq!(text.as_deref(), Some(""));
+    assert_eq!(requests.load(Ordering::SeqCst), 1);
+
+    let missing = c(dir.path().join("missing.flac").to_string_lossy());
+    assert!(take_string(flow_transcribe_file(handle, missing.as_ptr(), ptr::null())).is_none());
+    assert!(take_string(flow_get_last_error(handle)).is_some());
+
+    // The recording path, fed from the file instead of a microphone
+    assert!(flow_set_input_file(handle, path.as_ptr()));
+    assert!(flow_start_recording(handle));
+    assert_eq!(flow_stop_recording(handle), 2000);
+    let text = take_string(flow_transcribe(handle, ptr::null()));
+    assert_eq!(text.as_deref(), Some("be right back, see you at noon"));
+    assert_eq!(requests.load(Ordering::SeqCst), 2);
+
+    assert!(flow_set_input_file(handle, silent_path.as_ptr()));
+    assert!(flow_start_recording(handle));
+    assert_eq!(flow_stop_recording(handle), FLOW_NO_SPEECH);
+    assert_eq!(requests.load(Ordering::SeqCst), 2);
+
+    assert!(!flow_set_input_file(handle, missing.as_ptr()));
+    flow_destroy(handle);
+}
+
+#[test]
+fn test_hands_free_recording_stops_itself() {
+    let (url, requests) = transcription_server("see you at noon");
+    let (handle, dir) = init_flow(&url);
+
+    // 500ms silence, 1s speech, 500ms silence: 300ms of quiet ends it
+    let speech = dir.path().join("speech.wav");
+    write_wav(&speech, 1000);
+    assert!(flow_set_input_file(
+        handle,
+        c(speech.to_string_lossy()).as_ptr()
+    ));
+    assert!(!flow_set_hands_free(handle, true, 300, 5000, 1000));
+    assert!(flow_set_hands_free(handle, true, 300, 200, 60_000));
+
+    assert!(flow_start_recording(handle));
+    let stop = take_string(flow_get_auto_stop_json(handle)).expect("recording should stop itself");
+    let stop: serde_json::Value = serde_json::from_str(&stop).unwrap();
+    assert_eq!(stop["reason"], "silence");
+    assert_eq!(stop["duration_ms"], 1800);
+    assert!(!flow_is_recording(handle));
+
+    // The host collects the recording and transcribes it as usual
+    assert_eq!(flow_stop_recording(handle), 1800);
+    assert!(take_string(flow_get_auto_stop_json(handle)).is_none());
+    let text = take_string(flow_transcribe(handle, ptr::null()));
+    assert_eq!(text.as_deref(), Some("see you at noon"));
+    assert_eq!(requests.load(Ordering::SeqCst), 1);
+
+    // Speech running past the limit is cut off there
+    assert!(flow_set_hands_free(handle, true, 3000, 100, 900));
+    assert!(flow_start_recording(handle));
+    let stop = take_string(flow_get_auto_stop_json(handle)).expect("recording should stop itself");
+    let stop: serde_json::Value = serde_json::from_str(&stop).unwrap();
+    assert_eq!(stop["reason"], "max_duration");
+    assert_eq!(flow_stop_recording(handle), 900);
+
+    // Off again, the whole file is recorded and nothing stops it
+    assert!(flow_set_hands_free(handle, false, 0, 0, 0));
+    assert!(flow_start_recording(handle));
+    assert!(take_string(flow_get_auto_stop_json(handle)).is_none());
+    assert_eq!(flow_stop_recording(handle), 2000);
+
+    flow_destroy(handle);
+}
+
+#[test]
+fn test_archived_recording_is_transcribed_again() {
+    let (url, requests) = transcription_server("see you at noon");
+
+    // A dictation fails while the server is down; its recording is kept
+    let (handle, dir) = init_flow(&unreachable_url());
+    assert!(flow_set_audio_archive(handle, true, 100, 30));
+    let speech = dir.path().join("speech.wav");
+    write_wav(&speech, 1000);
+    assert!(flow_set_input_file(
+        handle,
+        c(speech.to_string_lossy()).as_ptr()
+    ));
+    assert!(flow_start_recording(handle));
+    assert_eq!(flow_stop_recording(handle), 2000);
+    assert!(take_string(flow_transcribe(handle, ptr::null())).is_none());
+    assert_eq!(requests.load(Ordering::SeqCst), 0);
+
+    let history = take_string(flow_get_recent_transcriptions_json(handle, 10)).unwrap();
+    let history: serde_json::Value = serde_json::from_str(&history).unwrap();
+    assert_eq!(history[0]["status"], "failed");
+    assert_eq!(history[0]["has_audio"], true);
+    let id = c(history[0]["id"].as_str().unwrap());
+
+    let archive = take_string(flow_get_audio_archive_json(handle)).unwrap();
+    let archive: serde_json::Value = serde_json::from_str(&archive).unwrap();
+    assert_eq!(archive["enabled"], true);
+    assert_eq!(archive["max_mb"], 100);
+    assert_eq!(archive["recordings"], 1);
+    let stored = take_string(flow_get_archived_audio_path(handle, id.as_ptr())).unwrap();
+    assert!(Path::new(&stored).starts_with(dir.path()));
+
+    // Exported as it was recorded
+    let export = dir.path().join("export.wav");
+    assert!(flow_export_archived_audio(
+        handle,
+        id.as_ptr(),
+        c(export.to_string_lossy()).as_ptr()
+    ));
+    let reader = hound::WavReader::open(&export).unwrap();
+    assert_eq!(reader.spec().sample_rate, 16000);
+    assert_eq!(reader.duration(), 32000);
+
+    // Once the server is back, the same entry is recovered with a chosen model
+    assert!(flow_set_openai_base_url(handle, c(&url).as_ptr()));
+    let text = take_string(flow_retranscribe(
+        handle,
+        id.as_ptr(),
+        c("openai").as_ptr(),
+        c("whisper-large").as_ptr(),
+    ));
+    assert_eq!(text.as_deref(), Some("see you at noon"));
+    assert_eq!(requests.load(Ordering::SeqCst), 1);
+
+    let history = take_string(flow_get_recent_transcriptions_json(handle, 10)).unwrap();
+    let history: serde_json::Value = serde_json::from_str(&history).unwrap();
+    assert_eq!(history.as_array().unwrap().len(), 1);
+    assert_eq!(history[0]["id"], id.to_str().unwrap());
+    assert_eq!(history[0]["status"], "success");
+    assert_eq!(history[0]["text"], "see you at noon");
+
+    assert!(
+        take_string(flow_retranscribe(
+            handle,
+            id.as_ptr(),
+            c("nope").as_ptr(),
+            ptr::null()
+        ))
+        .is_none()
+    );
+    assert!(take_string(flow_get_last_error(handle)).is_some());
+
+    // Without its recording an entry can't be transcribed again
+    assert!(flow_delete_archived_audio(handle, id.as_ptr()));
+    assert!(!flow_delete_archived_audio(handle, id.as_ptr()));
+    assert!(
+        take_string(flow_retranscribe(
+            handle,
+            id.as_ptr(),
+            ptr::null(),
+            ptr::null()
+        ))
+        .is_none()
+    );
+    assert_eq!(requests.load(Ordering::SeqCst), 1);
+
+    // With the archive off, nothing more is kept
+    assert!(flow_set_audio_archive(handle, false, 0, 0));
+    assert!(flow_start_recording(handle));
+    flow_stop_recording(handle);
+    assert!(take_string(flow_transcribe(handle, ptr::null())).is_some());
+    let archive = take_string(flow_get_audio_archive_json(handle)).unwrap();
+    let archive: serde_json::Value = serde_json::from_str(&archive).unwrap();
+    assert_eq!(archive["recordings"], 0);
+    assert_eq!(archive["max_age_days"], 0);
+
+    flow_destroy(handle);
+}
+
+extern "C" fn collect_queue_results(success: bool, result: *const c_char, context: *mut c_void) {
+    let results = unsafe { &*(context as *const Mutex<Vec<(bool, String)>>) };
+    let result = unsafe { CStr::from_ptr(result) }
+        .to_string_lossy()
+        .into_owned();
+    results.lock().unwrap().push((success, result));
+}
+
+#[test]
+fn test_queued_transcription_resolves_when_network_returns() {
+    let (url, requests) = transcription_server("see you at noon");
+
+    // The provider can't be reached, so the recording is queued
+    let (handle, dir) = init_flow(&unreachable_url());
+    let results: Box<Mutex<Vec<(bool, String)>>> = Box::default();
+    flow_set_transcription_queue_callback(
+        handle,
+        Some(collect_queue_results),
+        &*results as *const _ as *mut c_void,
+    );
+    let speech = dir.path().join("speech.wav");
+    write_wav(&speech, 1000);
+    assert!(flow_set_input_file(
+        handle,
+        c(speech.to_string_lossy()).as_ptr()
+    ));
+    assert!(flow_start_recording(handle));
+    assert_eq!(flow_stop_recording(handle), 2000);
+    assert!(take_string(flow_transcribe(handle, ptr::null())).is_none());
+    assert_eq!(requests.load(Ordering::SeqCst), 0);
+    assert_eq!(flow_queued_transcription_count(handle), 1);
+
+    let history = take_string(flow_get_recent_transcriptions_json(handle, 10)).unwrap();
+    let history: serde_json::Value = serde_json::from_str(&history).unwrap();
+    assert_eq!(history[0]["status"], "failed");
+    let id = history[0]["id"].as_str().unwrap().to_string();
+
+    // Back online, the queue goes through without waiting out the backoff
+    assert!(flow_set_openai_base_url(handle, c(&url).as_ptr()));
+    assert_eq!(flow_process_transcription_queue(handle), 1);
+    let start = Instant::now();
+    while results.lock().unwrap().is_empty() {
+        assert!(
+            start.elapsed() < Duration::from_secs(10),
+            "queue never resolved"
+        );
+        std::thread::sleep(Duration::from_millis(20));
+    }
+
+    let (success, result) = results.lock().unwrap()[0].clone();
+    assert!(success);
+    let result: serde_json::Value = serde_json::from_str(&result).unwrap();
+    assert_eq!(result["id"], id.as_str());
+    assert_eq!(result["status"], "success");
+    assert_eq!(result["text"], "see you at noon");
+    assert_eq!(requests.load(Ordering::SeqCst), 1);
+    assert_eq!(flow_queued_transcription_count(handle), 0);
+
+    // The failed entry became the success
+    let history = take_string(flow_get_recent_transcriptions_json(handle, 10)).unwrap();
+    let history: serde_json::Value = serde_json::from_str(&history).unwrap();
+    assert_eq!(history.as_array().unwrap().len(), 1);
+    assert_eq!(history[0]["id"], id.as_str());
+    assert_eq!(history[0]["status"], "success");
+    assert_eq!(history[0]["text"], "see you at noon");
+
+    flow_destroy(handle);
+    drop(results);
+}
+
+#[test]
+fn test_live_transcription_leaves_only_the_tail() {
+    let (url, requests) = transcription_server("see you at noon");
+    let (handle, dir) = init_flow(&url);
+    assert!(flow_set_live_transcription(handle, true));
+
+    // A long phrase, a pause, then a short one still being said
+    let speech = dir.path().join("speech.wav");
+    write_script(
+        &speech,
+        &[
+            (500, false),
+            (3000, true),
+            (1500, false),
+            (1000, true),
+            (500, false),
+        ],
+    );
+    assert!(flow_set_input_file(
+        handle,
+        c(speech.to_string_lossy()).as_ptr()
+    ));
+    assert!(take_string(flow_get_partial_transcription_json(handle)).is_none());
+    assert!(flow_start_recording(handle));
+
+    // The first phrase is closed by the pause; the second is not sent to a cloud provider
+    // until the recording stops
+    let start = Instant::now();
+    let partial = loop {
+        let partial = take_string(flow_get_partial_transcription_json(handle)).unwrap();
+        let partial: serde_json::Value = serde_json::from_str(&partial).unwrap();
+        if partial["stable_text"] != "" {
+            break partial;
+        }
+        assert!(
+            start.elapsed() < Duration::from_secs(10),
+            "nothing transcribed"
+        );
+        std::thread::sleep(Duration::from_millis(20));
+    };
+    assert_eq!(partial["stable_text"], "see you at noon");
+    assert_eq!(partial["text"], "see you at noon");
+    assert_eq!(partial["audio_ms"], 6500);
+    assert_eq!(requests.load(Ordering::SeqCst), 1);
+
+    // Stopping sends just the second phrase
+    assert_eq!(flow_stop_recording(handle), 6500);
+    let text = take_string(flow_transcribe(handle, ptr::null()));
+    assert_eq!(text.as_deref(), Some("see you at noon see you at noon"));
+    assert_eq!(requests.load(Ordering::SeqCst), 2);
+    assert!(take_string(flow_get_partial_transcription_json(handle)).is_none());
+
+    let history = take_string(flow_get_recent_transcriptions_json(handle, 10)).unwrap();
+    let history: serde_json::Value = serde_json::from_str(&history).unwrap();
+    assert_eq!(history[0]["status"], "success");
+    assert_eq!(history[0]["raw_text"], "see you at noon see you at noon");
+
+    flow_destroy(handle);
+}
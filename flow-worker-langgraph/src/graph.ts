@@ -27,7 +27,7 @@
  */
 
 import { Annotation, StateGraph, END } from "@langchain/langgraph";
-import type { FlowState, Env } from "./types.js";
+import type { FlowState, Env, ShortcutExpansion } from "./types.js";
 import {
   transcribeNode,
   detectWakePhraseNode,
@@ -47,6 +47,7 @@ const FlowStateAnnotation = Annotation.Root({
   mode: Annotation<string>(),
   appContext: Annotation<string | undefined>(),
   shortcutsTriggered: Annotation<string[]>(),
+  shortcuts: Annotation<ShortcutExpansion[]>(),
   voiceInstruction: Annotation<string | undefined>(),
 
   // Environment
@@ -95,6 +96,7 @@ export async function runFlowGraph(input: {
   mode: string;
   appContext?: string;
   shortcutsTriggered?: string[];
+  shortcuts?: ShortcutExpansion[];
   voiceInstruction?: string;
   env: Env;
 }): Promise<FlowState> {
@@ -105,6 +107,7 @@ export async function runFlowGraph(input: {
     mode: input.mode,
     appContext: input.appContext,
     shortcutsTriggered: input.shortcutsTriggered ?? [],
+    shortcuts: input.shortcuts ?? [],
     voiceInstruction: input.voiceInstruction,
     env: input.env,
   };
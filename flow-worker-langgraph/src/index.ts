@@ -14,6 +14,7 @@ import type {
   ValidateCorrectionsRequest,
   ValidateCorrectionsResponse,
 } from "./types.js";
+import { AUDIO_CODECS } from "./types.js";
 import { runFlowGraph } from "./graph.js";
 import { validateCorrections } from "./validation.js";
 
@@ -25,13 +26,14 @@ const app = new Hono<{ Bindings: Env }>();
  * Request body:
  * {
  *   "whisper_input": {
- *     "audio": { "audio_b64": "..." },
+ *     "audio": { "audio_b64": "...", "codec": "wav" | "flac" | "opus" },
  *     "whisper_params": { "audio_language": "en", "prompt": "optional hint" }
  *   },
  *   "completion": {
  *     "mode": "casual",
  *     "app_context": "optional context",
  *     "shortcuts_triggered": [],
+ *     "shortcuts": [{ "trigger": "my email", "replacement": "...", "case_sensitive": false }],
  *     "voice_instruction": "optional override"
  *   }
  * }
@@ -51,6 +53,11 @@ app.post("/", async (c) => {
   if (!body.whisper_input?.audio?.audio_b64) {
     return c.json({ error: "Missing audio_b64" }, 400);
   }
+  // Base10 decodes any of these containers, so the audio is forwarded as-is
+  const codec = body.whisper_input.audio.codec ?? "wav";
+  if (!AUDIO_CODECS.includes(codec)) {
+    return c.json({ error: `Unsupported codec: ${codec}` }, 400);
+  }
   if (!body.whisper_input?.whisper_params?.audio_language) {
     return c.json({ error: "Missing audio_language" }, 400);
   }
@@ -65,6 +72,7 @@ app.post("/", async (c) => {
     mode: body.completion.mode,
     appContext: body.completion.app_context,
     shortcutsTriggered: body.completion.shortcuts_triggered,
+    shortcuts: body.completion.shortcuts,
     voiceInstruction: body.completion.voice_instruction,
     env: c.env,
   });
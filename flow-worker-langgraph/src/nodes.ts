@@ -3,7 +3,14 @@
  * Each node performs a step in the transcription/formatting pipeline.
  */
 
-import type { FlowState, Base10Request, Base10Response, OpenRouterRequest, OpenRouterResponse } from "./types.js";
+import type {
+  FlowState,
+  Base10Request,
+  Base10Response,
+  OpenRouterRequest,
+  OpenRouterResponse,
+  ShortcutExpansion,
+} from "./types.js";
 import { buildFormattingPrompt, buildInstructionPrompt } from "./prompts.js";
 
 const BASE10_API_URL = "https://model-232nj723.api.baseten.co/environments/production/predict";
@@ -112,19 +119,62 @@ export function routeByWakePhrase(state: FlowState): "format" | "instruct" {
   return state.detectedCommand ? "instruct" : "format";
 }
 
+/**
+ * Replace every shortcut trigger in the text with its expansion, longest trigger first.
+ * Returns the expanded text and the expansions made, each listed once.
+ */
+export function expandShortcuts(
+  text: string,
+  shortcuts: ShortcutExpansion[]
+): { text: string; triggered: string[] } {
+  const candidates = shortcuts
+    .filter((s) => s.trigger.length > 0)
+    .sort((a, b) => b.trigger.length - a.trigger.length);
+  const lower = text.toLowerCase();
+  const triggered: string[] = [];
+  let expanded = "";
+  let i = 0;
+  while (i < text.length) {
+    const match = candidates.find((s) =>
+      s.case_sensitive
+        ? text.startsWith(s.trigger, i)
+        : lower.startsWith(s.trigger.toLowerCase(), i)
+    );
+    if (match) {
+      expanded += match.replacement;
+      if (!triggered.includes(match.replacement)) {
+        triggered.push(match.replacement);
+      }
+      i += match.trigger.length;
+    } else {
+      expanded += text[i];
+      i += 1;
+    }
+  }
+  return { text: expanded, triggered };
+}
+
 /**
  * Node: Format text using normal formatting mode.
  */
 export async function formatNode(state: FlowState): Promise<Partial<FlowState>> {
-  const { env, transcription, mode, appContext, shortcutsTriggered } = state;
+  const { env, mode, appContext, shortcutsTriggered, shortcuts } = state;
 
-  if (!transcription) {
+  if (!state.transcription) {
     return { error: "No transcription to format" };
   }
 
   console.log(`[DEBUG] Using normal formatting mode with mode=${mode}`);
 
-  const systemPrompt = buildFormattingPrompt(mode, appContext, shortcutsTriggered);
+  // Expand shortcuts first so the prompt only names expansions the text really holds
+  const { text: transcription, triggered } = expandShortcuts(state.transcription, shortcuts);
+  for (const replacement of shortcutsTriggered) {
+    if (transcription.includes(replacement) && !triggered.includes(replacement)) {
+      triggered.push(replacement);
+    }
+  }
+
+  const systemPrompt = buildFormattingPrompt(mode, appContext, triggered);
 
   const request: OpenRouterRequest = {
     models: OPENROUTER_MODELS,
@@ -19,8 +19,14 @@ export interface WhisperInput {
   whisper_params: WhisperParams;
 }
 
+/** Format of the encoded audio; WAV when omitted */
+export type AudioCodec = "wav" | "flac" | "opus";
+
+export const AUDIO_CODECS: readonly AudioCodec[] = ["wav", "flac", "opus"];
+
 export interface AudioInput {
   audio_b64: string;
+  codec?: AudioCodec;
 }
 
 export interface WhisperParams {
@@ -32,9 +38,17 @@ export interface CompletionParams {
   mode: string;
   app_context?: string;
   shortcuts_triggered?: string[];
+  /** Shortcuts to expand in the transcription before it is formatted */
+  shortcuts?: ShortcutExpansion[];
   voice_instruction?: string;
 }
 
+export interface ShortcutExpansion {
+  trigger: string;
+  replacement: string;
+  case_sensitive?: boolean;
+}
+
 // ============ Response Types ============
 
 export interface CombinedResponse {
@@ -121,6 +135,7 @@ export interface FlowState {
   mode: string;
   appContext?: string;
   shortcutsTriggered: string[];
+  shortcuts: ShortcutExpansion[];
   voiceInstruction?: string;
 
   // Environment (passed through state for node access)